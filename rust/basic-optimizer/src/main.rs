@@ -8,7 +8,8 @@ use std::collections::VecDeque;
 
 use icfpc2018_lib as kernel;
 use kernel::cmd::BotCommand;
-use kernel::coord::{LinearCoordDiff,Axis,M,Coord,CoordDiff};
+use kernel::cmd::optimize::Optimizer;
+use kernel::coord::{LinearCoordDiff,Axis,Coord,CoordDiff};
 
 #[derive(Debug)]
 enum Error {
@@ -18,191 +19,6 @@ enum Error {
     Cmd(kernel::cmd::Error),
 }
 
-#[derive(Debug,Clone,Copy)]
-struct Move {
-    axis: Axis,
-    value: M,
-}
-impl Move {
-    fn from_linear(l: &LinearCoordDiff) -> Move {
-        Move {
-            axis: l.get_axis(),
-            value: l.get_value(),
-        }
-    }
-    fn is_lld(&self) -> bool {
-        return (self.value>=-15)&&(self.value<=15);
-    }
-    fn to_llds(&self) -> Vec<LinearCoordDiff> {
-        let dest = if self.value>0 {1} else {-1};
-        let mut aval = self.value.abs();
-        let mut res = Vec::new();
-        while aval>0 {
-            if aval>15 {
-                res.push(LinearCoordDiff::Long{ axis: self.axis, value: dest*15 });
-            } else {
-                res.push(LinearCoordDiff::Long{ axis: self.axis, value: dest*aval });
-            }
-            aval -= 15;
-        }
-        res
-    }
-    fn to_lld(&self) -> LinearCoordDiff {
-        LinearCoordDiff::Long{ axis: self.axis, value: self.value }
-    }
-    fn is_sld(&self) -> bool {
-        return (self.value>=-5)&&(self.value<=5);
-    }
-    fn to_sld(&self) -> LinearCoordDiff {
-        LinearCoordDiff::Short{ axis: self.axis, value: self.value }
-    }
-}
-
-fn optimize_moves(movings: &mut Vec<Move>) {
-    fn get_diff(v1: M, v2: M) -> M {
-        if v1.abs()<=v2.abs() { v1 } else { -v2 }
-    }
-    
-    let mut opt = true;
-    while opt {
-        opt = false;
-        for i in (1 .. movings.len()-1) {
-            if ((movings[i].value == 1)||(movings[i].value == -1))&&
-                (movings[i-1].axis == movings[i+1].axis)&&((movings[i-1].value * movings[i+1].value)<0)
-            {
-                let d =  get_diff(movings[i-1].value,movings[i+1].value);
-                movings[i-1].value -= d;
-                movings[i+1].value += d;
-                opt = true;
-            }
-        }
-        if opt {
-            let mut tmp = movings.iter().cloned().filter(|mv|mv.value!=0).collect::<Vec<_>>();
-            std::mem::swap(&mut tmp, movings);
-        }
-        let mut sopt = false;
-        for i in (0 .. movings.len()-1) {
-            if movings[i].axis == movings[i+1].axis {
-                movings[i+1].value += movings[i].value;
-                movings[i].value = 0;
-                sopt = true;
-            }
-        }
-        if sopt {
-            let mut tmp = movings.iter().cloned().filter(|mv|mv.value!=0).collect::<Vec<_>>();
-            std::mem::swap(&mut tmp, movings);
-        }
-    }
-}
-
-fn optimize_lld_pairs(cmds: &mut Vec<BotCommand>) {
-    loop {
-        let mut tmp = None;
-        for i in (0 .. cmds.len()-1) {
-            match (cmds[i],cmds[i+1]) {
-                (BotCommand::SMove{ long: long1 },BotCommand::SMove{ long: long2 }) if (long1.get_value().abs()<=5)&&(long2.get_value().abs()<=5) => {
-                    let short1 = LinearCoordDiff::Short{
-                        axis: long1.get_axis(),
-                        value: long1.get_value(),
-                    };
-                    let short2 = LinearCoordDiff::Short{
-                        axis: long2.get_axis(),
-                        value: long2.get_value(),
-                    };
-                    tmp = Some((i,BotCommand::lmove(short1,short2).unwrap()));
-                    
-                    break;    
-                },
-                (_,_) => continue,
-            }
-        }
-        match tmp {
-            None => break,
-            Some((idx,cmd)) => {
-                cmds.remove(idx);
-                cmds[idx] = cmd;
-            }
-        }
-    }
-}
-
-struct Optimizer<I> {
-    cmds: I,
-    buffer: VecDeque<BotCommand>,
-    movings: Vec<Move>,
-}
-impl<I> Optimizer<I> {
-    pub fn new(iter: I) -> Optimizer<I> {
-        Optimizer {
-            cmds: iter,
-            buffer: VecDeque::new(),
-            movings: Vec::new(),
-        }
-    }
-    pub fn add_move(&mut self, d: &LinearCoordDiff) {
-        let mov = Move::from_linear(d);
-        if self.movings.len()>0 {
-            let idx = self.movings.len()-1;        
-            if self.movings[idx].axis == mov.axis {
-                self.movings[idx].value += mov.value
-            } else {
-                self.movings.push(mov)
-            }
-        } else {
-            self.movings.push(mov)
-        }
-    }
-    
-    pub fn flush_moves(&mut self) -> impl Iterator<Item = BotCommand> {
-        optimize_moves(&mut self.movings);
-        let mut res = Vec::new();
-        for m in self.movings.drain(0..) {
-            for lld in m.to_llds() {
-                res.push(BotCommand::smove(lld).unwrap());
-            }
-        }
-        optimize_lld_pairs(&mut res);
-        res.into_iter()
-    }
-}
-impl<I> Iterator for Optimizer<I>
-    where I: Iterator<Item = BotCommand>
-{
-    type Item = BotCommand;
-    fn next(&mut self) -> Option<Self::Item> {        
-        loop {
-            if self.buffer.len()>0 {
-                return self.buffer.pop_front();
-            }
-            match self.cmds.next() {
-                None => return None,
-                Some(BotCommand::Wait) => continue,
-                c @ Some(BotCommand::Halt) |
-                c @ Some(BotCommand::Flip) |
-                c @ Some(BotCommand::Fill{ .. }) |
-                c @ Some(BotCommand::FusionP{ .. }) |
-                c @ Some(BotCommand::FusionS{ .. }) |
-                c @ Some(BotCommand::Fission{ .. }) => {
-                    if self.movings.len()>0 {
-                        let moves = self.flush_moves();
-                        self.buffer.extend(moves);
-                        self.buffer.push_back(c.unwrap());
-                    } else {
-                        return c;
-                    }
-                },
-                Some(BotCommand::SMove{ long }) => {
-                    self.add_move(&long);
-                },
-                Some(BotCommand::LMove{ short1, short2 }) => {
-                     self.add_move(&short1);
-                     self.add_move(&short2);  
-                },
-            }
-        }
-    }
-}
-
 struct FillOptimizer<I> {
     cmds: I,
     buffer: VecDeque<BotCommand>,
@@ -227,6 +43,10 @@ impl<I> Iterator for FillOptimizer<I>
                 return self.buffer.pop_front();
             }
             match self.cmds.next() {
+                None if self.tmp.len()>0 => {
+                    self.buffer.extend(self.tmp.drain(0..));
+                    continue;
+                },
                 None => return None,
                 Some(BotCommand::Wait) => continue,
                 Some(BotCommand::Fill{ near }) if near == CoordDiff(Coord { x: 0, y: -1, z: 0 })  => {
@@ -237,6 +57,9 @@ impl<I> Iterator for FillOptimizer<I>
                 },
                 c @ Some(BotCommand::SMove{ .. }) |
                 c @ Some(BotCommand::Fill{ .. }) |
+                c @ Some(BotCommand::Void{ .. }) |
+                c @ Some(BotCommand::GFill{ .. }) |
+                c @ Some(BotCommand::GVoid{ .. }) |
                 c @ Some(BotCommand::Halt) |
                 c @ Some(BotCommand::Flip) |
                 c @ Some(BotCommand::LMove{ .. }) |  
@@ -308,6 +131,10 @@ impl<I> Iterator for FillOptimizer2<I>
                 return self.buffer.pop_front();
             }
             match self.cmds.next() {
+                None if self.tmp.len()>0 => {
+                    self.buffer.extend(self.tmp.drain(0..));
+                    continue;
+                },
                 None => return None,
                 Some(BotCommand::Wait) => continue,
                 Some(BotCommand::Fill{ near }) if near == CoordDiff(Coord { x: 0, y: -1, z: 0 })  => {
@@ -318,6 +145,9 @@ impl<I> Iterator for FillOptimizer2<I>
                 },
                 c @ Some(BotCommand::SMove{ .. }) |
                 c @ Some(BotCommand::Fill{ .. }) |
+                c @ Some(BotCommand::Void{ .. }) |
+                c @ Some(BotCommand::GFill{ .. }) |
+                c @ Some(BotCommand::GVoid{ .. }) |
                 c @ Some(BotCommand::Halt) |
                 c @ Some(BotCommand::Flip) |
                 c @ Some(BotCommand::LMove{ .. }) |  
@@ -359,6 +189,103 @@ impl<I> Iterator for FillOptimizer2<I>
 
 
 
+fn optimize_bot(cmds: Vec<BotCommand>) -> Vec<BotCommand> {
+    Optimizer::new(
+        FillOptimizer2::new(
+            Optimizer::new(
+                FillOptimizer::new(
+                    Optimizer::new(cmds.into_iter()))))).collect()
+}
+
+fn is_barrier(cmd: &BotCommand) -> bool {
+    match cmd {
+        BotCommand::Halt |
+        BotCommand::Flip |
+        BotCommand::Fission{ .. } |
+        BotCommand::FusionP{ .. } |
+        BotCommand::FusionS{ .. } => true,
+        _ => false,
+    }
+}
+
+fn flush_segment(segment: &mut Vec<Vec<BotCommand>>, res: &mut Vec<BotCommand>) {
+    let columns: Vec<Vec<BotCommand>> = segment.drain(0..).map(optimize_bot).collect();
+    let ticks = columns.iter().map(|c| c.len()).max().unwrap_or(0);
+    for t in 0 .. ticks {
+        for column in &columns {
+            res.push(column.get(t).cloned().unwrap_or(BotCommand::Wait));
+        }
+    }
+}
+
+// Ticks where the set of bots (or harmonics) changes are kept verbatim; between them every
+// bot's commands are optimized on their own and padded with `Wait` to a common length.
+fn optimize_trace(cmds: Vec<BotCommand>) -> Vec<BotCommand> {
+    let mut res = Vec::with_capacity(cmds.len());
+    let mut segment: Vec<Vec<BotCommand>> = Vec::new();
+    for tick in kernel::cmd::group_by_ticks(&cmds) {
+        if tick.iter().any(is_barrier) {
+            flush_segment(&mut segment, &mut res);
+            res.extend(tick);
+        } else {
+            segment.resize(tick.len(), Vec::new());
+            for (column, cmd) in segment.iter_mut().zip(tick) {
+                column.push(cmd);
+            }
+        }
+    }
+    flush_segment(&mut segment, &mut res);
+    strip_idle_ticks(remove_flip_pairs(res))
+}
+
+fn changes_matrix(cmd: &BotCommand) -> bool {
+    match cmd {
+        BotCommand::Fill{ .. } |
+        BotCommand::Void{ .. } |
+        BotCommand::GFill{ .. } |
+        BotCommand::GVoid{ .. } => true,
+        _ => false,
+    }
+}
+
+// A Low -> High -> Low round trip with no voxel changed in between never needed High harmonics.
+fn remove_flip_pairs(cmds: Vec<BotCommand>) -> Vec<BotCommand> {
+    let mut ticks = kernel::cmd::group_by_ticks(&cmds);
+    let mut high = false;
+    let mut opened: Option<usize> = None;
+    let mut dirty = false;
+    for t in 0 .. ticks.len() {
+        let flips = ticks[t].iter().filter(|&&c| c == BotCommand::Flip).count();
+        dirty |= ticks[t].iter().any(changes_matrix);
+        if flips % 2 == 0 {
+            continue;
+        }
+        high = !high;
+        if high {
+            opened = Some(t);
+            dirty = ticks[t].iter().any(changes_matrix);
+        } else if let Some(open) = opened.take() {
+            if !dirty {
+                for &tick in &[open, t] {
+                    if let Some(flip) = ticks[tick].iter_mut().find(|c| **c == BotCommand::Flip) {
+                        *flip = BotCommand::Wait;
+                    }
+                }
+            }
+        }
+    }
+    ticks.into_iter().flat_map(|tick| tick).collect()
+}
+
+// Ticks where every bot waits cost energy and change nothing; whatever follows the final Halt is never run.
+fn strip_idle_ticks(cmds: Vec<BotCommand>) -> Vec<BotCommand> {
+    kernel::cmd::group_by_ticks(&cmds)
+        .into_iter()
+        .filter(|tick| tick.iter().any(|&c| c != BotCommand::Wait))
+        .flat_map(|tick| tick)
+        .collect()
+}
+
 fn main() -> Result<(),Error> {
     let mut app = app_from_crate!()
         .arg(Arg::with_name("original")
@@ -393,11 +320,7 @@ fn main() -> Result<(),Error> {
     let cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
     let old_len = cmds.len();
     
-    let opt_cmds = Optimizer::new(
-        FillOptimizer2::new(
-            Optimizer::new(
-                FillOptimizer::new(
-                    Optimizer::new(cmds.into_iter()))))).collect::<Vec<_>>();
+    let opt_cmds = optimize_trace(cmds);
     let mut new_len = 0;
     buffer = kernel::cmd::into_bytes(&opt_cmds).unwrap();
     for c in  opt_cmds {
@@ -420,64 +343,91 @@ mod test {
     use super::*;
     
     #[test]
-    fn test_opt_moves1() {
-        let mut v = vec![
-            Move{ axis: Axis::X, value: 5 },
-            Move{ axis: Axis::Y, value: 1 },
-            Move{ axis: Axis::X, value: -5 },
-            ];
-        println!("");
-        println!("{:?}",v);
-        optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
-    }
+    fn test_optimize_two_bots_trace() {
+        use kernel::coord::{Matrix, Resolution};
+        use kernel::state::State;
 
-    #[test]
-    fn test_opt_moves2() {
-        let mut v = vec![
-            Move{ axis: Axis::X, value: -3 },
-            Move{ axis: Axis::Y, value: 1 },
-            Move{ axis: Axis::X, value: 5 },
-            ];
-        println!("");
-        println!("{:?}",v);
-        optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value }).unwrap();
+        let cmds = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0 }), 0).unwrap(),
+            smove(Axis::Z, 1), smove(Axis::X, 1),
+            smove(Axis::Z, 1), smove(Axis::X, 1),
+            smove(Axis::Z, -1), BotCommand::wait().unwrap(),
+            smove(Axis::Z, -1), smove(Axis::X, -2),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0 })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0 })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+
+        let mut original = State::new(Matrix::new(Resolution(5)), vec![]);
+        original.run_mut(cmds.clone()).unwrap();
+
+        let opt_cmds = optimize_trace(cmds.clone());
+        assert!(opt_cmds.len() < cmds.len());
+        let mut optimized = State::new(Matrix::new(Resolution(5)), vec![]);
+        assert_eq!(optimized.run_mut(opt_cmds), Ok(()));
+        assert!(optimized.energy < original.energy);
     }
 
     #[test]
-    fn test_opt_moves3() {
-        let mut v = vec![
-            Move{ axis: Axis::X, value: -6 },
-            Move{ axis: Axis::Y, value: 1 },
-            Move{ axis: Axis::X, value: 5 },
-            ];
-        println!("");
-        println!("{:?}",v);
-        optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+    fn test_remove_flip_pairs() {
+        use kernel::coord::{Matrix, Resolution};
+        use kernel::state::State;
+
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value }).unwrap();
+        let cmds = vec![
+            BotCommand::flip().unwrap(),
+            smove(Axis::X, 1),
+            BotCommand::flip().unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: 0, z: 1 })).unwrap(),
+            BotCommand::flip().unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: 1, z: 1 })).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: 0, z: 1 })).unwrap(),
+            BotCommand::flip().unwrap(),
+            smove(Axis::X, -1),
+            BotCommand::halt().unwrap(),
+        ];
+        let mut original = State::new(Matrix::new(Resolution(5)), vec![]);
+        original.run_mut(cmds.clone()).unwrap();
+
+        let opt_cmds = remove_flip_pairs(cmds.clone());
+        assert_eq!(opt_cmds[0], BotCommand::Wait);
+        assert_eq!(opt_cmds[2], BotCommand::Wait);
+        assert_eq!(opt_cmds[4], BotCommand::Flip);
+        assert_eq!(opt_cmds[7], BotCommand::Flip);
+        let mut optimized = State::new(Matrix::new(Resolution(5)), vec![]);
+        assert_eq!(optimized.run_mut(opt_cmds), Ok(()));
+        assert!(optimized.energy < original.energy);
     }
 
     #[test]
-    fn test_opt_moves4() {
-        let mut v = vec![
-            Move { axis: Axis::Z, value: 2 },
-            Move { axis: Axis::X, value: 1 },
-            Move { axis: Axis::Z, value: -7 },
-            Move { axis: Axis::Y, value: 1 },
-            Move { axis: Axis::Z, value: 7 },
-            Move { axis: Axis::X, value: -1 },
-            Move { axis: Axis::Z, value: -2 }
-            ];
-        println!("");
-        println!("{:?}",v);
-        optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
-    }
+    fn test_strip_idle_ticks() {
+        use kernel::coord::{Matrix, Resolution};
+        use kernel::state::State;
 
+        let cmds = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0 }), 0).unwrap(),
+            BotCommand::wait().unwrap(), BotCommand::wait().unwrap(),
+            BotCommand::wait().unwrap(), BotCommand::wait().unwrap(),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0 })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0 })).unwrap(),
+            BotCommand::wait().unwrap(),
+            BotCommand::halt().unwrap(),
+            BotCommand::wait().unwrap(),
+            BotCommand::wait().unwrap(),
+        ];
+        let opt_cmds = strip_idle_ticks(cmds.clone());
+        assert_eq!(opt_cmds, vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0 }), 0).unwrap(),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0 })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0 })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ]);
 
+        let mut original = State::new(Matrix::new(Resolution(5)), vec![]);
+        original.run_mut(cmds[.. 9].to_vec()).unwrap();
+        let mut optimized = State::new(Matrix::new(Resolution(5)), vec![]);
+        assert_eq!(optimized.run_mut(opt_cmds), Ok(()));
+        assert!(optimized.energy < original.energy);
+    }
 }
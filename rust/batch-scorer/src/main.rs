@@ -0,0 +1,169 @@
+extern crate icfpc2018_lib;
+extern crate rayon;
+#[macro_use] extern crate clap;
+
+use clap::Arg;
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use icfpc2018_lib as kernel;
+
+use kernel::{
+    cmd,
+    model,
+    state,
+};
+
+#[derive(Debug)]
+enum Error {
+    Args(clap::Error),
+    TracesDirRead(io::Error),
+    SomeTracesFailed { failed: usize, total: usize, },
+}
+
+#[derive(Debug)]
+enum TraceError {
+    Model(model::PairError),
+    Cmd(cmd::Error),
+    State(state::SimulationFailure),
+    ModelNotMatch,
+}
+
+struct Job {
+    problem: String,
+    trace: PathBuf,
+}
+
+struct Row {
+    problem: String,
+    result: Result<state::SimulationReport, TraceError>,
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let matches = app_from_crate!()
+        .about("Scores every PROBLEM.nbt in a traces directory against PROBLEM_src.mdl / PROBLEM_tgt.mdl")
+        .arg(Arg::with_name("problems")
+             .short("p")
+             .long("problems")
+             .value_name("DIR")
+             .help("Problems directory with PROBLEM_src.mdl and/or PROBLEM_tgt.mdl models")
+             .required(true)
+             .takes_value(true))
+        .arg(Arg::with_name("traces")
+             .short("t")
+             .long("traces")
+             .value_name("DIR")
+             .help("Traces directory with PROBLEM.nbt files")
+             .required(true)
+             .takes_value(true))
+        .get_matches();
+
+    let problems_dir = PathBuf::from(value_t!(matches, "problems", String).map_err(Error::Args)?);
+    let traces_dir = PathBuf::from(value_t!(matches, "traces", String).map_err(Error::Args)?);
+
+    let mut jobs = Vec::new();
+    for entry in fs::read_dir(&traces_dir).map_err(Error::TracesDirRead)? {
+        let path = entry.map_err(Error::TracesDirRead)?.path();
+        if let Some(problem) = problem_name(&path) {
+            jobs.push(Job { problem, trace: path, });
+        }
+    }
+    jobs.sort_by(|a, b| a.problem.cmp(&b.problem));
+
+    let rows: Vec<Row> = jobs.par_iter()
+        .map(|job| Row {
+            problem: job.problem.clone(),
+            result: score(&problems_dir, job),
+        })
+        .collect();
+
+    let mut total_energy = 0;
+    let mut failed = 0;
+    println!("{:<12} {:<6} {:>16} {:>10}  {}", "PROBLEM", "STATUS", "ENERGY", "STEPS", "ERROR");
+    for row in rows.iter() {
+        match row.result {
+            Ok(ref report) => {
+                total_energy += report.energy;
+                println!("{:<12} {:<6} {:>16} {:>10}", row.problem, "OK", report.energy, report.steps);
+            },
+            Err(ref error) => {
+                failed += 1;
+                println!("{:<12} {:<6} {:>16} {:>10}  {:?}", row.problem, "FAIL", "-", "-", error);
+            },
+        }
+    }
+    println!("Traces: {}, passed: {}, failed: {}, total energy of passed: {}", rows.len(), rows.len() - failed, failed, total_energy);
+
+    if failed > 0 {
+        Err(Error::SomeTracesFailed { failed, total: rows.len(), })
+    } else {
+        Ok(())
+    }
+}
+
+// `FA001.nbt` (or a gzipped `FA001.nbt.gz`) -> `FA001`
+fn problem_name(trace: &Path) -> Option<String> {
+    let trace = if model::is_gz_filename(trace) {
+        trace.file_stem().map(Path::new)?
+    } else {
+        trace
+    };
+    if trace.extension().map_or(false, |ext| ext == "nbt") {
+        trace.file_stem().map(|stem| stem.to_string_lossy().to_string())
+    } else {
+        None
+    }
+}
+
+fn existing_file(path: PathBuf) -> Option<PathBuf> {
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn score(problems_dir: &Path, job: &Job) -> Result<state::SimulationReport, TraceError> {
+    let source_file = existing_file(problems_dir.join(format!("{}_src.mdl", job.problem)));
+    let target_file = existing_file(problems_dir.join(format!("{}_tgt.mdl", job.problem)));
+    let (source_model, target_model) = model::load_pair(
+        source_file.as_ref().map(PathBuf::as_path),
+        target_file.as_ref().map(PathBuf::as_path),
+    ).map_err(TraceError::Model)?;
+
+    let cmds = cmd::read_trace_file(&job.trace).map_err(TraceError::Cmd)?;
+
+    let report = state::simulate(source_model, target_model, &cmds);
+    if let Some(failure) = report.failure {
+        Err(TraceError::State(failure))
+    } else if !report.success {
+        Err(TraceError::ModelNotMatch)
+    } else {
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    #[test]
+    fn problem_name_from_trace() {
+        assert_eq!(super::problem_name(Path::new("traces/FA001.nbt")), Some("FA001".to_string()));
+        assert_eq!(super::problem_name(Path::new("FR114.nbt")), Some("FR114".to_string()));
+        assert_eq!(super::problem_name(Path::new("traces/FA001_tgt.mdl")), None);
+        assert_eq!(super::problem_name(Path::new("traces/README")), None);
+        assert_eq!(super::problem_name(Path::new("traces/FD010.nbt.gz")), Some("FD010".to_string()));
+        assert_eq!(super::problem_name(Path::new("traces/FD010_src.mdl.gz")), None);
+    }
+}
@@ -0,0 +1,1626 @@
+use std::{cmp, fs, io::{self, Read, Write}, path::Path};
+use std::collections::{BTreeMap, BTreeSet};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+use coord::{LinearCoordDiff,CoordDiff,Coord,Axis,Region};
+use model;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "serde")]
+use serde_json;
+
+pub mod text;
+pub mod optimize;
+
+
+#[derive(Debug)]
+pub enum Error {
+    RestrictedLinearCoordDiff,
+    LinearCoordDiffTooLong(LinearCoordDiff),
+    CoordDiffIsNotNear,
+    CoordDiffIsNotFar,
+    DeserializeNotNear(u8),
+    DeserializeNotAxis(u8),
+    DeserializeUnknown(u8),
+    DeserializeSMoveDiff(u8),
+    // the command starting with this byte is cut short (0 when there is no byte at all)
+    DeserializeTruncated(u8),
+    NotInvertible,
+    ConcatNoHalt{trace: usize},
+    ConcatBotsAtSeam{trace: usize, bots: usize},
+    ConcatTrailingCommands{trace: usize, count: usize},
+    DeserializeHexDigit(char),
+    DeserializeHexOddLength,
+    DeserializeBase64Char(char),
+    DeserializeBase64Length,
+    TraceOpen(io::Error),
+    TraceRead(io::Error),
+    TraceCreate(io::Error),
+    TraceWrite(io::Error),
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BotCommand {
+    Halt,
+    Wait,
+    Flip,
+    SMove{ long: LinearCoordDiff },
+    LMove{ short1: LinearCoordDiff, short2: LinearCoordDiff },
+    Fission{ near: CoordDiff, split_m: u8 },
+    Fill{ near: CoordDiff },
+    FusionP{ near: CoordDiff },
+    FusionS{ near: CoordDiff },
+
+    Void{ near: CoordDiff },
+    GFill{ near: CoordDiff, far: CoordDiff },
+    GVoid{ near: CoordDiff, far: CoordDiff },
+}
+impl BotCommand {  
+    pub fn halt() -> Result<BotCommand,Error> {
+        Ok(BotCommand::Halt)
+    }
+    pub fn wait() -> Result<BotCommand,Error> {
+        Ok(BotCommand::Wait)
+    }
+    pub fn flip() -> Result<BotCommand,Error> {
+        Ok(BotCommand::Flip)
+    }
+    pub fn smove(mv: LinearCoordDiff) -> Result<BotCommand,Error> {
+        match mv {
+            LinearCoordDiff::Long { .. } if mv.is_valid() => {
+                Ok(BotCommand::SMove{ long: mv })
+            },
+            LinearCoordDiff::Long { .. }  => Err(Error::LinearCoordDiffTooLong(mv)),
+            LinearCoordDiff::Short { .. }  => Err(Error::RestrictedLinearCoordDiff),
+        }
+    }
+    pub fn lmove(first: LinearCoordDiff, second: LinearCoordDiff) -> Result<BotCommand,Error> {
+        let f = match first {
+            LinearCoordDiff::Short { .. } if first.is_valid() => first,
+            LinearCoordDiff::Short { .. }  => return Err(Error::LinearCoordDiffTooLong(first)),
+            LinearCoordDiff::Long { .. } => return Err(Error::RestrictedLinearCoordDiff),
+        };
+        let s = match second {
+            LinearCoordDiff::Short { .. } if second.is_valid() => second,
+            LinearCoordDiff::Short { .. }  => return Err(Error::LinearCoordDiffTooLong(second)),
+            LinearCoordDiff::Long { .. } => return Err(Error::RestrictedLinearCoordDiff),
+        };
+        Ok(BotCommand::LMove{ short1: f, short2: s })
+    }
+    pub fn fission(df: CoordDiff, m: u8) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        Ok(BotCommand::Fission{ near: df, split_m: m})
+    }
+    pub fn fill(df: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        Ok(BotCommand::Fill{ near: df })
+    }
+    pub fn pfusion(df: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        Ok(BotCommand::FusionP{ near: df })
+    }
+    pub fn sfusion(df: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        Ok(BotCommand::FusionS{ near: df })
+    }
+
+    pub fn void(df: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        Ok(BotCommand::Void{ near: df })
+    }
+    pub fn gfill(df: CoordDiff, df2: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        if !df2.is_far() { return Err(Error::CoordDiffIsNotFar); }
+        Ok(BotCommand::GFill{ near: df, far: df2 })
+    }
+    pub fn gvoid(df: CoordDiff, df2: CoordDiff) -> Result<BotCommand,Error> {
+        if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+        if !df2.is_far() { return Err(Error::CoordDiffIsNotFar); }
+        Ok(BotCommand::GVoid{ near: df, far: df2 })
+    }
+
+    // the command itself when its diffs satisfy the same checks as the constructors above
+    pub fn validated(self) -> Result<BotCommand,Error> {
+        match self {
+            BotCommand::Halt | BotCommand::Wait | BotCommand::Flip => Ok(self),
+            BotCommand::SMove{ long } => BotCommand::smove(long),
+            BotCommand::LMove{ short1, short2 } => BotCommand::lmove(short1, short2),
+            BotCommand::Fission{ near, split_m } => BotCommand::fission(near, split_m),
+            BotCommand::Fill{ near } => BotCommand::fill(near),
+            BotCommand::FusionP{ near } => BotCommand::pfusion(near),
+            BotCommand::FusionS{ near } => BotCommand::sfusion(near),
+            BotCommand::Void{ near } => BotCommand::void(near),
+            BotCommand::GFill{ near, far } => BotCommand::gfill(near, far),
+            BotCommand::GVoid{ near, far } => BotCommand::gvoid(near, far),
+        }
+    }
+
+    // Volatile regions of the command performed by a bot at `from`, as `State::check_precondition`
+    // sees them; bounds and matrix contents are not checked. The second region is the other LMove leg,
+    // the spawned bot position for Fission and the group region for GFill / GVoid.
+    pub fn affected_regions(&self, from: Coord) -> (Region, Option<Region>) {
+        let bot_reg = Region::from_corners(&from, &from);
+        match *self {
+            BotCommand::Halt |
+            BotCommand::Wait |
+            BotCommand::Flip |
+            BotCommand::FusionP{ .. } |
+            BotCommand::FusionS{ .. } =>
+                (bot_reg, None),
+            BotCommand::SMove{ long } =>
+                (Region::from_corners(&from, &from.add(long.to_coord_diff())), None),
+            BotCommand::LMove{ short1, short2 } => {
+                let cf = from.add(short1.to_coord_diff());
+                let cff = cf.add(short2.to_coord_diff());
+                let leg1 = Region::from_corners(&from, &cf);
+                let leg2 = Region::from_corners(&cf, &cff);
+                if short1.axis() == short2.axis() {
+                    // both legs on one line: a single region covering them, even when the second one turns back
+                    (Region {
+                        min: Coord {
+                            x: cmp::min(leg1.min.x, leg2.min.x),
+                            y: cmp::min(leg1.min.y, leg2.min.y),
+                            z: cmp::min(leg1.min.z, leg2.min.z),
+                        },
+                        max: Coord {
+                            x: cmp::max(leg1.max.x, leg2.max.x),
+                            y: cmp::max(leg1.max.y, leg2.max.y),
+                            z: cmp::max(leg1.max.z, leg2.max.z),
+                        },
+                    }, None)
+                } else {
+                    (leg1, Some(leg2))
+                }
+            },
+            BotCommand::Fill{ near } |
+            BotCommand::Void{ near } |
+            BotCommand::Fission{ near, .. } => {
+                let cf = from.add(near);
+                (bot_reg, Some(Region::from_corners(&cf, &cf)))
+            },
+            BotCommand::GFill{ near, far } | BotCommand::GVoid{ near, far } => {
+                let cn = from.add(near);
+                (bot_reg, Some(Region::from_corners(&cn, &cn.add(far))))
+            },
+        }
+    }
+
+    // the command name as in the spec (and the `text` format)
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BotCommand::Halt => "Halt",
+            BotCommand::Wait => "Wait",
+            BotCommand::Flip => "Flip",
+            BotCommand::SMove{ .. } => "SMove",
+            BotCommand::LMove{ .. } => "LMove",
+            BotCommand::Fission{ .. } => "Fission",
+            BotCommand::Fill{ .. } => "Fill",
+            BotCommand::FusionP{ .. } => "FusionP",
+            BotCommand::FusionS{ .. } => "FusionS",
+            BotCommand::Void{ .. } => "Void",
+            BotCommand::GFill{ .. } => "GFill",
+            BotCommand::GVoid{ .. } => "GVoid",
+        }
+    }
+
+    // length of the command in the `into_bytes` encoding
+    pub fn encoded_len(&self) -> usize {
+        match *self {
+            BotCommand::SMove{ .. } | BotCommand::LMove{ .. } | BotCommand::Fission{ .. } => 2,
+            BotCommand::GFill{ .. } | BotCommand::GVoid{ .. } => 4,
+            _ => 1,
+        }
+    }
+}
+
+fn near_to_u8(df: &CoordDiff) -> Result<u8,Error> {
+    if !df.is_near() { return Err(Error::CoordDiffIsNotNear); }
+    Ok(((df.0.x + 1) as u8)*9 + ((df.0.y + 1) as u8)*3 + ((df.0.z + 1) as u8))
+}
+
+fn check_near(b: u8) -> Result<CoordDiff,Error> {
+    let z = b % 3;
+    let y = (b % 9) / 3;
+    let x = b / 9;
+    if (x<3)&&(y<3)&&(z<3) {
+        Ok(CoordDiff(Coord{
+            x: (x as isize) - 1,
+            y: (y as isize) - 1,
+            z: (z as isize) - 1,
+        }))
+    } else {
+        Err(Error::DeserializeNotNear(b))
+    }
+}
+
+fn axis_to_u8(ax: &Axis) -> Result<u8,Error> {
+    match ax {
+        Axis::X => Ok(0b01),
+        Axis::Y => Ok(0b10),
+        Axis::Z => Ok(0b11),
+    }
+}
+
+fn check_axis(b: u8) -> Result<Axis,Error> {
+    match b {
+        0b01 => Ok(Axis::X),
+        0b10 => Ok(Axis::Y),
+        0b11 => Ok(Axis::Z),
+        _ => Err(Error::DeserializeNotAxis(b))
+    }
+}
+
+// Decodes the single command at the start of `bytes`, returning it along with how many bytes it took (1 to 4).
+pub fn decode_one(bytes: &[u8]) -> Result<(BotCommand,usize),Error> {
+    let b = match bytes.first() {
+        Some(&b) => b,
+        None => return Err(Error::DeserializeTruncated(0)),
+    };
+    let arg = |i: usize| bytes.get(i).cloned().ok_or(Error::DeserializeTruncated(b));
+    let cmd = match ((b>>3) & 0b11111, b & 0b111) {
+        (0b11111,0b111) => BotCommand::halt()?,
+        (0b11111,0b110) => BotCommand::wait()?,
+        (0b11111,0b101) => BotCommand::flip()?,
+        (near,0b011) => BotCommand::fill(check_near(near)?)?,
+        (near,0b111) => BotCommand::pfusion(check_near(near)?)?,
+        (near,0b110) => BotCommand::sfusion(check_near(near)?)?,
+        (near,0b101) => {
+            let df = check_near(near)?;
+            let m = arg(1)?;
+            BotCommand::fission(df,m)?
+        },
+        (near,0b010) => BotCommand::void(check_near(near)?)?,
+        (near,0b001) => {
+            let df = check_near(near)?;
+            let dx = (arg(1)? as isize) - 30;
+            let dy = (arg(2)? as isize) - 30;
+            let dz = (arg(3)? as isize) - 30;
+            let df2 = CoordDiff(Coord{ x: dx, y: dy, z: dz });
+            BotCommand::gfill(df,df2)?
+        },
+        (near,0b000) => {
+            let df = check_near(near)?;
+            let dx = (arg(1)? as isize) - 30;
+            let dy = (arg(2)? as isize) - 30;
+            let dz = (arg(3)? as isize) - 30;
+            let df2 = CoordDiff(Coord{ x: dx, y: dy, z: dz });
+            BotCommand::gvoid(df,df2)?
+        },
+        (p,0b100) => {
+            match ((p>>3) & 0b11,(p>>1) & 0b11, p & 0b1) {
+                (0,axis,0) => {
+                    let ax = check_axis(axis)?;
+                    let d = arg(1)?;
+                    if (d & 0b11100000) > 0 { return Err(Error::DeserializeSMoveDiff(d)); }
+                    BotCommand::smove(LinearCoordDiff::Long{
+                        axis: ax,
+                        value: ((d & 0b11111) as isize) - 15,
+                    })?
+                },
+                (axis2,axis1,1) => {
+                    let ax1 = check_axis(axis1)?;
+                    let ax2 = check_axis(axis2)?;
+                    let d = arg(1)?;
+                    BotCommand::lmove(
+                        LinearCoordDiff::Short{
+                            axis: ax1,
+                            value: ((d & 0b1111) as isize) - 5,
+                        },
+                        LinearCoordDiff::Short{
+                            axis: ax2,
+                            value: (((d >> 4) & 0b11111) as isize) - 5,
+                        }
+                        )?
+                },
+                (_,_,_) => return Err(Error::DeserializeUnknown(b)),
+            }
+        },
+        (_,_) => return Err(Error::DeserializeUnknown(b)),
+    };
+    let consumed = cmd.encoded_len();
+    Ok((cmd,consumed))
+}
+
+// Checks every command as it passes through (see `BotCommand::validated`), so a generator catches
+// a bad diff where it is produced instead of at `into_bytes`.
+pub fn validate_iter<I>(iter: I) -> impl Iterator<Item = Result<BotCommand,Error>> where I: Iterator<Item = BotCommand> {
+    iter.map(BotCommand::validated)
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Vec<BotCommand>,Error> {
+    let mut i = 0;
+    let mut res = Vec::new();
+    while i<bytes.len() {
+        let (cmd,consumed) = decode_one(&bytes[i..])?;
+        res.push(cmd);
+        i += consumed;
+    }
+    Ok(res)
+}
+
+// Reads a raw or a gzipped trace. The gzip magic `1f 8b` would be a FusionP as the very first
+// command, which the only bot of a valid trace can never do.
+pub fn read_trace_file<P>(filename: P) -> Result<Vec<BotCommand>,Error> where P: AsRef<Path> {
+    let file = fs::File::open(filename).map_err(Error::TraceOpen)?;
+    let mut reader = io::BufReader::new(file);
+    let gzipped = model::is_gzipped(&mut reader).map_err(Error::TraceRead)?;
+    let mut bytes = Vec::new();
+    let read = if gzipped {
+        GzDecoder::new(reader).read_to_end(&mut bytes)
+    } else {
+        reader.read_to_end(&mut bytes)
+    };
+    read.map_err(Error::TraceRead)?;
+    from_bytes(&bytes)
+}
+
+// A `.gz` filename (like `FA001.nbt.gz`) gets the trace gzipped.
+pub fn write_trace_file<P>(filename: P, commands: &[BotCommand]) -> Result<(),Error> where P: AsRef<Path> {
+    let bytes = into_bytes(&commands.to_vec())?;
+    let mut file = fs::File::create(&filename).map_err(Error::TraceCreate)?;
+    if model::is_gz_filename(filename.as_ref()) {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(&bytes).map_err(Error::TraceWrite)?;
+        encoder.finish().map(|_| ()).map_err(Error::TraceWrite)
+    } else {
+        file.write_all(&bytes).map_err(Error::TraceWrite)
+    }
+}
+
+pub fn into_bytes(commands: &Vec<BotCommand>) -> Result<Vec<u8>,Error> {
+    let mut res = Vec::new();
+    for c in commands {
+        match c {
+            BotCommand::Halt => res.push(0b11111111),
+            BotCommand::Wait => res.push(0b11111110),
+            BotCommand::Flip => res.push(0b11111101),
+            BotCommand::Fill{ near } => res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b011),
+            BotCommand::Void{ near } => res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b010),
+            BotCommand::FusionP{ near } => res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b111),
+            BotCommand::FusionS{ near } => res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b110),
+            BotCommand::Fission{ near , split_m } => {
+                res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b101);
+                res.push(*split_m)
+            },
+            BotCommand::GFill{ near, far } => {
+                res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b001);
+                res.push( (far.0.x + 30) as u8 );
+                res.push( (far.0.y + 30) as u8 );
+                res.push( (far.0.z + 30) as u8 );
+            },
+            BotCommand::GVoid{ near, far } => {
+                res.push( ((near_to_u8(near)? & 0b11111)<<3) | 0b000);
+                res.push( (far.0.x + 30) as u8 );
+                res.push( (far.0.y + 30) as u8 );
+                res.push( (far.0.z + 30) as u8 );
+            },
+            BotCommand::SMove{ long } => {
+                match long {
+                    LinearCoordDiff::Long { axis, value } if long.is_valid() => {
+                        res.push( ((axis_to_u8(axis)? & 0b11)<<4) | 0b0100);
+                        res.push( ((value + 15) as u8) & 0b00011111 )
+                    },
+                    LinearCoordDiff::Long { .. }  => return Err(Error::LinearCoordDiffTooLong(*long)),
+                    LinearCoordDiff::Short { .. }  => return Err(Error::RestrictedLinearCoordDiff),
+                }
+            },
+            BotCommand::LMove{ ref short1, ref short2 } => {
+                let (ax1, val1) = match short1 {
+                    LinearCoordDiff::Short { axis, value } if short1.is_valid() => {
+                         (axis_to_u8(axis)?, (value+5) as u8)
+                    },
+                    LinearCoordDiff::Long { .. }  => return Err(Error::RestrictedLinearCoordDiff),
+                    LinearCoordDiff::Short { .. }  => return Err(Error::LinearCoordDiffTooLong(*short1)),
+                };
+                let (ax2, val2) = match short2 {
+                    LinearCoordDiff::Short { axis, value } if short2.is_valid() => {
+                         (axis_to_u8(axis)?, (value+5) as u8)
+                    },
+                    LinearCoordDiff::Long { .. }  => return Err(Error::RestrictedLinearCoordDiff),
+                    LinearCoordDiff::Short { .. }  => return Err(Error::LinearCoordDiffTooLong(*short2)),
+                };
+                res.push( ((ax2 & 0b11) << 6) |
+                          ((ax1 & 0b11) << 4) | 0b1100 );
+                res.push( ((val2 & 0b1111) << 4) | (val1 & 0b1111) )
+            }            
+        }
+    }
+    // every encoding above has to agree with `BotCommand::encoded_len`
+    debug_assert_eq!(res.len(), trace_byte_len(commands));
+    Ok(res)
+}
+
+
+pub fn invert(cmd: &BotCommand) -> Result<BotCommand,Error> {
+    match cmd {
+        BotCommand::Wait => Ok(BotCommand::Wait),
+        BotCommand::Fill{ near } => Ok(BotCommand::Void{ near: *near }),
+        BotCommand::Void{ near } => Ok(BotCommand::Fill{ near: *near }),
+        BotCommand::GFill{ near, far } => Ok(BotCommand::GVoid{ near: *near, far: *far }),
+        BotCommand::GVoid{ near, far } => Ok(BotCommand::GFill{ near: *near, far: *far }),
+        BotCommand::SMove{ long: LinearCoordDiff::Long{ axis, value } } =>
+            Ok(BotCommand::SMove{ long: LinearCoordDiff::Long{ axis: *axis, value: -value } }),
+        BotCommand::SMove{ .. } => Err(Error::RestrictedLinearCoordDiff),
+        BotCommand::LMove{ short1: LinearCoordDiff::Short{ axis: a1, value: v1 }, short2: LinearCoordDiff::Short{ axis: a2, value: v2 } } =>
+            Ok(BotCommand::LMove{
+                short1: LinearCoordDiff::Short{ axis: *a2, value: -v2 },
+                short2: LinearCoordDiff::Short{ axis: *a1, value: -v1 },
+            }),
+        BotCommand::LMove{ .. } => Err(Error::RestrictedLinearCoordDiff),
+        BotCommand::Halt |
+        BotCommand::Flip |
+        BotCommand::Fission{ .. } |
+        BotCommand::FusionP{ .. } |
+        BotCommand::FusionS{ .. } => Err(Error::NotInvertible),
+    }
+}
+
+// index-wise comparison: every position where the traces disagree, `None` past the end of the shorter one
+pub fn diff(a: &[BotCommand], b: &[BotCommand]) -> Vec<(usize, Option<BotCommand>, Option<BotCommand>)> {
+    (0 .. ::std::cmp::max(a.len(), b.len()))
+        .map(|i| (i, a.get(i).cloned(), b.get(i).cloned()))
+        .filter(|&(_, ref cmd_a, ref cmd_b)| cmd_a != cmd_b)
+        .collect()
+}
+
+#[cfg(feature = "serde")]
+pub fn to_json(commands: &[BotCommand]) -> String {
+    serde_json::to_string(commands).expect("BotCommand is always serializable")
+}
+
+#[cfg(feature = "serde")]
+pub fn from_json(json: &str) -> Result<Vec<BotCommand>,Error> {
+    let commands: Vec<BotCommand> = serde_json::from_str(json).map_err(Error::Json)?;
+    // run every command through its constructor so out-of-range diffs are rejected like in from_bytes
+    commands.iter()
+        .map(|cmd| match *cmd {
+            BotCommand::Halt => BotCommand::halt(),
+            BotCommand::Wait => BotCommand::wait(),
+            BotCommand::Flip => BotCommand::flip(),
+            BotCommand::SMove{ long } => BotCommand::smove(long),
+            BotCommand::LMove{ short1, short2 } => BotCommand::lmove(short1, short2),
+            BotCommand::Fission{ near, split_m } => BotCommand::fission(near, split_m),
+            BotCommand::Fill{ near } => BotCommand::fill(near),
+            BotCommand::FusionP{ near } => BotCommand::pfusion(near),
+            BotCommand::FusionS{ near } => BotCommand::sfusion(near),
+            BotCommand::Void{ near } => BotCommand::void(near),
+            BotCommand::GFill{ near, far } => BotCommand::gfill(near, far),
+            BotCommand::GVoid{ near, far } => BotCommand::gvoid(near, far),
+        })
+        .collect()
+}
+
+// compact copy-pasteable forms of `into_bytes`/`from_bytes` for bug reports; whitespace in the input is ignored
+pub fn to_hex(commands: &[BotCommand]) -> Result<String,Error> {
+    let bytes = into_bytes(&commands.to_vec())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<BotCommand>,Error> {
+    let digits = hex.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(Error::DeserializeHexDigit(c)))
+        .collect::<Result<Vec<_>,_>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(Error::DeserializeHexOddLength);
+    }
+    let bytes: Vec<u8> = digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect();
+    from_bytes(&bytes)
+}
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn to_base64(commands: &[BotCommand]) -> Result<String,Error> {
+    let bytes = into_bytes(&commands.to_vec())?;
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | ((b as u32) << (16 - 8 * i)));
+        for i in 0 .. 4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[((group >> (18 - 6 * i)) & 0b111111) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    Ok(out)
+}
+
+pub fn from_base64(base64: &str) -> Result<Vec<BotCommand>,Error> {
+    let chars: Vec<char> = base64.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() % 4 != 0 {
+        return Err(Error::DeserializeBase64Length);
+    }
+    let mut bytes = Vec::with_capacity(chars.len() / 4 * 3);
+    for (chunk_index, chunk) in chars.chunks(4).enumerate() {
+        let is_last = chunk_index + 1 == chars.len() / 4;
+        let padding = chunk.iter().rev().take_while(|&&c| c == '=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return Err(Error::DeserializeBase64Length);
+        }
+        let mut group = 0u32;
+        for (i, &c) in chunk[.. 4 - padding].iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|&a| a as char == c)
+                .ok_or(Error::DeserializeBase64Char(c))?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        for i in 0 .. 3 - padding {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    from_bytes(&bytes)
+}
+
+pub fn group_by_ticks(commands: &[BotCommand]) -> Vec<Vec<BotCommand>> {
+    let mut ticks = Vec::new();
+    let mut bots = 1;
+    let mut offset = 0;
+    while offset < commands.len() && bots > 0 {
+        let tick_end = ::std::cmp::min(offset + bots, commands.len());
+        let tick = commands[offset .. tick_end].to_vec();
+        for c in &tick {
+            match c {
+                BotCommand::Fission{ .. } => bots += 1,
+                BotCommand::FusionS{ .. } | BotCommand::Halt => bots -= 1,
+                _ => (),
+            }
+        }
+        ticks.push(tick);
+        offset = tick_end;
+    }
+    ticks
+}
+
+pub fn command_counts(commands: &[BotCommand]) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for cmd in commands {
+        *counts.entry(cmd.name()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// the `into_bytes` output length without encoding (so invalid commands are counted too)
+pub fn trace_byte_len(commands: &[BotCommand]) -> usize {
+    commands.iter().map(BotCommand::encoded_len).sum()
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TraceMetrics {
+    pub commands: usize,
+    pub counts: BTreeMap<&'static str, usize>,
+    pub byte_len: usize,
+    pub ticks: usize,
+    pub peak_bots: usize,
+    pub flips: usize,
+}
+
+// what can be told about a trace without simulating it
+pub fn metrics(commands: &[BotCommand]) -> TraceMetrics {
+    let counts = command_counts(commands);
+    let ticks = group_by_ticks(commands);
+    TraceMetrics {
+        commands: commands.len(),
+        byte_len: trace_byte_len(commands),
+        ticks: ticks.len(),
+        peak_bots: ticks.iter().map(|tick| tick.len()).max().unwrap_or(0),
+        flips: counts.get("Flip").cloned().unwrap_or(0),
+        counts,
+    }
+}
+
+struct LaneBot {
+    pos: Coord,
+    seeds: Vec<usize>,
+}
+
+// A table with one row per tick and one column per bot, e.g.
+//
+//   tick | bot 1             | bot 2
+//   1    | Fission <1,0,0> 0 |
+//   2    | Fill <0,0,1>      | Wait
+//
+// Bot ids are followed through fissions and fusions the way the simulator assigns them (starting
+// with bot 1 at the origin holding seeds 2 to 40); a column is blank while its bot is not active.
+pub fn disassemble_lanes(commands: &[BotCommand]) -> String {
+    let mut bots = BTreeMap::new();
+    bots.insert(1, LaneBot { pos: Coord { x: 0, y: 0, z: 0, }, seeds: (2 .. 41).collect(), });
+    let mut rows: Vec<BTreeMap<usize, String>> = Vec::new();
+    for tick in group_by_ticks(commands) {
+        // commands go to the bots in the bid order, everybody acting from where it was at the tick start
+        let active: Vec<(usize, Coord)> = bots.iter().map(|(&bid, bot)| (bid, bot.pos)).collect();
+        let mut row = BTreeMap::new();
+        for (&(bid, pos), cmd) in active.iter().zip(tick.iter()) {
+            row.insert(bid, text::format_command(cmd));
+            match *cmd {
+                BotCommand::Halt => {
+                    bots.remove(&bid);
+                },
+                BotCommand::SMove{ long } => if let Some(bot) = bots.get_mut(&bid) {
+                    bot.pos = pos.add(long.to_coord_diff());
+                },
+                BotCommand::LMove{ short1, short2 } => if let Some(bot) = bots.get_mut(&bid) {
+                    bot.pos = pos.add(short1.to_coord_diff()).add(short2.to_coord_diff());
+                },
+                BotCommand::Fission{ near, split_m } => {
+                    let child = match bots.get_mut(&bid) {
+                        Some(bot) => if bot.seeds.is_empty() {
+                            None
+                        } else {
+                            let child_bid = bot.seeds.remove(0);
+                            let split = cmp::min(split_m as usize, bot.seeds.len());
+                            let seeds = bot.seeds.drain(.. split).collect();
+                            Some((child_bid, LaneBot { pos: pos.add(near), seeds, }))
+                        },
+                        None =>
+                            None,
+                    };
+                    if let Some((child_bid, child)) = child {
+                        bots.insert(child_bid, child);
+                    }
+                },
+                BotCommand::FusionP{ near } => {
+                    let secondary_pos = pos.add(near);
+                    let secondary = active.iter()
+                        .find(|&&(_, other_pos)| other_pos == secondary_pos)
+                        .and_then(|&(other_bid, _)| bots.remove(&other_bid).map(|other| (other_bid, other)));
+                    if let (Some((other_bid, other)), Some(bot)) = (secondary, bots.get_mut(&bid)) {
+                        bot.seeds.push(other_bid);
+                        bot.seeds.extend(other.seeds);
+                        bot.seeds.sort();
+                    }
+                },
+                _ =>
+                    (),
+            }
+        }
+        rows.push(row);
+    }
+
+    let columns: BTreeSet<usize> = rows.iter().flat_map(|row| row.keys().cloned()).collect();
+    let mut lines = vec![("tick".to_string(), columns.iter().map(|bid| format!("bot {}", bid)).collect::<Vec<_>>())];
+    for (index, row) in rows.iter().enumerate() {
+        let cells = columns.iter().map(|bid| row.get(bid).cloned().unwrap_or_default()).collect();
+        lines.push(((index + 1).to_string(), cells));
+    }
+    let tick_width = lines.iter().map(|line| line.0.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0 .. columns.len())
+        .map(|column| lines.iter().map(|line| line.1[column].len()).max().unwrap_or(0))
+        .collect();
+    let mut out = String::new();
+    for &(ref tick, ref cells) in lines.iter() {
+        let mut line = format!("{:<width$}", tick, width = tick_width);
+        for (cell, &width) in cells.iter().zip(widths.iter()) {
+            line.push_str(&format!(" | {:<width$}", cell, width = width));
+        }
+        while line.ends_with(' ') {
+            line.pop();
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+// Drops everything after the first `Halt` performed by the only active bot. A `Halt` in a tick with
+// several bots is invalid anyway, so it neither ends the trace nor changes the bots count.
+pub fn truncate_at_halt(cmds: &mut Vec<BotCommand>) {
+    let mut bots = 1;
+    let mut offset = 0;
+    while offset < cmds.len() {
+        if bots == 1 && cmds[offset] == BotCommand::Halt {
+            cmds.truncate(offset + 1);
+            return;
+        }
+        let tick_end = ::std::cmp::min(offset + bots, cmds.len());
+        for c in &cmds[offset .. tick_end] {
+            match c {
+                BotCommand::Fission{ .. } => bots += 1,
+                BotCommand::FusionS{ .. } => bots -= 1,
+                _ => (),
+            }
+        }
+        offset = tick_end;
+    }
+}
+
+// Chains single-phase traces into one, dropping every `Halt` but the last. Each trace should end
+// with exactly one bot halting and no commands after it, so the next one starts from a single bot.
+pub fn concat_aligned(traces: &[Vec<BotCommand>]) -> Result<Vec<BotCommand>,Error> {
+    let mut res = Vec::with_capacity(traces.iter().map(|trace| trace.len()).sum());
+    for (index, trace) in traces.iter().enumerate() {
+        let ticks = group_by_ticks(trace);
+        let (last_tick, prev_ticks) = match ticks.split_last() {
+            Some(split) => split,
+            None => return Err(Error::ConcatNoHalt{ trace: index }),
+        };
+        if !last_tick.contains(&BotCommand::Halt) {
+            return Err(Error::ConcatNoHalt{ trace: index });
+        }
+        let bots = prev_ticks.iter()
+            .flat_map(|tick| tick.iter())
+            .fold(1, |bots, c| match c {
+                BotCommand::Fission{ .. } => bots + 1,
+                BotCommand::FusionS{ .. } | BotCommand::Halt => bots - 1,
+                _ => bots,
+            });
+        if bots != 1 {
+            return Err(Error::ConcatBotsAtSeam{ trace: index, bots });
+        }
+        let consumed: usize = ticks.iter().map(|tick| tick.len()).sum();
+        if consumed < trace.len() {
+            return Err(Error::ConcatTrailingCommands{ trace: index, count: trace.len() - consumed });
+        }
+        res.extend_from_slice(&trace[.. consumed - 1]);
+    }
+    if !traces.is_empty() {
+        res.push(BotCommand::Halt);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[test]
+    fn test_deser_halt() {
+        let buf = [0b11111111];
+        let res = vec![BotCommand::halt().unwrap()];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_halt() {
+        let buf = vec![0b11111111];
+        let res = vec![BotCommand::halt().unwrap()];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    #[test]
+    fn test_deser_wait() {
+        let buf = [0b11111110];
+        let res = vec![BotCommand::wait().unwrap()];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_wait() {
+        let buf = vec![0b11111110];
+        let res = vec![BotCommand::wait().unwrap()];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    #[test]
+    fn test_deser_flip() {
+        let buf = [0b11111101];
+        let res = vec![BotCommand::flip().unwrap()];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_flip() {
+        let buf = vec![0b11111101];
+        let res = vec![BotCommand::flip().unwrap()];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    // For example, SMove <12,0,0> is encoded as [00010100] [00011011] and SMove <0,0,-4> is encoded as [00110100] [00001011].
+    #[test]
+    fn test_deser_smove() {
+        let buf = [0b00010100,0b00011011,0b00110100,0b00001011];
+        let res = vec![
+            BotCommand::smove(LinearCoordDiff::Long{
+                axis: Axis::X,
+                value: 12,
+            }).unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long{
+                axis: Axis::Z,
+                value: -4,
+            }).unwrap(),
+                       ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_smove() {
+        let buf = vec![0b00010100,0b00011011,0b00110100,0b00001011];
+        let res = vec![
+            BotCommand::smove(LinearCoordDiff::Long{
+                axis: Axis::X,
+                value: 12,
+            }).unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long{
+                axis: Axis::Z,
+                value: -4,
+            }).unwrap(),
+                       ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    // For example, LMove <3,0,0> <0,-5,0> is encoded as [10011100] [00001000] and LMove <0,-2,0> <0,0,2> is encoded as [11101100] [01110011].
+    #[test]
+    fn test_deser_lmove() {
+        let buf = [0b10011100,0b00001000,0b11101100,0b01110011];
+        let res = vec![
+            BotCommand::lmove(
+                LinearCoordDiff::Short{
+                    axis: Axis::X,
+                    value: 3,
+                },
+                LinearCoordDiff::Short{
+                    axis: Axis::Y,
+                    value: -5,
+                }).unwrap(),
+            BotCommand::lmove(
+                LinearCoordDiff::Short{
+                    axis: Axis::Y,
+                    value: -2,
+                },
+                LinearCoordDiff::Short{
+                    axis: Axis::Z,
+                    value: 2,
+                }).unwrap(),
+                       ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_lmove() {
+        let buf = vec![0b10011100,0b00001000,0b11101100,0b01110011];
+        let res = vec![
+            BotCommand::lmove(
+                LinearCoordDiff::Short{
+                    axis: Axis::X,
+                    value: 3,
+                },
+                LinearCoordDiff::Short{
+                    axis: Axis::Y,
+                    value: -5,
+                }).unwrap(),
+            BotCommand::lmove(
+                LinearCoordDiff::Short{
+                    axis: Axis::Y,
+                    value: -2,
+                },
+                LinearCoordDiff::Short{
+                    axis: Axis::Z,
+                    value: 2,
+                }).unwrap(),
+                       ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+    #[test]
+    fn test_ser_lmove_short_over_range() {
+        let over = LinearCoordDiff::Short{
+            axis: Axis::Z,
+            value: 6,
+        };
+        assert!(!over.is_valid());
+        // built around `lmove` the value would wrap into the neighbouring nibble
+        let res = vec![
+            BotCommand::LMove{
+                short1: LinearCoordDiff::Short{
+                    axis: Axis::X,
+                    value: 3,
+                },
+                short2: over,
+            },
+        ];
+        match into_bytes(&res) {
+            Err(Error::LinearCoordDiffTooLong(diff)) => assert_eq!(diff, over),
+            other => panic!("unexpected {:?}", other),
+        }
+        match BotCommand::lmove(over, over) {
+            Err(Error::LinearCoordDiffTooLong(diff)) => assert_eq!(diff, over),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    // For example, FusionP <-1,1,0> is encoded as [00111111].
+    #[test]
+    fn test_deser_fus_p() {
+        let buf = [0b00111111];
+        let res = vec![
+            BotCommand::pfusion(CoordDiff(Coord {
+                x: -1,
+                y: 1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_fus_p() {
+        let buf = vec![0b00111111];
+        let res = vec![
+            BotCommand::pfusion(CoordDiff(Coord {
+                x: -1,
+                y: 1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    // For example, FusionS <1,-1,0> is encoded as [10011110].
+    #[test]
+    fn test_deser_fus_s() {
+        let buf = [0b10011110];
+        let res = vec![
+            BotCommand::sfusion(CoordDiff(Coord {
+                x: 1,
+                y: -1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_fus_s() {
+        let buf = vec![0b10011110];
+        let res = vec![
+            BotCommand::sfusion(CoordDiff(Coord {
+                x: 1,
+                y: -1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    // For example, Fission <0,0,1> 5 is encoded as [01110101] [00000101].
+    #[test]
+    fn test_deser_fission() {
+        let buf = [0b01110101,0b00000101];
+        let res = vec![
+            BotCommand::fission(CoordDiff(Coord {
+                x: 0,
+                y: 0,
+                z: 1,
+            }),5).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_fission() {
+        let buf = vec![0b01110101,0b00000101];
+        let res = vec![
+            BotCommand::fission(CoordDiff(Coord {
+                x: 0,
+                y: 0,
+                z: 1,
+            }),5).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    // For example, Fill <0,-1,0> is encoded as [01010011].
+    #[test]
+    fn test_deser_fill() {
+        let buf = [0b01010011];
+        let res = vec![
+            BotCommand::fill(CoordDiff(Coord {
+                x: 0,
+                y: -1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_byte_len_mixed() {
+        let near = CoordDiff(Coord { x: 0, y: -1, z: 0, });
+        let far = CoordDiff(Coord { x: 3, y: 0, z: -2, });
+        let cmds = vec![
+            BotCommand::flip().unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Y, value: -12, }).unwrap(),
+            BotCommand::lmove(
+                LinearCoordDiff::Short { axis: Axis::X, value: 3, },
+                LinearCoordDiff::Short { axis: Axis::Z, value: -5, },
+            ).unwrap(),
+            BotCommand::fission(near, 4).unwrap(),
+            BotCommand::fill(near).unwrap(),
+            BotCommand::gfill(near, far).unwrap(),
+            BotCommand::gvoid(near, far).unwrap(),
+            BotCommand::pfusion(near).unwrap(),
+            BotCommand::wait().unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let expected: usize = cmds.iter().map(BotCommand::encoded_len).sum();
+        assert_eq!(expected, 1 + 2 + 2 + 2 + 1 + 4 + 4 + 1 + 1 + 1);
+        assert_eq!(trace_byte_len(&cmds), expected);
+        assert_eq!(into_bytes(&cmds).unwrap().len(), expected);
+    }
+    #[test]
+    fn test_validate_iter_bad_fill() {
+        let far_off = CoordDiff(Coord { x: 1, y: 1, z: 1, });
+        let cmds = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::X, value: 4, }).unwrap(),
+            BotCommand::Fill { near: far_off, },
+            BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Z, value: 16, }, },
+            BotCommand::LMove {
+                short1: LinearCoordDiff::Long { axis: Axis::X, value: 1, },
+                short2: LinearCoordDiff::Short { axis: Axis::Y, value: 1, },
+            },
+            BotCommand::GFill { near: CoordDiff(Coord { x: 0, y: 1, z: 0, }), far: CoordDiff(Coord { x: 31, y: 0, z: 0, }), },
+            BotCommand::Halt,
+        ];
+        let res: Vec<_> = validate_iter(cmds.clone().into_iter()).collect();
+        assert_eq!(res.len(), cmds.len());
+        assert_eq!(res[0].as_ref().ok(), Some(&cmds[0]));
+        match res[1] {
+            Err(Error::CoordDiffIsNotNear) => (),
+            ref other => panic!("expected a not near error, got {:?}", other),
+        }
+        match res[2] {
+            Err(Error::LinearCoordDiffTooLong(_)) => (),
+            ref other => panic!("expected a too long error, got {:?}", other),
+        }
+        match res[3] {
+            Err(Error::RestrictedLinearCoordDiff) => (),
+            ref other => panic!("expected a restricted diff error, got {:?}", other),
+        }
+        match res[4] {
+            Err(Error::CoordDiffIsNotFar) => (),
+            ref other => panic!("expected a not far error, got {:?}", other),
+        }
+        assert_eq!(res[5].as_ref().ok(), Some(&BotCommand::Halt));
+    }
+
+    #[test]
+    fn test_decode_one_consumed() {
+        let near = CoordDiff(Coord { x: 1, y: 0, z: -1, });
+        let far = CoordDiff(Coord { x: -4, y: 2, z: 7, });
+        let cases = vec![
+            (BotCommand::halt().unwrap(), 1),
+            (BotCommand::wait().unwrap(), 1),
+            (BotCommand::flip().unwrap(), 1),
+            (BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Z, value: 15, }).unwrap(), 2),
+            (BotCommand::lmove(
+                LinearCoordDiff::Short { axis: Axis::Y, value: -5, },
+                LinearCoordDiff::Short { axis: Axis::X, value: 2, },
+            ).unwrap(), 2),
+            (BotCommand::fission(near, 7).unwrap(), 2),
+            (BotCommand::fill(near).unwrap(), 1),
+            (BotCommand::void(near).unwrap(), 1),
+            (BotCommand::pfusion(near).unwrap(), 1),
+            (BotCommand::sfusion(near).unwrap(), 1),
+            (BotCommand::gfill(near, far).unwrap(), 4),
+            (BotCommand::gvoid(near, far).unwrap(), 4),
+        ];
+        for (cmd, len) in cases {
+            let mut bytes = into_bytes(&vec![cmd]).unwrap();
+            assert_eq!(bytes.len(), len);
+            // whatever follows the command is not touched
+            bytes.extend(&[0b11111111, 0b11111110]);
+            let (decoded, consumed) = decode_one(&bytes).unwrap();
+            assert_eq!(decoded, cmd);
+            assert_eq!(consumed, len);
+            // and a command cut short is an error, not a panic
+            match decode_one(&bytes[.. len - 1]) {
+                Err(Error::DeserializeTruncated(_)) => (),
+                other => panic!("expected truncated error for {:?}, got {:?}", cmd, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_one_embedded() {
+        let cmds = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::X, value: -3, }).unwrap(),
+            BotCommand::gfill(CoordDiff(Coord { x: 0, y: 1, z: 0, }), CoordDiff(Coord { x: 5, y: 0, z: 0, })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let mut container = vec![0xde, 0xad];
+        container.extend(into_bytes(&cmds).unwrap());
+        let mut offset = 2;
+        let mut decoded = Vec::new();
+        while decoded.last() != Some(&BotCommand::Halt) {
+            let (cmd, consumed) = decode_one(&container[offset ..]).unwrap();
+            decoded.push(cmd);
+            offset += consumed;
+        }
+        assert_eq!(decoded, cmds);
+        assert_eq!(offset, container.len());
+        assert_eq!(from_bytes(&container[2 ..]).unwrap(), cmds);
+    }
+
+    #[test]
+    fn test_ser_fill() {
+        let buf = vec![0b01010011];
+        let res = vec![
+            BotCommand::fill(CoordDiff(Coord {
+                x: 0,
+                y: -1,
+                z: 0,
+            })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+
+    //For example, Void <1,0,1> is encoded as [10111010].
+    #[test]
+    fn test_deser_void() {
+        let buf = [0b10111010];
+        let res = vec![
+            BotCommand::void(CoordDiff(Coord {
+                x: 1,
+                y: 0,
+                z: 1,
+            })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_void() {
+        let buf = vec![0b10111010];
+        let res = vec![
+            BotCommand::void(CoordDiff(Coord {
+                x: 1,
+                y: 0,
+                z: 1,
+            })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    //For example, GFill <0,-1,0> <10,-15,20> is encoded as [01010001] [00101000] [00001111] [00110010].
+    #[test]
+    fn test_deser_gfill() {
+        let buf = [0b01010001,0b00101000,0b00001111,0b00110010];
+        let res = vec![
+            BotCommand::gfill(
+                CoordDiff(Coord {
+                    x: 0,
+                    y: -1,
+                    z: 0,
+                }),
+                CoordDiff(Coord {
+                    x: 10,
+                    y: -15,
+                    z: 20,
+                })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_gfill() {
+        let buf = vec![0b01010001,0b00101000,0b00001111,0b00110010];
+        let res = vec![
+            BotCommand::gfill(
+                CoordDiff(Coord {
+                    x: 0,
+                    y: -1,
+                    z: 0,
+                }),
+                CoordDiff(Coord {
+                    x: 10,
+                    y: -15,
+                    z: 20,
+                })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+
+    //For example, GVoid <1,0,0> <5,5,-5> is encoded as [10110000] [00100011] [00100011] [00011001].
+    #[test]
+    fn test_deser_gvoid() {
+        let buf = [0b10110000,0b00100011,0b00100011,0b00011001];
+        let res = vec![
+            BotCommand::gvoid(
+                CoordDiff(Coord {
+                    x: 1,
+                    y: 0,
+                    z: 0,
+                }),
+                CoordDiff(Coord {
+                    x: 5,
+                    y: 5,
+                    z: -5,
+                })).unwrap()
+                ];
+        assert_eq!(res,from_bytes(&buf).unwrap());
+    }
+    #[test]
+    fn test_ser_gvoid() {
+        let buf = vec![0b10110000,0b00100011,0b00100011,0b00011001];
+        let res = vec![
+            BotCommand::gvoid(
+                CoordDiff(Coord {
+                    x: 1,
+                    y: 0,
+                    z: 0,
+                }),
+                CoordDiff(Coord {
+                    x: 5,
+                    y: 5,
+                    z: -5,
+                })).unwrap()
+                ];
+        assert_eq!(buf,into_bytes(&res).unwrap());
+    }
+    
+
+    #[test]
+    fn test_la003_nbt() {
+        let buf = vec![
+            0xfe, 0x34, 0x10, 0x24, 0x10, 0x14, 0x10, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34,
+            0x10, 0x34, 0x10, 0x14, 0x10, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34,
+            0x0e, 0x34, 0x0e, 0x14, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x34, 0x10, 0x14, 0x10, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x14, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x14, 0x10, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x14, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x14, 0x10, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x14, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34,
+            0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10,
+            0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x14, 0x10, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x14, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x14, 0x10, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x14, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x14, 0x10, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x14, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x14, 0x10, 0x34, 0x0e, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53,
+            0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34, 0x0e, 0x14, 0x10, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x53,
+            0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34,
+            0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10, 0x53, 0x34, 0x10,
+            0x53, 0x34, 0x10, 0x34, 0x10, 0x34, 0x10, 0x14, 0x10, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x34,
+            0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e,
+            0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x53, 0x34, 0x0e, 0x34,
+            0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x34, 0x0e, 0x24, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14,
+            0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14,
+            0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0x14, 0x0e, 0xfe, 0xff
+                ];
+        let cmds = from_bytes(&buf).unwrap();
+        let buf2 = into_bytes(&cmds).unwrap();
+        assert_eq!(buf,buf2);
+    }
+
+    #[test]
+    fn test_group_by_ticks_fa001() {
+        use junk::FA001_MULTIBOT_NBT;
+        let cmds = from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let ticks = group_by_ticks(&cmds);
+        assert_eq!(ticks.len(), 212);
+        assert_eq!(ticks.iter().map(|t| t.len()).sum::<usize>(), cmds.len());
+        assert_eq!(ticks[0].len(), 1);
+        assert_eq!(ticks.last().unwrap(), &vec![BotCommand::Halt]);
+    }
+
+    #[test]
+    fn test_disassemble_lanes_two_bots() {
+        let near = |x, y, z| CoordDiff(Coord { x, y, z, });
+        let cmds = vec![
+            BotCommand::Fission { near: near(1, 0, 0), split_m: 0, },
+            BotCommand::Fill { near: near(0, 0, 1), },
+            BotCommand::Wait,
+            BotCommand::FusionP { near: near(1, 0, 0), },
+            BotCommand::FusionS { near: near(-1, 0, 0), },
+            BotCommand::Halt,
+        ];
+        assert_eq!(disassemble_lanes(&cmds), "\
+            tick | bot 1             | bot 2\n\
+            1    | Fission <1,0,0> 0 |\n\
+            2    | Fill <0,0,1>      | Wait\n\
+            3    | FusionP <1,0,0>   | FusionS <-1,0,0>\n\
+            4    | Halt              |\n");
+    }
+
+    #[test]
+    fn test_disassemble_lanes_fa001() {
+        use junk::FA001_MULTIBOT_NBT;
+        let cmds = from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let table = disassemble_lanes(&cmds);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 1 + 212);
+        // every bot of the trace gets a column
+        assert_eq!(lines[0].split(" | ").count(), 1 + 6);
+        assert!(lines[0].starts_with("tick | bot 1 "));
+        assert!(lines[212].starts_with("212  | Halt"));
+    }
+
+    #[test]
+    fn test_trace_file_gzipped() {
+        use std::{env, process};
+        use junk::FA001_MULTIBOT_NBT;
+        let cmds = from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let path = |name: &str| env::temp_dir().join(format!("icfpc2018_trace_{}_{}", process::id(), name));
+        let (raw_path, gz_path) = (path("FA001.nbt"), path("FA001.nbt.gz"));
+        write_trace_file(&raw_path, &cmds).unwrap();
+        write_trace_file(&gz_path, &cmds).unwrap();
+        assert_eq!(fs::read(&raw_path).unwrap(), FA001_MULTIBOT_NBT.to_vec());
+        assert_eq!(&fs::read(&gz_path).unwrap()[.. 2], &[0x1f, 0x8b]);
+        assert_eq!(read_trace_file(&raw_path).unwrap(), cmds);
+        assert_eq!(read_trace_file(&gz_path).unwrap(), cmds);
+        fs::remove_file(&raw_path).unwrap();
+        fs::remove_file(&gz_path).unwrap();
+    }
+
+    #[test]
+    fn test_metrics_fa001() {
+        use junk::FA001_MULTIBOT_NBT;
+        let cmds = from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let metrics = metrics(&cmds);
+        assert_eq!(metrics.commands, 1212);
+        assert_eq!(metrics.byte_len, FA001_MULTIBOT_NBT.len());
+        assert_eq!(metrics.ticks, 212);
+        assert_eq!(metrics.peak_bots, 6);
+        assert_eq!(metrics.flips, 2);
+        assert_eq!(metrics.counts["Fill"], 511);
+        assert_eq!(metrics.counts["Fission"], 5);
+        assert_eq!(metrics.counts["FusionS"], 5);
+        assert_eq!(metrics.counts.get("GFill"), None);
+        assert_eq!(metrics.counts.values().sum::<usize>(), cmds.len());
+    }
+
+    #[test]
+    fn test_invert_smove() {
+        let cmd = BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Y, value: 7 }).unwrap();
+        assert_eq!(invert(&cmd).unwrap(), BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Y, value: -7 }).unwrap());
+        assert_eq!(invert(&invert(&cmd).unwrap()).unwrap(), cmd);
+    }
+
+    #[test]
+    fn test_invert_lmove() {
+        let cmd = BotCommand::lmove(
+            LinearCoordDiff::Short{ axis: Axis::X, value: 3 },
+            LinearCoordDiff::Short{ axis: Axis::Z, value: -2 }).unwrap();
+        let exp = BotCommand::lmove(
+            LinearCoordDiff::Short{ axis: Axis::Z, value: 2 },
+            LinearCoordDiff::Short{ axis: Axis::X, value: -3 }).unwrap();
+        assert_eq!(invert(&cmd).unwrap(), exp);
+    }
+
+    #[test]
+    fn test_invert_fill_void() {
+        let near = CoordDiff(Coord{ x: 0, y: -1, z: 1 });
+        assert_eq!(invert(&BotCommand::fill(near).unwrap()).unwrap(), BotCommand::void(near).unwrap());
+        assert_eq!(invert(&BotCommand::void(near).unwrap()).unwrap(), BotCommand::fill(near).unwrap());
+        assert!(invert(&BotCommand::halt().unwrap()).is_err());
+        assert!(invert(&BotCommand::flip().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_diff_optimized() {
+        let smove = |value| BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Y, value }).unwrap();
+        let fill = BotCommand::fill(CoordDiff(Coord{ x: 0, y: -1, z: 0 })).unwrap();
+        let trace = vec![
+            BotCommand::flip().unwrap(),
+            smove(1),
+            fill,
+            smove(1),
+            BotCommand::flip().unwrap(),
+            BotCommand::wait().unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let optimized = vec![
+            BotCommand::wait().unwrap(),
+            smove(1),
+            fill,
+            smove(1),
+            BotCommand::wait().unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        assert_eq!(diff(&trace, &trace), vec![]);
+        assert_eq!(diff(&trace, &optimized), vec![
+            (0, Some(BotCommand::Flip), Some(BotCommand::Wait)),
+            (4, Some(BotCommand::Flip), Some(BotCommand::Wait)),
+            (5, Some(BotCommand::Wait), Some(BotCommand::Halt)),
+            (6, Some(BotCommand::Halt), None),
+        ]);
+        assert_eq!(diff(&optimized, &trace)[3], (6, None, Some(BotCommand::Halt)));
+    }
+
+    #[test]
+    fn test_affected_regions() {
+        let from = Coord{ x: 3, y: 3, z: 3 };
+        let region = |min: (isize, isize, isize), max: (isize, isize, isize)| Region {
+            min: Coord{ x: min.0, y: min.1, z: min.2 },
+            max: Coord{ x: max.0, y: max.1, z: max.2 },
+        };
+        let short = |axis, value| LinearCoordDiff::Short{ axis, value };
+
+        let smove = BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Y, value: -3 }).unwrap();
+        assert_eq!(smove.affected_regions(from), (region((3, 0, 3), (3, 3, 3)), None));
+
+        // the first leg starts at the bot, the second one at the turn
+        let lmove = BotCommand::lmove(short(Axis::X, 2), short(Axis::Z, -3)).unwrap();
+        assert_eq!(lmove.affected_regions(from), (region((3, 3, 3), (5, 3, 3)), Some(region((5, 3, 0), (5, 3, 3)))));
+        let lmove = BotCommand::lmove(short(Axis::Z, -3), short(Axis::X, 2)).unwrap();
+        assert_eq!(lmove.affected_regions(from), (region((3, 3, 0), (3, 3, 3)), Some(region((3, 3, 0), (5, 3, 0)))));
+
+        // legs along the same axis
+        let lmove = BotCommand::lmove(short(Axis::X, 1), short(Axis::X, 2)).unwrap();
+        assert_eq!(lmove.affected_regions(from), (region((3, 3, 3), (6, 3, 3)), None));
+        let lmove = BotCommand::lmove(short(Axis::X, 2), short(Axis::X, -4)).unwrap();
+        assert_eq!(lmove.affected_regions(from), (region((1, 3, 3), (5, 3, 3)), None));
+
+        let fission = BotCommand::fission(CoordDiff(Coord{ x: 1, y: -1, z: 0 }), 3).unwrap();
+        assert_eq!(fission.affected_regions(from), (region((3, 3, 3), (3, 3, 3)), Some(region((4, 2, 3), (4, 2, 3)))));
+
+        // the bot and the voxel it works on, not the box between them
+        let fill = BotCommand::fill(CoordDiff(Coord{ x: 1, y: -1, z: 0 })).unwrap();
+        assert_eq!(fill.affected_regions(from), (region((3, 3, 3), (3, 3, 3)), Some(region((4, 2, 3), (4, 2, 3)))));
+
+        let gfill = BotCommand::gfill(CoordDiff(Coord{ x: 0, y: 0, z: 1 }), CoordDiff(Coord{ x: 2, y: 0, z: 3 })).unwrap();
+        assert_eq!(gfill.affected_regions(from), (region((3, 3, 3), (3, 3, 3)), Some(region((3, 3, 4), (5, 3, 7)))));
+        assert_eq!(BotCommand::Wait.affected_regions(from), (region((3, 3, 3), (3, 3, 3)), None));
+    }
+
+    #[test]
+    fn test_truncate_at_halt() {
+        let halt = BotCommand::halt().unwrap();
+        let wait = BotCommand::wait().unwrap();
+        let flip = BotCommand::flip().unwrap();
+        let near = CoordDiff(Coord{ x: 1, y: 0, z: 0 });
+        let fission = BotCommand::fission(near, 0).unwrap();
+        let pfusion = BotCommand::pfusion(near).unwrap();
+        let sfusion = BotCommand::sfusion(CoordDiff(Coord{ x: -1, y: 0, z: 0 })).unwrap();
+
+        let mut cmds = vec![flip, flip, halt, wait, halt, flip];
+        truncate_at_halt(&mut cmds);
+        assert_eq!(cmds, vec![flip, flip, halt]);
+
+        // the Halt in the two bots tick does not end the trace
+        let mut cmds = vec![fission, halt, wait, pfusion, sfusion, halt, wait, wait];
+        truncate_at_halt(&mut cmds);
+        assert_eq!(cmds, vec![fission, halt, wait, pfusion, sfusion, halt]);
+
+        let mut cmds = vec![wait, flip];
+        truncate_at_halt(&mut cmds);
+        assert_eq!(cmds, vec![wait, flip]);
+    }
+
+    #[test]
+    fn test_concat_aligned_build_demolish() {
+        use coord::{Matrix, Resolution};
+        use state::State;
+
+        let x = |value| BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::X, value }).unwrap();
+        let front = CoordDiff(Coord{ x: 0, y: 0, z: 1 });
+        let build = vec![
+            BotCommand::fission(CoordDiff(Coord{ x: 1, y: 0, z: 0 }), 0).unwrap(),
+            BotCommand::fill(front).unwrap(),
+            BotCommand::fill(front).unwrap(),
+            BotCommand::pfusion(CoordDiff(Coord{ x: 1, y: 0, z: 0 })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord{ x: -1, y: 0, z: 0 })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let demolish = vec![
+            BotCommand::void(front).unwrap(),
+            x(1),
+            BotCommand::void(front).unwrap(),
+            x(-1),
+            BotCommand::halt().unwrap(),
+        ];
+        let trace = concat_aligned(&[build.clone(), demolish.clone()]).unwrap();
+        assert_eq!(trace.len(), build.len() + demolish.len() - 1);
+        assert_eq!(trace.iter().filter(|&&c| c == BotCommand::Halt).count(), 1);
+
+        let empty = Matrix::new(Resolution(3));
+        let mut state = State::new(empty.clone(), vec![]);
+        assert_eq!(state.run_mut(trace), Ok(()));
+        assert!(state.is_halt());
+        assert!(state.model_diff(&empty).is_empty());
+
+        assert_eq!(concat_aligned(&[]).unwrap(), vec![]);
+        assert_eq!(concat_aligned(&[demolish.clone()]).unwrap(), demolish);
+    }
+
+    #[test]
+    fn test_concat_aligned_seams() {
+        let halt = BotCommand::halt().unwrap();
+        let wait = BotCommand::wait().unwrap();
+        let fission = BotCommand::fission(CoordDiff(Coord{ x: 1, y: 0, z: 0 }), 0).unwrap();
+        match concat_aligned(&[vec![halt], vec![wait]]) {
+            Err(Error::ConcatNoHalt{ trace: 1 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match concat_aligned(&[vec![], vec![halt]]) {
+            Err(Error::ConcatNoHalt{ trace: 0 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match concat_aligned(&[vec![fission, wait, halt], vec![halt]]) {
+            Err(Error::ConcatBotsAtSeam{ trace: 0, bots: 2 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        match concat_aligned(&[vec![halt], vec![halt, wait, wait]]) {
+            Err(Error::ConcatTrailingCommands{ trace: 1, count: 2 }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hex_base64_round_trip() {
+        let near = CoordDiff(Coord{ x: 0, y: -1, z: 1 });
+        let far = CoordDiff(Coord{ x: 5, y: 0, z: 5 });
+        let cmds = vec![
+            BotCommand::flip().unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Z, value: -15 }).unwrap(),
+            BotCommand::lmove(LinearCoordDiff::Short{ axis: Axis::X, value: 3 }, LinearCoordDiff::Short{ axis: Axis::Y, value: -5 }).unwrap(),
+            BotCommand::fission(near, 7).unwrap(),
+            BotCommand::fill(near).unwrap(),
+            BotCommand::gvoid(near, far).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let bytes = into_bytes(&cmds).unwrap();
+        let hex = to_hex(&cmds).unwrap();
+        assert_eq!(hex.len(), bytes.len() * 2);
+        assert_eq!(from_hex(&hex).unwrap(), cmds);
+        assert_eq!(from_hex(&hex.to_uppercase()).unwrap(), cmds);
+        assert_eq!(from_hex("fd\n ff").unwrap(), vec![BotCommand::Flip, BotCommand::Halt]);
+        let base64 = to_base64(&cmds).unwrap();
+        assert_eq!(base64.len() % 4, 0);
+        assert_eq!(from_base64(&base64).unwrap(), cmds);
+        assert_eq!(to_base64(&[BotCommand::Halt]).unwrap(), "/w==");
+        assert_eq!(to_base64(&[BotCommand::Flip, BotCommand::Halt]).unwrap(), "/f8=");
+        assert_eq!(to_base64(&[BotCommand::Wait, BotCommand::Flip, BotCommand::Halt]).unwrap(), "/v3/");
+        assert_eq!(from_base64("/v3/\n/w==").unwrap(), vec![BotCommand::Wait, BotCommand::Flip, BotCommand::Halt, BotCommand::Halt]);
+        assert_eq!(to_hex(&[]).unwrap(), "");
+        assert_eq!(from_base64("").unwrap(), vec![]);
+        match from_hex("ffz") { Err(Error::DeserializeHexDigit('z')) => (), other => panic!("unexpected {:?}", other) }
+        match from_hex("fff") { Err(Error::DeserializeHexOddLength) => (), other => panic!("unexpected {:?}", other) }
+        match from_base64("/w=") { Err(Error::DeserializeBase64Length) => (), other => panic!("unexpected {:?}", other) }
+        match from_base64("/w==/w==") { Err(Error::DeserializeBase64Length) => (), other => panic!("unexpected {:?}", other) }
+        match from_base64("/w!=") { Err(Error::DeserializeBase64Char('!')) => (), other => panic!("unexpected {:?}", other) }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_round_trip() {
+        let near = CoordDiff(Coord{ x: 0, y: -1, z: 1 });
+        let far = CoordDiff(Coord{ x: 5, y: 0, z: 5 });
+        let cmds = vec![
+            BotCommand::halt().unwrap(),
+            BotCommand::wait().unwrap(),
+            BotCommand::flip().unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Z, value: -15 }).unwrap(),
+            BotCommand::lmove(LinearCoordDiff::Short{ axis: Axis::X, value: 3 }, LinearCoordDiff::Short{ axis: Axis::Y, value: -5 }).unwrap(),
+            BotCommand::fission(near, 7).unwrap(),
+            BotCommand::fill(near).unwrap(),
+            BotCommand::pfusion(near).unwrap(),
+            BotCommand::sfusion(near).unwrap(),
+            BotCommand::void(near).unwrap(),
+            BotCommand::gfill(near, far).unwrap(),
+            BotCommand::gvoid(near, far).unwrap(),
+        ];
+        let json = to_json(&cmds);
+        assert_eq!(from_json(&json).unwrap(), cmds);
+        assert_eq!(from_json("[]").unwrap(), vec![]);
+        assert!(from_json("[{\"Fill\":{\"near\":{\"x\":0,\"y\":-2,\"z\":0}}}]").is_err());
+        assert!(from_json("not json").is_err());
+    }
+}
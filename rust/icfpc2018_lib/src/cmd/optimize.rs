@@ -1,10 +1,13 @@
-use std;
 use std::collections::VecDeque;
 
-use icfpc2018_lib::coord::{LinearCoordDiff,Axis,M};
-use kernel::cmd::BotCommand;
+use coord::{self,LinearCoordDiff,Axis,M};
+use super::BotCommand;
 
-#[derive(Debug,Clone,Copy)]
+// Single bot command stream optimizer: consecutive moves are accumulated up to the next
+// non-move command (`Wait`s are dropped), then same-axis moves are collapsed, unit
+// back-and-forth detours are cancelled and short move pairs are packed into LMoves.
+
+#[derive(Debug,Clone,Copy,PartialEq)]
 struct Move {
     axis: Axis,
     value: M,
@@ -16,59 +19,35 @@ impl Move {
             value: l.get_value(),
         }
     }
-    /*fn is_lld(&self) -> bool {
-        return (self.value>=-15)&&(self.value<=15);
-    }*/
     fn to_llds(&self) -> Vec<LinearCoordDiff> {
-        let dest = if self.value>0 {1} else {-1};
-        let mut aval = self.value.abs();
-        let mut res = Vec::new();
-        while aval>0 {
-            if aval>15 {
-                res.push(LinearCoordDiff::Long{ axis: self.axis, value: dest*15 });
-            } else {
-                res.push(LinearCoordDiff::Long{ axis: self.axis, value: dest*aval });
-            }
-            aval -= 15;
-        }
-        res
-    }
-    /*
-    fn to_lld(&self) -> LinearCoordDiff {
-        LinearCoordDiff::Long{ axis: self.axis, value: self.value }
+        LinearCoordDiff::Long{ axis: self.axis, value: self.value }.split_into_smoves()
     }
-    fn is_sld(&self) -> bool {
-        return (self.value>=-5)&&(self.value<=5);
-    }
-    fn to_sld(&self) -> LinearCoordDiff {
-        LinearCoordDiff::Short{ axis: self.axis, value: self.value }
-    }*/
 }
 
 fn optimize_moves(movings: &mut Vec<Move>) {
     fn get_diff(v1: M, v2: M) -> M {
         if v1.abs()<=v2.abs() { v1 } else { -v2 }
     }
-    
+
     let mut opt = true;
-    while opt {
+    while opt && movings.len()>1 {
         opt = false;
+        // a unit step between two opposite moves on another axis: shorten both of them
         for i in 1 .. movings.len()-1 {
             if ((movings[i].value == 1)||(movings[i].value == -1))&&
                 (movings[i-1].axis == movings[i+1].axis)&&((movings[i-1].value * movings[i+1].value)<0)
             {
-                let d =  get_diff(movings[i-1].value,movings[i+1].value);
+                let d = get_diff(movings[i-1].value,movings[i+1].value);
                 movings[i-1].value -= d;
                 movings[i+1].value += d;
                 opt = true;
             }
         }
         if opt {
-            let mut tmp = movings.iter().cloned().filter(|mv|mv.value!=0).collect::<Vec<_>>();
-            std::mem::swap(&mut tmp, movings);
+            movings.retain(|mv| mv.value != 0);
         }
         let mut sopt = false;
-        for i in 0 .. movings.len()-1 {
+        for i in 0 .. movings.len().saturating_sub(1) {
             if movings[i].axis == movings[i+1].axis {
                 movings[i+1].value += movings[i].value;
                 movings[i].value = 0;
@@ -76,8 +55,7 @@ fn optimize_moves(movings: &mut Vec<Move>) {
             }
         }
         if sopt {
-            let mut tmp = movings.iter().cloned().filter(|mv|mv.value!=0).collect::<Vec<_>>();
-            std::mem::swap(&mut tmp, movings);
+            movings.retain(|mv| mv.value != 0);
         }
     }
 }
@@ -85,20 +63,13 @@ fn optimize_moves(movings: &mut Vec<Move>) {
 fn optimize_lld_pairs(cmds: &mut Vec<BotCommand>) {
     loop {
         let mut tmp = None;
-        for i in 0 .. cmds.len()-1 {
+        for i in 0 .. cmds.len().saturating_sub(1) {
             match (cmds[i],cmds[i+1]) {
-                (BotCommand::SMove{ long: long1 },BotCommand::SMove{ long: long2 }) if (long1.get_value().abs()<=5)&&(long2.get_value().abs()<=5) => {
-                    let short1 = LinearCoordDiff::Short{
-                        axis: long1.get_axis(),
-                        value: long1.get_value(),
-                    };
-                    let short2 = LinearCoordDiff::Short{
-                        axis: long2.get_axis(),
-                        value: long2.get_value(),
-                    };
-                    tmp = Some((i,BotCommand::lmove(short1,short2).unwrap()));
-                    
-                    break;    
+                (BotCommand::SMove{ long: long1 },BotCommand::SMove{ long: long2 }) => {
+                    if let Some((short1,short2)) = coord::pair_into_lmove(long1,long2) {
+                        tmp = Some((i,BotCommand::lmove(short1,short2).unwrap()));
+                        break;
+                    }
                 },
                 (_,_) => continue,
             }
@@ -128,18 +99,15 @@ impl<I> Optimizer<I> {
     }
     pub fn add_move(&mut self, d: &LinearCoordDiff) {
         let mov = Move::from_linear(d);
-        if self.movings.len()>0 {
-            let idx = self.movings.len()-1;        
-            if self.movings[idx].axis == mov.axis {
-                self.movings[idx].value += mov.value
-            } else {
-                self.movings.push(mov)
+        if let Some(last) = self.movings.last_mut() {
+            if last.axis == mov.axis {
+                last.value += mov.value;
+                return;
             }
-        } else {
-            self.movings.push(mov)
         }
+        self.movings.push(mov)
     }
-    
+
     pub fn flush_moves(&mut self) -> impl Iterator<Item = BotCommand> {
         optimize_moves(&mut self.movings);
         let mut res = Vec::new();
@@ -156,20 +124,17 @@ impl<I> Iterator for Optimizer<I>
     where I: Iterator<Item = BotCommand>
 {
     type Item = BotCommand;
-    fn next(&mut self) -> Option<Self::Item> {        
+    fn next(&mut self) -> Option<Self::Item> {
         loop {
             if self.buffer.len()>0 {
                 return self.buffer.pop_front();
             }
             match self.cmds.next() {
-                None => {
-                    if self.movings.len()>0 {
-                        let moves = self.flush_moves();
-                        self.buffer.extend(moves);
-                    } else {
-                        return None;
-                    }
+                None if self.movings.len()>0 => {
+                    let moves = self.flush_moves();
+                    self.buffer.extend(moves);
                 },
+                None => return None,
                 Some(BotCommand::Wait) => continue,
                 c @ Some(BotCommand::Halt) |
                 c @ Some(BotCommand::Flip) |
@@ -192,63 +157,59 @@ impl<I> Iterator for Optimizer<I>
                     self.add_move(&long);
                 },
                 Some(BotCommand::LMove{ short1, short2 }) => {
-                     self.add_move(&short1);
-                     self.add_move(&short2);  
+                    self.add_move(&short1);
+                    self.add_move(&short2);
                 },
             }
         }
     }
 }
 
-
 #[cfg(test)]
 mod test {
+    use coord::{Coord,CoordDiff};
     use super::*;
-    
+
     #[test]
     fn test_opt_moves1() {
+        // X:5 Y:1 X:-5 -> the unit step between opposite X moves cancels them entirely: Y:1
         let mut v = vec![
             Move{ axis: Axis::X, value: 5 },
             Move{ axis: Axis::Y, value: 1 },
             Move{ axis: Axis::X, value: -5 },
             ];
-        println!("");
-        println!("{:?}",v);
         optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+        assert_eq!(v, vec![Move{ axis: Axis::Y, value: 1 }]);
     }
 
     #[test]
     fn test_opt_moves2() {
+        // X:-3 Y:1 X:5 -> the shorter X:-3 is cancelled against X:5 leaving X:2 after the step: Y:1 X:2
         let mut v = vec![
             Move{ axis: Axis::X, value: -3 },
             Move{ axis: Axis::Y, value: 1 },
             Move{ axis: Axis::X, value: 5 },
             ];
-        println!("");
-        println!("{:?}",v);
         optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+        assert_eq!(v, vec![Move{ axis: Axis::Y, value: 1 }, Move{ axis: Axis::X, value: 2 }]);
     }
 
     #[test]
     fn test_opt_moves3() {
+        // X:-6 Y:1 X:5 -> the shorter X:5 is cancelled against X:-6 leaving X:-1 before the step: X:-1 Y:1
         let mut v = vec![
             Move{ axis: Axis::X, value: -6 },
             Move{ axis: Axis::Y, value: 1 },
             Move{ axis: Axis::X, value: 5 },
             ];
-        println!("");
-        println!("{:?}",v);
         optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+        assert_eq!(v, vec![Move{ axis: Axis::X, value: -1 }, Move{ axis: Axis::Y, value: 1 }]);
     }
 
     #[test]
     fn test_opt_moves4() {
+        // Z:2 X:1 Z:-7 Y:1 Z:7 X:-1 Z:-2 -> the Z detours cancel around each unit step, then the
+        // now adjacent X:1 X:-1 collapse to nothing, leaving only the net displacement: Y:1
         let mut v = vec![
             Move { axis: Axis::Z, value: 2 },
             Move { axis: Axis::X, value: 1 },
@@ -258,12 +219,34 @@ mod test {
             Move { axis: Axis::X, value: -1 },
             Move { axis: Axis::Z, value: -2 }
             ];
-        println!("");
-        println!("{:?}",v);
         optimize_moves(&mut v);
-        println!("{:?}",v);
-        assert_eq!(1,0);
+        assert_eq!(v, vec![Move{ axis: Axis::Y, value: 1 }]);
     }
 
-
+    #[test]
+    fn test_optimizer_stream() {
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value }).unwrap();
+        let near = CoordDiff(Coord { x: 0, y: -1, z: 0 });
+        let cmds = vec![
+            smove(Axis::X, 3),
+            BotCommand::wait().unwrap(),
+            smove(Axis::X, 14),
+            BotCommand::void(near).unwrap(),
+            smove(Axis::Z, 2),
+            smove(Axis::Y, -3),
+            BotCommand::fill(near).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let optimized: Vec<_> = Optimizer::new(cmds.into_iter()).collect();
+        assert_eq!(optimized, vec![
+            smove(Axis::X, 15),
+            smove(Axis::X, 2),
+            BotCommand::void(near).unwrap(),
+            BotCommand::lmove(
+                LinearCoordDiff::Short { axis: Axis::Z, value: 2 },
+                LinearCoordDiff::Short { axis: Axis::Y, value: -3 }).unwrap(),
+            BotCommand::fill(near).unwrap(),
+            BotCommand::halt().unwrap(),
+        ]);
+    }
 }
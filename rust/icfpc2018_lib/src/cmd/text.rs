@@ -0,0 +1,244 @@
+use coord::{LinearCoordDiff,CoordDiff,Coord,Axis};
+use super::BotCommand;
+
+// One command per line, e.g. `SMove <0,0,5>`, `LMove <1,0,0> <0,0,-2>`,
+// `Fission <1,0,0> 3`, `GFill <0,-1,0> <5,0,5>`. Text after `#` is ignored.
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownCommand { line: usize, name: String, },
+    InvalidArgsCount { line: usize, expected: usize, given: usize, },
+    InvalidCoordDiff { line: usize, arg: String, },
+    InvalidLinearCoordDiff { line: usize, arg: String, },
+    InvalidSplitM { line: usize, arg: String, },
+    Command { line: usize, error: super::Error, },
+}
+
+pub fn parse_text(text: &str) -> Vec<Result<BotCommand,Error>> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("")))
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(line_no, line)| parse_line(line_no, line))
+        .collect()
+}
+
+// The line `parse_text` reads back as `cmd`.
+pub fn format_command(cmd: &BotCommand) -> String {
+    let diff = |&CoordDiff(Coord { x, y, z, }): &CoordDiff| format!("<{},{},{}>", x, y, z);
+    let linear = |lcd: &LinearCoordDiff| diff(&lcd.to_coord_diff());
+    match *cmd {
+        BotCommand::Halt | BotCommand::Wait | BotCommand::Flip =>
+            cmd.name().to_string(),
+        BotCommand::SMove { ref long, } =>
+            format!("{} {}", cmd.name(), linear(long)),
+        BotCommand::LMove { ref short1, ref short2, } =>
+            format!("{} {} {}", cmd.name(), linear(short1), linear(short2)),
+        BotCommand::Fission { ref near, split_m, } =>
+            format!("{} {} {}", cmd.name(), diff(near), split_m),
+        BotCommand::Fill { ref near, } |
+        BotCommand::Void { ref near, } |
+        BotCommand::FusionP { ref near, } |
+        BotCommand::FusionS { ref near, } =>
+            format!("{} {}", cmd.name(), diff(near)),
+        BotCommand::GFill { ref near, ref far, } |
+        BotCommand::GVoid { ref near, ref far, } =>
+            format!("{} {} {}", cmd.name(), diff(near), diff(far)),
+    }
+}
+
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+    for c in line.chars() {
+        match c {
+            '<' => {
+                in_brackets = true;
+                current.push(c);
+            },
+            '>' => {
+                in_brackets = false;
+                current.push(c);
+            },
+            c if c.is_whitespace() && !in_brackets => {
+                if !current.is_empty() {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+            },
+            c if c.is_whitespace() =>
+                (),
+            c =>
+                current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_coord_diff(line: usize, arg: &str) -> Result<CoordDiff,Error> {
+    let invalid = || Error::InvalidCoordDiff { line, arg: arg.to_string(), };
+    if !arg.starts_with('<') || !arg.ends_with('>') {
+        return Err(invalid());
+    }
+    let values = arg[1 .. arg.len() - 1]
+        .split(',')
+        .map(|v| v.parse::<isize>())
+        .collect::<Result<Vec<_>,_>>()
+        .map_err(|_| invalid())?;
+    match values.as_slice() {
+        &[x, y, z] => Ok(CoordDiff(Coord { x, y, z, })),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_linear_coord_diff(line: usize, arg: &str, long: bool) -> Result<LinearCoordDiff,Error> {
+    let CoordDiff(Coord { x, y, z, }) = parse_coord_diff(line, arg)?;
+    let (axis, value) = match (x, y, z) {
+        (x, 0, 0) if x != 0 => (Axis::X, x),
+        (0, y, 0) if y != 0 => (Axis::Y, y),
+        (0, 0, z) if z != 0 => (Axis::Z, z),
+        _ => return Err(Error::InvalidLinearCoordDiff { line, arg: arg.to_string(), }),
+    };
+    if long {
+        Ok(LinearCoordDiff::Long { axis, value, })
+    } else {
+        Ok(LinearCoordDiff::Short { axis, value, })
+    }
+}
+
+fn parse_line(line: usize, text: &str) -> Result<BotCommand,Error> {
+    let tokens = tokenize(text);
+    let name = &tokens[0];
+    let args = &tokens[1 ..];
+    let expect_args = |expected| if args.len() == expected {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgsCount { line, expected, given: args.len(), })
+    };
+    let cmd = match name.as_str() {
+        "Halt" => {
+            expect_args(0)?;
+            BotCommand::halt()
+        },
+        "Wait" => {
+            expect_args(0)?;
+            BotCommand::wait()
+        },
+        "Flip" => {
+            expect_args(0)?;
+            BotCommand::flip()
+        },
+        "SMove" => {
+            expect_args(1)?;
+            BotCommand::smove(parse_linear_coord_diff(line, &args[0], true)?)
+        },
+        "LMove" => {
+            expect_args(2)?;
+            BotCommand::lmove(
+                parse_linear_coord_diff(line, &args[0], false)?,
+                parse_linear_coord_diff(line, &args[1], false)?,
+            )
+        },
+        "Fission" => {
+            expect_args(2)?;
+            let split_m = args[1].parse()
+                .map_err(|_| Error::InvalidSplitM { line, arg: args[1].clone(), })?;
+            BotCommand::fission(parse_coord_diff(line, &args[0])?, split_m)
+        },
+        "Fill" => {
+            expect_args(1)?;
+            BotCommand::fill(parse_coord_diff(line, &args[0])?)
+        },
+        "Void" => {
+            expect_args(1)?;
+            BotCommand::void(parse_coord_diff(line, &args[0])?)
+        },
+        "FusionP" => {
+            expect_args(1)?;
+            BotCommand::pfusion(parse_coord_diff(line, &args[0])?)
+        },
+        "FusionS" => {
+            expect_args(1)?;
+            BotCommand::sfusion(parse_coord_diff(line, &args[0])?)
+        },
+        "GFill" => {
+            expect_args(2)?;
+            BotCommand::gfill(parse_coord_diff(line, &args[0])?, parse_coord_diff(line, &args[1])?)
+        },
+        "GVoid" => {
+            expect_args(2)?;
+            BotCommand::gvoid(parse_coord_diff(line, &args[0])?, parse_coord_diff(line, &args[1])?)
+        },
+        _ =>
+            return Err(Error::UnknownCommand { line, name: name.clone(), }),
+    };
+    cmd.map_err(|error| Error::Command { line, error, })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_all_commands() {
+        let text = "
+            # comment line
+            Halt
+            Wait
+            Flip             # trailing comment
+            SMove <0,0,-12>
+            LMove <3,0,0> <0, 2, 0>
+            Fission <1,0,0> 5
+            Fill <0,-1,0>
+            Void <0,-1,1>
+            FusionP <1,1,0>
+            FusionS <-1,-1,0>
+            GFill <0,-1,0> <10,0,10>
+            GVoid <1,0,0> <5,5,0>
+        ";
+        let cmds: Vec<BotCommand> = parse_text(text).into_iter().map(|c| c.unwrap()).collect();
+        let near = |x, y, z| CoordDiff(Coord { x, y, z, });
+        assert_eq!(cmds, vec![
+            BotCommand::Halt,
+            BotCommand::Wait,
+            BotCommand::Flip,
+            BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Z, value: -12, }, },
+            BotCommand::LMove {
+                short1: LinearCoordDiff::Short { axis: Axis::X, value: 3, },
+                short2: LinearCoordDiff::Short { axis: Axis::Y, value: 2, },
+            },
+            BotCommand::Fission { near: near(1, 0, 0), split_m: 5, },
+            BotCommand::Fill { near: near(0, -1, 0), },
+            BotCommand::Void { near: near(0, -1, 1), },
+            BotCommand::FusionP { near: near(1, 1, 0), },
+            BotCommand::FusionS { near: near(-1, -1, 0), },
+            BotCommand::GFill { near: near(0, -1, 0), far: near(10, 0, 10), },
+            BotCommand::GVoid { near: near(1, 0, 0), far: near(5, 5, 0), },
+        ]);
+    }
+
+    #[test]
+    fn format_parses_back() {
+        let text = "Halt\nWait\nFlip\nSMove <0,0,-12>\nLMove <3,0,0> <0,2,0>\nFission <1,0,0> 5\nFill <0,-1,0>\n\
+                    Void <0,-1,1>\nFusionP <1,1,0>\nFusionS <-1,-1,0>\nGFill <0,-1,0> <10,0,10>\nGVoid <1,0,0> <5,5,0>";
+        let cmds: Vec<BotCommand> = parse_text(text).into_iter().map(|c| c.unwrap()).collect();
+        let formatted: Vec<String> = cmds.iter().map(format_command).collect();
+        assert_eq!(formatted.join("\n"), text);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let res = parse_text("Jump\nSMove <1,1,0>\nFill <0,0,2>\nWait <0,0,1>\nSMove <0,0,16>\nFill <0,0>");
+        assert_eq!(res.len(), 6);
+        match res[0] { Err(Error::UnknownCommand { line: 1, .. }) => (), ref other => panic!("unexpected {:?}", other), }
+        match res[1] { Err(Error::InvalidLinearCoordDiff { line: 2, .. }) => (), ref other => panic!("unexpected {:?}", other), }
+        match res[2] { Err(Error::Command { line: 3, error: super::super::Error::CoordDiffIsNotNear, }) => (), ref other => panic!("unexpected {:?}", other), }
+        match res[3] { Err(Error::InvalidArgsCount { line: 4, expected: 0, given: 1, }) => (), ref other => panic!("unexpected {:?}", other), }
+        match res[4] { Err(Error::Command { line: 5, error: super::super::Error::LinearCoordDiffTooLong(..), }) => (), ref other => panic!("unexpected {:?}", other), }
+        match res[5] { Err(Error::InvalidCoordDiff { line: 6, .. }) => (), ref other => panic!("unexpected {:?}", other), }
+    }
+}
@@ -1,19 +1,26 @@
 use std::{cmp, iter};
-use std::collections::HashSet;
+use std::ops::Neg;
+use std::hash::{Hash, Hasher};
+use std::collections::{HashSet, HashMap};
 use bit_vec::BitVec;
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
 
 pub const LOWER_LIMIT: isize = 0;
 pub const UPPER_LIMIT: isize = 250;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Axis { X, Y, Z, }
 
 pub type M = isize;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Resolution(pub M);
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Coord {
     pub x: M,
     pub y: M,
@@ -21,9 +28,11 @@ pub struct Coord {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CoordDiff(pub Coord);
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LinearCoordDiff {
     Short { axis: Axis, value: M, },
     Long { axis: Axis, value: M, },
@@ -42,6 +51,7 @@ impl LinearCoordDiff {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Region {
     pub min: Coord,
     pub max: Coord,
@@ -60,6 +70,20 @@ pub struct Matrix {
     dim: usize,
     field: BitVec,
     filled: HashSet<Coord>,
+    grounding: Option<GroundingCache>,
+}
+
+#[derive(Clone, Default)]
+struct GroundingCache {
+    labels: HashMap<Coord, usize>,
+    components: HashMap<usize, GroundedComponent>,
+    next_label: usize,
+}
+
+#[derive(Clone)]
+struct GroundedComponent {
+    members: Vec<Coord>,
+    grounded: bool,
 }
 
 impl Coord {
@@ -79,6 +103,14 @@ impl Coord {
         })
     }
 
+    pub fn manhattan(&self, other: &Coord) -> usize {
+        self.diff(other).l_1_norm()
+    }
+
+    pub fn chebyshev(&self, other: &Coord) -> usize {
+        self.diff(other).l_inf_norm()
+    }
+
     pub fn is_adjacent(&self, other: &Coord) -> bool {
         self.diff(other).l_1_norm() == 1
     }
@@ -116,7 +148,21 @@ impl Coord {
     }
 }
 
+impl Axis {
+    pub fn unit_diff(&self, value: M) -> CoordDiff {
+        match self {
+            Axis::X => CoordDiff::new(value, 0, 0),
+            Axis::Y => CoordDiff::new(0, value, 0),
+            Axis::Z => CoordDiff::new(0, 0, value),
+        }
+    }
+}
+
 impl CoordDiff {
+    pub fn new(x: M, y: M, z: M) -> CoordDiff {
+        CoordDiff(Coord { x, y, z, })
+    }
+
     pub fn l_1_norm(&self) -> usize {
         (self.0.x.abs() + self.0.y.abs() + self.0.z.abs()) as usize
     }
@@ -139,6 +185,14 @@ impl CoordDiff {
     }
 }
 
+impl Neg for CoordDiff {
+    type Output = CoordDiff;
+
+    fn neg(self) -> CoordDiff {
+        CoordDiff::new(-self.0.x, -self.0.y, -self.0.z)
+    }
+}
+
 impl LinearCoordDiff {
     pub fn axis(&self) -> Axis {
         match self {
@@ -155,11 +209,41 @@ impl LinearCoordDiff {
     }
 
     pub fn to_coord_diff(&self) -> CoordDiff {
-        match self.axis() {
-            Axis::X => CoordDiff(Coord{ x: self.value(), y: 0, z: 0 }),
-            Axis::Y => CoordDiff(Coord{ x: 0, y: self.value(), z: 0 }),
-            Axis::Z => CoordDiff(Coord{ x: 0, y: 0, z: self.value() }),
+        self.axis().unit_diff(self.value())
+    }
+
+    // fits the trace encoding: `Short` within -5..5, `Long` within -15..15
+    pub fn is_valid(&self) -> bool {
+        match self {
+            LinearCoordDiff::Short{axis: _, value} => value.abs() <= 5,
+            LinearCoordDiff::Long{axis: _, value} => value.abs() <= 15,
+        }
+    }
+
+    // the same displacement as `Long` legs of at most 15 (one SMove each), none for a zero one
+    pub fn split_into_smoves(&self) -> Vec<LinearCoordDiff> {
+        let (axis, value) = (self.axis(), self.value());
+        let mut left = value.abs();
+        let mut legs = Vec::new();
+        while left > 0 {
+            let step = cmp::min(left, 15);
+            legs.push(LinearCoordDiff::Long { axis, value: value.signum() * step, });
+            left -= step;
         }
+        legs
+    }
+}
+
+// two moves of at most 5 as the `Short` legs of one LMove
+pub fn pair_into_lmove(a: LinearCoordDiff, b: LinearCoordDiff) -> Option<(LinearCoordDiff, LinearCoordDiff)> {
+    let is_short = |value: M| value != 0 && value.abs() <= 5;
+    if is_short(a.value()) && is_short(b.value()) {
+        Some((
+            LinearCoordDiff::Short { axis: a.axis(), value: a.value(), },
+            LinearCoordDiff::Short { axis: b.axis(), value: b.value(), },
+        ))
+    } else {
+        None
     }
 }
 
@@ -177,14 +261,30 @@ impl Region {
             coord.z >= self.min.z && coord.z <= self.max.z
     }
 
+    // Boxes share a voxel iff their spans overlap along every axis: a common voxel lies in all three
+    // spans, and a point from each of the three overlaps makes one.
     pub fn intersects(&self, other: &Region) -> bool {
-        let not_x_proj =
-            self.max.z < other.min.z || other.max.z < self.min.z || self.max.y < other.min.y || other.max.y < self.min.y;
-        let not_y_proj =
-            self.max.z < other.min.z || other.max.z < self.min.z || self.max.x < other.min.x || other.max.x < self.min.x;
-        let not_z_proj =
-            self.max.y < other.min.y || other.max.y < self.min.y || self.max.x < other.min.x || other.max.x < self.min.x;
-        !(not_x_proj || not_y_proj || not_z_proj)
+        self.min.x <= other.max.x && other.min.x <= self.max.x &&
+            self.min.y <= other.max.y && other.min.y <= self.max.y &&
+            self.min.z <= other.max.z && other.min.z <= self.max.z
+    }
+
+    pub fn intersection(&self, other: &Region) -> Option<Region> {
+        if !self.intersects(other) {
+            return None;
+        }
+        Some(Region {
+            min: Coord {
+                x: cmp::max(self.min.x, other.min.x),
+                y: cmp::max(self.min.y, other.min.y),
+                z: cmp::max(self.min.z, other.min.z),
+            },
+            max: Coord {
+                x: cmp::min(self.max.x, other.max.x),
+                y: cmp::min(self.max.y, other.max.y),
+                z: cmp::min(self.max.z, other.max.z),
+            },
+        })
     }
 
     pub fn dimension(&self) -> RegionDim {
@@ -213,6 +313,85 @@ impl Region {
 
         set
     }
+
+    // the same as `coord_set().len()` without building the set
+    pub fn contents_count(&self) -> usize {
+        ((self.max.x - self.min.x + 1) * (self.max.y - self.min.y + 1) * (self.max.z - self.min.z + 1)) as usize
+    }
+
+    pub fn surface(&self) -> impl Iterator<Item = Coord> {
+        // flat regions (points, lines, planes) lie on their faces entirely
+        let Region { min, max, } = *self;
+        iproduct!(min.x ..= max.x, min.y ..= max.y, min.z ..= max.z)
+            .map(|(x, y, z)| Coord { x, y, z, })
+            .filter(move |c| {
+                c.x == min.x || c.x == max.x ||
+                    c.y == min.y || c.y == max.y ||
+                    c.z == min.z || c.z == max.z
+            })
+    }
+
+    pub fn contents_grounded_order(&self) -> impl Iterator<Item = Coord> {
+        // bottom-up layer by layer, every next coord adjacent to the previous one, so filling in this
+        // order keeps a region standing on the floor (or on a grounded base) grounded all the way
+        serpentine(self, Axis::Y)
+    }
+
+    pub fn contents_morton(&self) -> impl Iterator<Item = Coord> {
+        // the Z-order curve only covers cubes with a power of two side: walk the smallest one holding
+        // the region and skip the padding, so it pays off for boxy regions, not for flat ones
+        let min = self.min;
+        let span = cmp::max(self.max.x - min.x, cmp::max(self.max.y - min.y, self.max.z - min.z)) as usize + 1;
+        let side = span.next_power_of_two();
+        let max = self.max;
+        (0 .. side * side * side)
+            .map(move |code| Coord {
+                x: min.x + morton_compact(code) as M,
+                y: min.y + morton_compact(code >> 1) as M,
+                z: min.z + morton_compact(code >> 2) as M,
+            })
+            .filter(move |c| c.x <= max.x && c.y <= max.y && c.z <= max.z)
+    }
+
+    // the two corners a two bot GFill or GVoid of the region is issued from
+    pub fn opposite_corners(&self) -> (Coord, Coord) {
+        (self.min, self.max)
+    }
+
+    // The four diagonals of the box, each as (the corner with x = min.x, the opposite one), starting with
+    // `opposite_corners`. On a flat or a line region some of them are the same.
+    pub fn all_corner_pairs(&self) -> [(Coord, Coord); 4] {
+        let (min, max) = (self.min, self.max);
+        let pair = |y_min: bool, z_min: bool| (
+            Coord { x: min.x, y: if y_min { min.y } else { max.y }, z: if z_min { min.z } else { max.z }, },
+            Coord { x: max.x, y: if y_min { max.y } else { min.y }, z: if z_min { max.z } else { min.z }, },
+        );
+        [pair(true, true), pair(true, false), pair(false, true), pair(false, false)]
+    }
+
+    // The cube `center ± radius`, not clamped to any matrix.
+    pub fn around(center: &Coord, radius: M) -> Region {
+        Region { min: *center, max: *center, }.expand(radius)
+    }
+
+    pub fn around_clamped(center: &Coord, radius: M, dim: usize) -> Region {
+        Region::around(center, radius).clamp_to_dim(dim)
+    }
+
+    pub fn expand(&self, margin: M) -> Region {
+        Region {
+            min: Coord { x: self.min.x - margin, y: self.min.y - margin, z: self.min.z - margin, },
+            max: Coord { x: self.max.x + margin, y: self.max.y + margin, z: self.max.z + margin, },
+        }
+    }
+
+    pub fn clamp_to_dim(&self, dim: usize) -> Region {
+        let clamp = |v: M| cmp::min(cmp::max(v, 0), dim as M - 1);
+        Region {
+            min: Coord { x: clamp(self.min.x), y: clamp(self.min.y), z: clamp(self.min.z), },
+            max: Coord { x: clamp(self.max.x), y: clamp(self.max.y), z: clamp(self.max.z), },
+        }
+    }
 }
 
 impl Matrix {
@@ -223,6 +402,7 @@ impl Matrix {
             dim,
             field: BitVec::from_elem(total_size, false),
             filled: HashSet::new(),
+            grounding: None,
         }
     }
 
@@ -230,6 +410,36 @@ impl Matrix {
         Matrix::new(Resolution(self.dim() as isize))
     }
 
+    // complement within the `dim` cube: note the result is almost never grounded
+    // (every void pocket of the original becomes a floating voxel)
+    pub fn invert(&self) -> Matrix {
+        let dim = self.dim as M;
+        Matrix::from_iter(
+            Resolution(dim),
+            iproduct!(0 .. dim, 0 .. dim, 0 .. dim)
+                .map(|(x, y, z)| Coord { x, y, z, })
+                .filter(|coord| !self.is_filled(coord)))
+    }
+
+    // voxels keep their coordinates, those out of the new range are dropped when shrinking
+    pub fn resize(&self, new_dim: usize) -> Matrix {
+        let max = new_dim as M;
+        Matrix::from_iter(
+            Resolution(max),
+            self.filled.iter()
+                .filter(|voxel| voxel.x < max && voxel.y < max && voxel.z < max)
+                .cloned())
+    }
+
+    // every filled voxel shifted by `diff`, the ones shifted out of the matrix are dropped
+    pub fn translate(&self, diff: CoordDiff) -> Matrix {
+        Matrix::from_iter(
+            Resolution(self.dim as M),
+            self.filled.iter()
+                .map(|voxel| voxel.add(diff))
+                .filter(|voxel| self.is_valid_coord(voxel)))
+    }
+
     pub fn from_iter<I>(dim: Resolution, filled_coords: I) -> Matrix where I: IntoIterator<Item = Coord> {
         let mut matrix = Matrix::new(dim);
         for coord in filled_coords {
@@ -245,13 +455,35 @@ impl Matrix {
     pub fn set_filled(&mut self, &coord: &Coord) {
         let offset = (coord.x as usize * self.dim * self.dim) + (coord.y as usize * self.dim) + coord.z as usize;
         self.field.set(offset, true);
-        self.filled.insert(coord);
+        if self.filled.insert(coord) {
+            if let Some(cache) = self.grounding.as_mut() {
+                cache.fill(coord);
+            }
+        }
     }
 
     pub fn set_void(&mut self, &coord: &Coord) {
         let offset = (coord.x as usize * self.dim * self.dim) + (coord.y as usize * self.dim) + coord.z as usize;
         self.field.set(offset, false);
-        self.filled.remove(&coord);
+        if self.filled.remove(&coord) {
+            if let Some(cache) = self.grounding.as_mut() {
+                cache.void(coord);
+            }
+        }
+    }
+
+    // `set_filled` telling whether the voxel was void before, i.e. whether anything changed
+    pub fn apply_fill(&mut self, coord: &Coord) -> bool {
+        let changed = !self.is_filled(coord);
+        self.set_filled(coord);
+        changed
+    }
+
+    // `set_void` telling whether the voxel was filled before, i.e. whether anything changed
+    pub fn apply_void(&mut self, coord: &Coord) -> bool {
+        let changed = self.is_filled(coord);
+        self.set_void(coord);
+        changed
     }
 
     pub fn is_filled(&self, coord: &Coord) -> bool {
@@ -291,6 +523,16 @@ impl Matrix {
             .filter(move |c| self.is_filled(c))
     }
 
+    // the same as `filled_near_neighbours(coord).count()` without building the iterator chain
+    pub fn filled_neighbour_count(&self, coord: &Coord) -> usize {
+        let dim = self.dim as M;
+        let &Coord { x, y, z, } = coord;
+        [(x - 1, y, z), (x + 1, y, z), (x, y - 1, z), (x, y + 1, z), (x, y, z - 1), (x, y, z + 1)].iter()
+            .filter(|&&(x, y, z)| x >= 0 && x < dim && y >= 0 && y < dim && z >= 0 && z < dim)
+            .filter(|&&(x, y, z)| self.is_filled(&Coord { x, y, z, }))
+            .count()
+    }
+
     pub fn will_be_grounded(&self, coord: &Coord) -> bool {
         use pathfinding::directed::astar;
 
@@ -303,6 +545,12 @@ impl Matrix {
         ).is_some()
     }
 
+    // whether the whole region (a connected box) is grounded once filled, e.g. by a GFill:
+    // it stands on the floor or some of its surface voxels touches an already grounded one
+    pub fn region_would_be_grounded(&self, region: &Region) -> bool {
+        region.min.y == 0 || region.surface().any(|coord| self.will_be_grounded(&coord))
+    }
+
     pub fn is_grounded(&self, coord: &Coord) -> bool {
         if !self.is_filled(coord) {
             return false;
@@ -310,18 +558,163 @@ impl Matrix {
         self.will_be_grounded(coord)
     }
 
+    // The cache is built from scratch only here; afterwards `set_filled` merges the touched
+    // components and `set_void` re-floods just the component the voxel belonged to.
+    pub fn enable_grounding_cache(&mut self) {
+        self.grounding = Some(GroundingCache::build(&self.filled));
+    }
+
+    pub fn disable_grounding_cache(&mut self) {
+        self.grounding = None;
+    }
+
+    // Falls back to `is_grounded` when the cache is not enabled.
+    pub fn is_grounded_cached(&self, coord: &Coord) -> bool {
+        match self.grounding {
+            Some(ref cache) => cache.is_grounded(coord),
+            None => self.is_grounded(coord),
+        }
+    }
+
     pub fn filled_voxels(&self) -> impl Iterator<Item = &Coord> {
         self.filled.iter()
     }
 
+    pub fn filled_voxels_in<'a>(&'a self, region: &Region) -> impl Iterator<Item = Coord> + 'a {
+        // walk whichever is smaller: the (clamped) region box or the filled set
+        let region = *region;
+        let scan = region.clamp_to_dim(self.dim);
+        let by_box = if scan.contents_count() <= self.filled.len() {
+            Some(iproduct!(scan.min.x ..= scan.max.x, scan.min.y ..= scan.max.y, scan.min.z ..= scan.max.z)
+                 .map(|(x, y, z)| Coord { x, y, z, })
+                 .filter(move |c| self.is_filled(c)))
+        } else {
+            None
+        };
+        let by_set = if by_box.is_none() {
+            Some(self.filled.iter().cloned())
+        } else {
+            None
+        };
+        by_box.into_iter().flat_map(|voxels| voxels)
+            .chain(by_set.into_iter().flat_map(|voxels| voxels))
+            .filter(move |c| region.contains(c))
+    }
+
+    pub fn count_filled_in(&self, region: &Region) -> usize {
+        self.filled_voxels_in(region).count()
+    }
+
+    pub fn par_filled_voxels<'a>(&'a self) -> impl ParallelIterator<Item = Coord> + 'a {
+        let dim = self.dim;
+        (0 .. dim).into_par_iter()
+            .flat_map(move |x| {
+                let slab_offset = x * dim * dim;
+                (0 .. dim * dim)
+                    .filter(|&offset| self.field.get(slab_offset + offset).unwrap_or(false))
+                    .map(|offset| Coord { x: x as M, y: (offset / dim) as M, z: (offset % dim) as M, })
+                    .collect::<Vec<_>>()
+            })
+    }
+
     pub fn all_voxels_are_grounded(&self) -> bool {
-        all_voxels_are_grounded(self.filled.clone())
+        voxels_are_grounded(&self.filled)
+    }
+
+    // Falls back to `all_voxels_are_grounded` when the cache is not enabled.
+    pub fn all_voxels_are_grounded_cached(&self) -> bool {
+        match self.grounding {
+            Some(ref cache) => cache.all_grounded(),
+            None => self.all_voxels_are_grounded(),
+        }
     }
 
     pub fn first_ungrounded_voxel(&self) -> Option<Coord> {
         first_ungrounded_voxel(self.filled.clone())
     }
 
+    pub fn ungrounded_voxels(&self) -> HashSet<Coord> {
+        let mut voxels_pending = self.filled.clone();
+        let mut queue: Vec<Coord> = voxels_pending.iter().filter(|c| c.y == 0).cloned().collect();
+        while let Some(voxel) = queue.pop() {
+            if voxels_pending.remove(&voxel) {
+                queue.extend(voxel.near_neighbours().filter(|c| voxels_pending.contains(c)));
+            }
+        }
+        voxels_pending
+    }
+
+    pub fn count_filled(&self) -> usize {
+        self.filled.len()
+    }
+
+    pub fn bounding_box(&self) -> Option<Region> {
+        let mut voxels = self.filled.iter();
+        let first = voxels.next()?;
+        let mut region = Region { min: *first, max: *first, };
+        for voxel in voxels {
+            region.min.x = cmp::min(region.min.x, voxel.x);
+            region.min.y = cmp::min(region.min.y, voxel.y);
+            region.min.z = cmp::min(region.min.z, voxel.z);
+            region.max.x = cmp::max(region.max.x, voxel.x);
+            region.max.y = cmp::max(region.max.y, voxel.y);
+            region.max.z = cmp::max(region.max.z, voxel.z);
+        }
+        Some(region)
+    }
+
+    // rounded average of filled voxels coordinates, `None` for an empty matrix
+    pub fn center_of_mass(&self) -> Option<Coord> {
+        let count = self.filled.len() as i64;
+        if count == 0 {
+            return None;
+        }
+        let (sx, sy, sz) = self.filled.iter()
+            .fold((0i64, 0i64, 0i64), |(sx, sy, sz), c| (sx + c.x as i64, sy + c.y as i64, sz + c.z as i64));
+        // coordinates are never negative so adding half of count rounds to nearest
+        let avg = |sum: i64| ((sum + count / 2) / count) as M;
+        Some(Coord { x: avg(sx), y: avg(sy), z: avg(sz), })
+    }
+
+    pub fn connected_components(&self) -> usize {
+        self.connected_component_voxels().len()
+    }
+
+    // every component voxels sorted, components ordered by their least voxel
+    pub fn connected_component_voxels(&self) -> Vec<Vec<Coord>> {
+        let mut voxels_pending = self.filled.clone();
+        let mut queue = Vec::with_capacity(voxels_pending.len());
+        let mut components = Vec::new();
+        while let Some(&voxel) = voxels_pending.iter().next() {
+            let mut component = Vec::new();
+            queue.push(voxel);
+            while let Some(voxel) = queue.pop() {
+                if voxels_pending.remove(&voxel) {
+                    component.push(voxel);
+                    queue.extend(voxel.near_neighbours().filter(|c| voxels_pending.contains(c)));
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components.sort();
+        components
+    }
+
+    pub fn diff(&self, other: &Matrix) -> (Vec<Coord>, Vec<Coord>) {
+        let mut removed: Vec<Coord> = self.filled.iter()
+            .filter(|voxel| !other.filled.contains(voxel))
+            .cloned()
+            .collect();
+        removed.sort();
+        let mut added: Vec<Coord> = other.filled.iter()
+            .filter(|voxel| !self.filled.contains(voxel))
+            .cloned()
+            .collect();
+        added.sort();
+        (removed, added)
+    }
+
     pub fn is_valid_coord(&self, c: &Coord) -> bool {
         c.x >= 0 && c.y >= 0 && c.z >= 0
             && (c.x as usize) < self.dim()
@@ -329,13 +722,252 @@ impl Matrix {
             && (c.z as usize) < self.dim()
     }
 
+    // the same bits index different voxels under different dimensions
     pub fn equals(&self, other: &Matrix) -> bool {
-        &self.field == &other.field
+        self.dim == other.dim && &self.field == &other.field
+    }
+
+    // a model of another dimension covers nothing of the target
+    pub fn coverage_ratio(&self, target: &Matrix) -> f64 {
+        if self.dim != target.dim {
+            return 0.0;
+        }
+        if target.filled.is_empty() {
+            return if self.filled.is_empty() { 1.0 } else { 0.0 };
+        }
+        let present = target.filled.iter().filter(|voxel| self.filled.contains(voxel)).count();
+        let extra = self.filled.len() - present;
+        let score = present as f64 - extra as f64;
+        if score <= 0.0 {
+            0.0
+        } else {
+            score / target.filled.len() as f64
+        }
+    }
+
+    pub fn shell(&self) -> Matrix {
+        let interior = |voxel: &Coord| {
+            let faces = [
+                Coord { x: voxel.x - 1, ..*voxel },
+                Coord { x: voxel.x + 1, ..*voxel },
+                Coord { y: voxel.y - 1, ..*voxel },
+                Coord { y: voxel.y + 1, ..*voxel },
+                Coord { z: voxel.z - 1, ..*voxel },
+                Coord { z: voxel.z + 1, ..*voxel },
+            ];
+            faces.iter().all(|face| self.is_valid_coord(face) && self.is_filled(face))
+        };
+        Matrix::from_iter(
+            Resolution(self.dim as M),
+            self.filled.iter().filter(|voxel| !interior(voxel)).cloned(),
+        )
+    }
+
+    pub fn is_mirror_symmetric(&self, axis: Axis) -> bool {
+        let bbox = if let Some(bbox) = self.bounding_box() {
+            bbox
+        } else {
+            return true;
+        };
+        // reflect about the bounding box center: c' = min + max - c
+        self.filled.iter().all(|voxel| {
+            let reflected = match axis {
+                Axis::X => Coord { x: bbox.min.x + bbox.max.x - voxel.x, ..*voxel },
+                Axis::Y => Coord { y: bbox.min.y + bbox.max.y - voxel.y, ..*voxel },
+                Axis::Z => Coord { z: bbox.min.z + bbox.max.z - voxel.z, ..*voxel },
+            };
+            self.is_filled(&reflected)
+        })
+    }
+
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl PartialEq for Matrix {
+    fn eq(&self, other: &Matrix) -> bool {
+        self.equals(other)
+    }
+}
+
+impl Eq for Matrix {}
+
+impl Hash for Matrix {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.dim.hash(state);
+        self.field.hash(state);
+    }
+}
+
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+impl GroundingCache {
+    fn build(filled: &HashSet<Coord>) -> GroundingCache {
+        let mut cache = GroundingCache::default();
+        cache.label_components(filled.clone());
+        cache
+    }
+
+    fn label_components(&mut self, mut pending: HashSet<Coord>) {
+        while let Some(&seed) = pending.iter().next() {
+            pending.remove(&seed);
+            let mut members = vec![seed];
+            let mut queue = vec![seed];
+            while let Some(voxel) = queue.pop() {
+                for neighbour in voxel.near_neighbours() {
+                    if pending.remove(&neighbour) {
+                        members.push(neighbour);
+                        queue.push(neighbour);
+                    }
+                }
+            }
+            self.add_component(members);
+        }
+    }
+
+    fn add_component(&mut self, members: Vec<Coord>) {
+        let label = self.next_label;
+        self.next_label += 1;
+        for &voxel in members.iter() {
+            self.labels.insert(voxel, label);
+        }
+        let grounded = members.iter().any(|voxel| voxel.y == 0);
+        self.components.insert(label, GroundedComponent { members, grounded, });
+    }
+
+    fn fill(&mut self, coord: Coord) {
+        let mut touched: Vec<usize> = coord.near_neighbours()
+            .filter_map(|neighbour| self.labels.get(&neighbour).cloned())
+            .collect();
+        touched.sort();
+        touched.dedup();
+        let target = match touched.iter().max_by_key(|label| self.components[*label].members.len()) {
+            Some(&label) => label,
+            None => return self.add_component(vec![coord]),
+        };
+        let mut merged = vec![coord];
+        let mut grounded = coord.y == 0;
+        for label in touched.into_iter().filter(|&label| label != target) {
+            let component = self.components.remove(&label).unwrap();
+            grounded |= component.grounded;
+            merged.extend(component.members);
+        }
+        for &voxel in merged.iter() {
+            self.labels.insert(voxel, target);
+        }
+        let component = self.components.get_mut(&target).unwrap();
+        component.members.extend(merged);
+        component.grounded |= grounded;
+    }
+
+    fn void(&mut self, coord: Coord) {
+        let label = match self.labels.remove(&coord) {
+            Some(label) => label,
+            None => return,
+        };
+        let component = self.components.remove(&label).unwrap();
+        self.label_components(component.members.into_iter().filter(|voxel| voxel != &coord).collect());
+    }
+
+    fn is_grounded(&self, coord: &Coord) -> bool {
+        self.labels.get(coord)
+            .map(|label| self.components[label].grounded)
+            .unwrap_or(false)
+    }
+
+    fn all_grounded(&self) -> bool {
+        self.components.values().all(|component| component.grounded)
     }
 }
 
 pub fn all_voxels_are_grounded(voxels_pending: HashSet<Coord>) -> bool {
-    first_ungrounded_voxel(voxels_pending).is_none()
+    voxels_are_grounded(&voxels_pending)
+}
+
+struct DisjointSets {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl DisjointSets {
+    fn new(size: usize) -> DisjointSets {
+        DisjointSets {
+            parent: (0 .. size).collect(),
+            rank: vec![0; size],
+        }
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return;
+        }
+        if self.rank[ra] < self.rank[rb] {
+            self.parent[ra] = rb;
+        } else if self.rank[ra] > self.rank[rb] {
+            self.parent[rb] = ra;
+        } else {
+            self.parent[rb] = ra;
+            self.rank[ra] += 1;
+        }
+    }
+}
+
+fn voxels_are_grounded(voxels: &HashSet<Coord>) -> bool {
+    let index: HashMap<Coord, usize> = voxels.iter()
+        .enumerate()
+        .map(|(i, &voxel)| (voxel, i))
+        .collect();
+    // the last node is a virtual floor every `y == 0` voxel is attached to
+    let floor = index.len();
+    let mut sets = DisjointSets::new(floor + 1);
+    for (voxel, &i) in index.iter() {
+        if voxel.y == 0 {
+            sets.union(i, floor);
+        }
+        let upper = [
+            Coord { x: voxel.x + 1, y: voxel.y, z: voxel.z, },
+            Coord { x: voxel.x, y: voxel.y + 1, z: voxel.z, },
+            Coord { x: voxel.x, y: voxel.y, z: voxel.z + 1, },
+        ];
+        for neighbour in upper.iter() {
+            if let Some(&j) = index.get(neighbour) {
+                sets.union(i, j);
+            }
+        }
+    }
+    let floor_root = sets.find(floor);
+    (0 .. floor).all(|i| sets.find(i) == floor_root)
 }
 
 pub fn first_ungrounded_voxel(mut voxels_pending: HashSet<Coord>) -> Option<Coord> {
@@ -359,6 +991,131 @@ pub fn first_ungrounded_voxel(mut voxels_pending: HashSet<Coord>) -> Option<Coor
     None
 }
 
+// every third bit of `code` starting from the lowest one, packed together
+fn morton_compact(code: usize) -> usize {
+    let mut value = 0;
+    let mut bit = 0;
+    while code >> (3 * bit) != 0 {
+        value |= ((code >> (3 * bit)) & 1) << bit;
+        bit += 1;
+    }
+    value
+}
+
+pub fn serpentine(region: &Region, axis: Axis) -> impl Iterator<Item = Coord> {
+    // layers go along `axis`, rows along the first remaining axis and columns along the last one;
+    // both rows and columns reverse direction every other time so consecutive coords stay adjacent
+    let (row_axis, col_axis) = match axis {
+        Axis::X => (Axis::Y, Axis::Z),
+        Axis::Y => (Axis::X, Axis::Z),
+        Axis::Z => (Axis::X, Axis::Y),
+    };
+    let span = |axis| match axis {
+        Axis::X => (region.min.x, (region.max.x - region.min.x + 1) as usize),
+        Axis::Y => (region.min.y, (region.max.y - region.min.y + 1) as usize),
+        Axis::Z => (region.min.z, (region.max.z - region.min.z + 1) as usize),
+    };
+    let (layer_min, layers) = span(axis);
+    let (row_min, rows) = span(row_axis);
+    let (col_min, cols) = span(col_axis);
+    (0 .. layers * rows * cols)
+        .map(move |i| {
+            let layer = i / (rows * cols);
+            let row = (i / cols) % rows;
+            let row = if layer % 2 == 1 { rows - 1 - row } else { row };
+            let col = i % cols;
+            let col = if (i / cols) % 2 == 1 { cols - 1 - col } else { col };
+            let mut coord = Coord { x: 0, y: 0, z: 0, };
+            for &(axis, value) in [(axis, layer_min + layer as M), (row_axis, row_min + row as M), (col_axis, col_min + col as M)].iter() {
+                match axis {
+                    Axis::X => coord.x = value,
+                    Axis::Y => coord.y = value,
+                    Axis::Z => coord.z = value,
+                }
+            }
+            coord
+        })
+}
+
+pub fn partition_region(region: &Region, n: usize, axis: Axis) -> Vec<Region> {
+    let (lo, hi) = match axis {
+        Axis::X => (region.min.x, region.max.x),
+        Axis::Y => (region.min.y, region.max.y),
+        Axis::Z => (region.min.z, region.max.z),
+    };
+    let len = (hi - lo + 1) as usize;
+    let slabs = cmp::min(n, len);
+    let mut parts = Vec::with_capacity(slabs);
+    let mut start = lo;
+    for i in 0 .. slabs {
+        let width = (len / slabs + if i < len % slabs { 1 } else { 0 }) as M;
+        let (mut min, mut max) = (region.min, region.max);
+        match axis {
+            Axis::X => { min.x = start; max.x = start + width - 1; },
+            Axis::Y => { min.y = start; max.y = start + width - 1; },
+            Axis::Z => { min.z = start; max.z = start + width - 1; },
+        }
+        parts.push(Region { min, max, });
+        start += width;
+    }
+    parts
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct MatrixRle {
+    dim: usize,
+    // lengths of alternating void and filled runs over the bit field, starting with void
+    runs: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Matrix {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut runs = Vec::new();
+        let mut current = false;
+        let mut run = 0;
+        for bit in self.field.iter() {
+            if bit != current {
+                runs.push(run);
+                current = bit;
+                run = 0;
+            }
+            run += 1;
+        }
+        runs.push(run);
+        MatrixRle { dim: self.dim, runs, }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D>(deserializer: D) -> Result<Matrix, D::Error> where D: Deserializer<'de> {
+        let rle = MatrixRle::deserialize(deserializer)?;
+        let total: usize = rle.runs.iter().sum();
+        if total != rle.dim * rle.dim * rle.dim {
+            return Err(de::Error::custom(format!(
+                "matrix runs cover {} voxels while dimension {} needs {}", total, rle.dim, rle.dim * rle.dim * rle.dim)));
+        }
+        let mut matrix = Matrix::new(Resolution(rle.dim as M));
+        let mut offset = 0;
+        for (i, &run) in rle.runs.iter().enumerate() {
+            if i % 2 == 1 {
+                for index in offset .. offset + run {
+                    let coord = Coord {
+                        x: (index / (rle.dim * rle.dim)) as M,
+                        y: ((index / rle.dim) % rle.dim) as M,
+                        z: (index % rle.dim) as M,
+                    };
+                    matrix.set_filled(&coord);
+                }
+            }
+            offset += run;
+        }
+        Ok(matrix)
+    }
+}
+
 use std::fmt;
 
 impl fmt::Debug for Matrix {
@@ -372,7 +1129,41 @@ impl fmt::Debug for Matrix {
 
 #[cfg(test)]
 mod tests {
-    use super::{Coord, Resolution, Matrix, LinearCoordDiff, Axis, Region};
+    use std::collections::HashSet;
+    use super::{M, Coord, CoordDiff, Resolution, Matrix, LinearCoordDiff, Axis, Region};
+
+    #[test]
+    fn coord_diff_neg() {
+        for &(x, y, z) in [(0, 0, 0), (1, -2, 3), (-15, 0, 7), (0, 30, -30)].iter() {
+            let diff = CoordDiff(Coord { x, y, z, });
+            assert_eq!(-diff, CoordDiff(Coord { x: -x, y: -y, z: -z, }));
+            assert_eq!(-(-diff), diff);
+            assert_eq!(diff, CoordDiff::new(x, y, z));
+        }
+    }
+
+    #[test]
+    fn coord_distances() {
+        let a = Coord { x: 1, y: 2, z: 3, };
+        let b = Coord { x: 4, y: 0, z: 3, };
+        assert_eq!(a.manhattan(&b), 5);
+        assert_eq!(b.manhattan(&a), 5);
+        assert_eq!(a.chebyshev(&b), 3);
+        assert_eq!(a.manhattan(&a), 0);
+        assert_eq!(a.chebyshev(&a), 0);
+        let c = Coord { x: -2, y: 7, z: 0, };
+        assert_eq!(a.manhattan(&c), 3 + 5 + 3);
+        assert_eq!(a.chebyshev(&c), 5);
+    }
+
+    #[test]
+    fn axis_unit_diff() {
+        assert_eq!(Axis::X.unit_diff(3), CoordDiff::new(3, 0, 0));
+        assert_eq!(Axis::Y.unit_diff(-1), CoordDiff::new(0, -1, 0));
+        assert_eq!(Axis::Z.unit_diff(15), CoordDiff::new(0, 0, 15));
+        let long = LinearCoordDiff::Long { axis: Axis::Y, value: -7, };
+        assert_eq!(long.to_coord_diff(), Axis::Y.unit_diff(-7));
+    }
 
     #[test]
     fn is_grounded_single_empty() {
@@ -458,6 +1249,29 @@ mod tests {
         assert!(!matrix.all_voxels_are_grounded());
     }
 
+    #[test]
+    fn bounding_box_and_components() {
+        let matrix = Matrix::from_iter(
+            Resolution(4),
+            vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 3, y: 2, z: 0, },
+            ]);
+        assert_eq!(matrix.count_filled(), 3);
+        assert_eq!(matrix.bounding_box(), Some(Region {
+            min: Coord { x: 1, y: 0, z: 0, },
+            max: Coord { x: 3, y: 2, z: 1, },
+        }));
+        assert_eq!(matrix.connected_components(), 2);
+        assert_eq!(matrix.connected_component_voxels(), vec![
+            vec![Coord { x: 1, y: 0, z: 1, }, Coord { x: 1, y: 1, z: 1, }],
+            vec![Coord { x: 3, y: 2, z: 0, }],
+        ]);
+        assert_eq!(matrix.ungrounded_voxels().len(), 1);
+        assert_eq!(Matrix::new(Resolution(4)).bounding_box(), None);
+    }
+
     #[test]
     fn is_coord_valid() {
         let matrix = Matrix::from_iter(Resolution(3), vec![]);
@@ -554,6 +1368,96 @@ mod tests {
             min: Coord { x: 0, y: 2, z: 0, },
             max: Coord { x: 2, y: 2, z: 2, },
         }));
+        assert_eq!(region.intersection(&Region {
+            min: Coord { x: 1, y: 1, z: 0, },
+            max: Coord { x: 2, y: 1, z: 2, },
+        }), Some(Region {
+            min: Coord { x: 1, y: 1, z: 0, },
+            max: Coord { x: 1, y: 1, z: 1, },
+        }));
+        assert_eq!(region.intersection(&Region {
+            min: Coord { x: 0, y: 2, z: 0, },
+            max: Coord { x: 2, y: 2, z: 2, },
+        }), None);
+    }
+
+    #[test]
+    fn regions_intersect_as_coord_sets() {
+        use rand::{Rng, SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // small spans in a small box, so touching, nested and disjoint pairs all come up often
+        let random_region = |rng: &mut XorShiftRng| Region::from_corners(
+            &Coord { x: rng.gen_range(0, 5), y: rng.gen_range(0, 5), z: rng.gen_range(0, 5), },
+            &Coord { x: rng.gen_range(0, 5), y: rng.gen_range(0, 5), z: rng.gen_range(0, 5), },
+        );
+        let (mut overlapping, mut touching) = (0, 0);
+        for _ in 0 .. 2000 {
+            let a = random_region(&mut rng);
+            let b = random_region(&mut rng);
+            let expected = !a.coord_set().is_disjoint(&b.coord_set());
+            assert_eq!(a.intersects(&b), expected, "{:?} and {:?}", a, b);
+            assert_eq!(b.intersects(&a), expected, "{:?} and {:?}", b, a);
+            assert_eq!(a.intersection(&b).is_some(), expected);
+            if expected {
+                overlapping += 1;
+                assert_eq!(a.intersection(&b).unwrap().coord_set(), &a.coord_set() & &b.coord_set());
+            } else if a.expand(1).intersects(&b) {
+                touching += 1;
+            }
+        }
+        assert!(overlapping > 100 && touching > 100);
+
+        // face to face, edge to edge and corner to corner, one voxel apart
+        let unit = Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 1, y: 1, z: 1, }, };
+        for &(x, y, z) in &[(2, 0, 0), (0, 2, 0), (0, 0, 2), (2, 2, 0), (2, 0, 2), (0, 2, 2), (2, 2, 2)] {
+            let next = Region { min: Coord { x, y, z, }, max: Coord { x: x + 1, y: y + 1, z: z + 1, }, };
+            assert!(!unit.intersects(&next));
+            assert!(!next.intersects(&unit));
+        }
+    }
+
+    #[test]
+    fn split_into_smoves_32() {
+        let legs = LinearCoordDiff::Long { axis: Axis::Y, value: -32, }.split_into_smoves();
+        assert_eq!(legs, vec![
+            LinearCoordDiff::Long { axis: Axis::Y, value: -15, },
+            LinearCoordDiff::Long { axis: Axis::Y, value: -15, },
+            LinearCoordDiff::Long { axis: Axis::Y, value: -2, },
+        ]);
+        assert_eq!(LinearCoordDiff::Long { axis: Axis::X, value: 15, }.split_into_smoves().len(), 1);
+        assert_eq!(LinearCoordDiff::Long { axis: Axis::X, value: 0, }.split_into_smoves(), vec![]);
+    }
+
+    #[test]
+    fn pair_into_lmove_short_legs() {
+        let x = |value| LinearCoordDiff::Long { axis: Axis::X, value, };
+        let z = |value| LinearCoordDiff::Long { axis: Axis::Z, value, };
+        assert_eq!(super::pair_into_lmove(x(3), z(-5)), Some((
+            LinearCoordDiff::Short { axis: Axis::X, value: 3, },
+            LinearCoordDiff::Short { axis: Axis::Z, value: -5, },
+        )));
+        assert_eq!(super::pair_into_lmove(x(3), z(6)), None);
+        assert_eq!(super::pair_into_lmove(x(0), z(1)), None);
+    }
+
+    #[test]
+    fn region_contents_count() {
+        let region = |min: (M, M, M), max: (M, M, M)| Region::from_corners(
+            &Coord { x: min.0, y: min.1, z: min.2, },
+            &Coord { x: max.0, y: max.1, z: max.2, },
+        );
+        let point = region((3, 2, 1), (3, 2, 1));
+        let line = region((0, 5, 2), (0, 1, 2));
+        let plane = region((1, 1, 1), (4, 1, 3));
+        let cube = region((2, 0, 7), (5, 2, 4));
+        assert_eq!(point.contents_count(), 1);
+        assert_eq!(line.contents_count(), 5);
+        assert_eq!(plane.contents_count(), 12);
+        assert_eq!(cube.contents_count(), 48);
+        for region in &[point, line, plane, cube] {
+            assert_eq!(region.contents_count(), region.coord_set().len());
+        }
     }
 
     #[test]
@@ -572,6 +1476,522 @@ mod tests {
         assert!(!matrix.will_be_grounded(&Coord { x: 2, y: 1, z: 0, }));
     }
 
+    #[test]
+    fn grounded_union_find_matches_flood() {
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        for density in 1 .. 8 {
+            for _ in 0 .. 20 {
+                let voxels: HashSet<Coord> = iproduct!(0 .. 6, 0 .. 6, 0 .. 6)
+                    .filter(|_| {
+                        seed ^= seed << 13;
+                        seed ^= seed >> 7;
+                        seed ^= seed << 17;
+                        seed % 8 < density
+                    })
+                    .map(|(x, y, z)| Coord { x, y, z, })
+                    .collect();
+                assert_eq!(
+                    super::all_voxels_are_grounded(voxels.clone()),
+                    super::first_ungrounded_voxel(voxels).is_none(),
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use serde_json;
+        let matrix = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 0, y: 0, z: 1, },
+            Coord { x: 1, y: 2, z: 3, },
+            Coord { x: 3, y: 3, z: 3, },
+        ]);
+        let json = serde_json::to_string(&matrix).unwrap();
+        assert_eq!(json, r#"{"dim":4,"runs":[0,2,25,1,35,1]}"#);
+        let restored: Matrix = serde_json::from_str(&json).unwrap();
+        assert!(restored.equals(&matrix));
+        assert_eq!(restored.count_filled(), 4);
+        assert!(serde_json::from_str::<Matrix>(r#"{"dim":4,"runs":[1,2]}"#).is_err());
+
+        let region = Region::from_corners(&Coord { x: 1, y: 2, z: 3, }, &Coord { x: 0, y: 5, z: 1, });
+        let json = serde_json::to_string(&region).unwrap();
+        assert_eq!(serde_json::from_str::<Region>(&json).unwrap(), region);
+        let lcd = LinearCoordDiff::Long { axis: Axis::Y, value: -7, };
+        let json = serde_json::to_string(&lcd).unwrap();
+        assert_eq!(serde_json::from_str::<LinearCoordDiff>(&json).unwrap(), lcd);
+    }
+
+    #[test]
+    fn par_filled_voxels_matches_serial() {
+        use rayon::prelude::*;
+        let matrix = Matrix::from_iter(
+            Resolution(20),
+            iproduct!(0 .. 20, 0 .. 20, 0 .. 20)
+                .filter(|&(x, y, z)| (x * 7 + y * 3 + z) % 5 != 0)
+                .map(|(x, y, z)| Coord { x, y, z, }));
+        let mut parallel: Vec<Coord> = matrix.par_filled_voxels().collect();
+        parallel.sort();
+        let mut serial: Vec<Coord> = matrix.filled_voxels().cloned().collect();
+        serial.sort();
+        assert_eq!(parallel, serial);
+        assert_eq!(Matrix::new(Resolution(3)).par_filled_voxels().count(), 0);
+    }
+
+    #[test]
+    fn grounding_cache_matches_uncached() {
+        let mut seed: u64 = 0x9e3779b97f4a7c15;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        let mut matrix = Matrix::new(Resolution(6));
+        matrix.set_filled(&Coord { x: 2, y: 0, z: 2, });
+        matrix.enable_grounding_cache();
+        for _ in 0 .. 500 {
+            let coord = Coord { x: (next() % 6) as isize, y: (next() % 6) as isize, z: (next() % 6) as isize, };
+            // bias towards filling so components grow and then get split by voids
+            if next() % 3 == 0 {
+                matrix.set_void(&coord);
+            } else {
+                matrix.set_filled(&coord);
+            }
+            for voxel in iproduct!(0 .. 6, 0 .. 6, 0 .. 6).map(|(x, y, z)| Coord { x, y, z, }) {
+                assert_eq!(matrix.is_grounded_cached(&voxel), matrix.is_grounded(&voxel), "{:?}", voxel);
+            }
+        }
+        let cached: Vec<bool> = matrix.filled_voxels().map(|voxel| matrix.is_grounded_cached(voxel)).collect();
+        matrix.disable_grounding_cache();
+        let uncached: Vec<bool> = matrix.filled_voxels().map(|voxel| matrix.is_grounded_cached(voxel)).collect();
+        assert_eq!(cached, uncached);
+    }
+
+    #[test]
+    fn coverage_ratio() {
+        let target = Matrix::from_iter(
+            Resolution(4),
+            (0 .. 4).map(|x| Coord { x, y: 0, z: 0, }));
+        assert_eq!(target.clone().coverage_ratio(&target), 1.0);
+
+        let half = Matrix::from_iter(
+            Resolution(4),
+            (0 .. 2).map(|x| Coord { x, y: 0, z: 0, }));
+        assert_eq!(half.coverage_ratio(&target), 0.5);
+
+        let mut half_with_extra = half.clone();
+        half_with_extra.set_filled(&Coord { x: 0, y: 1, z: 0, });
+        assert_eq!(half_with_extra.coverage_ratio(&target), 0.25);
+
+        let empty = target.new_empty_of_same_size();
+        assert_eq!(empty.coverage_ratio(&target), 0.0);
+        let elsewhere = Matrix::from_iter(Resolution(4), vec![Coord { x: 3, y: 3, z: 3, }]);
+        assert_eq!(elsewhere.coverage_ratio(&target), 0.0);
+
+        assert_eq!(empty.coverage_ratio(&empty), 1.0);
+        assert_eq!(half.coverage_ratio(&empty), 0.0);
+    }
+
+    #[test]
+    fn coverage_ratio_dimension_mismatch() {
+        assert_eq!(Matrix::new(Resolution(3)).coverage_ratio(&Matrix::new(Resolution(4))), 0.0);
+        let target = Matrix::from_iter(Resolution(4), vec![Coord { x: 0, y: 0, z: 0, }]);
+        assert_eq!(target.resize(5).coverage_ratio(&target), 0.0);
+    }
+
+    #[test]
+    fn shell_of_solid_cube() {
+        let cube = Matrix::from_iter(
+            Resolution(5),
+            iproduct!(1 .. 4, 0 .. 3, 1 .. 4).map(|(x, y, z)| Coord { x, y, z, }));
+        let shell = cube.shell();
+        assert_eq!(shell.count_filled(), 26);
+        assert!(!shell.is_filled(&Coord { x: 2, y: 1, z: 2, }));
+        assert!(shell.all_voxels_are_grounded());
+
+        let full = Matrix::from_iter(
+            Resolution(3),
+            iproduct!(0 .. 3, 0 .. 3, 0 .. 3).map(|(x, y, z)| Coord { x, y, z, }));
+        assert_eq!(full.shell().count_filled(), 26);
+    }
+
+    #[test]
+    fn mirror_symmetry() {
+        // an upright cross off-center in x and z: symmetric along x and z, but not along y
+        let cross = Matrix::from_iter(
+            Resolution(6),
+            vec![
+                Coord { x: 3, y: 0, z: 2, },
+                Coord { x: 3, y: 1, z: 2, },
+                Coord { x: 3, y: 2, z: 2, },
+                Coord { x: 2, y: 2, z: 2, },
+                Coord { x: 4, y: 2, z: 2, },
+                Coord { x: 3, y: 2, z: 1, },
+                Coord { x: 3, y: 2, z: 3, },
+                Coord { x: 3, y: 3, z: 2, },
+            ]);
+        assert!(cross.is_mirror_symmetric(Axis::X));
+        assert!(cross.is_mirror_symmetric(Axis::Z));
+        assert!(!cross.is_mirror_symmetric(Axis::Y));
+
+        let mut cross_3d = cross.clone();
+        cross_3d.set_filled(&Coord { x: 3, y: 4, z: 2, });
+        assert!(cross_3d.is_mirror_symmetric(Axis::Y));
+
+        // an L shape has no mirror symmetry along x or z
+        let l_shape = Matrix::from_iter(
+            Resolution(4),
+            vec![
+                Coord { x: 0, y: 0, z: 0, },
+                Coord { x: 1, y: 0, z: 0, },
+                Coord { x: 2, y: 0, z: 0, },
+                Coord { x: 0, y: 0, z: 1, },
+            ]);
+        assert!(!l_shape.is_mirror_symmetric(Axis::X));
+        assert!(!l_shape.is_mirror_symmetric(Axis::Z));
+        assert!(l_shape.is_mirror_symmetric(Axis::Y));
+
+        assert!(Matrix::new(Resolution(3)).is_mirror_symmetric(Axis::X));
+    }
+
+    #[test]
+    fn content_hash() {
+        let voxels = vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 2, y: 2, z: 0, },
+        ];
+        let a = Matrix::from_iter(Resolution(4), voxels.clone());
+        let mut b = Matrix::new(Resolution(4));
+        for voxel in voxels.iter().rev() {
+            b.set_filled(voxel);
+        }
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        b.set_void(&Coord { x: 2, y: 2, z: 0, });
+        assert_ne!(a.content_hash(), b.content_hash());
+        b.set_filled(&Coord { x: 2, y: 2, z: 0, });
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        assert_ne!(Matrix::new(Resolution(4)).content_hash(), Matrix::new(Resolution(5)).content_hash());
+    }
+
+    #[test]
+    fn serpentine_adjacent() {
+        for &(min, max) in [
+            (Coord { x: 0, y: 0, z: 0, }, Coord { x: 3, y: 2, z: 4, }),
+            (Coord { x: 1, y: 2, z: 3, }, Coord { x: 3, y: 4, z: 3, }),
+            (Coord { x: 2, y: 0, z: 5, }, Coord { x: 2, y: 0, z: 5, }),
+        ].iter() {
+            let region = Region::from_corners(&min, &max);
+            for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+                let coords: Vec<Coord> = super::serpentine(&region, axis).collect();
+                assert_eq!(coords.len(), region.coord_set().len());
+                assert_eq!(coords.iter().cloned().collect::<HashSet<_>>(), region.coord_set());
+                assert_eq!(coords[0], region.min);
+                for pair in coords.windows(2) {
+                    assert_eq!(pair[0].diff(&pair[1]).l_1_norm(), 1, "{:?} -> {:?}", pair[0], pair[1]);
+                }
+            }
+        }
+        let region = Region::from_corners(&Coord { x: 0, y: 0, z: 0, }, &Coord { x: 1, y: 0, z: 2, });
+        assert_eq!(super::serpentine(&region, Axis::Y).collect::<Vec<_>>(), vec![
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 0, y: 0, z: 1, },
+            Coord { x: 0, y: 0, z: 2, },
+            Coord { x: 1, y: 0, z: 2, },
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 0, z: 0, },
+        ]);
+    }
+
+    #[test]
+    fn filled_neighbour_count() {
+        let center = Coord { x: 1, y: 1, z: 1, };
+        let cross = Matrix::from_iter(Resolution(3), Some(center).into_iter().chain(center.near_neighbours()));
+        assert_eq!(cross.filled_neighbour_count(&center), 6);
+        assert_eq!(cross.filled_neighbour_count(&Coord { x: 1, y: 0, z: 1, }), 1);
+        assert_eq!(cross.filled_neighbour_count(&Coord { x: 0, y: 0, z: 0, }), 0);
+        assert_eq!(cross.filled_neighbour_count(&Coord { x: 0, y: 0, z: 1, }), 2);
+        for coord in Region::from_corners(&Coord { x: 0, y: 0, z: 0, }, &Coord { x: 2, y: 2, z: 2, }).coord_set() {
+            assert_eq!(cross.filled_neighbour_count(&coord), cross.filled_near_neighbours(&coord).count());
+        }
+    }
+
+    #[test]
+    fn center_of_mass() {
+        assert_eq!(Matrix::new(Resolution(5)).center_of_mass(), None);
+        let center = Coord { x: 2, y: 1, z: 3, };
+        let cross = Matrix::from_iter(Resolution(5), Some(center).into_iter().chain(center.near_neighbours()));
+        assert_eq!(cross.center_of_mass(), Some(center));
+        let frame = Region::from_corners(&Coord { x: 0, y: 0, z: 0, }, &Coord { x: 4, y: 2, z: 4, });
+        let hollow = Matrix::from_iter(Resolution(5), frame.surface());
+        assert_eq!(hollow.center_of_mass(), Some(Coord { x: 2, y: 1, z: 2, }));
+        let pair = Matrix::from_iter(Resolution(5), vec![Coord { x: 0, y: 0, z: 0, }, Coord { x: 3, y: 0, z: 0, }]);
+        assert_eq!(pair.center_of_mass(), Some(Coord { x: 2, y: 0, z: 0, }));
+    }
+
+    #[test]
+    fn invert() {
+        let matrix = Matrix::from_iter(Resolution(3), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 0, y: 2, z: 2, },
+        ]);
+        let inverted = matrix.invert();
+        assert_eq!(inverted.dim(), 3);
+        assert_eq!(inverted.count_filled(), 27 - 3);
+        assert!(!inverted.is_filled(&Coord { x: 1, y: 1, z: 1, }));
+        assert!(inverted.is_filled(&Coord { x: 0, y: 0, z: 0, }));
+        let twice = inverted.invert();
+        assert_eq!(twice.filled_voxels().collect::<HashSet<_>>(), matrix.filled_voxels().collect::<HashSet<_>>());
+        assert_eq!(Matrix::new(Resolution(2)).invert().count_filled(), 8);
+    }
+
+    #[test]
+    fn equals_compares_dim() {
+        let matrix = Matrix::from_iter(Resolution(3), vec![Coord { x: 1, y: 0, z: 1, }]);
+        assert!(matrix.equals(&matrix.clone()));
+        assert_eq!(matrix, matrix.clone());
+        assert!(matrix != Matrix::new(Resolution(3)));
+
+        let mut same_bits = matrix.clone();
+        same_bits.dim = 2;
+        assert!(!matrix.equals(&same_bits));
+        assert!(matrix != same_bits);
+    }
+
+    #[test]
+    fn filled_voxels_in_tower_sub_box() {
+        let tower: Vec<_> = iproduct!(1 .. 3, 0 .. 6, 1 .. 3).map(|(x, y, z)| Coord { x, y, z, }).collect();
+        let matrix = Matrix::from_iter(Resolution(8), tower.clone());
+        let region = Region { min: Coord { x: 2, y: 2, z: 0, }, max: Coord { x: 4, y: 3, z: 1, }, };
+        let expected: HashSet<_> = tower.iter().cloned().filter(|c| region.contains(c)).collect();
+        assert_eq!(expected.len(), 2);
+        assert_eq!(matrix.filled_voxels_in(&region).collect::<HashSet<_>>(), expected);
+        assert_eq!(matrix.filled_voxels_in(&region).count(), 2);
+
+        // wider than the filled set and sticking out of the matrix
+        let region = Region { min: Coord { x: -2, y: 4, z: -2, }, max: Coord { x: 9, y: 9, z: 9, }, };
+        let expected: HashSet<_> = tower.iter().cloned().filter(|c| c.y >= 4).collect();
+        assert_eq!(matrix.filled_voxels_in(&region).collect::<HashSet<_>>(), expected);
+
+        let outside = Region { min: Coord { x: 9, y: 0, z: 0, }, max: Coord { x: 10, y: 1, z: 1, }, };
+        assert_eq!(matrix.filled_voxels_in(&outside).count(), 0);
+    }
+
+    #[test]
+    fn apply_fill_void_changes() {
+        let mut matrix = Matrix::new(Resolution(3));
+        let coord = Coord { x: 1, y: 0, z: 2, };
+        assert!(matrix.apply_fill(&coord));
+        assert!(!matrix.apply_fill(&coord));
+        assert!(matrix.is_filled(&coord));
+        assert_eq!(matrix.count_filled(), 1);
+        assert!(matrix.apply_void(&coord));
+        assert!(!matrix.apply_void(&coord));
+        assert!(!matrix.is_filled(&coord));
+        assert_eq!(matrix.count_filled(), 0);
+    }
+
+    #[test]
+    fn count_filled_in_cross() {
+        let matrix = Matrix::from_iter(
+            Resolution(3),
+            vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 0, y: 1, z: 1, },
+                Coord { x: 1, y: 1, z: 0, },
+                Coord { x: 1, y: 1, z: 2, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 2, y: 1, z: 1, },
+                Coord { x: 1, y: 2, z: 1, },
+            ]);
+        // the lower half of the x = 1 slice: the foot, the center and the two z arms
+        let region = Region { min: Coord { x: 1, y: 0, z: 0, }, max: Coord { x: 1, y: 1, z: 2, }, };
+        assert_eq!(matrix.count_filled_in(&region), 4);
+        assert_eq!(matrix.count_filled_in(&Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 2, y: 2, z: 2, }, }), 7);
+        assert_eq!(matrix.count_filled_in(&Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 0, y: 0, z: 2, }, }), 0);
+    }
+
+    #[test]
+    fn resize_tower() {
+        let tower: Vec<_> = (0 .. 4).map(|y| Coord { x: 1, y, z: 2, }).collect();
+        let matrix = Matrix::from_iter(Resolution(5), tower.clone());
+
+        let grown = matrix.resize(8);
+        assert_eq!(grown.dim(), 8);
+        assert_eq!(grown, Matrix::from_iter(Resolution(8), tower.clone()));
+        assert_eq!(grown.resize(5), matrix);
+
+        let shrunk = matrix.resize(3);
+        assert_eq!(shrunk.dim(), 3);
+        assert_eq!(shrunk, Matrix::from_iter(Resolution(3), tower[.. 3].to_vec()));
+        assert_eq!(matrix.resize(1).count_filled(), 0);
+    }
+
+    #[test]
+    fn region_would_be_grounded() {
+        let matrix = Matrix::from_iter(Resolution(8), (0 .. 3).map(|y| Coord { x: 1, y, z: 1, }));
+        let floor_box = Region::from_corners(&Coord { x: 4, y: 0, z: 4, }, &Coord { x: 6, y: 2, z: 6, });
+        assert!(matrix.region_would_be_grounded(&floor_box));
+        let floating_box = Region::from_corners(&Coord { x: 4, y: 2, z: 4, }, &Coord { x: 6, y: 4, z: 6, });
+        assert!(!matrix.region_would_be_grounded(&floating_box));
+        // hanging off the top of the column
+        let attached_box = Region::from_corners(&Coord { x: 2, y: 2, z: 1, }, &Coord { x: 5, y: 3, z: 3, });
+        assert!(matrix.region_would_be_grounded(&attached_box));
+    }
+
+    #[test]
+    fn region_surface() {
+        let cube = Region::from_corners(&Coord { x: 1, y: 1, z: 1, }, &Coord { x: 3, y: 3, z: 3, });
+        let surface: HashSet<Coord> = cube.surface().collect();
+        assert_eq!(cube.surface().count(), 26);
+        assert!(!surface.contains(&Coord { x: 2, y: 2, z: 2, }));
+        assert!(surface.is_subset(&cube.coord_set()));
+
+        let box_4x3x5 = Region::from_corners(&Coord { x: 0, y: 0, z: 0, }, &Coord { x: 3, y: 2, z: 4, });
+        assert_eq!(box_4x3x5.surface().count(), 4 * 3 * 5 - 2 * 1 * 3);
+
+        let line = Region::from_corners(&Coord { x: 0, y: 2, z: 5, }, &Coord { x: 0, y: 2, z: 9, });
+        assert_eq!(line.surface().collect::<HashSet<_>>(), line.coord_set());
+        let point = Region::from_corners(&Coord { x: 4, y: 4, z: 4, }, &Coord { x: 4, y: 4, z: 4, });
+        assert_eq!(point.surface().collect::<Vec<_>>(), vec![Coord { x: 4, y: 4, z: 4, }]);
+    }
+
+    #[test]
+    fn translate_tower_up() {
+        let tower: Vec<Coord> = (0 .. 3).map(|y| Coord { x: 1, y, z: 2, }).collect();
+        let matrix = Matrix::from_iter(Resolution(3), tower);
+        let shifted = matrix.translate(CoordDiff(Coord { x: 0, y: 1, z: 0, }));
+        assert_eq!(shifted.dim(), 3);
+        assert!(!shifted.is_filled(&Coord { x: 1, y: 0, z: 2, }));
+        assert!(shifted.is_filled(&Coord { x: 1, y: 1, z: 2, }));
+        assert!(shifted.is_filled(&Coord { x: 1, y: 2, z: 2, }));
+        // the top one went through the ceiling
+        assert_eq!(shifted.count_filled(), 2);
+        assert_eq!(shifted.translate(CoordDiff(Coord { x: 0, y: -1, z: 0, })).count_filled(), 2);
+        assert_eq!(matrix.translate(CoordDiff(Coord { x: 0, y: 0, z: 0, })), matrix);
+    }
+
+    #[test]
+    fn region_corner_pairs() {
+        let region = Region::from_corners(&Coord { x: 1, y: 2, z: 3, }, &Coord { x: 4, y: 6, z: 5, });
+        let c = |x, y, z| Coord { x, y, z, };
+        assert_eq!(region.opposite_corners(), (c(1, 2, 3), c(4, 6, 5)));
+        assert_eq!(region.all_corner_pairs(), [
+            (c(1, 2, 3), c(4, 6, 5)),
+            (c(1, 2, 5), c(4, 6, 3)),
+            (c(1, 6, 3), c(4, 2, 5)),
+            (c(1, 6, 5), c(4, 2, 3)),
+        ]);
+        // every corner once, every pair spans the whole region
+        let corners: HashSet<Coord> = region.all_corner_pairs().iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        assert_eq!(corners.len(), 8);
+        assert!(region.all_corner_pairs().iter().all(|&(a, b)| Region::from_corners(&a, &b) == region));
+    }
+
+    #[test]
+    fn region_contents_morton() {
+        let cube = Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 1, y: 1, z: 1, }, };
+        assert_eq!(cube.contents_morton().take(3).collect::<Vec<_>>(), vec![
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 1, y: 0, z: 0, },
+            Coord { x: 0, y: 1, z: 0, },
+        ]);
+        for region in &[
+            cube,
+            Region { min: Coord { x: 3, y: 3, z: 3, }, max: Coord { x: 3, y: 3, z: 3, }, },
+            Region { min: Coord { x: 1, y: 2, z: 3, }, max: Coord { x: 5, y: 2, z: 3, }, },
+            Region { min: Coord { x: 2, y: 0, z: 1, }, max: Coord { x: 8, y: 3, z: 5, }, },
+        ] {
+            let morton: Vec<_> = region.contents_morton().collect();
+            assert_eq!(morton.len(), region.contents_count());
+            assert_eq!(morton.into_iter().collect::<HashSet<_>>(), region.coord_set());
+        }
+    }
+
+    #[test]
+    fn region_contents_grounded_order() {
+        let region = Region::from_corners(&Coord { x: 1, y: 0, z: 2, }, &Coord { x: 4, y: 3, z: 4, });
+        let order: Vec<_> = region.contents_grounded_order().collect();
+        assert_eq!(order.len(), 4 * 4 * 3);
+        assert_eq!(order.iter().cloned().collect::<HashSet<_>>(), region.coord_set());
+        assert!(order.windows(2).all(|w| w[0].y <= w[1].y));
+        assert!(order.windows(2).all(|w| w[0].diff(&w[1]).l_1_norm() == 1));
+
+        // every voxel touches the floor or one filled before it
+        let mut filled = HashSet::new();
+        for coord in order {
+            assert!(coord.y == 0 || coord.get_neighbours().any(|n| filled.contains(&n)));
+            filled.insert(coord);
+        }
+    }
+
+    #[test]
+    fn region_expand_clamp() {
+        let region = Region::from_corners(&Coord { x: 1, y: 0, z: 3, }, &Coord { x: 2, y: 4, z: 5, });
+        let expanded = region.expand(2);
+        assert_eq!(expanded, Region {
+            min: Coord { x: -1, y: -2, z: 1, },
+            max: Coord { x: 4, y: 6, z: 7, },
+        });
+        assert_eq!(expanded.clamp_to_dim(6), Region {
+            min: Coord { x: 0, y: 0, z: 1, },
+            max: Coord { x: 4, y: 5, z: 5, },
+        });
+        assert_eq!(region.expand(0).clamp_to_dim(10), region);
+    }
+
+    #[test]
+    fn region_around_origin() {
+        let origin = Coord { x: 0, y: 0, z: 0, };
+        assert_eq!(Region::around(&origin, 2), Region {
+            min: Coord { x: -2, y: -2, z: -2, },
+            max: Coord { x: 2, y: 2, z: 2, },
+        });
+        assert_eq!(Region::around_clamped(&origin, 2, 10), Region {
+            min: origin,
+            max: Coord { x: 2, y: 2, z: 2, },
+        });
+        assert_eq!(Region::around(&origin, 0), Region { min: origin, max: origin, });
+    }
+
+    #[test]
+    fn partition_region_even() {
+        let region = Region::from_corners(&Coord { x: 0, y: 1, z: 2, }, &Coord { x: 5, y: 6, z: 7, });
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let parts = super::partition_region(&region, 3, axis);
+            assert_eq!(parts.len(), 3);
+            let volume: usize = parts.iter().map(|p| p.coord_set().len()).sum();
+            assert_eq!(volume, region.coord_set().len());
+            for part in parts.iter() {
+                assert_eq!(part.coord_set().len(), 72);
+            }
+        }
+        assert_eq!(super::partition_region(&region, 2, Axis::Y), vec![
+            Region::from_corners(&Coord { x: 0, y: 1, z: 2, }, &Coord { x: 5, y: 3, z: 7, }),
+            Region::from_corners(&Coord { x: 0, y: 4, z: 2, }, &Coord { x: 5, y: 6, z: 7, }),
+        ]);
+    }
+
+    #[test]
+    fn partition_region_uneven() {
+        let region = Region::from_corners(&Coord { x: 0, y: 0, z: 0, }, &Coord { x: 6, y: 6, z: 6, });
+        for &axis in [Axis::X, Axis::Y, Axis::Z].iter() {
+            let parts = super::partition_region(&region, 3, axis);
+            let volumes: Vec<usize> = parts.iter().map(|p| p.coord_set().len()).collect();
+            assert_eq!(volumes, vec![147, 98, 98]);
+            for pair in parts.windows(2) {
+                assert!(!pair[0].intersects(&pair[1]));
+            }
+        }
+        assert_eq!(super::partition_region(&region, 10, Axis::Z).len(), 7);
+        assert!(super::partition_region(&region, 0, Axis::Z).is_empty());
+    }
+
     #[test]
     fn tower_all_voxels_are_grounded() {
         let matrix = Matrix::from_iter(Resolution(3), vec![
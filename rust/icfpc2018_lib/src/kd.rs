@@ -1,10 +1,11 @@
-use std::{iter, cmp::Ordering};
+use std::{iter, cmp::{self, Ordering}, collections::HashSet};
 use kdvtree;
 
 use super::{
     coord::{
         Axis,
         Coord,
+        Region,
     },
 };
 
@@ -63,6 +64,27 @@ impl kdvtree::GetCutPoint<Axis, Coord> for CutPoint {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RegionBox(Region);
+
+impl kdvtree::BoundingVolume<Coord> for RegionBox {
+    fn min_corner(&self) -> Coord {
+        self.0.min
+    }
+
+    fn max_corner(&self) -> Coord {
+        self.0.max
+    }
+}
+
+fn get_region_volume(region: &Region) -> RegionBox {
+    RegionBox(*region)
+}
+
+fn region_cutter(_shape: &Region, _fragment: &RegionBox, _cut_axis: &Axis, _cut_point: &Coord) -> Result<Option<(RegionBox, RegionBox)>, ()> {
+    Ok(None)
+}
+
 fn empty_cutter(_shape: &Coord, _fragment: &BoundingBox, _cut_axis: &Axis, _cut_point: &Coord) -> Result<Option<(BoundingBox, BoundingBox)>, ()> {
     Ok(None)
 }
@@ -105,12 +127,218 @@ impl KdTree {
             .map(|maybe_result| maybe_result.unwrap_or_else(|()| unreachable!()))
             .map(|nearest_shape| (*nearest_shape.shape, nearest_shape.dist))
     }
+
+    pub fn range<'a>(&'a self, region: &'a Region) -> impl Iterator<Item = Coord> + 'a {
+        self.kd.intersects(
+            region,
+            cmp_points,
+            get_region_volume,
+            CutPoint::new(),
+            region_cutter,
+        )
+            .map(|maybe_result| maybe_result.unwrap_or_else(|()| unreachable!()))
+            .map(|intersection| *intersection.shape)
+    }
+}
+
+pub struct CoordIndex {
+    tree: KdTree,
+    bounds: Option<Region>,
+    // the tree is immutable, so removed coords are only masked out of the queries
+    removed: HashSet<Coord>,
+    len: usize,
+}
+
+impl CoordIndex {
+    pub fn build<I>(coords: I) -> CoordIndex where I: IntoIterator<Item = Coord> {
+        let coords: Vec<Coord> = coords.into_iter().collect();
+        let bounds = coords.iter()
+            .fold(None, |bounds: Option<Region>, coord| Some(match bounds {
+                None =>
+                    Region { min: *coord, max: *coord, },
+                Some(region) => Region {
+                    min: Coord {
+                        x: cmp::min(region.min.x, coord.x),
+                        y: cmp::min(region.min.y, coord.y),
+                        z: cmp::min(region.min.z, coord.z),
+                    },
+                    max: Coord {
+                        x: cmp::max(region.max.x, coord.x),
+                        y: cmp::max(region.max.y, coord.y),
+                        z: cmp::max(region.max.z, coord.z),
+                    },
+                },
+            }));
+        let len = coords.len();
+        CoordIndex { tree: KdTree::build(coords), bounds, removed: HashSet::new(), len, }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn remove(&mut self, coord: &Coord) -> bool {
+        if self.removed.contains(coord) {
+            return false;
+        }
+        let count = self.tree.range(&Region { min: *coord, max: *coord, }).count();
+        if count == 0 {
+            return false;
+        }
+        self.removed.insert(*coord);
+        self.len -= count;
+        true
+    }
+
+    // a removed coord is just unmasked, a new one rebuilds the whole tree
+    pub fn insert(&mut self, coord: Coord) {
+        if self.removed.remove(&coord) {
+            self.len += self.tree.range(&Region { min: coord, max: coord, }).count();
+            return;
+        }
+        let mut coords: Vec<Coord> = match self.bounds {
+            Some(ref bounds) => self.live_range(bounds).collect(),
+            None => Vec::new(),
+        };
+        coords.push(coord);
+        *self = CoordIndex::build(coords);
+    }
+
+    fn live_range<'a>(&'a self, region: &'a Region) -> impl Iterator<Item = Coord> + 'a {
+        self.tree.range(region)
+            .filter(move |c| !self.removed.contains(c))
+    }
+
+    // `KdTree::nearest` may skip shapes lying on a cut plane, so both queries go through `range`
+    // over the L-inf cube enclosing the L1 ball.
+    pub fn nearest(&self, coord: &Coord) -> Option<Coord> {
+        if self.is_empty() {
+            return None;
+        }
+        let bounds = self.bounds?;
+        let mut r = 1;
+        loop {
+            let cube = cube_around(coord, r);
+            let found = self.live_range(&cube)
+                .map(|c| (c.diff(coord).l_1_norm(), c))
+                .min();
+            let covers_all = cube.contains(&bounds.min) && cube.contains(&bounds.max);
+            match found {
+                Some((dist, nearest)) if dist <= r || covers_all =>
+                    return Some(nearest),
+                None if covers_all =>
+                    return None,
+                _ =>
+                    r *= 2,
+            }
+        }
+    }
+
+    pub fn nearest_many(&self, queries: &[Coord]) -> Vec<Option<Coord>> {
+        queries.iter()
+            .map(|query| self.nearest(query))
+            .collect()
+    }
+
+    pub fn nearest_within(&self, coord: &Coord, max_dist: usize) -> Option<(usize, Coord)> {
+        let cube = cube_around(coord, max_dist);
+        self.live_range(&cube)
+            .map(|c| (c.diff(coord).l_1_norm(), c))
+            .filter(|&(dist, _)| dist <= max_dist)
+            .min()
+    }
+
+    pub fn within_radius(&self, coord: &Coord, r: usize) -> Vec<Coord> {
+        let cube = cube_around(coord, r);
+        self.live_range(&cube)
+            .filter(|c| c.diff(coord).l_1_norm() <= r)
+            .collect()
+    }
+}
+
+// fresh regions are checked one by one until there are this many of them, then the tree is rebuilt
+const REGION_INDEX_PENDING_MAX: usize = 16;
+
+// A growing set of reserved regions (like the volatile regions of a tick) queried for intersections.
+// The kd tree is immutable, so it is rebuilt from scratch every `REGION_INDEX_PENDING_MAX` inserts.
+#[derive(Default)]
+pub struct RegionIndex {
+    tree: Option<kdvtree::KdvTree<Axis, Coord, RegionBox, Region>>,
+    regions: Vec<Region>,
+    // `regions[.. indexed]` are in the tree
+    indexed: usize,
+}
+
+impl RegionIndex {
+    pub fn new() -> RegionIndex {
+        RegionIndex::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree = None;
+        self.regions.clear();
+        self.indexed = 0;
+    }
+
+    pub fn insert(&mut self, region: Region) {
+        self.regions.push(region);
+        if self.regions.len() - self.indexed >= REGION_INDEX_PENDING_MAX {
+            let tree = kdvtree::KdvTree::build(
+                iter::once(Axis::X).chain(iter::once(Axis::Y)).chain(iter::once(Axis::Z)),
+                self.regions.iter().cloned(),
+                cmp_points,
+                get_region_volume,
+                CutPoint::new(),
+                region_cutter,
+            ).unwrap_or_else(|()| unreachable!());
+            self.tree = Some(tree);
+            self.indexed = self.regions.len();
+        }
+    }
+
+    pub fn any_intersecting(&self, region: &Region) -> bool {
+        if self.regions[self.indexed ..].iter().any(|reserved| reserved.intersects(region)) {
+            return true;
+        }
+        match self.tree {
+            None =>
+                false,
+            // the tree sends a region ending at a cut point to the left and one starting there to
+            // the right, so regions sharing the voxels on the cut plane are only found by a grown needle
+            Some(ref tree) =>
+                tree.intersects(
+                    &region.expand(1),
+                    cmp_points,
+                    get_region_volume,
+                    CutPoint::new(),
+                    region_cutter,
+                )
+                    .map(|maybe_result| maybe_result.unwrap_or_else(|()| unreachable!()))
+                    .any(|intersection| intersection.shape.intersects(region)),
+        }
+    }
+}
+
+fn cube_around(coord: &Coord, r: usize) -> Region {
+    Region::around(coord, r as isize)
 }
 
 #[cfg(test)]
 mod test {
-    use super::super::coord::Coord;
-    use super::KdTree;
+    use super::super::coord::{Coord, Region};
+    use super::{KdTree, CoordIndex, RegionIndex};
 
     #[test]
     fn build_empty() {
@@ -180,4 +408,143 @@ mod test {
             ],
         );
     }
+
+    #[test]
+    fn range_vs_brute_force() {
+        use rand::{Rng, SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let random_coord = |rng: &mut XorShiftRng| Coord {
+            x: rng.gen_range(0, 30),
+            y: rng.gen_range(0, 30),
+            z: rng.gen_range(0, 30),
+        };
+        let points: Vec<_> = (0 .. 1000).map(|_| random_coord(&mut rng)).collect();
+        let kd = KdTree::build(points.iter().cloned());
+        for _ in 0 .. 100 {
+            let region = Region::from_corners(&random_coord(&mut rng), &random_coord(&mut rng));
+            let mut found: Vec<_> = kd.range(&region).collect();
+            found.sort();
+            let mut expected: Vec<_> = points.iter()
+                .filter(|p| region.contains(p))
+                .cloned()
+                .collect();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn coord_index_vs_brute_force() {
+        use rand::{Rng, SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let random_coord = |rng: &mut XorShiftRng| Coord {
+            x: rng.gen_range(0, 30),
+            y: rng.gen_range(0, 30),
+            z: rng.gen_range(0, 30),
+        };
+        let points: Vec<_> = (0 .. 1000).map(|_| random_coord(&mut rng)).collect();
+        let index = CoordIndex::build(points.iter().cloned());
+        for _ in 0 .. 100 {
+            let query = random_coord(&mut rng);
+            let nearest = index.nearest(&query).unwrap();
+            let expected_dist = points.iter()
+                .map(|p| p.diff(&query).l_1_norm())
+                .min()
+                .unwrap();
+            assert_eq!(nearest.diff(&query).l_1_norm(), expected_dist);
+
+            let mut found = index.within_radius(&query, 5);
+            found.sort();
+            let mut expected: Vec<_> = points.iter()
+                .filter(|p| p.diff(&query).l_1_norm() <= 5)
+                .cloned()
+                .collect();
+            expected.sort();
+            assert_eq!(found, expected);
+        }
+        assert_eq!(CoordIndex::build(None).nearest(&Coord { x: 0, y: 0, z: 0, }), None);
+    }
+
+    #[test]
+    fn coord_index_nearest_within() {
+        let index = CoordIndex::build(vec![
+            Coord { x: 10, y: 0, z: 0, },
+            Coord { x: 0, y: 7, z: 5, },
+        ]);
+        let origin = Coord { x: 0, y: 0, z: 0, };
+        assert_eq!(index.nearest_within(&origin, 9), None);
+        assert_eq!(index.nearest_within(&origin, 10), Some((10, Coord { x: 10, y: 0, z: 0, })));
+        assert_eq!(index.nearest_within(&Coord { x: 0, y: 6, z: 5, }, 1), Some((1, Coord { x: 0, y: 7, z: 5, })));
+        assert_eq!(CoordIndex::build(None).nearest_within(&origin, 100), None);
+    }
+
+    #[test]
+    fn coord_index_remove() {
+        let mut index = CoordIndex::build(vec![
+            Coord { x: 1, y: 0, z: 0, },
+            Coord { x: 3, y: 0, z: 0, },
+            Coord { x: 9, y: 9, z: 9, },
+        ]);
+        let origin = Coord { x: 0, y: 0, z: 0, };
+        assert_eq!(index.len(), 3);
+        assert!(index.remove(&Coord { x: 1, y: 0, z: 0, }));
+        assert!(!index.remove(&Coord { x: 1, y: 0, z: 0, }));
+        assert!(!index.remove(&Coord { x: 2, y: 0, z: 0, }));
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.nearest(&origin), Some(Coord { x: 3, y: 0, z: 0, }));
+        assert_eq!(index.within_radius(&origin, 3), vec![Coord { x: 3, y: 0, z: 0, }]);
+        assert!(index.remove(&Coord { x: 3, y: 0, z: 0, }));
+        assert_eq!(index.nearest_within(&origin, 5), None);
+        assert_eq!(index.nearest(&origin), Some(Coord { x: 9, y: 9, z: 9, }));
+        assert!(index.remove(&Coord { x: 9, y: 9, z: 9, }));
+        assert!(index.is_empty());
+        assert_eq!(index.nearest(&origin), None);
+    }
+
+    #[test]
+    fn coord_index_nearest_many() {
+        use rand::{Rng, SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let random_coord = |rng: &mut XorShiftRng| Coord {
+            x: rng.gen_range(0, 30),
+            y: rng.gen_range(0, 30),
+            z: rng.gen_range(0, 30),
+        };
+        let index = CoordIndex::build((0 .. 500).map(|_| random_coord(&mut rng)));
+        let queries: Vec<_> = (0 .. 50).map(|_| random_coord(&mut rng)).collect();
+        let expected: Vec<_> = queries.iter().map(|q| index.nearest(q)).collect();
+        assert_eq!(index.nearest_many(&queries), expected);
+        assert_eq!(CoordIndex::build(None).nearest_many(&queries[.. 2]), vec![None, None]);
+    }
+
+    #[test]
+    fn region_index_vs_brute_force() {
+        use rand::{Rng, SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let random_region = |rng: &mut XorShiftRng| {
+            let corner = Coord { x: rng.gen_range(0, 30), y: rng.gen_range(0, 30), z: rng.gen_range(0, 30), };
+            let far = Coord { x: corner.x + rng.gen_range(0, 4), y: corner.y + rng.gen_range(0, 4), z: corner.z, };
+            Region::from_corners(&corner, &far)
+        };
+        let mut index = RegionIndex::new();
+        let mut regions = Vec::new();
+        assert!(!index.any_intersecting(&random_region(&mut rng)));
+        for _ in 0 .. 100 {
+            let region = random_region(&mut rng);
+            index.insert(region);
+            regions.push(region);
+            for _ in 0 .. 10 {
+                let query = random_region(&mut rng);
+                assert_eq!(index.any_intersecting(&query), regions.iter().any(|r| r.intersects(&query)));
+            }
+        }
+        assert_eq!(index.len(), 100);
+        index.clear();
+        assert!(index.is_empty());
+        assert!(!index.any_intersecting(&regions[0]));
+    }
 }
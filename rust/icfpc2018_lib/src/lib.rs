@@ -2,9 +2,13 @@ extern crate rtt;
 extern crate rand;
 extern crate kdvtree;
 extern crate bit_vec;
+extern crate rayon;
+extern crate flate2;
 extern crate pathfinding;
 #[macro_use] extern crate log;
 #[macro_use] extern crate itertools;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(feature = "serde")] extern crate serde_json;
 
 pub mod solver;
 pub mod router;
@@ -14,4 +18,6 @@ pub mod model;
 pub mod cmd;
 pub mod kd;
 
+pub use state::{simulate, SimulationReport, compare_traces, TraceComparison};
+
 #[cfg(test)] mod junk;
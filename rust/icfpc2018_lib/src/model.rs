@@ -1,12 +1,19 @@
 use std::{
     fs,
     path::Path,
-    io::{self, Read},
+    io::{self, Read, BufRead, Write},
+};
+
+use flate2::{
+    Compression,
+    read::GzDecoder,
+    write::GzEncoder,
 };
 
 use super::coord::{
     M,
     Coord,
+    Region,
     Matrix,
     Resolution,
 };
@@ -15,21 +22,78 @@ use super::coord::{
 pub enum ModelError {
     ResolutionRead(io::Error),
     VoxelsRead(io::Error),
+    LengthMismatch { expected: usize, actual: usize, },
 }
 
 #[derive(Debug)]
 pub enum ReadError {
     FileOpen(io::Error),
+    FileRead(io::Error),
     Model(ModelError),
 }
 
+#[derive(Debug)]
+pub enum WriteError {
+    FileCreate(io::Error),
+    FileWrite(io::Error),
+    Gzip(io::Error),
+}
+
+#[derive(Debug)]
+pub enum AsciiError {
+    LayerHeaderExpected { line: usize, },
+    LayerOutOfOrder { line: usize, expected: usize, },
+    RowLengthMismatch { line: usize, expected: usize, actual: usize, },
+    RowsCountMismatch { layer: usize, expected: usize, actual: usize, },
+    LayersCountMismatch { expected: usize, actual: usize, },
+    InvalidVoxel { line: usize, voxel: char, },
+    DimensionTooLarge { dim: usize, },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ModelValidation {
+    pub first_ungrounded: Coord,
+    pub ungrounded_count: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ModelStats {
+    pub resolution: usize,
+    pub filled_count: usize,
+    pub bounding_box: Option<Region>,
+    pub grounded_count: usize,
+    pub ungrounded_count: usize,
+    pub components: usize,
+    pub center_of_mass: Option<Coord>,
+}
+
 #[derive(Debug)]
 pub struct Error {
     filename: String,
     error: ReadError,
 }
 
-pub fn read_model<R>(mut reader: R) -> Result<Matrix, ModelError> where R: Read {
+#[derive(Debug)]
+pub enum PairError {
+    Read(Error),
+    NoSourceOrTarget,
+    DimMismatch { source_dim: usize, target_dim: usize, },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProblemKind {
+    LightningAssembly,
+    Assembly,
+    Disassembly,
+    Reassembly,
+    Unknown,
+}
+
+pub fn read_model<R>(reader: R) -> Result<Matrix, ModelError> where R: Read {
+    read_model_from(reader)
+}
+
+pub fn read_model_from<R>(mut reader: R) -> Result<Matrix, ModelError> where R: Read {
     let mut buf = [0u8];
     reader.read_exact(&mut buf)
         .map_err(ModelError::ResolutionRead)?;
@@ -37,9 +101,12 @@ pub fn read_model<R>(mut reader: R) -> Result<Matrix, ModelError> where R: Read
     let res = Resolution(dim as M);
 
     let bytes_total = ((dim * dim * dim) + 7) / 8;
-    let mut bytes: Vec<u8> = (0 .. bytes_total).map(|_| 0).collect();
-    reader.read_exact(&mut bytes)
+    let mut bytes: Vec<u8> = Vec::with_capacity(bytes_total);
+    reader.read_to_end(&mut bytes)
         .map_err(ModelError::VoxelsRead)?;
+    if bytes.len() != bytes_total {
+        return Err(ModelError::LengthMismatch { expected: bytes_total, actual: bytes.len(), });
+    }
 
     let mut coord = Coord { x: 0, y: 0, z: 0, };
     let coords_iter = bytes
@@ -76,14 +143,203 @@ pub fn read_model_file<P>(filename: P) -> Result<Matrix, Error> where P: AsRef<P
             filename: filename.as_ref().to_string_lossy().to_string(),
             error,
         })?;
-    read_model(io::BufReader::new(file))
-        .map_err(ReadError::Model)
+    let mut reader = io::BufReader::new(file);
+    is_gzipped(&mut reader)
+        .map_err(ReadError::FileRead)
+        .and_then(|gzipped| if gzipped {
+            read_model_from(GzDecoder::new(reader))
+                .map_err(ReadError::Model)
+        } else {
+            read_model_from(reader)
+                .map_err(ReadError::Model)
+        })
         .map_err(|error| Error {
             filename: filename.as_ref().to_string_lossy().to_string(),
             error,
         })
 }
 
+// Source and target of a problem: either one may be missing (an assembly or a disassembly problem),
+// then it is taken empty of the same size as the other one.
+pub fn load_pair(source: Option<&Path>, target: Option<&Path>) -> Result<(Matrix, Matrix), PairError> {
+    let source_model = source.map(read_model_file).map_or(Ok(None), |read| read.map(Some)).map_err(PairError::Read)?;
+    let target_model = target.map(read_model_file).map_or(Ok(None), |read| read.map(Some)).map_err(PairError::Read)?;
+    match (source_model, target_model) {
+        (Some(source_model), Some(target_model)) =>
+            if source_model.dim() == target_model.dim() {
+                Ok((source_model, target_model))
+            } else {
+                Err(PairError::DimMismatch { source_dim: source_model.dim(), target_dim: target_model.dim(), })
+            },
+        (Some(source_model), None) => {
+            let target_model = source_model.new_empty_of_same_size();
+            Ok((source_model, target_model))
+        },
+        (None, Some(target_model)) =>
+            Ok((target_model.new_empty_of_same_size(), target_model)),
+        (None, None) =>
+            Err(PairError::NoSourceOrTarget),
+    }
+}
+
+// The contest names problems `LAnnn`, `FAnnn`, `FDnnn` and `FRnnn` with models `<problem>_src.mdl` and
+// `<problem>_tgt.mdl`: the problem prefix wins, then the model suffix (a lone source is disassembled,
+// a lone target assembled), and without a usable hint a nonempty model is taken as a target.
+pub fn classify(path: Option<&Path>, matrix: &Matrix) -> ProblemKind {
+    let stem = path
+        .and_then(|path| path.file_stem())
+        .map(|stem| stem.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    if stem.starts_with("LA") {
+        ProblemKind::LightningAssembly
+    } else if stem.starts_with("FA") {
+        ProblemKind::Assembly
+    } else if stem.starts_with("FD") {
+        ProblemKind::Disassembly
+    } else if stem.starts_with("FR") {
+        ProblemKind::Reassembly
+    } else if stem.ends_with("_SRC") {
+        ProblemKind::Disassembly
+    } else if stem.ends_with("_TGT") || matrix.count_filled() > 0 {
+        ProblemKind::Assembly
+    } else {
+        ProblemKind::Unknown
+    }
+}
+
+// A valid model never fills voxel (0, 0, 0), so a raw file can not start with the gzip magic (and
+// neither can a valid trace, see `cmd::read_trace_file`).
+pub fn is_gzipped<R>(reader: &mut R) -> Result<bool, io::Error> where R: BufRead {
+    let buf = reader.fill_buf()?;
+    Ok(buf.len() >= 2 && buf[0] == 0x1f && buf[1] == 0x8b)
+}
+
+pub fn write_model(matrix: &Matrix) -> Vec<u8> {
+    let dim = matrix.dim();
+    let bytes_total = ((dim * dim * dim) + 7) / 8;
+    let mut bytes: Vec<u8> = Vec::with_capacity(bytes_total + 1);
+    bytes.push(dim as u8);
+    bytes.extend((0 .. bytes_total).map(|_| 0));
+    for voxel in matrix.filled_voxels() {
+        let index = (voxel.x as usize * dim * dim) + (voxel.y as usize * dim) + voxel.z as usize;
+        bytes[1 + index / 8] |= 1 << (index % 8);
+    }
+    bytes
+}
+
+// A `.gz` filename (like `LA001_tgt.mdl.gz`) gets the model gzipped.
+pub fn write_model_file<P>(filename: P, matrix: &Matrix) -> Result<(), WriteError> where P: AsRef<Path> {
+    let mut file = fs::File::create(&filename)
+        .map_err(WriteError::FileCreate)?;
+    if is_gz_filename(filename.as_ref()) {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(&write_model(matrix))
+            .map_err(WriteError::FileWrite)?;
+        encoder.finish()
+            .map(|_| ())
+            .map_err(WriteError::Gzip)
+    } else {
+        file.write_all(&write_model(matrix))
+            .map_err(WriteError::FileWrite)
+    }
+}
+
+pub fn is_gz_filename(filename: &Path) -> bool {
+    filename.extension().map_or(false, |ext| ext == "gz")
+}
+
+pub fn validate(matrix: &Matrix) -> Result<(), ModelValidation> {
+    let first_ungrounded = if let Some(voxel) = matrix.first_ungrounded_voxel() {
+        voxel
+    } else {
+        return Ok(());
+    };
+    Err(ModelValidation { first_ungrounded, ungrounded_count: matrix.ungrounded_voxels().len(), })
+}
+
+pub fn stats(matrix: &Matrix) -> ModelStats {
+    let filled_count = matrix.count_filled();
+    let ungrounded_count = matrix.ungrounded_voxels().len();
+    ModelStats {
+        resolution: matrix.dim(),
+        filled_count,
+        bounding_box: matrix.bounding_box(),
+        grounded_count: filled_count - ungrounded_count,
+        ungrounded_count,
+        components: matrix.connected_components(),
+        center_of_mass: matrix.center_of_mass(),
+    }
+}
+
+pub fn to_ascii_slices(matrix: &Matrix) -> String {
+    let dim = matrix.dim() as M;
+    let mut out = String::new();
+    for y in 0 .. dim {
+        out.push_str(&format!("y={:02}\n", y));
+        for z in 0 .. dim {
+            for x in 0 .. dim {
+                out.push(if matrix.is_filled(&Coord { x, y, z, }) { '#' } else { '.' });
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// reads back the `to_ascii_slices` format: a `y=NN` header per layer followed by
+// `dim` rows (z) of `dim` voxels (x), `#` filled and `.` void; blank lines are ignored
+pub fn from_ascii_slices(text: &str) -> Result<Matrix, AsciiError> {
+    let mut dim = None;
+    let mut layers: Vec<Vec<&str>> = Vec::new();
+    for (line_no, line) in text.lines().enumerate().map(|(i, l)| (i + 1, l.trim())) {
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("y=") {
+            let y: usize = line[2 ..].parse()
+                .map_err(|_| AsciiError::LayerHeaderExpected { line: line_no, })?;
+            if y != layers.len() {
+                return Err(AsciiError::LayerOutOfOrder { line: line_no, expected: layers.len(), });
+            }
+            layers.push(Vec::new());
+            continue;
+        }
+        let layer = layers.last_mut()
+            .ok_or(AsciiError::LayerHeaderExpected { line: line_no, })?;
+        let expected = *dim.get_or_insert(line.chars().count());
+        let actual = line.chars().count();
+        if actual != expected {
+            return Err(AsciiError::RowLengthMismatch { line: line_no, expected, actual, });
+        }
+        if let Some(voxel) = line.chars().find(|&c| c != '#' && c != '.') {
+            return Err(AsciiError::InvalidVoxel { line: line_no, voxel, });
+        }
+        layer.push(line);
+    }
+
+    let dim = dim.unwrap_or(layers.len());
+    if dim > 250 {
+        return Err(AsciiError::DimensionTooLarge { dim, });
+    }
+    if layers.len() != dim {
+        return Err(AsciiError::LayersCountMismatch { expected: dim, actual: layers.len(), });
+    }
+    let mut filled = Vec::new();
+    for (y, rows) in layers.iter().enumerate() {
+        if rows.len() != dim {
+            return Err(AsciiError::RowsCountMismatch { layer: y, expected: dim, actual: rows.len(), });
+        }
+        for (z, row) in rows.iter().enumerate() {
+            for (x, voxel) in row.chars().enumerate() {
+                if voxel == '#' {
+                    filled.push(Coord { x: x as M, y: y as M, z: z as M, });
+                }
+            }
+        }
+    }
+    Ok(Matrix::from_iter(Resolution(dim as M), filled))
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::junk::LA008_TGT_MDL;
@@ -95,4 +351,261 @@ mod tests {
         assert_eq!(matrix.filled_voxels().count(), 1856);
         assert!(matrix.all_voxels_are_grounded());
     }
+
+    #[test]
+    fn la008_tgt_mdl_write_read() {
+        let matrix = super::read_model(LA008_TGT_MDL).unwrap();
+        let bytes = super::write_model(&matrix);
+        assert_eq!(&bytes[..], LA008_TGT_MDL);
+        let matrix_again = super::read_model(&bytes[..]).unwrap();
+        assert_eq!(matrix_again.dim(), matrix.dim());
+        assert!(matrix_again.equals(&matrix));
+    }
+
+    #[test]
+    fn classify_problem_names() {
+        use std::path::Path;
+        use super::ProblemKind;
+        use super::super::coord::{Coord, Matrix, Resolution};
+        let model = Matrix::from_iter(Resolution(4), vec![Coord { x: 1, y: 0, z: 1, }]);
+        let empty = Matrix::new(Resolution(4));
+        let classify = |name: &str| super::classify(Some(Path::new(name)), &model);
+        assert_eq!(classify("../../problems/LA008_tgt.mdl"), ProblemKind::LightningAssembly);
+        assert_eq!(classify("FA001_tgt.mdl"), ProblemKind::Assembly);
+        assert_eq!(classify("problems/FD042_src.mdl"), ProblemKind::Disassembly);
+        assert_eq!(classify("FR114_src.mdl"), ProblemKind::Reassembly);
+        assert_eq!(classify("FR114_tgt.mdl"), ProblemKind::Reassembly);
+        assert_eq!(classify("fa002_tgt.mdl"), ProblemKind::Assembly);
+        assert_eq!(super::classify(Some(Path::new("castle_src.mdl")), &model), ProblemKind::Disassembly);
+        assert_eq!(super::classify(Some(Path::new("castle_tgt.mdl")), &empty), ProblemKind::Assembly);
+        assert_eq!(super::classify(Some(Path::new("castle.mdl")), &model), ProblemKind::Assembly);
+        assert_eq!(super::classify(None, &model), ProblemKind::Assembly);
+        assert_eq!(super::classify(None, &empty), ProblemKind::Unknown);
+    }
+
+    #[test]
+    fn load_pair_presence() {
+        use std::{env, fs, process};
+        use super::super::coord::{Coord, Matrix, Resolution};
+        let path = |name: &str| env::temp_dir().join(format!("icfpc2018_load_pair_{}_{}.mdl", process::id(), name));
+        let (source_path, target_path, small_path) = (path("src"), path("tgt"), path("small"));
+        let source = Matrix::from_iter(Resolution(4), vec![Coord { x: 1, y: 0, z: 1, }]);
+        let target = Matrix::from_iter(Resolution(4), vec![Coord { x: 2, y: 0, z: 2, }, Coord { x: 2, y: 1, z: 2, }]);
+        super::write_model_file(&source_path, &source).unwrap();
+        super::write_model_file(&target_path, &target).unwrap();
+        super::write_model_file(&small_path, &Matrix::new(Resolution(3))).unwrap();
+
+        let (a, b) = super::load_pair(Some(source_path.as_path()), Some(target_path.as_path())).unwrap();
+        assert_eq!((a, b), (source.clone(), target.clone()));
+        let (a, b) = super::load_pair(Some(source_path.as_path()), None).unwrap();
+        assert_eq!((a, b), (source.clone(), Matrix::new(Resolution(4))));
+        let (a, b) = super::load_pair(None, Some(target_path.as_path())).unwrap();
+        assert_eq!((a, b), (Matrix::new(Resolution(4)), target.clone()));
+        match super::load_pair(None, None) {
+            Err(super::PairError::NoSourceOrTarget) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::load_pair(Some(small_path.as_path()), Some(target_path.as_path())) {
+            Err(super::PairError::DimMismatch { source_dim: 3, target_dim: 4, }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::load_pair(Some(path("missing").as_path()), Some(target_path.as_path())) {
+            Err(super::PairError::Read(..)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        for path in [source_path, target_path, small_path].iter() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn la008_tgt_mdl_from_cursor() {
+        use std::io::Cursor;
+        let matrix = super::read_model_from(Cursor::new(LA008_TGT_MDL.to_vec())).unwrap();
+        assert_eq!(matrix.dim(), 20);
+        assert_eq!(matrix.filled_voxels().count(), 1856);
+    }
+
+    #[test]
+    fn la008_tgt_mdl_truncated() {
+        use std::io::Cursor;
+        match super::read_model_from(Cursor::new(LA008_TGT_MDL[.. 100].to_vec())) {
+            Err(super::ModelError::LengthMismatch { expected: 1000, actual: 99, }) =>
+                (),
+            other =>
+                panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn la008_tgt_mdl_overlong() {
+        let mut bytes = LA008_TGT_MDL.to_vec();
+        bytes.extend(&[0, 0]);
+        match super::read_model(&bytes[..]) {
+            Err(super::ModelError::LengthMismatch { expected: 1000, actual: 1002, }) =>
+                (),
+            other =>
+                panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_grounded() {
+        let matrix = super::read_model(LA008_TGT_MDL).unwrap();
+        assert_eq!(super::validate(&matrix), Ok(()));
+    }
+
+    #[test]
+    fn validate_floating() {
+        use super::super::coord::{Coord, Matrix, Resolution};
+        let matrix = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 2, y: 2, z: 2, },
+            Coord { x: 2, y: 3, z: 2, },
+        ]);
+        let validation = super::validate(&matrix).unwrap_err();
+        assert_eq!(validation.ungrounded_count, 2);
+        assert_eq!(validation.first_ungrounded.x, 2);
+        assert_eq!(validation.first_ungrounded.z, 2);
+    }
+
+    #[test]
+    fn stats_cross() {
+        use super::super::coord::{Coord, Region, Matrix, Resolution};
+        let matrix = Matrix::from_iter(
+            Resolution(3),
+            vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 0, y: 1, z: 1, },
+                Coord { x: 1, y: 1, z: 0, },
+                Coord { x: 1, y: 1, z: 2, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 2, y: 1, z: 1, },
+                Coord { x: 1, y: 2, z: 1, },
+            ]);
+        assert_eq!(super::stats(&matrix), super::ModelStats {
+            resolution: 3,
+            filled_count: 7,
+            bounding_box: Some(Region {
+                min: Coord { x: 0, y: 0, z: 0, },
+                max: Coord { x: 2, y: 2, z: 2, },
+            }),
+            grounded_count: 7,
+            ungrounded_count: 0,
+            components: 1,
+            center_of_mass: Some(Coord { x: 1, y: 1, z: 1, }),
+        });
+    }
+
+    #[test]
+    fn gzip_magic_detected() {
+        use std::io::Cursor;
+        assert!(super::is_gzipped(&mut Cursor::new(vec![0x1f, 0x8b, 0x08, 0x00])).unwrap());
+        assert!(!super::is_gzipped(&mut Cursor::new(LA008_TGT_MDL.to_vec())).unwrap());
+    }
+
+    #[test]
+    fn read_gzipped_model() {
+        use std::{env, fs, process, io::Write};
+        use flate2::{Compression, write::GzEncoder};
+        let path = |name: &str| env::temp_dir().join(format!("icfpc2018_gzipped_{}_{}", process::id(), name));
+        let raw = super::read_model(LA008_TGT_MDL).unwrap();
+
+        let gz_path = path("LA008_tgt.mdl.gz");
+        let mut encoder = GzEncoder::new(fs::File::create(&gz_path).unwrap(), Compression::default());
+        encoder.write_all(LA008_TGT_MDL).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(super::read_model_file(&gz_path).unwrap(), raw);
+
+        // and the writer picks gzip from the filename, the raw one still reads as it is
+        let written_path = path("written.mdl.gz");
+        super::write_model_file(&written_path, &raw).unwrap();
+        assert_eq!(&fs::read(&written_path).unwrap()[.. 2], &[0x1f, 0x8b]);
+        assert_eq!(super::read_model_file(&written_path).unwrap(), raw);
+        let raw_path = path("written.mdl");
+        super::write_model_file(&raw_path, &raw).unwrap();
+        assert_eq!(fs::read(&raw_path).unwrap(), LA008_TGT_MDL.to_vec());
+        assert_eq!(super::read_model_file(&raw_path).unwrap(), raw);
+
+        for path in [gz_path, written_path, raw_path].iter() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn ascii_slices_tower() {
+        use super::super::coord::{Coord, Matrix, Resolution};
+        let matrix = Matrix::from_iter(
+            Resolution(3),
+            vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 1, y: 2, z: 1, },
+                Coord { x: 2, y: 0, z: 1, },
+            ]);
+        let dump = super::to_ascii_slices(&matrix);
+        assert!(dump.starts_with("y=00\n...\n.##\n...\ny=01\n...\n.#.\n...\n"));
+        assert_eq!(dump.lines().count(), 12);
+    }
+
+    #[test]
+    fn ascii_slices_roundtrip() {
+        use super::super::coord::{Coord, Matrix, Resolution};
+        let matrix = Matrix::from_iter(
+            Resolution(4),
+            vec![
+                Coord { x: 0, y: 0, z: 0, },
+                Coord { x: 3, y: 0, z: 2, },
+                Coord { x: 3, y: 1, z: 2, },
+                Coord { x: 2, y: 1, z: 2, },
+                Coord { x: 2, y: 3, z: 1, },
+            ]);
+        let parsed = super::from_ascii_slices(&super::to_ascii_slices(&matrix)).unwrap();
+        assert_eq!(parsed.dim(), 4);
+        assert!(parsed.equals(&matrix));
+
+        let inline = super::from_ascii_slices("
+            y=00
+            .#.
+            .#.
+            ...
+            y=01
+            ...
+            .#.
+            ...
+            y=02
+            ...
+            ...
+            ...
+        ").unwrap();
+        assert_eq!(inline.count_filled(), 3);
+        assert!(inline.is_filled(&Coord { x: 1, y: 0, z: 1, }));
+        assert!(inline.is_filled(&Coord { x: 1, y: 1, z: 1, }));
+    }
+
+    #[test]
+    fn ascii_slices_errors() {
+        match super::from_ascii_slices("...\n") {
+            Err(super::AsciiError::LayerHeaderExpected { line: 1, }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::from_ascii_slices("y=00\n..\n.#.\n") {
+            Err(super::AsciiError::RowLengthMismatch { line: 3, expected: 2, actual: 3, }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::from_ascii_slices("y=00\n.x\n") {
+            Err(super::AsciiError::InvalidVoxel { line: 2, voxel: 'x', }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::from_ascii_slices("y=01\n..\n") {
+            Err(super::AsciiError::LayerOutOfOrder { line: 1, expected: 0, }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match super::from_ascii_slices("y=00\n..\n..\n") {
+            Err(super::AsciiError::LayersCountMismatch { expected: 2, actual: 1, }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
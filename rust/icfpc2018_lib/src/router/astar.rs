@@ -1,4 +1,6 @@
 use std::iter;
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
 use super::super::{
     coord::{
         M,
@@ -19,20 +21,185 @@ pub struct Move {
     pub cmd_performed: Option<BotCommand>,
 }
 
-pub fn plan_route<VI>(&bot_start: &Coord, bot_finish: &Coord, matrix: &Matrix, volatile: VI) -> Option<(Vec<Move>, usize)> where
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Heuristic {
+    // distance along the longest axis
+    Chebyshev,
+    // commands count estimate: L1 distance in SMoves of at most 15
+    MoveCount,
+}
+
+impl Heuristic {
+    pub fn estimate(&self, coord: &Coord, finish: &Coord) -> usize {
+        match *self {
+            Heuristic::Chebyshev =>
+                coord.diff(finish).l_inf_norm(),
+            Heuristic::MoveCount =>
+                (coord.diff(finish).l_1_norm() + 14) / 15,
+        }
+    }
+}
+
+pub fn plan_route<VI>(bot_start: &Coord, bot_finish: &Coord, matrix: &Matrix, volatile: VI) -> Option<(Vec<Move>, usize)> where
+    VI: Iterator<Item = Region> + Clone
+{
+    plan_route_heuristic(bot_start, bot_finish, matrix, volatile, Heuristic::Chebyshev)
+}
+
+pub fn plan_route_heuristic<VI>(
+    bot_start: &Coord,
+    bot_finish: &Coord,
+    matrix: &Matrix,
+    volatile: VI,
+    heuristic: Heuristic,
+)
+    -> Option<(Vec<Move>, usize)> where
+    VI: Iterator<Item = Region> + Clone
+{
+    plan_route_expanded(bot_start, bot_finish, matrix, volatile, heuristic, &Cell::new(0))
+}
+
+// the same as `plan_route_heuristic`, counting the nodes expanded in `expanded`
+fn plan_route_expanded<VI>(
+    &bot_start: &Coord,
+    bot_finish: &Coord,
+    matrix: &Matrix,
+    volatile: VI,
+    heuristic: Heuristic,
+    expanded: &Cell<usize>,
+)
+    -> Option<(Vec<Move>, usize)> where
     VI: Iterator<Item = Region> + Clone
 {
     let start = Move { coord: bot_start, cmd_performed: None, };
 
     astar::astar(
         &start,
-        |mv| mv.moves_allowed(matrix, volatile.clone())
-            .map(|mvn| (mvn, mvn.coord.diff(bot_finish).l_inf_norm())),
-        |mv| mv.coord.diff(bot_finish).l_inf_norm(),
+        |mv| {
+            expanded.set(expanded.get() + 1);
+            mv.moves_allowed(matrix, volatile.clone())
+                .map(|mvn| (mvn, mvn.coord.diff(bot_finish).l_inf_norm()))
+        },
+        |mv| heuristic.estimate(&mv.coord, bot_finish),
         |mv| &mv.coord == bot_finish,
     )
 }
 
+pub fn plan_route_passable<FP>(
+    &bot_start: &Coord,
+    &bot_finish: &Coord,
+    matrix_dim: usize,
+    is_passable: FP,
+)
+    -> Option<(Vec<Move>, usize)> where
+    FP: Fn(&Region) -> bool,
+{
+    if !is_passable(&Region { min: bot_finish, max: bot_finish, }) {
+        return None;
+    }
+    let (coords, cost) = astar::astar(
+        &bot_start,
+        |&coord| passable_smoves(coord, matrix_dim, &is_passable).into_iter().map(|next_coord| (next_coord, 1)),
+        |coord| (coord.diff(&bot_finish).l_1_norm() + 14) / 15,
+        |coord| coord == &bot_finish,
+    )?;
+    Some((smoves_route(&coords), cost))
+}
+
+// Like `plan_route_passable`, but when `bot_finish` can not be reached returns the shortest route
+// to the reachable position closest to it, so the bot can still make some progress. The flag tells
+// whether the route actually ends at `bot_finish`.
+pub fn plan_route_best_effort<FP>(
+    &bot_start: &Coord,
+    &bot_finish: &Coord,
+    matrix_dim: usize,
+    is_passable: FP,
+)
+    -> (Vec<Move>, bool) where
+    FP: Fn(&Region) -> bool,
+{
+    if let Some((moves, _cost)) = plan_route_passable(&bot_start, &bot_finish, matrix_dim, &is_passable) {
+        return (moves, true);
+    }
+    // plain bfs: every smove costs the same
+    let mut parents: HashMap<Coord, Coord> = HashMap::new();
+    let mut queue = VecDeque::new();
+    let mut best = bot_start;
+    parents.insert(bot_start, bot_start);
+    queue.push_back(bot_start);
+    while let Some(coord) = queue.pop_front() {
+        if coord.diff(&bot_finish).l_1_norm() < best.diff(&bot_finish).l_1_norm() {
+            best = coord;
+        }
+        for next_coord in passable_smoves(coord, matrix_dim, &is_passable) {
+            if !parents.contains_key(&next_coord) {
+                parents.insert(next_coord, coord);
+                queue.push_back(next_coord);
+            }
+        }
+    }
+    let mut coords = vec![best];
+    while coords[coords.len() - 1] != bot_start {
+        let parent = parents[&coords[coords.len() - 1]];
+        coords.push(parent);
+    }
+    coords.reverse();
+    (smoves_route(&coords), false)
+}
+
+pub fn passable_smoves<FP>(coord: Coord, matrix_dim: usize, is_passable: &FP) -> Vec<Coord> where
+    FP: Fn(&Region) -> bool,
+{
+    let dim = matrix_dim as M;
+    let mut next = Vec::new();
+    for &axis in &[Axis::X, Axis::Y, Axis::Z] {
+        for &sign in &[-1, 1] {
+            for value in 1 ..= 15 {
+                let next_coord = coord.add(LinearCoordDiff::Long { axis, value: sign * value, }.to_coord_diff());
+                if next_coord.x < 0 || next_coord.x >= dim ||
+                    next_coord.y < 0 || next_coord.y >= dim ||
+                    next_coord.z < 0 || next_coord.z >= dim
+                {
+                    break;
+                }
+                if !is_passable(&Region::from_corners(&coord, &next_coord)) {
+                    break;
+                }
+                next.push(next_coord);
+            }
+        }
+    }
+    next
+}
+
+pub fn smoves_route(coords: &[Coord]) -> Vec<Move> {
+    coords.iter()
+        .enumerate()
+        .map(|(index, &coord)| Move {
+            coord,
+            cmd_performed: if index == 0 {
+                None
+            } else {
+                let diff = coord.diff(&coords[index - 1]);
+                let (axis, value) = if diff.0.x != 0 {
+                    (Axis::X, diff.0.x)
+                } else if diff.0.y != 0 {
+                    (Axis::Y, diff.0.y)
+                } else {
+                    (Axis::Z, diff.0.z)
+                };
+                Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis, value, }, })
+            },
+        })
+        .collect()
+}
+
+pub fn route_cost(moves: &[Move]) -> (usize, usize) {
+    moves.iter()
+        .filter_map(|mv| mv.cmd_performed)
+        .fold((0, 0), |(energy, count), cmd| (energy + super::move_energy(&cmd), count + 1))
+}
+
 impl Move {
     pub fn moves_allowed<'a, VI>(&self, matrix: &'a Matrix, volatile: VI) -> impl Iterator<Item = Move> + 'a where
         VI: Iterator<Item = Region> + Clone + 'a
@@ -100,7 +267,11 @@ mod tests {
         },
         cmd::BotCommand,
     };
-    use super::Move;
+    use std::cell::Cell;
+    use super::{
+        Move,
+        Heuristic,
+    };
 
     #[test]
     fn moves_allowed_empty() {
@@ -180,6 +351,18 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn route_cost() {
+        let smove = |axis, value| Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis, value, } });
+        let moves = [
+            Move { coord: Coord { x: 0, y: 0, z: 0, }, cmd_performed: None, },
+            Move { coord: Coord { x: 7, y: 0, z: 0, }, cmd_performed: smove(Axis::X, 7), },
+            Move { coord: Coord { x: 7, y: 3, z: 0, }, cmd_performed: smove(Axis::Y, 3), },
+            Move { coord: Coord { x: 7, y: 3, z: 1, }, cmd_performed: smove(Axis::Z, 1), },
+        ];
+        assert_eq!(super::route_cost(&moves), (2 * 7 + 2 * 3 + 2 * 1, 3));
+    }
+
     #[test]
     fn plan_route() {
         let matrix = Matrix::from_iter(Resolution(3), vec![Coord { x: 1, y: 0, z: 0, }]);
@@ -218,4 +401,88 @@ mod tests {
             ))
         )
     }
+
+    #[test]
+    fn plan_route_heuristic_expansions() {
+        // edge costs of `plan_route` are Chebyshev distances too, so on a long straight route
+        // the Chebyshev heuristic is the tighter one: 3 expansions against 8 for `MoveCount`
+        let matrix = Matrix::new(Resolution(40));
+        let start = Coord { x: 0, y: 0, z: 0, };
+        let finish = Coord { x: 35, y: 0, z: 0, };
+        let expand = |heuristic| {
+            let expanded = Cell::new(0);
+            let (_, cost) = super::plan_route_expanded(&start, &finish, &matrix, None.into_iter(), heuristic, &expanded).unwrap();
+            (expanded.get(), cost)
+        };
+        let (chebyshev_expanded, chebyshev_cost) = expand(Heuristic::Chebyshev);
+        let (move_count_expanded, move_count_cost) = expand(Heuristic::MoveCount);
+        assert_eq!(chebyshev_cost, move_count_cost);
+        assert_eq!(chebyshev_expanded, 3);
+        assert!(chebyshev_expanded < move_count_expanded);
+
+        assert_eq!(
+            super::plan_route(&start, &finish, &matrix, None.into_iter()),
+            super::plan_route_heuristic(&start, &finish, &matrix, None.into_iter(), Heuristic::Chebyshev),
+        );
+    }
+
+    #[test]
+    fn plan_route_passable() {
+        let matrix = Matrix::from_iter(Resolution(3), vec![Coord { x: 1, y: 0, z: 0, }]);
+        let volatile = Region { min: Coord { x: 0, y: 1, z: 0, }, max: Coord { x: 0, y: 1, z: 0, }, };
+        let route = super::plan_route_passable(
+            &Coord { x: 0, y: 0, z: 0, },
+            &Coord { x: 2, y: 0, z: 0, },
+            matrix.dim(),
+            |region| !matrix.contains_filled(region) && !region.intersects(&volatile),
+        );
+        assert_eq!(
+            route,
+            Some((
+                vec![
+                    Move {
+                        coord: Coord { x: 0, y: 0, z: 0 },
+                        cmd_performed: None,
+                    },
+                    Move {
+                        coord: Coord { x: 0, y: 0, z: 1 },
+                        cmd_performed: Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Z, value: 1 } }),
+                    },
+                    Move {
+                        coord: Coord { x: 2, y: 0, z: 1 },
+                        cmd_performed: Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::X, value: 2 } }),
+                    },
+                    Move {
+                        coord: Coord { x: 2, y: 0, z: 0 },
+                        cmd_performed: Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Z, value: -1 } }),
+                    },
+                ],
+                3,
+            ))
+        )
+    }
+
+    #[test]
+    fn plan_route_best_effort_walled_in() {
+        let finish = Coord { x: 2, y: 2, z: 2, };
+        let walls: Vec<_> = [(-1, 0, 0), (1, 0, 0), (0, -1, 0), (0, 1, 0), (0, 0, -1), (0, 0, 1)].iter()
+            .map(|&(x, y, z)| Coord { x: finish.x + x, y: finish.y + y, z: finish.z + z, })
+            .collect();
+        let matrix = Matrix::from_iter(Resolution(5), walls);
+        let start = Coord { x: 0, y: 0, z: 0, };
+        let is_passable = |region: &Region| !matrix.contains_filled(region);
+        assert_eq!(super::plan_route_passable(&start, &finish, matrix.dim(), &is_passable), None);
+
+        let (moves, reached) = super::plan_route_best_effort(&start, &finish, matrix.dim(), &is_passable);
+        assert!(!reached);
+        assert!(moves.len() > 1);
+        assert_eq!(moves[0], Move { coord: start, cmd_performed: None, });
+        // the closest free spots are the edge neighbours of the walled cell
+        assert_eq!(moves[moves.len() - 1].coord.diff(&finish).l_1_norm(), 2);
+
+        let open = Coord { x: 4, y: 0, z: 0, };
+        let (moves, reached) = super::plan_route_best_effort(&start, &open, matrix.dim(), &is_passable);
+        assert!(reached);
+        assert_eq!(moves[moves.len() - 1].coord, open);
+    }
 }
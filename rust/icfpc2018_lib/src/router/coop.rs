@@ -0,0 +1,292 @@
+use std::cmp;
+use std::collections::HashMap;
+use super::super::{
+    coord::{
+        Coord,
+        Region,
+    },
+    cmd::BotCommand,
+    state::Bid,
+};
+use super::astar::{
+    Move,
+    passable_smoves,
+    smoves_route,
+};
+use pathfinding::directed::astar;
+
+// Cells other bots are going to occupy, indexed by (coord, tick). A bot moving during a tick
+// reserves the whole region it sweeps for that tick.
+#[derive(Clone, Default, Debug)]
+pub struct ReservationTable {
+    cells: HashMap<(Coord, usize), Bid>,
+}
+
+impl ReservationTable {
+    pub fn new() -> ReservationTable {
+        ReservationTable { cells: HashMap::new(), }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    // true when nobody but `bid` has reserved anything in `region` for `tick`
+    pub fn is_free(&self, region: &Region, tick: usize, bid: Bid) -> bool {
+        self.is_free_except(region, tick, bid, None)
+    }
+
+    // Like `is_free`, but ignores the reservations of `occupied`: the bot already stands there, so
+    // whoever reserved it has to get around it anyway.
+    pub fn is_free_except(&self, region: &Region, tick: usize, bid: Bid, occupied: Option<Coord>) -> bool {
+        for x in region.min.x ..= region.max.x {
+            for y in region.min.y ..= region.max.y {
+                for z in region.min.z ..= region.max.z {
+                    let coord = Coord { x, y, z, };
+                    if Some(coord) == occupied {
+                        continue;
+                    }
+                    match self.cells.get(&(coord, tick)) {
+                        Some(&owner) if owner != bid =>
+                            return false,
+                        _ =>
+                            (),
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Reserves the route `moves` starting at `tick` (the first move is the start position) and
+    // then its last position for `hold` more ticks.
+    pub fn reserve_route(&mut self, bid: Bid, tick: usize, moves: &[Move], hold: usize) {
+        for (step, pair) in moves.windows(2).enumerate() {
+            let region = Region::from_corners(&pair[0].coord, &pair[1].coord);
+            self.reserve_region(bid, tick + step, &region);
+        }
+        if let Some(last) = moves.last() {
+            let arrival = tick + moves.len() - 1;
+            for hold_tick in arrival .. arrival + hold {
+                self.cells.insert((last.coord, hold_tick), bid);
+            }
+        }
+    }
+
+    fn reserve_region(&mut self, bid: Bid, tick: usize, region: &Region) {
+        for x in region.min.x ..= region.max.x {
+            for y in region.min.y ..= region.max.y {
+                for z in region.min.z ..= region.max.z {
+                    self.cells.insert((Coord { x, y, z, }, tick), bid);
+                }
+            }
+        }
+    }
+
+    pub fn release(&mut self, bid: Bid) {
+        self.cells.retain(|_, owner| *owner != bid);
+    }
+
+    pub fn forget_before(&mut self, tick: usize) {
+        self.cells.retain(|&(_, cell_tick), _| cell_tick >= tick);
+    }
+}
+
+// Windowed cooperative A*: searches over (coord, tick) so the route steps around cells that
+// other bots have reserved, waiting in place when that is cheaper. `is_passable_now` is used for
+// the very first command (it knows this tick's volatiles and bot positions), `is_passable_later`
+// for the rest. After `window` ticks reservations are ignored and the search becomes a plain one.
+// Gives up with `None` once it has expanded `max_expansions` nodes.
+pub fn plan_route_cooperative<FN, FL>(
+    &bot_start: &Coord,
+    &bot_finish: &Coord,
+    bid: Bid,
+    tick: usize,
+    window: usize,
+    max_expansions: usize,
+    matrix_dim: usize,
+    is_passable_now: FN,
+    is_passable_later: FL,
+    reservations: &ReservationTable,
+)
+    -> Option<Vec<Move>> where
+    FN: Fn(&Region) -> bool,
+    FL: Fn(&Region) -> bool,
+{
+    if !is_passable_later(&Region { min: bot_finish, max: bot_finish, }) {
+        return None;
+    }
+    let mut expansions = 0;
+    let (nodes, _cost) = astar::astar(
+        &(bot_start, 0),
+        |&(coord, step)| {
+            expansions += 1;
+            if expansions > max_expansions {
+                // out of budget: no successors left, so the search runs dry
+                return Vec::new();
+            }
+            let is_passable = |region: &Region| {
+                let passable = if step == 0 {
+                    is_passable_now(region)
+                } else {
+                    is_passable_later(region)
+                };
+                // a bot that has just been spawned may stand on a cell reserved by an older route
+                let occupied = if step == 0 { Some(bot_start) } else { None };
+                passable && (step >= window || reservations.is_free_except(region, tick + step, bid, occupied))
+            };
+            let next_step = cmp::min(step + 1, window);
+            let mut next: Vec<_> = passable_smoves(coord, matrix_dim, &is_passable)
+                .into_iter()
+                .map(|next_coord| ((next_coord, next_step), 1))
+                .collect();
+            // waiting past the window would loop forever on the same node
+            if step < window && is_passable(&Region { min: coord, max: coord, }) {
+                next.push(((coord, next_step), 1));
+            }
+            next
+        },
+        |&(coord, _)| (coord.diff(&bot_finish).l_1_norm() + 14) / 15,
+        |&(coord, _)| coord == bot_finish,
+    )?;
+    let moves = nodes.iter()
+        .enumerate()
+        .map(|(index, &(coord, _))| Move {
+            coord,
+            cmd_performed: if index == 0 {
+                None
+            } else if coord == nodes[index - 1].0 {
+                Some(BotCommand::Wait)
+            } else {
+                smoves_route(&[nodes[index - 1].0, coord])[1].cmd_performed
+            },
+        })
+        .collect();
+    Some(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::{
+        coord::{
+            Matrix,
+            Resolution,
+            Coord,
+            Region,
+        },
+        cmd::BotCommand,
+    };
+    use super::{
+        ReservationTable,
+        plan_route_cooperative,
+    };
+
+    // two corridors crossing at (2, 0, 1): along x at z = 1 and along z at x = 2
+    fn crossing() -> Matrix {
+        let mut filled = vec![];
+        for x in 0 .. 5 {
+            for z in 0 .. 5 {
+                if z != 1 && x != 2 {
+                    filled.push(Coord { x, y: 0, z, });
+                }
+            }
+        }
+        Matrix::from_iter(Resolution(5), filled)
+    }
+
+    #[test]
+    fn reservations_per_tick() {
+        let mut table = ReservationTable::new();
+        let moves = plan_route_cooperative(
+            &Coord { x: 0, y: 0, z: 0, },
+            &Coord { x: 3, y: 0, z: 0, },
+            1, 10, 8, 4096, 4,
+            |_| true,
+            |_| true,
+            &table,
+        ).unwrap();
+        assert_eq!(moves.len(), 2);
+        table.reserve_route(1, 10, &moves, 2);
+        let middle = Region { min: Coord { x: 2, y: 0, z: 0, }, max: Coord { x: 2, y: 0, z: 0, }, };
+        assert!(!table.is_free(&middle, 10, 2));
+        assert!(table.is_free(&middle, 10, 1));
+        assert!(table.is_free(&middle, 11, 2));
+        let finish = Region { min: Coord { x: 3, y: 0, z: 0, }, max: Coord { x: 3, y: 0, z: 0, }, };
+        assert!(!table.is_free(&finish, 12, 2));
+        assert!(table.is_free(&finish, 13, 2));
+        table.forget_before(11);
+        assert!(table.is_free(&middle, 10, 2));
+        assert!(!table.is_free(&finish, 11, 2));
+        table.release(1);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn two_bots_crossing_corridors() {
+        let matrix = crossing();
+        let is_passable = |region: &Region| !matrix.contains_filled(region);
+        let mut table = ReservationTable::new();
+
+        // first bot runs along the x corridor through the crossing
+        let first = plan_route_cooperative(
+            &Coord { x: 0, y: 0, z: 1, },
+            &Coord { x: 4, y: 0, z: 1, },
+            1, 0, 8, 4096, 5,
+            &is_passable,
+            &is_passable,
+            &table,
+        ).unwrap();
+        assert_eq!(first.len(), 2);
+        table.reserve_route(1, 0, &first, 8);
+
+        // alone the second bot would go straight through the crossing the same tick
+        let alone = plan_route_cooperative(
+            &Coord { x: 2, y: 0, z: 4, },
+            &Coord { x: 2, y: 0, z: 0, },
+            2, 0, 8, 4096, 5,
+            &is_passable,
+            &is_passable,
+            &ReservationTable::new(),
+        ).unwrap();
+        assert_eq!(alone.len(), 2);
+
+        // with the first bot's route reserved it lets it pass instead of bumping into it
+        let second = plan_route_cooperative(
+            &Coord { x: 2, y: 0, z: 4, },
+            &Coord { x: 2, y: 0, z: 0, },
+            2, 0, 8, 4096, 5,
+            &is_passable,
+            &is_passable,
+            &table,
+        ).unwrap();
+        assert_eq!(second.len(), 3);
+        assert_eq!(second[2].coord, Coord { x: 2, y: 0, z: 0, });
+        let crossing_cell = Coord { x: 2, y: 0, z: 1, };
+        assert!(!Region::from_corners(&second[0].coord, &second[1].coord).contains(&crossing_cell));
+        for (step, pair) in second.windows(2).enumerate() {
+            assert!(table.is_free(&Region::from_corners(&pair[0].coord, &pair[1].coord), step, 2));
+        }
+        assert!(second[1..].iter().all(|mv| mv.cmd_performed.is_some()));
+        assert!(second[1].cmd_performed == Some(BotCommand::Wait) || second[1].coord.z > 1);
+    }
+
+    #[test]
+    fn expansions_limit() {
+        let matrix = crossing();
+        let is_passable = |region: &Region| !matrix.contains_filled(region);
+        let plan = |max_expansions| plan_route_cooperative(
+            &Coord { x: 0, y: 0, z: 1, },
+            &Coord { x: 2, y: 0, z: 4, },
+            1, 0, 8, max_expansions, 5,
+            &is_passable,
+            &is_passable,
+            &ReservationTable::new(),
+        );
+        assert!(plan(4096).is_some());
+        assert_eq!(plan(1), None);
+    }
+}
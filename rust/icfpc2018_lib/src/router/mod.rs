@@ -1,2 +1,141 @@
+use rand::Rng;
+
+use super::{
+    coord::{
+        Coord,
+        Region,
+        Matrix,
+    },
+    cmd::BotCommand,
+};
+
 pub mod astar;
 pub mod rtt;
+pub mod coop;
+
+pub fn move_energy(cmd: &BotCommand) -> usize {
+    match cmd {
+        &BotCommand::SMove { long, } =>
+            2 * long.to_coord_diff().l_1_norm(),
+        &BotCommand::LMove { short1, short2, } =>
+            2 * (short1.to_coord_diff().l_1_norm() + 2 + short2.to_coord_diff().l_1_norm()),
+        _ =>
+            0,
+    }
+}
+
+// A region is passable for the bot at `self_pos` when it is within the matrix, has no filled voxels,
+// does not touch anything volatile this tick and has no other bot in it.
+pub fn make_passability<'a>(
+    matrix: &'a Matrix,
+    volatiles: &'a [Region],
+    positions: &'a [Coord],
+    self_pos: Coord,
+)
+    -> impl Fn(&Region) -> bool + 'a
+{
+    let dim = matrix.dim() as isize;
+    move |region: &Region| if region.min.x < 0 || region.min.y < 0 || region.min.z < 0 {
+        false
+    } else if region.max.x >= dim || region.max.y >= dim || region.max.z >= dim {
+        false
+    } else if matrix.contains_filled(region) {
+        false
+    } else if volatiles.iter().any(|reg| reg.intersects(region)) {
+        false
+    } else if positions.iter().filter(|&pos| pos != &self_pos).any(|pos| region.contains(pos)) {
+        false
+    } else {
+        true
+    }
+}
+
+pub trait RouteBackend {
+    fn route<FP, R>(
+        &self,
+        start: &Coord,
+        finish: &Coord,
+        matrix: &Matrix,
+        is_passable: FP,
+        rng: &mut R,
+    )
+        -> Option<Vec<Coord>> where
+        FP: Fn(&Region) -> bool,
+        R: Rng;
+}
+
+pub struct RttBackend {
+    pub max_iters: usize,
+}
+
+impl RouteBackend for RttBackend {
+    fn route<FP, R>(&self, start: &Coord, finish: &Coord, matrix: &Matrix, is_passable: FP, rng: &mut R) -> Option<Vec<Coord>> where
+        FP: Fn(&Region) -> bool,
+        R: Rng,
+    {
+        let route = rtt::plan_route_rng(start, finish, matrix.dim(), is_passable, self.max_iters, rng);
+        if cfg!(debug_assertions) {
+            if let Some((a, b)) = route.as_ref().and_then(|route| rtt::first_bad_segment(route, matrix)) {
+                warn!("rtt route from {:?} to {:?} clips the matrix on segment {:?} -> {:?}", start, finish, a, b);
+            }
+        }
+        route
+    }
+}
+
+pub struct AstarBackend;
+
+impl RouteBackend for AstarBackend {
+    fn route<FP, R>(&self, start: &Coord, finish: &Coord, matrix: &Matrix, is_passable: FP, _rng: &mut R) -> Option<Vec<Coord>> where
+        FP: Fn(&Region) -> bool,
+        R: Rng,
+    {
+        astar::plan_route_passable(start, finish, matrix.dim(), is_passable)
+            .map(|(moves, _cost)| moves.into_iter().map(|mv| mv.coord).collect())
+    }
+}
+
+// A* for short hops (optimal there), RRT for the long ones where A* explodes
+pub struct HybridBackend {
+    pub max_iters: usize,
+    pub astar_max_distance: usize,
+}
+
+impl RouteBackend for HybridBackend {
+    fn route<FP, R>(&self, start: &Coord, finish: &Coord, matrix: &Matrix, is_passable: FP, rng: &mut R) -> Option<Vec<Coord>> where
+        FP: Fn(&Region) -> bool,
+        R: Rng,
+    {
+        if finish.diff(start).l_1_norm() < self.astar_max_distance {
+            AstarBackend.route(start, finish, matrix, is_passable, rng)
+        } else {
+            RttBackend { max_iters: self.max_iters, }.route(start, finish, matrix, is_passable, rng)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{
+        coord::{Coord, Matrix, Region, Resolution},
+    };
+
+    #[test]
+    fn passability_blocked_by_volatile() {
+        let matrix = Matrix::new(Resolution(8));
+        let volatiles = vec![Region { min: Coord { x: 3, y: 0, z: 2, }, max: Coord { x: 3, y: 0, z: 4, }, }];
+        let self_pos = Coord { x: 1, y: 0, z: 3, };
+        let positions = [self_pos, Coord { x: 6, y: 6, z: 6, }];
+        let is_passable = super::make_passability(&matrix, &volatiles, &positions, self_pos);
+
+        // the matrix is empty, the only other bot is far away
+        let across = Region { min: self_pos, max: Coord { x: 5, y: 0, z: 3, }, };
+        assert!(!matrix.contains_filled(&across));
+        assert!(!is_passable(&across));
+        assert!(is_passable(&Region { min: self_pos, max: Coord { x: 2, y: 0, z: 3, }, }));
+
+        assert!(!is_passable(&Region { min: Coord { x: 6, y: 5, z: 6, }, max: Coord { x: 6, y: 7, z: 6, }, }));
+        assert!(!is_passable(&Region { min: Coord { x: 0, y: 0, z: -1, }, max: Coord { x: 0, y: 0, z: 0, }, }));
+        assert!(!is_passable(&Region { min: Coord { x: 0, y: 0, z: 7, }, max: Coord { x: 0, y: 0, z: 8, }, }));
+    }
+}
@@ -15,6 +15,7 @@ use super::super::{
         Axis,
         Coord,
         Region,
+        Matrix,
         CoordDiff,
         LinearCoordDiff,
     },
@@ -35,11 +36,45 @@ pub fn plan_route<FP>(
 }
 
 pub fn plan_route_rng<FP, R>(
+    bot_start: &Coord,
+    bot_finish: &Coord,
+    matrix_dim: usize,
+    is_passable: FP,
+    max_iters: usize,
+    rng: &mut R,
+)
+    -> Option<Vec<Coord>> where
+    FP: Fn(&Region) -> bool,
+    R: Rng,
+{
+    plan_route_edges(bot_start, bot_finish, matrix_dim, is_passable, max_iters, false, rng)
+}
+
+// The same as `plan_route_rng`, but the tree is extended along the edge paths in a fixed order (the
+// longest axis first) rather than a shuffled one, so a clear way is always the same. The sampling
+// still draws from `rng`, so a detour around obstacles depends on its state like any other route.
+pub fn plan_route_ordered<FP, R>(
+    bot_start: &Coord,
+    bot_finish: &Coord,
+    matrix_dim: usize,
+    is_passable: FP,
+    max_iters: usize,
+    rng: &mut R,
+)
+    -> Option<Vec<Coord>> where
+    FP: Fn(&Region) -> bool,
+    R: Rng,
+{
+    plan_route_edges(bot_start, bot_finish, matrix_dim, is_passable, max_iters, true, rng)
+}
+
+fn plan_route_edges<FP, R>(
     &bot_start: &Coord,
     &bot_finish: &Coord,
     matrix_dim: usize,
     is_passable: FP,
     max_iters: usize,
+    ordered: bool,
     rng: &mut R,
 )
     -> Option<Vec<Coord>> where
@@ -113,7 +148,11 @@ pub fn plan_route_rng<FP, R>(
                 let node_ref = &planner_closest.node_ref().node_ref;
                 let &dst = planner_closest.sample();
                 let &src = rtt.get_state(node_ref);
-                random_valid_edge_path(src, dst, &is_passable, rng)
+                if ordered {
+                    first_valid_edge_path(ordered_edge_paths(src, dst), &is_passable)
+                } else {
+                    random_valid_edge_path(src, dst, &is_passable, rng)
+                }
             };
 
             if let Some(jump) = maybe_route {
@@ -146,8 +185,41 @@ pub fn plan_route_rng<FP, R>(
     Some(path)
 }
 
+// Checks the route against the matrix itself rather than the `is_passable` closure it was planned with:
+// every segment should go along one axis, stay within bounds and cross no filled voxels.
+pub fn verify_route(route: &[Coord], matrix: &Matrix) -> bool {
+    first_bad_segment(route, matrix).is_none()
+}
+
+pub fn first_bad_segment(route: &[Coord], matrix: &Matrix) -> Option<(Coord, Coord)> {
+    let single = route.first().map(|&coord| (coord, coord));
+    single.into_iter()
+        .chain(route.iter().zip(route.iter().skip(1)).map(|(&a, &b)| (a, b)))
+        .find(|&(a, b)| {
+            let diff = b.diff(&a).0;
+            let axes_changed = [diff.x, diff.y, diff.z].iter().filter(|&&d| d != 0).count();
+            axes_changed > 1
+                || !matrix.is_valid_coord(&a)
+                || !matrix.is_valid_coord(&b)
+                || matrix.contains_filled(&Region::from_corners(&a, &b))
+        })
+}
+
 pub fn plan_route_commands(route: &[Coord], commands: &mut Vec<(Coord, BotCommand)>) {
     commands.clear();
+    route_commands_each(route, |coord, cmd| commands.push((coord, cmd)));
+}
+
+pub fn route_cost(route: &[Coord]) -> (usize, usize) {
+    let (mut energy, mut count) = (0, 0);
+    route_commands_each(route, |_coord, cmd| {
+        energy += super::move_energy(&cmd);
+        count += 1;
+    });
+    (energy, count)
+}
+
+fn route_commands_each<F>(route: &[Coord], mut emit: F) where F: FnMut(Coord, BotCommand) {
     let mut coord_a = if let Some(&coord) = route.first() {
         coord
     } else {
@@ -170,10 +242,10 @@ pub fn plan_route_commands(route: &[Coord], commands: &mut Vec<(Coord, BotComman
                     _ =>
                         unreachable!(),
                 };
-                commands.push((sb, BotCommand::LMove {
+                emit(sb, BotCommand::LMove {
                     short1: short(sa.diff(&coord_a)),
                     short2: short(sb.diff(&sa)),
-                }));
+                });
                 if let Some(..) = maybe_sc {
                     coord_a = sb;
                     index += 1;
@@ -186,7 +258,7 @@ pub fn plan_route_commands(route: &[Coord], commands: &mut Vec<(Coord, BotComman
         }
         // construct S-Move
         let (sa, sb, maybe_sc) = split_limit(coord_a, coord_b, 15);
-        commands.push((sb, BotCommand::SMove {
+        emit(sb, BotCommand::SMove {
             long: match sb.diff(&sa) {
                 CoordDiff(Coord { y: 0, z: 0, x, }) =>
                     LinearCoordDiff::Long { axis: Axis::X, value: x, },
@@ -197,7 +269,7 @@ pub fn plan_route_commands(route: &[Coord], commands: &mut Vec<(Coord, BotComman
                 _ =>
                     unreachable!(),
             },
-        }));
+        });
         if let Some(..) = maybe_sc {
             coord_a = sb;
         } else {
@@ -220,7 +292,17 @@ struct EdgesJump {
     finish: Coord,
 }
 
-fn random_edge_paths<R>(start: Coord, finish: Coord, rng: &mut R) -> impl Iterator<Item = EdgesJump> where R: Rng {
+// the order the axes are travelled along in each of the `edge_path` choices
+const EDGE_PATH_AXES: [[Axis; 3]; 6] = [
+    [Axis::X, Axis::Y, Axis::Z],
+    [Axis::X, Axis::Z, Axis::Y],
+    [Axis::Y, Axis::X, Axis::Z],
+    [Axis::Y, Axis::Z, Axis::X],
+    [Axis::Z, Axis::X, Axis::Y],
+    [Axis::Z, Axis::Y, Axis::X],
+];
+
+fn edge_path(start: Coord, finish: Coord, choice: usize) -> EdgesJump {
     let table = [
         ((finish.x, start.y, start.z), (finish.x, finish.y, start.z)),
         ((finish.x, start.y, start.z), (finish.x, start.y, finish.z)),
@@ -229,16 +311,32 @@ fn random_edge_paths<R>(start: Coord, finish: Coord, rng: &mut R) -> impl Iterat
         ((start.x, start.y, finish.z), (finish.x, start.y, finish.z)),
         ((start.x, start.y, finish.z), (start.x, finish.y, finish.z)),
     ];
+    EdgesJump {
+        start, finish,
+        mid_a: Coord { x: (table[choice].0).0, y: (table[choice].0).1, z: (table[choice].0).2, },
+        mid_b: Coord { x: (table[choice].1).0, y: (table[choice].1).1, z: (table[choice].1).2, },
+    }
+}
 
+fn random_edge_paths<R>(start: Coord, finish: Coord, rng: &mut R) -> impl Iterator<Item = EdgesJump> where R: Rng {
     let mut picks = [0, 1, 2, 3, 4, 5];
     rng.shuffle(&mut picks);
     (0 .. 6)
-        .map(move |index| picks[index])
-        .map(move |choice| EdgesJump {
-            start, finish,
-            mid_a: Coord { x: (table[choice].0).0, y: (table[choice].0).1, z: (table[choice].0).2, },
-            mid_b: Coord { x: (table[choice].1).0, y: (table[choice].1).1, z: (table[choice].1).2, },
-        })
+        .map(move |index| edge_path(start, finish, picks[index]))
+}
+
+// the longest axis goes first, then the longest of the other two; ties go in X, Y, Z order
+fn ordered_edge_paths(start: Coord, finish: Coord) -> impl Iterator<Item = EdgesJump> {
+    let delta = finish.diff(&start).0;
+    let span = |axis: Axis| match axis {
+        Axis::X => delta.x.abs(),
+        Axis::Y => delta.y.abs(),
+        Axis::Z => delta.z.abs(),
+    };
+    let mut picks = [0, 1, 2, 3, 4, 5];
+    picks.sort_by_key(|&choice| (-span(EDGE_PATH_AXES[choice][0]), -span(EDGE_PATH_AXES[choice][1])));
+    (0 .. 6)
+        .map(move |index| edge_path(start, finish, picks[index]))
 }
 
 fn random_valid_edge_path<FP, R>(
@@ -251,7 +349,14 @@ fn random_valid_edge_path<FP, R>(
     FP: Fn(&Region) -> bool,
     R: Rng,
 {
-    random_edge_paths(start, finish, rng)
+    first_valid_edge_path(random_edge_paths(start, finish, rng), is_passable)
+}
+
+fn first_valid_edge_path<I, FP>(paths: I, is_passable: FP) -> Option<EdgesJump> where
+    I: Iterator<Item = EdgesJump>,
+    FP: Fn(&Region) -> bool,
+{
+    paths
         .map(|jump| {
             let ra = Region::from_corners(&jump.start, &jump.mid_a);
             let rb = Region::from_corners(&jump.mid_a, &jump.mid_b);
@@ -288,6 +393,30 @@ mod test {
     use super::super::super::coord::{Coord, Matrix, Region, Resolution};
     use super::EdgesJump;
 
+    #[test]
+    fn route_cost_three_segments() {
+        let route = [
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 10, y: 0, z: 0, },
+            Coord { x: 10, y: 8, z: 0, },
+            Coord { x: 10, y: 8, z: 20, },
+        ];
+        // SMove 10, SMove 8, and the last 20 split into SMove 15 + SMove 5
+        assert_eq!(super::route_cost(&route), (2 * 10 + 2 * 8 + 2 * 15 + 2 * 5, 4));
+
+        let mut commands = Vec::new();
+        super::plan_route_commands(&route, &mut commands);
+        assert_eq!(commands.len(), 4);
+
+        let route = [
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 3, y: 0, z: 0, },
+            Coord { x: 3, y: 0, z: 4, },
+        ];
+        assert_eq!(super::route_cost(&route), (2 * (3 + 2 + 4), 1));
+        assert_eq!(super::route_cost(&route[.. 1]), (0, 0));
+    }
+
     #[test]
     fn random_edge_paths() {
         let paths: Vec<_> = super::random_edge_paths(
@@ -358,6 +487,40 @@ mod test {
         ));
     }
 
+    #[test]
+    fn ordered_edge_paths_longest_axis_first() {
+        let start = Coord { x: 0, y: 0, z: 0, };
+        let finish = Coord { x: 1, y: 4, z: 2, };
+        // all clear: along Y, then Z, then X
+        assert_eq!(
+            super::first_valid_edge_path(super::ordered_edge_paths(start, finish), |_region| true),
+            Some(EdgesJump { start, finish, mid_a: Coord { x: 0, y: 4, z: 0, }, mid_b: Coord { x: 0, y: 4, z: 2, }, }),
+        );
+        // Z after Y is blocked: along Y, then X, then Z
+        let blocked = Coord { x: 0, y: 4, z: 1, };
+        assert_eq!(
+            super::first_valid_edge_path(super::ordered_edge_paths(start, finish), |region| !region.contains(&blocked)),
+            Some(EdgesJump { start, finish, mid_a: Coord { x: 0, y: 4, z: 0, }, mid_b: Coord { x: 1, y: 4, z: 0, }, }),
+        );
+    }
+
+    #[test]
+    fn plan_route_ordered_clear_way() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let start = Coord { x: 0, y: 0, z: 0, };
+        let finish = Coord { x: 3, y: 1, z: 2, };
+        for seed in 0 .. 4 {
+            let mut rng = XorShiftRng::seed_from_u64(seed);
+            let path = super::plan_route_ordered(&start, &finish, 4, |_region| true, 16, &mut rng);
+            assert_eq!(path, Some(vec![
+                start,
+                Coord { x: 3, y: 0, z: 0, },
+                Coord { x: 3, y: 0, z: 2, },
+                finish,
+            ]));
+        }
+    }
+
     #[test]
     fn plan_route() {
         let matrix = Matrix::from_iter(Resolution(3), vec![
@@ -394,6 +557,30 @@ mod test {
         ]));
     }
 
+    #[test]
+    fn verify_route() {
+        let matrix = Matrix::from_iter(Resolution(3), vec![
+            Coord { x: 1, y: 0, z: 0, },
+            ]);
+        let start = Coord { x: 0, y: 0, z: 0, };
+        let finish = Coord { x: 2, y: 0, z: 0, };
+
+        // the closure looks at a stale (empty) matrix, so the route goes right through the filled voxel
+        let stale = Matrix::new(Resolution(3));
+        let path = super::plan_route(&start, &finish, matrix.dim(), |region| !stale.contains_filled(region), 128).unwrap();
+        assert!(!super::verify_route(&path, &matrix));
+        assert_eq!(super::first_bad_segment(&path, &matrix), Some((path[0], path[1])));
+
+        let path = super::plan_route(&start, &finish, matrix.dim(), |region| !matrix.contains_filled(region), 128).unwrap();
+        assert!(super::verify_route(&path, &matrix));
+
+        let diagonal = vec![start, Coord { x: 0, y: 1, z: 1, }];
+        assert!(!super::verify_route(&diagonal, &matrix));
+        let out_of_bounds = vec![start, Coord { x: 0, y: 3, z: 0, }];
+        assert!(!super::verify_route(&out_of_bounds, &matrix));
+        assert!(super::verify_route(&[], &matrix));
+    }
+
     #[test]
     fn split_limit() {
         assert_eq!(
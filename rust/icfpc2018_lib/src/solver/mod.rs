@@ -1,2 +1,5 @@
 
 pub mod random_swarm;
+pub mod spawn;
+
+pub use self::spawn::spawn_tree;
@@ -1,21 +1,57 @@
-use rand::{self, Rng};
+use std::cmp;
+use std::collections::{HashMap, BTreeMap};
+use std::time::{Instant, Duration};
+
+use rand::{self, Rng, SeedableRng, prng::XorShiftRng};
 
 use super::super::{
     coord::{
+        M,
+        Axis,
         Coord,
+        CoordDiff,
+        LinearCoordDiff,
         Matrix,
         Region,
+        Resolution,
+        pair_into_lmove,
     },
     cmd::BotCommand,
+    kd::CoordIndex,
     state::{
         Bid,
         Bot,
         Harmonics,
     },
-    router::rtt,
+    router::{
+        rtt,
+        astar,
+        RouteBackend,
+        RttBackend,
+        make_passability,
+        AstarBackend,
+        HybridBackend,
+        coop::{self, ReservationTable},
+    },
+    solver::spawn::line_moves,
 };
 
 const INIT_POS: Coord = Coord { x: 0, y: 0, z: 0, };
+const PROGRESS_TICKS: usize = 100;
+const GROUP_LINE_MAX: M = 31;
+const WORK_BOUNDS_MARGIN: M = 2;
+// free voxels kept around the work bounds in a cropped matrix, see `cropped_frame`
+const CROP_MARGIN: M = 1;
+// `auto_ticks_limit` ticks per voxel of a bot share: the fill or void itself and the walk to the next job
+const AUTO_TICKS_PER_VOXEL: usize = 8;
+// `auto_ticks_limit` never goes below this, small models still need the bots to spawn and come home
+const AUTO_TICKS_MIN: usize = 128;
+// wander routes never back off below this many rtt samples
+const WANDER_RTT_MIN: usize = 8;
+// cooperative routes give up after expanding this many (coord, tick) nodes
+const COOPERATIVE_EXPANSIONS_MAX: usize = 4096;
+// the spec never allows more active bots than this, whatever the seeds are
+pub const MAX_ACTIVE_BOTS: usize = 40;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Error {
@@ -23,8 +59,39 @@ pub enum Error {
     EmptyCommandsBufferForRoute { route: Vec<Coord>, },
     RouteAttempsLimitExceeded { source: Coord, target: Coord, attempts: usize, },
     GlobalTicksLimitExceeded { ticks: usize, voxels_to_do: usize, },
+    TimeLimitExceeded { ticks: usize, voxels_to_do: usize, },
     NoRouteToVoidDest { start: Coord, finish: Coord, region: Region, },
     NoRouteToFillDest { start: Coord, finish: Coord, region: Region, },
+    NoRouteToFinalize { bots_left: usize, },
+    // `Config::work_bounds` (clamped to the matrix) leave out some voxels of the models
+    WorkBoundsMissModels { work_bounds: Region, models: Region, },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RouterKind {
+    Rtt,
+    Astar,
+    // astar for routes shorter than `astar_max_distance` (L1), rtt for the rest
+    Hybrid { astar_max_distance: usize, },
+}
+
+// how the seeds are handed out on fission
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SeedDistribution {
+    // children get no seeds: every spawn comes from the bot holding them all
+    Root,
+    // the child gets half of the spawns still allowed: jobs come from a pool shared by everybody, so
+    // either of the two is up for half of the work left, and both keep spawning in parallel
+    Split,
+}
+
+// which kind of job an idle bot takes when both void and fill towers are left
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReassemblyOrder {
+    VoidFirst,
+    FillFirst,
+    // bots with even bids prefer voids, with odd bids fills
+    Interleaved,
 }
 
 pub struct Config {
@@ -33,10 +100,129 @@ pub struct Config {
     pub route_attempts_limit: usize,
     pub global_ticks_limit: usize,
     pub max_spawns: usize,
+    pub max_active_bots: usize,
+    pub time_limit: Option<Duration>,
+    pub seed: Option<u64>,
+    pub router: RouterKind,
+    pub nearest_jobs: bool,
+    pub allow_partial: bool,
+    pub use_group_commands: bool,
+    // ticks in a row without a voxel filled or voided before forcing a recovery, 0 to never force it
+    pub stall_ticks_limit: usize,
+    // wander targets are picked inside, `None` for the models bounding box with a small margin around
+    pub work_bounds: Option<Region>,
+    // every target connected component gets its own bot (spawning up to the components count)
+    // taking fill jobs only from that component until it is done
+    pub per_component: bool,
+    // rtt samples limit for routes to wander targets, `rtt_limit` is for the rest
+    pub rtt_wander_limit: usize,
+    // once this many wander routes in a row have failed, every next batch of as many gets half the
+    // samples and a target twice as close to the bot, 0 to never back off
+    pub wander_backoff_after: usize,
+    // every this many ticks the script so far, taken home and halted, is handed to the checkpoint callback
+    pub checkpoint_every: Option<usize>,
+    pub reassembly_order: ReassemblyOrder,
+    // bots first head to spots spread evenly along the x axis of the target bounding box instead of
+    // random ones, so they start the work apart from each other
+    pub spread_spawns: bool,
+    // once the script gets this long the bots are taken home and the solver halts with what is built
+    // so far (the way home goes over the limit), see `SolveStats::commands_limit_hit`
+    pub max_commands: Option<usize>,
+    pub seed_distribution: SeedDistribution,
+    // plan routes over (coord, tick) around the cells other bots have reserved for this many ticks
+    // ahead, waiting in place when that is cheaper, `None` for the plain routers
+    pub cooperative_window: Option<usize>,
+    // solve in a cube around the work bounds (and the initial bots) instead of the whole matrix,
+    // see `cropped_frame`
+    pub crop_to_models: bool,
+    // derive the ticks limit from the model instead of taking `global_ticks_limit`, see `auto_ticks_limit`
+    pub auto_tick_limit: bool,
+}
+
+// the same as the `random_swarm` binary defaults
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            init_bots: vec![],
+            rtt_limit: 256,
+            route_attempts_limit: 16,
+            global_ticks_limit: 1024,
+            max_spawns: 1,
+            max_active_bots: MAX_ACTIVE_BOTS,
+            time_limit: None,
+            seed: None,
+            router: RouterKind::Rtt,
+            nearest_jobs: false,
+            allow_partial: false,
+            use_group_commands: false,
+            stall_ticks_limit: 0,
+            work_bounds: None,
+            per_component: false,
+            rtt_wander_limit: 256,
+            wander_backoff_after: 0,
+            checkpoint_every: None,
+            reassembly_order: ReassemblyOrder::VoidFirst,
+            spread_spawns: false,
+            max_commands: None,
+            seed_distribution: SeedDistribution::Root,
+            cooperative_window: None,
+            crop_to_models: false,
+            auto_tick_limit: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Progress<'a> {
+    pub tick: usize,
+    pub voxels_remaining: usize,
+    pub active_bots: usize,
+    pub energy_estimate: usize,
+    // the model built so far and the one to build, see `voxels_to_fill` and `voxels_to_void`
+    current_model: &'a Matrix,
+    target_model: &'a Matrix,
+}
+
+impl<'a> Progress<'a> {
+    pub fn current_model(&self) -> &'a Matrix {
+        self.current_model
+    }
+
+    // The target voxels not filled yet.
+    pub fn voxels_to_fill(&self) -> impl Iterator<Item = &'a Coord> {
+        let current_model = self.current_model;
+        self.target_model.filled_voxels().filter(move |voxel| !current_model.is_filled(voxel))
+    }
+
+    // The filled voxels not in the target, still to be voided.
+    pub fn voxels_to_void(&self) -> impl Iterator<Item = &'a Coord> {
+        let target_model = self.target_model;
+        self.current_model.filled_voxels().filter(move |voxel| !target_model.is_filled(voxel))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct SolveStats {
+    pub ticks: usize,
+    pub peak_bots: usize,
+    pub route_retries: usize,
+    pub voxels_filled: usize,
+    pub voxels_voided: usize,
+    pub coverage_ratio: f64,
+    pub recoveries: usize,
+    // the solver stopped early on `Config::max_commands`
+    pub commands_limit_hit: bool,
+    // fill towers put back for later as nothing would ground them yet
+    pub fill_deferrals: usize,
 }
 
 pub fn solve(source_model: Matrix, target_model: Matrix, config: Config) -> Result<Vec<BotCommand>, (Error, Vec<BotCommand>)> {
-    solve_rng(source_model, target_model, config, &mut rand::thread_rng())
+    if let Some(seed) = config.seed {
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        solve_rng(source_model, target_model, config, &mut rng)
+    } else {
+        solve_rng(source_model, target_model, config, &mut rand::thread_rng())
+    }
 }
 
 pub fn solve_rng<R>(
@@ -47,14 +233,208 @@ pub fn solve_rng<R>(
 )
     -> Result<Vec<BotCommand>, (Error, Vec<BotCommand>)> where
     R: Rng
+{
+    solve_with_progress(source_model, target_model, config, rng, None)
+}
+
+pub fn solve_detailed<R>(
+    source_model: Matrix,
+    target_model: Matrix,
+    config: Config,
+    rng: &mut R,
+)
+    -> Result<(Vec<BotCommand>, SolveStats), (Error, Vec<BotCommand>)> where
+    R: Rng
+{
+    solve_detailed_with_progress(source_model, target_model, config, rng, None)
+}
+
+pub fn solve_with_progress<R>(
+    source_model: Matrix,
+    target_model: Matrix,
+    config: Config,
+    rng: &mut R,
+    progress: Option<&mut dyn FnMut(Progress)>,
+)
+    -> Result<Vec<BotCommand>, (Error, Vec<BotCommand>)> where
+    R: Rng
+{
+    solve_detailed_with_progress(source_model, target_model, config, rng, progress)
+        .map(|(script, _stats)| script)
+}
+
+pub fn solve_detailed_with_progress<R>(
+    source_model: Matrix,
+    target_model: Matrix,
+    config: Config,
+    rng: &mut R,
+    progress: Option<&mut dyn FnMut(Progress)>,
+)
+    -> Result<(Vec<BotCommand>, SolveStats), (Error, Vec<BotCommand>)> where
+    R: Rng
+{
+    solve_detailed_with_checkpoints(source_model, target_model, config, rng, progress, None)
+}
+
+// the same as `solve_detailed_with_progress`, but with `config.checkpoint_every` set `checkpoint` gets
+// a valid trace (halting with whatever is built so far) every so often, to keep if the run is cut off
+pub fn solve_detailed_with_checkpoints<R>(
+    source_model: Matrix,
+    target_model: Matrix,
+    config: Config,
+    rng: &mut R,
+    progress: Option<&mut dyn FnMut(Progress)>,
+    checkpoint: Option<&mut dyn FnMut(&[BotCommand])>,
+)
+    -> Result<(Vec<BotCommand>, SolveStats), (Error, Vec<BotCommand>)> where
+    R: Rng
 {
     let source_dim = source_model.dim();
     let target_dim = target_model.dim();
     if source_dim != target_dim {
         return Err((Error::ModelsDimMismatch { source_dim, target_dim, }, vec![]));
     }
+    let mut config = config;
+    config.work_bounds = Some(checked_work_bounds(&source_model, &target_model, &config).map_err(|e| (e, vec![]))?);
+    if !config.crop_to_models {
+        let current_model = source_model.clone();
+        let env = Env::new(source_model, target_model, config);
+        return solve_from(env, current_model, rng, progress, checkpoint);
+    }
+
+    let frame = cropped_frame(&source_model, &target_model, &config);
+    let dim = (frame.max.x - frame.min.x + 1) as usize;
+    debug!("solving in a cropped {} cube at {:?} out of {}", dim, frame.min, source_dim);
+    let offset = INIT_POS.diff(&frame.min);
+    let source_model = source_model.translate(offset).resize(dim);
+    let target_model = target_model.translate(offset).resize(dim);
+    config.work_bounds = config.work_bounds
+        .map(|bounds| Region { min: bounds.min.add(offset), max: bounds.max.add(offset), });
+    for &mut (_, ref mut bot) in config.init_bots.iter_mut() {
+        bot.pos = bot.pos.add(offset);
+    }
+
+    // the bots start and end at the origin of the cropped cube: the lead bot walks there first
+    // (unless the initial bots are already inside) and back home before halting, both times outside
+    // of the models bounding box. The rest of the trace only has moves relative to the bots, so it
+    // is valid as is in the whole matrix.
+    let walk_in = if config.init_bots.is_empty() { walk_between(&INIT_POS, &frame.min) } else { vec![] };
+    let walk_out = walk_between(&frame.min, &INIT_POS);
+    let uncrop = |script: &[BotCommand]| {
+        let mut full_script = walk_in.clone();
+        match script.split_last() {
+            Some((&BotCommand::Halt, rest)) => {
+                full_script.extend(rest.iter().cloned());
+                full_script.extend(walk_out.iter().cloned());
+                full_script.push(BotCommand::Halt);
+            },
+            _ =>
+                full_script.extend(script.iter().cloned()),
+        }
+        full_script
+    };
+
+    let has_checkpoint = checkpoint.is_some();
+    let mut checkpoint = checkpoint;
+    let mut uncropped_checkpoint = |script: &[BotCommand]| {
+        if let Some(ref mut checkpoint) = checkpoint {
+            checkpoint(&uncrop(script));
+        }
+    };
+    let current_model = source_model.clone();
     let env = Env::new(source_model, target_model, config);
-    let mut current_model = env.source_model.clone();
+    let result = solve_from(
+        env,
+        current_model,
+        rng,
+        progress,
+        if has_checkpoint { Some(&mut uncropped_checkpoint) } else { None },
+    );
+    match result {
+        Ok((script, mut stats)) => {
+            stats.ticks += walk_in.len() + walk_out.len();
+            Ok((uncrop(&script), stats))
+        },
+        Err((error, script)) =>
+            Err((error, uncrop(&script))),
+    }
+}
+
+// The cube to solve in with `crop_to_models`: it holds the work bounds and the initial bots with a
+// margin and is pushed back inside the matrix if it sticks out, so its origin corner is either
+// below the models bounding box or at the matrix origin along every axis.
+fn cropped_frame(source_model: &Matrix, target_model: &Matrix, config: &Config) -> Region {
+    let matrix_dim = source_model.dim() as M;
+    let work_bounds = config.work_bounds
+        .unwrap_or_else(|| default_work_bounds(source_model, target_model));
+    let bounds = config.init_bots.iter()
+        .fold(work_bounds, |bounds, &(_, ref bot)| Region {
+            min: Coord { x: cmp::min(bounds.min.x, bot.pos.x), y: cmp::min(bounds.min.y, bot.pos.y), z: cmp::min(bounds.min.z, bot.pos.z), },
+            max: Coord { x: cmp::max(bounds.max.x, bot.pos.x), y: cmp::max(bounds.max.y, bot.pos.y), z: cmp::max(bounds.max.z, bot.pos.z), },
+        })
+        .expand(CROP_MARGIN)
+        .clamp_to_dim(source_model.dim());
+    let side = cmp::max(bounds.max.x - bounds.min.x, cmp::max(bounds.max.y - bounds.min.y, bounds.max.z - bounds.min.z)) + 1;
+    let corner = |min: M| cmp::min(min, matrix_dim - side);
+    let min = Coord { x: corner(bounds.min.x), y: corner(bounds.min.y), z: corner(bounds.min.z), };
+    Region { min, max: Coord { x: min.x + side - 1, y: min.y + side - 1, z: min.z + side - 1, }, }
+}
+
+// SMoves from `start` to `finish` along x, y and z, or along z, y and x when heading for the origin,
+// so that both ways take the same voxels
+fn walk_between(start: &Coord, finish: &Coord) -> Vec<BotCommand> {
+    let diff = finish.diff(start).0;
+    let mut axes = vec![(Axis::X, diff.x), (Axis::Y, diff.y), (Axis::Z, diff.z)];
+    if *finish == INIT_POS {
+        axes.reverse();
+    }
+    axes.into_iter()
+        .flat_map(|(axis, delta)| line_moves(axis, delta))
+        .collect()
+}
+
+// continues from an intermediate `current_model` with `active_bots` wherever they are, so a big
+// problem can be solved in chunks (e.g. with a `time_limit`) across process restarts; only voxels
+// filled in `current_model` but absent from `target_model` are voided, the rest is kept
+pub fn resume(
+    current_model: Matrix,
+    target_model: Matrix,
+    active_bots: Vec<(Bid, Bot)>,
+    mut config: Config,
+)
+    -> Result<Vec<BotCommand>, (Error, Vec<BotCommand>)>
+{
+    let source_dim = current_model.dim();
+    let target_dim = target_model.dim();
+    if source_dim != target_dim {
+        return Err((Error::ModelsDimMismatch { source_dim, target_dim, }, vec![]));
+    }
+    let mut void_model = current_model.clone();
+    for voxel in target_model.filled_voxels() {
+        void_model.set_void(voxel);
+    }
+    config.init_bots = active_bots;
+    config.work_bounds = Some(checked_work_bounds(&void_model, &target_model, &config).map_err(|e| (e, vec![]))?);
+    let seed = config.seed;
+    let env = Env::new(void_model, target_model, config);
+    let result = if let Some(seed) = seed {
+        solve_from(env, current_model, &mut XorShiftRng::seed_from_u64(seed), None, None)
+    } else {
+        solve_from(env, current_model, &mut rand::thread_rng(), None, None)
+    };
+    result.map(|(script, _stats)| script)
+}
+
+fn solve_from<R>(
+    env: Env,
+    mut current_model: Matrix,
+    rng: &mut R,
+    mut progress: Option<&mut dyn FnMut(Progress)>,
+    mut checkpoint: Option<&mut dyn FnMut(&[BotCommand])>,
+)
+    -> Result<(Vec<BotCommand>, SolveStats), (Error, Vec<BotCommand>)> where
+    R: Rng
+{
     let mut commands_buf: Vec<(Coord, BotCommand)> = Vec::new();
     let mut script: Vec<BotCommand> = Vec::new();
     let mut script_tick: Vec<BotCommand> = Vec::new();
@@ -62,9 +442,18 @@ pub fn solve_rng<R>(
     let mut positions: Vec<Coord> = Vec::new();
     let mut pending_voids: Vec<Coord> = Vec::new();
     let mut pending_fills: Vec<Coord> = Vec::new();
+    let mut reservations = ReservationTable::new();
 
-    let mut void_towers = make_towers(&env.source_model);
-    let mut fill_towers = make_towers(&env.target_model);
+    // jobs are only the voxels to change: the ones kept from the source are never voided and refilled
+    let (void_voxels, fill_voxels) = current_model.diff(&env.target_model);
+    let resolution = Resolution(current_model.dim() as M);
+    // voids go from the top down, fills from the bottom up
+    let mut void_towers = TowerJobs::new(
+        make_towers(&Matrix::from_iter(resolution, void_voxels)),
+        |tower| Coord { x: tower.max.x, y: tower.max.y + 1, z: tower.max.z, },
+        |tower| -tower.min.y,
+    );
+    let mut fill_towers = FillJobs::new(&env, make_towers(&Matrix::from_iter(resolution, fill_voxels)));
 
     let mut nanobots = if env.config.init_bots.is_empty() {
         let (init_bid, init_bot) = Nanobot::init_bot();
@@ -85,37 +474,146 @@ pub fn solve_rng<R>(
     let mut work_complete = false;
     let mut harmonics = Harmonics::Low;
     let mut ungrounded_voxel: Option<Coord> = None;
+    // only the progress callback reports it, so nobody pays for it without one
+    let track_energy = progress.is_some();
+    let mut energy_estimate = 0;
+    let deadline = env.config.time_limit.map(|limit| Instant::now() + limit);
+    let mut time_limit_hit: Option<(usize, usize)> = None;
+    let mut stats = SolveStats::default();
+    let mut fusion_bots_count = 0;
+    let mut fusion_stalled_ticks = 0;
+    let mut stalled_ticks = 0;
+    let mut recovery_holder: Option<Bid> = None;
+
+    let group_possible = env.config.use_group_commands &&
+        env.config.init_bots.is_empty() &&
+        env.config.max_spawns >= 2 &&
+        env.config.max_active_bots >= 2;
+    let filled_before = current_model.count_filled();
+    let group_result = if group_possible && current_model.count_filled() == 0 {
+        group_fill_lines(&env.target_model, &mut current_model)
+    } else if group_possible && env.target_model.count_filled() == 0 {
+        group_void_lines(&mut current_model)
+    } else {
+        None
+    };
+    if let Some((ticks, group_nanobots)) = group_result {
+        let dim = current_model.dim();
+        for tick in ticks {
+            ticks_count += 1;
+            if track_energy {
+                energy_estimate += 3 * dim * dim * dim + 20 * tick.len();
+                // both group members fill (or void) the same region, so it is counted once
+                let mut group_counted = false;
+                for cmd in tick.iter() {
+                    match cmd {
+                        &BotCommand::GFill { .. } | &BotCommand::GVoid { .. } => {
+                            if group_counted {
+                                continue;
+                            }
+                            group_counted = true;
+                        },
+                        _ => (),
+                    }
+                    energy_estimate = apply_command_energy(energy_estimate, cmd);
+                }
+            }
+            script.extend(tick);
+        }
+        let filled_now = current_model.count_filled();
+        if filled_now > filled_before {
+            stats.voxels_filled += filled_now - filled_before;
+        } else {
+            stats.voxels_voided += filled_before - filled_now;
+        }
+        nanobots = group_nanobots;
+    }
+
     loop {
         ticks_count += 1;
+        stats.ticks = ticks_count;
+        stats.peak_bots = cmp::max(stats.peak_bots, nanobots.len());
+        reservations.forget_before(ticks_count);
 
-        if ticks_count % 100 == 0 {
+        if ticks_count % PROGRESS_TICKS == 0 {
             debug!("ticks_count = {}", ticks_count);
+            if let Some(ref mut progress) = progress {
+                progress(Progress {
+                    tick: ticks_count,
+                    voxels_remaining: voxels_to_do(&env, &current_model),
+                    active_bots: nanobots.len(),
+                    energy_estimate,
+                    current_model: &current_model,
+                    target_model: &env.target_model,
+                });
+            }
         }
 
-        if ticks_count >= env.config.global_ticks_limit {
-            let mut voxels_to_do = 0;
-            for voxel in env.source_model.filled_voxels() {
-                if current_model.is_filled(voxel) && !env.target_model.is_filled(voxel) {
-                    voxels_to_do += 1;
-                }
-            }
-            for voxel in env.target_model.filled_voxels() {
-                if !current_model.is_filled(voxel) {
-                    voxels_to_do += 1;
-                }
-            }
+        if time_limit_hit.is_none() && deadline.map(|d| Instant::now() >= d).unwrap_or(false) {
+            time_limit_hit = Some((ticks_count, voxels_to_do(&env, &current_model)));
+        }
+        let out_of_ticks = ticks_count >= env.global_ticks_limit;
+        let out_of_commands = env.config.max_commands.map(|max| script.len() >= max).unwrap_or(false);
+        if (out_of_ticks || out_of_commands || time_limit_hit.is_some()) && ungrounded_voxel.is_none() {
+            // out of ticks, commands or time: take the bots home and halt with what is built so far, so
+            // the script returned is valid whatever the result is
+            let voxels_left = voxels_to_do(&env, &current_model);
+            script.extend(finalize(&mut nanobots, &current_model));
+            return if !nanobots.is_empty() {
+                Err((Error::NoRouteToFinalize { bots_left: nanobots.len(), }, script))
+            } else if let Some((ticks, voxels_to_do)) = time_limit_hit {
+                Err((Error::TimeLimitExceeded { ticks, voxels_to_do, }, script))
+            } else if out_of_commands {
+                stats.commands_limit_hit = true;
+                stats.coverage_ratio = coverage_ratio(&current_model, &env.target_model);
+                Ok((script, stats))
+            } else if env.config.allow_partial {
+                stats.coverage_ratio = coverage_ratio(&current_model, &env.target_model);
+                Ok((script, stats))
+            } else {
+                Err((Error::GlobalTicksLimitExceeded { ticks: ticks_count, voxels_to_do: voxels_left, }, script))
+            };
+        } else if ticks_count >= 2 * env.global_ticks_limit {
+            // the model has stayed ungrounded long past the limit, so there is no way to halt
             return Err((Error::GlobalTicksLimitExceeded {
                 ticks: ticks_count,
-                voxels_to_do,
+                voxels_to_do: voxels_to_do(&env, &current_model),
             }, script));
         }
+
+        let checkpoint_due = match env.config.checkpoint_every {
+            Some(every) if every > 0 => ticks_count % every == 0,
+            _ => false,
+        };
+        if checkpoint_due && ungrounded_voxel.is_none() {
+            // the same way home as on the ticks limit, but on copies: the solver goes on as it was
+            if let Some(ref mut checkpoint) = checkpoint {
+                let mut homing_nanobots = nanobots.clone();
+                let mut checkpoint_script = script.clone();
+                checkpoint_script.extend(finalize(&mut homing_nanobots, &current_model));
+                if homing_nanobots.is_empty() {
+                    checkpoint(&checkpoint_script);
+                } else {
+                    debug!("skipping checkpoint on tick {}: {} bots have no way home", ticks_count, homing_nanobots.len());
+                }
+            }
+        }
+
         // check for stop condition
         let work_state = if work_complete || current_model.equals(&env.target_model) {
             work_complete = true;
             if nanobots.is_empty() {
-                return Ok(script);
+                stats.coverage_ratio = coverage_ratio(&current_model, &env.target_model);
+                return Ok((script, stats));
             }
 
+            if nanobots.len() < fusion_bots_count {
+                fusion_stalled_ticks = 0;
+            } else {
+                fusion_stalled_ticks += 1;
+            }
+            fusion_bots_count = nanobots.len();
+
             let (mut master, mut slave) = (None, None);
             for nanobot in nanobots.iter() {
                 let pos = nanobot.bot.pos;
@@ -125,9 +623,36 @@ pub fn solve_rng<R>(
                     slave = Some(pos);
                 }
             }
+            let slave_pick = master.and_then(|_| slave);
+            let fusion_pair = if slave_pick.is_none() && fusion_stalled_ticks > env.config.route_attempts_limit {
+                // no progress for too long: fuse any adjacent pair, primary is the one closer to origin
+                let mut best_pair: Option<(Coord, Coord)> = None;
+                for nanobot_a in nanobots.iter() {
+                    for nanobot_b in nanobots.iter() {
+                        let (pos_a, pos_b) = (nanobot_a.bot.pos, nanobot_b.bot.pos);
+                        if pos_a == pos_b || !pos_b.diff(&pos_a).is_near() {
+                            continue;
+                        }
+                        if pos_a.diff(&INIT_POS).l_1_norm() > pos_b.diff(&INIT_POS).l_1_norm() {
+                            continue;
+                        }
+                        let is_better = best_pair
+                            .map(|(best_a, _)| pos_a.diff(&INIT_POS).l_1_norm() < best_a.diff(&INIT_POS).l_1_norm())
+                            .unwrap_or(true);
+                        if is_better {
+                            best_pair = Some((pos_a, pos_b));
+                        }
+                    }
+                }
+                best_pair
+            } else {
+                None
+            };
             WorkState::Completed {
                 nanobots_left: nanobots.len(),
-                slave_pick: master.and_then(|_| slave),
+                slave_pick,
+                fusion_pair,
+                lead_bid: nanobots[0].bid,
             }
         } else {
             WorkState::InProgress
@@ -136,13 +661,19 @@ pub fn solve_rng<R>(
         volatiles.clear();
         positions.clear();
         positions.extend(nanobots.iter().map(|nanobot| nanobot.bot.pos));
+        let holder = if let WorkState::InProgress = work_state { recovery_holder.take() } else { None };
 
         let mut nanobots_count = nanobots.len();
         let mut next_nanobots =
             Vec::with_capacity(nanobots_count);
         for nanobot in nanobots {
+            if Some(nanobot.bid) == holder {
+                // stands still for a tick so the others can get around it
+                script_tick.push(BotCommand::Wait);
+                next_nanobots.push(nanobot);
+                continue;
+            }
             let nanobot_pos = nanobot.bot.pos;
-            let dim = current_model.dim() as isize;
             let implement_result =
                 nanobot.implement_plan(
                     &env,
@@ -150,22 +681,13 @@ pub fn solve_rng<R>(
                     work_state,
                     ungrounded_voxel,
                     nanobots_count,
-                    |region| if region.min.x < 0 || region.min.y < 0 || region.min.y < 0 {
-                        false
-                    } else if region.max.x >= dim || region.max.y >= dim || region.max.z >= dim {
-                        false
-                    } else if current_model.contains_filled(region) {
-                        false
-                    } else if volatiles.iter().any(|reg| reg.intersects(region)) {
-                        false
-                    } else if positions.iter().filter(|&pos| pos != &nanobot_pos).any(|pos| region.contains(pos)) {
-                        false
-                    } else {
-                        true
-                    },
+                    make_passability(&current_model, &volatiles, &positions, nanobot_pos),
                     &mut commands_buf,
                     &mut void_towers,
                     &mut fill_towers,
+                    &mut reservations,
+                    ticks_count,
+                    &mut stats,
                     rng,
                 );
 
@@ -221,6 +743,13 @@ pub fn solve_rng<R>(
                     script_tick.push(cmd),
                 PlanResult::Regular { mut nanobot, cmd, } => {
                     interpret(&mut nanobot, &cmd).map_err(|e| (e, script.clone()))?;
+                    if let Plan::HeadingFor { goal: Goal::Park, target, .. } = nanobot.plan {
+                        // reserve the landing spot: the bots planned after this one see it as occupied,
+                        // so they do not pick the same fusion slot or cut through it
+                        if target != nanobot.bot.pos {
+                            positions.push(target);
+                        }
+                    }
                     script_tick.push(cmd);
                     next_nanobots.push(nanobot);
                 },
@@ -237,14 +766,53 @@ pub fn solve_rng<R>(
         nanobots = next_nanobots;
         nanobots.sort_by_key(|nanobot| nanobot.bid);
 
+        if let WorkState::InProgress = work_state {
+            if pending_voids.is_empty() && pending_fills.is_empty() {
+                stalled_ticks += 1;
+            } else {
+                stalled_ticks = 0;
+            }
+            if env.config.stall_ticks_limit > 0 && stalled_ticks >= env.config.stall_ticks_limit {
+                // a jam: give everybody a fresh routing budget and hold one bot (a different one each time)
+                debug!("no voxels done for {} ticks, forcing recovery", stalled_ticks);
+                stalled_ticks = 0;
+                stats.recoveries += 1;
+                for nanobot in nanobots.iter_mut() {
+                    if let Plan::HeadingFor { ref mut attempts, .. } = nanobot.plan {
+                        *attempts = 0;
+                    }
+                }
+                recovery_holder = if nanobots.len() > 1 {
+                    Some(nanobots[stats.recoveries % nanobots.len()].bid)
+                } else {
+                    None
+                };
+            }
+        }
+
+        stats.voxels_voided += pending_voids.len();
         for void_coord in pending_voids.drain(..) {
             current_model.set_void(&void_coord);
         }
+        stats.voxels_filled += pending_fills.len();
         for fill_coord in pending_fills.drain(..) {
             current_model.set_filled(&fill_coord);
         }
 
-        ungrounded_voxel = current_model.first_ungrounded_voxel();
+        if track_energy {
+            let dim = current_model.dim();
+            energy_estimate += match harmonics {
+                Harmonics::Low => 3,
+                Harmonics::High => 30,
+            } * dim * dim * dim;
+            energy_estimate += 20 * nanobots.len();
+            for cmd in script_tick.iter() {
+                energy_estimate = apply_command_energy(energy_estimate, cmd);
+            }
+        }
+
+        // `first_ungrounded_voxel` depends on the hash set order, take the least one for reproducible runs
+        ungrounded_voxel = current_model.ungrounded_voxels().into_iter().min();
         if ungrounded_voxel.is_some() {
             if let Harmonics::Low = harmonics {
                 script.push(BotCommand::Flip);
@@ -271,18 +839,116 @@ struct Env {
     source_model: Matrix,
     target_model: Matrix,
     config: Config,
+    work_bounds: Region,
+    // target connected components, empty unless `per_component` is set
+    components: Vec<Vec<Coord>>,
+    max_spawns: usize,
+    // the first wander target of the bot with bid `b` is `spawn_slots[(b - 1) % len]`, random when empty
+    spawn_slots: Vec<Coord>,
+    global_ticks_limit: usize,
 }
 
 impl Env {
     fn new(source_model: Matrix, target_model: Matrix, config: Config) -> Env {
+        let work_bounds = config.work_bounds
+            .unwrap_or_else(|| default_work_bounds(&source_model, &target_model));
+        let components = if config.per_component {
+            target_model.connected_component_voxels()
+        } else {
+            Vec::new()
+        };
+        let max_spawns = cmp::max(config.max_spawns, cmp::min(components.len(), MAX_ACTIVE_BOTS));
+        let spawn_slots = if config.spread_spawns {
+            spawn_slots(&target_model, cmp::min(max_spawns, MAX_ACTIVE_BOTS))
+        } else {
+            Vec::new()
+        };
+        let global_ticks_limit = if config.auto_tick_limit {
+            auto_ticks_limit(&source_model, &target_model, cmp::min(max_spawns, config.max_active_bots))
+        } else {
+            config.global_ticks_limit
+        };
         Env {
             source_model,
             target_model,
             config,
+            work_bounds,
+            components,
+            max_spawns,
+            spawn_slots,
+            global_ticks_limit,
         }
     }
 }
 
+// Ticks limit for the voxels to change shared evenly by `max_bots` bots (at most 40):
+//   max(AUTO_TICKS_MIN, 3 * dim + AUTO_TICKS_PER_VOXEL * ceil(voxels / max_bots))
+// where 3 * dim is for spreading the bots over the matrix in the beginning and taking them home in the end.
+fn auto_ticks_limit(source_model: &Matrix, target_model: &Matrix, max_bots: usize) -> usize {
+    let (void_voxels, fill_voxels) = source_model.diff(target_model);
+    let voxels = void_voxels.len() + fill_voxels.len();
+    let bots = cmp::max(1, cmp::min(max_bots, MAX_ACTIVE_BOTS));
+    let share = (voxels + bots - 1) / bots;
+    cmp::max(AUTO_TICKS_MIN, 3 * source_model.dim() + AUTO_TICKS_PER_VOXEL * share)
+}
+
+// `count` spots right above the target bounding box, centered in as many equal stripes along x
+fn spawn_slots(target_model: &Matrix, count: usize) -> Vec<Coord> {
+    let bbox = match target_model.bounding_box() {
+        Some(bbox) => bbox,
+        None => return Vec::new(),
+    };
+    let y = cmp::min(bbox.max.y + 1, target_model.dim() as M - 1);
+    let z = (bbox.min.z + bbox.max.z) / 2;
+    let width = bbox.max.x - bbox.min.x + 1;
+    (0 .. count as M)
+        .map(|index| Coord { x: bbox.min.x + (2 * index + 1) * width / (2 * count as M), y, z, })
+        .collect()
+}
+
+fn models_bounding_box(source_model: &Matrix, target_model: &Matrix) -> Option<Region> {
+    match (source_model.bounding_box(), target_model.bounding_box()) {
+        (Some(a), Some(b)) => Some(Region {
+            min: Coord { x: cmp::min(a.min.x, b.min.x), y: cmp::min(a.min.y, b.min.y), z: cmp::min(a.min.z, b.min.z), },
+            max: Coord { x: cmp::max(a.max.x, b.max.x), y: cmp::max(a.max.y, b.max.y), z: cmp::max(a.max.z, b.max.z), },
+        }),
+        (Some(a), None) | (None, Some(a)) =>
+            Some(a),
+        (None, None) =>
+            None,
+    }
+}
+
+fn default_work_bounds(source_model: &Matrix, target_model: &Matrix) -> Region {
+    let dim = source_model.dim();
+    match models_bounding_box(source_model, target_model) {
+        Some(models_box) =>
+            models_box.expand(WORK_BOUNDS_MARGIN).clamp_to_dim(dim),
+        None => {
+            let max = dim as M - 1;
+            Region { min: INIT_POS, max: Coord { x: max, y: max, z: max, }, }
+        },
+    }
+}
+
+// `Config::work_bounds` with the corners put in order and clamped to the matrix (so wander targets
+// can always be picked in there), the default ones when not set. They have to hold both models:
+// wandering would never get close to what is left out, and `crop_to_models` would drop it.
+fn checked_work_bounds(source_model: &Matrix, target_model: &Matrix, config: &Config) -> Result<Region, Error> {
+    let work_bounds = match config.work_bounds {
+        None =>
+            return Ok(default_work_bounds(source_model, target_model)),
+        Some(bounds) =>
+            Region::from_corners(&bounds.min, &bounds.max).clamp_to_dim(source_model.dim()),
+    };
+    match models_bounding_box(source_model, target_model) {
+        Some(models) if !work_bounds.contains(&models.min) || !work_bounds.contains(&models.max) =>
+            Err(Error::WorkBoundsMissModels { work_bounds, models, }),
+        _ =>
+            Ok(work_bounds),
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 struct Nanobot {
     bid: Bid,
@@ -310,6 +976,8 @@ enum WorkState {
     Completed {
         nanobots_left: usize,
         slave_pick: Option<Coord>,
+        fusion_pair: Option<(Coord, Coord)>,
+        lead_bid: Bid,
     },
 }
 
@@ -337,8 +1005,11 @@ impl Nanobot {
         nanobots_count: usize,
         is_passable: FP,
         commands_buf: &mut Vec<(Coord, BotCommand)>,
-        void_towers: &mut Vec<Region>,
-        fill_towers: &mut Vec<Region>,
+        void_towers: &mut TowerJobs,
+        fill_towers: &mut FillJobs,
+        reservations: &mut ReservationTable,
+        tick: usize,
+        stats: &mut SolveStats,
         rng: &mut R,
     )
         -> PlanResult where FP: Fn(&Region) -> bool, R: Rng,
@@ -346,7 +1017,14 @@ impl Nanobot {
         match work_state {
             WorkState::InProgress =>
                 (),
-            WorkState::Completed { nanobots_left, slave_pick, } =>
+            WorkState::Completed { fusion_pair: Some((primary, secondary)), .. } if primary == self.bot.pos =>
+                return PlanResult::Regular {
+                    cmd: BotCommand::FusionP { near: secondary.diff(&self.bot.pos), },
+                    nanobot: self,
+                },
+            WorkState::Completed { fusion_pair: Some((primary, secondary)), .. } if secondary == self.bot.pos =>
+                return PlanResult::DoAndPerish(BotCommand::FusionS { near: primary.diff(&self.bot.pos), }),
+            WorkState::Completed { nanobots_left, slave_pick, lead_bid, .. } =>
                 if self.bot.pos == INIT_POS {
                     // i am the master
                     return if nanobots_left == 1 {
@@ -367,7 +1045,7 @@ impl Nanobot {
                             }),
                         _ =>
                             self.plan = Plan::HeadingFor {
-                                target: if self.bid == 1 { INIT_POS } else { Coord { x: 1, y: 0, z: 0, } },
+                                target: park_target(self.bid, lead_bid, &is_passable),
                                 attempts: 0,
                                 goal: Goal::Park,
                             },
@@ -378,7 +1056,11 @@ impl Nanobot {
             match self.plan {
                 Plan::Init => {
                     // go somewhere
-                    let target = pick_random_coord(current_model.dim() as isize, rng);
+                    let target = if env.spawn_slots.is_empty() {
+                        pick_random_coord(&env.work_bounds, rng)
+                    } else {
+                        env.spawn_slots[(self.bid + env.spawn_slots.len() - 1) % env.spawn_slots.len()]
+                    };
                     self.plan = Plan::HeadingFor { target, attempts: 0, goal: Goal::Wander, };
                 },
                 Plan::HeadingFor { target, attempts, goal, } if attempts > env.config.route_attempts_limit => {
@@ -397,41 +1079,79 @@ impl Nanobot {
                     return PlanResult::Regular { nanobot: self, cmd: BotCommand::Wait, },
                 Plan::HeadingFor { goal: Goal::Wander, target, .. } if target == self.bot.pos => {
                     // spawn if able to
-                    if self.bot.seeds.len() > 0 && nanobots_count < env.config.max_spawns {
+                    let below_cap = nanobots_count < cmp::min(env.config.max_active_bots, MAX_ACTIVE_BOTS);
+                    if self.bot.seeds.len() > 0 && nanobots_count < env.max_spawns && below_cap {
                         if let Some(pos) = target.get_neighbours().find(|&p| is_passable(&Region { min: p, max: p, })) {
+                            let split_m = match env.config.seed_distribution {
+                                SeedDistribution::Root =>
+                                    0,
+                                SeedDistribution::Split => {
+                                    let bots_limit = cmp::min(env.max_spawns, cmp::min(env.config.max_active_bots, MAX_ACTIVE_BOTS));
+                                    let spawns_left = bots_limit.saturating_sub(nanobots_count + 1);
+                                    cmp::min(spawns_left / 2, self.bot.seeds.len() - 1)
+                                },
+                            };
+                            let bid = self.bot.seeds.remove(0);
                             let child = Nanobot {
-                                bid: self.bot.seeds.remove(0),
-                                bot: Bot { pos, seeds: vec![], },
+                                bid,
+                                bot: Bot { pos, seeds: self.bot.seeds.drain(.. split_m).collect(), },
                                 plan: Plan::Init,
                             };
                             return PlanResult::Spawn {
                                 parent: self,
                                 child,
-                                cmd: BotCommand::Fission { near: pos.diff(&target), split_m: 0, },
+                                cmd: BotCommand::Fission { near: pos.diff(&target), split_m: split_m as u8, },
                             };
                         }
                     }
 
                     // take a job if any
                     let dim = current_model.dim() as isize;
-                    let maybe_void_index = void_towers.iter()
-                        .enumerate()
-                        .min_by_key(|(_, region)| {
-                            let top_center = Coord { x: dim / 2, y: dim - 1, z: dim / 2, };
-                            let flag = ungrounded_voxel.map(|v| !region.contains(&v)).unwrap_or(true);
-                            (flag, -region.min.y, top_center.diff(&region.min).l_1_norm() + top_center.diff(&region.max).l_1_norm())
-                        })
-                        .map(|p| p.0);
-                    let maybe_fill_index = fill_towers.iter()
-                        .enumerate()
-                        .min_by_key(|(_, region)| {
-                            let bottom_center = Coord { x: dim / 2, y: 0, z: dim / 2, };
-                            let flag = ungrounded_voxel.map(|v| !region.contains(&v)).unwrap_or(true);
-                            (flag, region.min.y, bottom_center.diff(&region.min).l_1_norm() + bottom_center.diff(&region.max).l_1_norm())
-                        })
-                        .map(|p| p.0);
-                    self.plan = if let Some(index) = maybe_void_index {
-                        let void_region = void_towers.swap_remove(index);
+                    let top_center = Coord { x: dim / 2, y: dim - 1, z: dim / 2, };
+                    let bottom_center = Coord { x: dim / 2, y: 0, z: dim / 2, };
+                    let (void_anchor, fill_anchor) = if env.config.nearest_jobs {
+                        (self.bot.pos, self.bot.pos)
+                    } else {
+                        (top_center, bottom_center)
+                    };
+                    // towers are cut before the group phase, so some of their voxels may already be done
+                    let is_satisfied = |coord: &Coord| current_model.is_filled(coord) == env.target_model.is_filled(coord);
+                    let mut take_void_job = || loop {
+                        match void_towers.take_nearest(&void_anchor, ungrounded_voxel) {
+                            None => break None,
+                            Some(tower) => if let Some(tower) = trim_satisfied(tower, &is_satisfied) {
+                                break Some(tower);
+                            },
+                        }
+                    };
+                    let bid = self.bid;
+                    let mut take_fill_job = || {
+                        let (job, deferred) = fill_towers.take_groundable(
+                            bid,
+                            &fill_anchor,
+                            ungrounded_voxel,
+                            &is_satisfied,
+                            |coord: &Coord| current_model.will_be_grounded(coord),
+                        );
+                        if deferred > 0 {
+                            debug!("bot {}: deferred {} fill towers standing on nothing yet, took {:?}", bid, deferred, job);
+                            stats.fill_deferrals += deferred;
+                        }
+                        job
+                    };
+                    let fills_first = match env.config.reassembly_order {
+                        ReassemblyOrder::VoidFirst => false,
+                        ReassemblyOrder::FillFirst => true,
+                        ReassemblyOrder::Interleaved => bid % 2 == 1,
+                    };
+                    let job = if fills_first {
+                        take_fill_job().map(|tower| Goal::Fill { tower, })
+                            .or_else(|| take_void_job().map(|tower| Goal::Void { tower, }))
+                    } else {
+                        take_void_job().map(|tower| Goal::Void { tower, })
+                            .or_else(|| take_fill_job().map(|tower| Goal::Fill { tower, }))
+                    };
+                    self.plan = if let Some(Goal::Void { tower: void_region, }) = job {
                         Plan::HeadingFor {
                             goal: Goal::Void { tower: void_region, },
                             target: Coord {
@@ -441,8 +1161,7 @@ impl Nanobot {
                             },
                             attempts: 0,
                         }
-                    } else if let Some(index) = maybe_fill_index {
-                        let fill_region = fill_towers.swap_remove(index);
+                    } else if let Some(Goal::Fill { tower: fill_region, }) = job {
                         Plan::HeadingFor {
                             goal: Goal::Fill { tower: fill_region, },
                             target: Coord {
@@ -454,7 +1173,7 @@ impl Nanobot {
                         }
                     } else {
                         // no jobs left, but model isn't fully printed yet: go to left side
-                        let mut target = pick_random_coord(current_model.dim() as isize, rng);
+                        let mut target = pick_random_coord(&env.work_bounds, rng);
                         target.x = 0;
                         Plan::HeadingFor { target, attempts: 0, goal: Goal::Wander, }
                     };
@@ -462,8 +1181,8 @@ impl Nanobot {
                 Plan::HeadingFor { goal: Goal::Void { mut tower, }, mut target, .. } if target == self.bot.pos => {
                     let job_coord = tower.max;
                     let current_filled = current_model.is_filled(&job_coord);
-                    let source_filled = env.source_model.is_filled(&job_coord);
-                    if current_filled && source_filled {
+                    let target_filled = env.target_model.is_filled(&job_coord);
+                    if current_filled && !target_filled {
                         return PlanResult::Regular { nanobot: self, cmd: BotCommand::Void { near: job_coord.diff(&target), }, };
                     } else if tower.min == tower.max {
                         // get next tower
@@ -503,27 +1222,40 @@ impl Nanobot {
                 },
                 Plan::HeadingFor { target, attempts, goal, } => {
                     // still moving to target
-                    let route_result =
-                        route_and_step(&self.bot.pos, &target, current_model, &is_passable, commands_buf, env.config.rtt_limit, rng);
+                    let rtt_limit = if goal == Goal::Wander {
+                        wander_rtt_limit(&env.config, attempts)
+                    } else {
+                        env.config.rtt_limit
+                    };
+                    let route_result = if let Some(window) = env.config.cooperative_window {
+                        Ok(route_cooperative(self.bid, &self.bot.pos, &target, current_model, &is_passable, reservations, tick, window))
+                    } else {
+                        route_and_step(&self.bot.pos, &target, current_model, &is_passable, commands_buf, &env.config, rtt_limit, rng)
+                    };
                     match route_result {
                         Ok(Some(moving_cmd)) => {
                             // can continue moving
                             self.plan = Plan::HeadingFor { target, goal, attempts: 0, };
                             return PlanResult::Regular { nanobot: self, cmd: moving_cmd, };
                         },
-                        Ok(None) =>
+                        Ok(None) => {
                             // can not move there
+                            stats.route_retries += 1;
                             match goal {
                                 Goal::Wander => {
-                                    // pick another wandering target
-                                    let target = pick_random_coord(current_model.dim() as isize, rng);
+                                    // pick another wandering target (a cheaper one if it keeps failing)
+                                    let target = pick_wander_target(env, &self.bot.pos, attempts + 1, rng);
                                     self.plan = Plan::HeadingFor { target, attempts: attempts + 1, goal: Goal::Wander, };
                                 },
                                 Goal::Park => {
                                     // try to find a free position nearby
+                                    let lead_bid = match work_state {
+                                        WorkState::Completed { lead_bid, .. } => lead_bid,
+                                        WorkState::InProgress => 1,
+                                    };
                                     self.plan = Plan::HeadingFor {
                                         goal: Goal::Park,
-                                        target: if self.bid == 1 { INIT_POS } else { Coord { x: 1, y: 0, z: 0, } },
+                                        target: park_target(self.bid, lead_bid, &is_passable),
                                         attempts: attempts + 1,
                                     };
                                     return PlanResult::Regular { nanobot: self, cmd: BotCommand::Wait, }
@@ -550,6 +1282,7 @@ impl Nanobot {
                                     return PlanResult::Regular { nanobot: self, cmd: BotCommand::Wait, };
                                 },
                             }
+                        },
                         Err(error) =>
                             return PlanResult::Error(error),
                     }
@@ -559,62 +1292,525 @@ impl Nanobot {
     }
 }
 
-fn make_towers(model: &Matrix) -> Vec<Region> {
-    let dim = model.dim() as isize;
-    let mut regions = Vec::new();
-    for x in 0 .. dim {
+fn voxels_to_do(env: &Env, current_model: &Matrix) -> usize {
+    let mut voxels_to_do = 0;
+    for voxel in env.source_model.filled_voxels() {
+        if current_model.is_filled(voxel) && !env.target_model.is_filled(voxel) {
+            voxels_to_do += 1;
+        }
+    }
+    for voxel in env.target_model.filled_voxels() {
+        if !current_model.is_filled(voxel) {
+            voxels_to_do += 1;
+        }
+    }
+    voxels_to_do
+}
+
+fn coverage_ratio(current_model: &Matrix, target_model: &Matrix) -> f64 {
+    let target_voxels = target_model.filled_voxels().count();
+    if target_voxels == 0 {
+        return if current_model.filled_voxels().next().is_none() { 1.0 } else { 0.0 };
+    }
+    let matched_voxels = target_model.filled_voxels()
+        .filter(|voxel| current_model.is_filled(voxel))
+        .count();
+    matched_voxels as f64 / target_voxels as f64
+}
+
+fn apply_command_energy(energy: usize, cmd: &BotCommand) -> usize {
+    match cmd {
+        &BotCommand::Halt |
+        &BotCommand::Wait |
+        &BotCommand::Flip |
+        &BotCommand::FusionS { .. } =>
+            energy,
+        &BotCommand::SMove { ref long, } =>
+            energy + 2 * long.to_coord_diff().l_1_norm(),
+        &BotCommand::LMove { ref short1, ref short2, } =>
+            energy + 2 * (short1.to_coord_diff().l_1_norm() + 2 + short2.to_coord_diff().l_1_norm()),
+        &BotCommand::Fission { .. } =>
+            energy + 24,
+        &BotCommand::FusionP { .. } =>
+            energy.saturating_sub(24),
+        &BotCommand::Fill { .. } =>
+            energy + 12,
+        &BotCommand::Void { .. } =>
+            energy.saturating_sub(12),
+        &BotCommand::GFill { far, .. } =>
+            energy + 12 * far_volume(&far),
+        &BotCommand::GVoid { far, .. } =>
+            energy.saturating_sub(12 * far_volume(&far)),
+    }
+}
+
+// Fills the straight x-lines of the target with GFill issued by two bots standing above both ends,
+// layer by layer from the bottom. It is run only from an empty source, so the layer above the
+// current one is always free to fly over. Lines which are too short or not grounded yet are
+// left for the swarm.
+fn group_fill_lines(target_model: &Matrix, current_model: &mut Matrix) -> Option<(Vec<Vec<BotCommand>>, Vec<Nanobot>)> {
+    let dim = target_model.dim() as M;
+    let is_pending = |current_model: &Matrix, coord: &Coord| target_model.is_filled(coord) && !current_model.is_filled(coord);
+
+    let (bid_a, mut bot_a) = Nanobot::init_bot();
+    let bid_b = bot_a.seeds.remove(0);
+    let mut ticks = vec![
+        vec![BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Y, value: 1, }, }],
+        vec![BotCommand::Fission { near: CoordDiff(Coord { x: 1, y: 0, z: 0, }), split_m: 0, }],
+    ];
+    // bot a always stays to the left (lower x) of bot b
+    let mut pos_a = Coord { x: 0, y: 1, z: 0, };
+    let mut pos_b = Coord { x: 1, y: 1, z: 0, };
+    let mut lines_filled = 0;
+    for y in 0 .. dim - 1 {
         for z in 0 .. dim {
-            let mut current_reg: Option<Region> = None;
-            for y in 0 .. dim {
-                let p = Coord { x, y, z, };
-                if model.is_filled(&p) {
-                    if let Some(ref mut reg) = current_reg {
-                        reg.max = p;
-                    } else {
-                        current_reg = Some(Region { min: p, max: p, });
-                    }
-                } else if let Some(reg) = current_reg {
-                    regions.push(reg);
-                    current_reg = None;
+            let mut x = 0;
+            while x < dim {
+                if !is_pending(current_model, &Coord { x, y, z, }) {
+                    x += 1;
+                    continue;
                 }
-            }
-            if let Some(reg) = current_reg {
-                regions.push(reg);
+                let x0 = x;
+                while x < dim && x - x0 < GROUP_LINE_MAX && is_pending(current_model, &Coord { x, y, z, }) {
+                    x += 1;
+                }
+                let x1 = x - 1;
+                let grounded = current_model.region_would_be_grounded(&Region {
+                    min: Coord { x: x0, y, z, },
+                    max: Coord { x: x1, y, z, },
+                });
+                if x1 == x0 || !grounded {
+                    continue;
+                }
+
+                push_group_approach(&mut ticks, &mut pos_a, &mut pos_b, Coord { x: x0, y: y + 1, z, }, x1);
+
+                let near = CoordDiff(Coord { x: 0, y: -1, z: 0, });
+                ticks.push(vec![
+                    BotCommand::GFill { near, far: CoordDiff(Coord { x: x1 - x0, y: 0, z: 0, }), },
+                    BotCommand::GFill { near, far: CoordDiff(Coord { x: x0 - x1, y: 0, z: 0, }), },
+                ]);
+                for x in x0 ..= x1 {
+                    current_model.set_filled(&Coord { x, y, z, });
+                }
+                lines_filled += 1;
             }
         }
     }
-    regions
+    if lines_filled == 0 {
+        return None;
+    }
+
+    let nanobots = vec![
+        Nanobot { bid: bid_a, bot: Bot { pos: pos_a, seeds: bot_a.seeds, }, plan: Plan::Init, },
+        Nanobot { bid: bid_b, bot: Bot { pos: pos_b, seeds: vec![], }, plan: Plan::Init, },
+    ];
+    Some((ticks, nanobots))
 }
 
-fn pick_random_coord<R>(dim: isize, rng: &mut R) -> Coord where R: Rng {
-    Coord {
-        x: rng.gen_range(0, dim),
-        y: rng.gen_range(0, dim),
-        z: rng.gen_range(0, dim),
+// Voids the straight x-lines of the source with GVoid issued by two bots standing above both ends,
+// layer by layer from the top, for a demolition to an empty target. Two bots can only void lines
+// (a box needs four or eight). A line is skipped (and left for the swarm single voxel `Void`) when
+// it is too short or when voiding it ungrounds the rest; the group stops at the first layer it can
+// not clear completely, so the layer above the current one is always free to fly over.
+fn group_void_lines(current_model: &mut Matrix) -> Option<(Vec<Vec<BotCommand>>, Vec<Nanobot>)> {
+    let dim = current_model.dim() as M;
+    // the bots climb from the origin along the z = 0 edge
+    let climb_free = (0 .. dim)
+        .all(|y| !current_model.is_filled(&Coord { x: 0, y, z: 0, }) && !current_model.is_filled(&Coord { x: 1, y, z: 0, }));
+    if dim < 2 || !climb_free {
+        return None;
     }
-}
 
-fn route_and_step<FP, R>(
-    start: &Coord,
-    finish: &Coord,
-    current_model: &Matrix,
-    is_passable: FP,
-    commands_buf: &mut Vec<(Coord, BotCommand)>,
-    rtt_limit: usize,
-    rng: &mut R,
-)
-    -> Result<Option<BotCommand>, Error> where
+    let (bid_a, mut bot_a) = Nanobot::init_bot();
+    let bid_b = bot_a.seeds.remove(0);
+    let mut ticks = vec![
+        vec![BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Y, value: 1, }, }],
+        vec![BotCommand::Fission { near: CoordDiff(Coord { x: 1, y: 0, z: 0, }), split_m: 0, }],
+    ];
+    // bot a always stays to the left (lower x) of bot b
+    let mut pos_a = Coord { x: 0, y: 1, z: 0, };
+    let mut pos_b = Coord { x: 1, y: 1, z: 0, };
+    let mut lines_voided = 0;
+    for y in (0 .. dim - 1).rev() {
+        for z in 0 .. dim {
+            let mut x = 0;
+            while x < dim {
+                if !current_model.is_filled(&Coord { x, y, z, }) {
+                    x += 1;
+                    continue;
+                }
+                let x0 = x;
+                while x < dim && x - x0 < GROUP_LINE_MAX && current_model.is_filled(&Coord { x, y, z, }) {
+                    x += 1;
+                }
+                let x1 = x - 1;
+                if x1 == x0 {
+                    continue;
+                }
+                for x in x0 ..= x1 {
+                    current_model.set_void(&Coord { x, y, z, });
+                }
+                if !current_model.all_voxels_are_grounded() {
+                    for x in x0 ..= x1 {
+                        current_model.set_filled(&Coord { x, y, z, });
+                    }
+                    continue;
+                }
+
+                push_group_approach(&mut ticks, &mut pos_a, &mut pos_b, Coord { x: x0, y: y + 1, z, }, x1);
+
+                let near = CoordDiff(Coord { x: 0, y: -1, z: 0, });
+                ticks.push(vec![
+                    BotCommand::GVoid { near, far: CoordDiff(Coord { x: x1 - x0, y: 0, z: 0, }), },
+                    BotCommand::GVoid { near, far: CoordDiff(Coord { x: x0 - x1, y: 0, z: 0, }), },
+                ]);
+                lines_voided += 1;
+            }
+        }
+        let layer_cleared = iproduct!(0 .. dim, 0 .. dim)
+            .all(|(x, z)| !current_model.is_filled(&Coord { x, y, z, }));
+        if !layer_cleared {
+            break;
+        }
+    }
+    if lines_voided == 0 {
+        return None;
+    }
+
+    let nanobots = vec![
+        Nanobot { bid: bid_a, bot: Bot { pos: pos_a, seeds: bot_a.seeds, }, plan: Plan::Init, },
+        Nanobot { bid: bid_b, bot: Bot { pos: pos_b, seeds: vec![], }, plan: Plan::Init, },
+    ];
+    Some((ticks, nanobots))
+}
+
+// brings the group to stand above a line: bot a over `line_a` and bot b over the same line at `x1`,
+// moving along y first, then z, then x in the order that never lets the two bots cross
+fn push_group_approach(ticks: &mut Vec<Vec<BotCommand>>, pos_a: &mut Coord, pos_b: &mut Coord, line_a: Coord, x1: M) {
+    push_group_moves(ticks, line_moves(Axis::Y, line_a.y - pos_a.y), line_moves(Axis::Y, line_a.y - pos_b.y));
+    push_group_moves(ticks, line_moves(Axis::Z, line_a.z - pos_a.z), line_moves(Axis::Z, line_a.z - pos_b.z));
+    let (moves_a, moves_b) = (line_moves(Axis::X, line_a.x - pos_a.x), line_moves(Axis::X, x1 - pos_b.x));
+    if x1 > pos_b.x {
+        push_group_moves(ticks, vec![], moves_b);
+        push_group_moves(ticks, moves_a, vec![]);
+    } else {
+        push_group_moves(ticks, moves_a, vec![]);
+        push_group_moves(ticks, vec![], moves_b);
+    }
+    *pos_a = line_a;
+    *pos_b = Coord { x: x1, ..line_a };
+}
+
+fn push_group_moves(ticks: &mut Vec<Vec<BotCommand>>, moves_a: Vec<BotCommand>, moves_b: Vec<BotCommand>) {
+    for i in 0 .. cmp::max(moves_a.len(), moves_b.len()) {
+        ticks.push(vec![
+            moves_a.get(i).cloned().unwrap_or(BotCommand::Wait),
+            moves_b.get(i).cloned().unwrap_or(BotCommand::Wait),
+        ]);
+    }
+}
+
+fn far_volume(far: &CoordDiff) -> usize {
+    Region::from_corners(&INIT_POS, &INIT_POS.add(*far)).contents_count()
+}
+
+fn park_target<FP>(bid: Bid, lead_bid: Bid, is_passable: FP) -> Coord where FP: Fn(&Region) -> bool {
+    if bid == lead_bid {
+        return INIT_POS;
+    }
+    let default_slot = Coord { x: 1, y: 0, z: 0, };
+    if is_passable(&Region { min: default_slot, max: default_slot, }) {
+        default_slot
+    } else {
+        // default slot is taken, try any other position near the master
+        INIT_POS.get_neighbours()
+            .filter(|p| p.x >= 0 && p.y >= 0 && p.z >= 0)
+            .find(|&p| is_passable(&Region { min: p, max: p, }))
+            .unwrap_or(default_slot)
+    }
+}
+
+// Pending towers bucketed by priority level (lowest first), each level indexed by the towers
+// approach coordinates, so picking the nearest job does not scan all of them.
+struct TowerJobs {
+    towers: HashMap<Coord, Region>,
+    levels: BTreeMap<M, CoordIndex>,
+    columns: HashMap<(M, M), Vec<Coord>>,
+}
+
+impl TowerJobs {
+    fn new<FA, FL>(towers: Vec<Region>, approach: FA, level: FL) -> TowerJobs where FA: Fn(&Region) -> Coord, FL: Fn(&Region) -> M {
+        let mut by_level: BTreeMap<M, Vec<Coord>> = BTreeMap::new();
+        let mut columns: HashMap<(M, M), Vec<Coord>> = HashMap::new();
+        let mut approaches = HashMap::with_capacity(towers.len());
+        for tower in towers {
+            let coord = approach(&tower);
+            by_level.entry(level(&tower)).or_insert_with(Vec::new).push(coord);
+            columns.entry((tower.min.x, tower.min.z)).or_insert_with(Vec::new).push(coord);
+            approaches.insert(coord, tower);
+        }
+        TowerJobs {
+            towers: approaches,
+            levels: by_level.into_iter().map(|(level, coords)| (level, CoordIndex::build(coords))).collect(),
+            columns,
+        }
+    }
+
+    // a tower holding the ungrounded voxel goes first, then the nearest one on the lowest level;
+    // `CoordIndex::nearest` breaks distance ties by the least coordinate, so the choice is deterministic
+    fn take_nearest(&mut self, anchor: &Coord, ungrounded_voxel: Option<Coord>) -> Option<Region> {
+        if let Some(voxel) = ungrounded_voxel {
+            let maybe_coord = self.columns.get(&(voxel.x, voxel.z))
+                .and_then(|coords| coords.iter().find(|coord| self.towers[*coord].contains(&voxel)))
+                .cloned();
+            if let Some(coord) = maybe_coord {
+                return self.take(&coord);
+            }
+        }
+        loop {
+            let (level, maybe_coord) = match self.levels.iter().next() {
+                None => return None,
+                Some((&level, index)) => (level, index.nearest(anchor)),
+            };
+            match maybe_coord {
+                Some(coord) => return self.take(&coord),
+                None => { self.levels.remove(&level); },
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.towers.is_empty()
+    }
+
+    // undoes `take`, `coord` and `level` being what `new` would give the tower
+    fn put_back(&mut self, coord: Coord, level: M, tower: Region) {
+        self.towers.insert(coord, tower);
+        self.levels.entry(level).or_insert_with(|| CoordIndex::build(Vec::new())).insert(coord);
+        self.columns.entry((tower.min.x, tower.min.z)).or_insert_with(Vec::new).push(coord);
+    }
+
+    fn take(&mut self, coord: &Coord) -> Option<Region> {
+        let tower = self.towers.remove(coord)?;
+        for index in self.levels.values_mut() {
+            if index.remove(coord) {
+                break;
+            }
+        }
+        if let Some(coords) = self.columns.get_mut(&(tower.min.x, tower.min.z)) {
+            coords.retain(|c| c != coord);
+        }
+        Some(tower)
+    }
+}
+
+// Fill towers, one pool per target component in `per_component` mode (a tower is a vertical
+// run of filled voxels so it always belongs to a single component), otherwise a single pool.
+struct FillJobs {
+    pools: Vec<TowerJobs>,
+    assigned: HashMap<Bid, usize>,
+}
+
+impl FillJobs {
+    fn new(env: &Env, towers: Vec<Region>) -> FillJobs {
+        let pools_towers: Vec<Vec<Region>> = if env.components.is_empty() {
+            vec![towers]
+        } else {
+            let component_of: HashMap<Coord, usize> = env.components.iter()
+                .enumerate()
+                .flat_map(|(index, voxels)| voxels.iter().map(move |&voxel| (voxel, index)))
+                .collect();
+            let mut pools_towers = vec![Vec::new(); env.components.len()];
+            for tower in towers {
+                pools_towers[component_of[&tower.min]].push(tower);
+            }
+            pools_towers
+        };
+        FillJobs {
+            pools: pools_towers.into_iter()
+                .map(|towers| TowerJobs::new(
+                    towers,
+                    |tower| Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, },
+                    |tower| tower.min.y,
+                ))
+                .collect(),
+            assigned: HashMap::new(),
+        }
+    }
+
+    // a bot sticks to its component, a new one gets the least crowded component with jobs left;
+    // when its own component is done it helps with the others
+    fn own_pool(&mut self, bid: Bid) -> Option<usize> {
+        if self.pools.len() == 1 {
+            return Some(0);
+        }
+        match self.assigned.get(&bid).cloned() {
+            Some(index) if !self.pools[index].is_empty() =>
+                Some(index),
+            _ => {
+                let assigned = &self.assigned;
+                let index = (0 .. self.pools.len())
+                    .filter(|&index| !self.pools[index].is_empty())
+                    .min_by_key(|&index| (assigned.values().filter(|&&a| a == index).count(), index))?;
+                self.assigned.insert(bid, index);
+                Some(index)
+            },
+        }
+    }
+
+    // Takes the nearest tower from the bot's component (skipping the towers already done), but
+    // passes over the towers whose base would not be grounded yet and puts them back into the same
+    // component for later, unless there is nothing else left there or the model is ungrounded
+    // already. The component is picked once up front, so towers put off do not make it look done.
+    // Returns the tower along with how many were put back.
+    fn take_groundable<FS, FG>(
+        &mut self,
+        bid: Bid,
+        anchor: &Coord,
+        ungrounded_voxel: Option<Coord>,
+        is_satisfied: FS,
+        is_groundable: FG,
+    )
+        -> (Option<Region>, usize) where
+        FS: Fn(&Coord) -> bool,
+        FG: Fn(&Coord) -> bool,
+    {
+        let own = match self.own_pool(bid) {
+            None => return (None, 0),
+            Some(own) => own,
+        };
+        let mut deferred = Vec::new();
+        let job = loop {
+            match self.pools[own].take_nearest(anchor, ungrounded_voxel) {
+                None => break None,
+                Some(tower) => if let Some(tower) = trim_satisfied(tower, &is_satisfied) {
+                    if ungrounded_voxel.is_some() || is_groundable(&tower.min) {
+                        break Some(tower);
+                    }
+                    deferred.push(tower);
+                },
+            }
+        };
+        let job = job.or_else(|| if deferred.is_empty() { None } else { Some(deferred.remove(0)) });
+        let deferred_count = deferred.len();
+        for tower in deferred {
+            self.put_back(own, tower);
+        }
+        (job, deferred_count)
+    }
+
+    fn put_back(&mut self, pool: usize, tower: Region) {
+        let coord = Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, };
+        self.pools[pool].put_back(coord, tower.min.y, tower);
+    }
+}
+
+// Shrinks a vertical tower past its already satisfied ends, `None` when nothing is left to do.
+fn trim_satisfied<F>(mut tower: Region, is_satisfied: F) -> Option<Region> where F: Fn(&Coord) -> bool {
+    while tower.min.y <= tower.max.y && is_satisfied(&tower.min) {
+        tower.min.y += 1;
+    }
+    while tower.min.y <= tower.max.y && is_satisfied(&tower.max) {
+        tower.max.y -= 1;
+    }
+    if tower.min.y <= tower.max.y {
+        Some(tower)
+    } else {
+        None
+    }
+}
+
+fn make_towers(model: &Matrix) -> Vec<Region> {
+    let dim = model.dim() as isize;
+    let mut regions = Vec::new();
+    for x in 0 .. dim {
+        for z in 0 .. dim {
+            let mut current_reg: Option<Region> = None;
+            for y in 0 .. dim {
+                let p = Coord { x, y, z, };
+                if model.is_filled(&p) {
+                    if let Some(ref mut reg) = current_reg {
+                        reg.max = p;
+                    } else {
+                        current_reg = Some(Region { min: p, max: p, });
+                    }
+                } else if let Some(reg) = current_reg {
+                    regions.push(reg);
+                    current_reg = None;
+                }
+            }
+            if let Some(reg) = current_reg {
+                regions.push(reg);
+            }
+        }
+    }
+    regions
+}
+
+fn pick_random_coord<R>(bounds: &Region, rng: &mut R) -> Coord where R: Rng {
+    Coord {
+        x: rng.gen_range(bounds.min.x, bounds.max.x + 1),
+        y: rng.gen_range(bounds.min.y, bounds.max.y + 1),
+        z: rng.gen_range(bounds.min.z, bounds.max.z + 1),
+    }
+}
+
+// halvings of the wander budget after `failures` wander routes in a row have failed
+fn wander_backoff_level(config: &Config, failures: usize) -> usize {
+    if config.wander_backoff_after == 0 {
+        0
+    } else {
+        cmp::min(failures / config.wander_backoff_after, 16)
+    }
+}
+
+fn wander_rtt_limit(config: &Config, failures: usize) -> usize {
+    let limit = config.rtt_wander_limit >> wander_backoff_level(config, failures);
+    cmp::max(limit, cmp::min(WANDER_RTT_MIN, config.rtt_wander_limit))
+}
+
+fn pick_wander_target<R>(env: &Env, pos: &Coord, failures: usize, rng: &mut R) -> Coord where R: Rng {
+    let level = wander_backoff_level(&env.config, failures);
+    if level > 0 {
+        let bounds = env.work_bounds;
+        let span = cmp::max(bounds.max.x - bounds.min.x, cmp::max(bounds.max.y - bounds.min.y, bounds.max.z - bounds.min.z));
+        let radius = cmp::max(span >> level, 1);
+        let nearby = Region::around(pos, radius).intersection(&bounds);
+        if let Some(nearby) = nearby.filter(|nearby| nearby.contents_count() > 1) {
+            loop {
+                let target = pick_random_coord(&nearby, rng);
+                if target != *pos {
+                    return target;
+                }
+            }
+        }
+    }
+    pick_random_coord(&env.work_bounds, rng)
+}
+
+fn route_and_step<FP, R>(
+    start: &Coord,
+    finish: &Coord,
+    current_model: &Matrix,
+    is_passable: FP,
+    commands_buf: &mut Vec<(Coord, BotCommand)>,
+    config: &Config,
+    rtt_limit: usize,
+    rng: &mut R,
+)
+    -> Result<Option<BotCommand>, Error> where
     FP: Fn(&Region) -> bool,
     R: Rng,
 {
-    let maybe_route = rtt::plan_route_rng(
-        start,
-        finish,
-        current_model.dim(),
-        is_passable,
-        rtt_limit,
-        rng,
-    );
+    let maybe_route = match config.router {
+        RouterKind::Rtt =>
+            RttBackend { max_iters: rtt_limit, }.route(start, finish, current_model, is_passable, rng),
+        RouterKind::Astar =>
+            AstarBackend.route(start, finish, current_model, is_passable, rng),
+        RouterKind::Hybrid { astar_max_distance, } =>
+            HybridBackend { max_iters: rtt_limit, astar_max_distance, }.route(start, finish, current_model, is_passable, rng),
+    };
     Ok(if let Some(route) = maybe_route {
         rtt::plan_route_commands(&route, commands_buf);
         if commands_buf.is_empty() {
@@ -628,6 +1824,173 @@ fn route_and_step<FP, R>(
     })
 }
 
+// Like `route_and_step`, but the route goes around the cells other bots have reserved and is
+// reserved in turn (its end for `window` more ticks, as the bot is going to work there).
+fn route_cooperative<FP>(
+    bid: Bid,
+    start: &Coord,
+    finish: &Coord,
+    current_model: &Matrix,
+    is_passable: FP,
+    reservations: &mut ReservationTable,
+    tick: usize,
+    window: usize,
+)
+    -> Option<BotCommand> where
+    FP: Fn(&Region) -> bool,
+{
+    reservations.release(bid);
+    let maybe_moves = coop::plan_route_cooperative(
+        start,
+        finish,
+        bid,
+        tick,
+        window,
+        COOPERATIVE_EXPANSIONS_MAX,
+        current_model.dim(),
+        &is_passable,
+        |region: &Region| !current_model.contains_filled(region),
+        reservations,
+    );
+    let mut moves = maybe_moves?;
+    if let Some(lmove) = lmove_lookahead(&moves, &is_passable, reservations, tick, bid) {
+        moves.remove(1);
+        moves[1].cmd_performed = Some(lmove);
+    }
+    reservations.reserve_route(bid, tick, &moves, window);
+    Some(moves.get(1)
+         .and_then(|mv| mv.cmd_performed)
+         .unwrap_or(BotCommand::Wait))
+}
+
+// The first two legs of a route as one LMove this tick: both have to be SMoves of at most 5 along
+// different axes, and the second one has to be free already now rather than a tick later.
+fn lmove_lookahead<FP>(
+    moves: &[astar::Move],
+    is_passable: FP,
+    reservations: &ReservationTable,
+    tick: usize,
+    bid: Bid,
+)
+    -> Option<BotCommand> where
+    FP: Fn(&Region) -> bool,
+{
+    if moves.len() < 3 {
+        return None;
+    }
+    let (long1, long2) = match (moves[1].cmd_performed, moves[2].cmd_performed) {
+        (Some(BotCommand::SMove { long: long1, }), Some(BotCommand::SMove { long: long2, })) =>
+            (long1, long2),
+        _ =>
+            return None,
+    };
+    if long1.axis() == long2.axis() {
+        return None;
+    }
+    let (short1, short2) = pair_into_lmove(long1, long2)?;
+    let second_leg = Region::from_corners(&moves[1].coord, &moves[2].coord);
+    if is_passable(&second_leg) && reservations.is_free(&second_leg, tick, bid) {
+        Some(BotCommand::LMove { short1, short2, })
+    } else {
+        None
+    }
+}
+
+// Takes the bots home from wherever they are: the one closest to the origin goes there, the rest
+// come one by one next to it and fuse, then the last one halts. Only one bot acts in a tick while
+// the others wait, so nothing can interfere. The model must be grounded (harmonics are low). Bots
+// with no way home are left in `nanobots`, which is empty once the script halts.
+fn finalize(nanobots: &mut Vec<Nanobot>, current_model: &Matrix) -> Vec<BotCommand> {
+    let mut script = Vec::new();
+    if nanobots.is_empty() {
+        return script;
+    }
+    nanobots.sort_by_key(|nanobot| nanobot.bid);
+    let dim = current_model.dim() as M;
+
+    let push_tick = |script: &mut Vec<BotCommand>, nanobots: &[Nanobot], acting: &[(Bid, BotCommand)]| {
+        for nanobot in nanobots.iter() {
+            script.push(acting.iter()
+                .find(|&&(bid, _)| bid == nanobot.bid)
+                .map(|&(_, cmd)| cmd)
+                .unwrap_or(BotCommand::Wait));
+        }
+    };
+    let route = |nanobots: &[Nanobot], bid: Bid, finish: Coord| {
+        let start = nanobots.iter().find(|nanobot| nanobot.bid == bid).unwrap().bot.pos;
+        astar::plan_route_passable(&start, &finish, current_model.dim(), |region| {
+            !current_model.contains_filled(region) &&
+                nanobots.iter().all(|other| other.bid == bid || !region.contains(&other.bot.pos))
+        }).map(|(moves, _cost)| moves.into_iter().filter_map(|mv| mv.cmd_performed).collect::<Vec<_>>())
+    };
+    let by_distance = |nanobots: &[Nanobot], coord: Coord| {
+        let mut bids: Vec<(usize, Bid)> = nanobots.iter()
+            .map(|nanobot| (nanobot.bot.pos.diff(&coord).l_1_norm(), nanobot.bid))
+            .collect();
+        bids.sort();
+        bids.into_iter().map(|(_, bid)| bid).collect::<Vec<_>>()
+    };
+
+    let lead = by_distance(nanobots, INIT_POS).into_iter()
+        .filter_map(|bid| route(nanobots, bid, INIT_POS).map(|moves| (bid, moves)))
+        .next();
+    let lead_bid = match lead {
+        Some((bid, moves)) => {
+            for cmd in moves {
+                push_tick(&mut script, nanobots, &[(bid, cmd)]);
+            }
+            bid
+        },
+        None =>
+            return script,
+    };
+    for nanobot in nanobots.iter_mut().filter(|nanobot| nanobot.bid == lead_bid) {
+        nanobot.bot.pos = INIT_POS;
+    }
+
+    let slots: Vec<Coord> = iproduct!(0 .. 2, 0 .. 2, 0 .. 2)
+        .map(|(x, y, z)| Coord { x, y, z, })
+        .filter(|slot| slot.diff(&INIT_POS).is_near() && slot.x < dim && slot.y < dim && slot.z < dim)
+        .filter(|slot| !current_model.is_filled(slot))
+        .collect();
+    while nanobots.len() > 1 {
+        // the closest bot with a way to a free slot next to the lead
+        let mut secondary = None;
+        'search: for bid in by_distance(nanobots, INIT_POS).into_iter().filter(|&bid| bid != lead_bid) {
+            for &slot in slots.iter() {
+                if nanobots.iter().any(|other| other.bid != bid && other.bot.pos == slot) {
+                    continue;
+                }
+                if let Some(moves) = route(nanobots, bid, slot) {
+                    secondary = Some((bid, slot, moves));
+                    break 'search;
+                }
+            }
+        }
+        let (bid, slot, moves) = match secondary {
+            Some(secondary) => secondary,
+            None => return script,
+        };
+        for cmd in moves {
+            push_tick(&mut script, nanobots, &[(bid, cmd)]);
+        }
+        push_tick(&mut script, nanobots, &[
+            (lead_bid, BotCommand::FusionP { near: slot.diff(&INIT_POS), }),
+            (bid, BotCommand::FusionS { near: INIT_POS.diff(&slot), }),
+        ]);
+        let index = nanobots.iter().position(|nanobot| nanobot.bid == bid).unwrap();
+        let fused = nanobots.remove(index);
+        for nanobot in nanobots.iter_mut().filter(|nanobot| nanobot.bid == lead_bid) {
+            nanobot.bot.seeds.push(fused.bid);
+            nanobot.bot.seeds.extend(fused.bot.seeds.iter().cloned());
+            nanobot.bot.seeds.sort();
+        }
+    }
+    script.push(BotCommand::Halt);
+    nanobots.clear();
+    script
+}
+
 #[cfg(test)]
 mod test {
     use super::super::super::{
@@ -645,8 +2008,60 @@ mod test {
     };
     use super::{
         Nanobot,
+        TowerJobs,
     };
 
+    #[test]
+    fn cooperative_short_l_turn_is_one_lmove() {
+        let model = Matrix::new(Resolution(8));
+        let start = Coord { x: 1, y: 0, z: 1, };
+        let finish = Coord { x: 4, y: 0, z: 3, };
+        let mut reservations = super::ReservationTable::new();
+        let cmd = super::route_cooperative(2, &start, &finish, &model, |_: &Region| true, &mut reservations, 5, 4);
+        match cmd {
+            Some(BotCommand::LMove { short1, short2, }) =>
+                assert_eq!(start.add(short1.to_coord_diff()).add(short2.to_coord_diff()), finish),
+            other =>
+                panic!("expected an LMove, got {:?}", other),
+        }
+        // the bot is there after this tick and stays for the window
+        let at_finish = Region { min: finish, max: finish, };
+        assert!(!reservations.is_free(&at_finish, 5, 1));
+        assert!(!reservations.is_free(&at_finish, 9, 1));
+    }
+
+    #[test]
+    fn lmove_lookahead_needs_free_second_leg() {
+        let smove = |coord, axis, value| super::astar::Move {
+            coord,
+            cmd_performed: Some(BotCommand::SMove { long: LinearCoordDiff::Long { axis, value, }, }),
+        };
+        let route = vec![
+            super::astar::Move { coord: Coord { x: 1, y: 0, z: 1, }, cmd_performed: None, },
+            smove(Coord { x: 4, y: 0, z: 1, }, Axis::X, 3),
+            smove(Coord { x: 4, y: 0, z: 3, }, Axis::Z, 2),
+        ];
+        let mut reservations = super::ReservationTable::new();
+        assert_eq!(
+            super::lmove_lookahead(&route, |_: &Region| true, &reservations, 5, 2),
+            Some(BotCommand::LMove {
+                short1: LinearCoordDiff::Short { axis: Axis::X, value: 3, },
+                short2: LinearCoordDiff::Short { axis: Axis::Z, value: 2, },
+            }),
+        );
+        // the second leg is only free a tick later
+        assert_eq!(super::lmove_lookahead(&route, |region: &Region| region.min.z == region.max.z, &reservations, 5, 2), None);
+        let other = [super::astar::Move { coord: Coord { x: 4, y: 0, z: 2, }, cmd_performed: None, }];
+        reservations.reserve_route(1, 5, &other, 1);
+        assert_eq!(super::lmove_lookahead(&route, |_: &Region| true, &reservations, 5, 2), None);
+        assert!(super::lmove_lookahead(&route, |_: &Region| true, &reservations, 6, 2).is_some());
+        // neither a long leg nor a straight line
+        let long = vec![route[0], smove(Coord { x: 7, y: 0, z: 1, }, Axis::X, 6), smove(Coord { x: 7, y: 0, z: 3, }, Axis::Z, 2)];
+        assert_eq!(super::lmove_lookahead(&long, |_: &Region| true, &super::ReservationTable::new(), 5, 2), None);
+        let straight = vec![route[0], route[1], smove(Coord { x: 6, y: 0, z: 1, }, Axis::X, 2)];
+        assert_eq!(super::lmove_lookahead(&straight, |_: &Region| true, &super::ReservationTable::new(), 5, 2), None);
+    }
+
     #[test]
     fn nanobot_init_bot() {
         assert_eq!(
@@ -712,66 +2127,245 @@ mod test {
     }
 
     #[test]
-    fn solve_empty() {
-        let source_model = Matrix::from_iter(Resolution(3), vec![]);
-        let target_model = Matrix::from_iter(Resolution(3), vec![]);
-        let script = super::solve(source_model, target_model, super::Config {
-            init_bots: vec![],
-            rtt_limit: 64,
-            route_attempts_limit: 16,
-            global_ticks_limit: 100,
-            max_spawns: 1,
-        }).unwrap();
-        assert_eq!(script, vec![BotCommand::Halt]);
+    fn wander_targets_within_work_bounds() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(20), vec![Coord { x: 5, y: 0, z: 5, }]);
+        let target_model = Matrix::from_iter(Resolution(20), vec![Coord { x: 7, y: 3, z: 6, }, Coord { x: 18, y: 0, z: 4, }]);
+        let bounds = super::default_work_bounds(&source_model, &target_model);
+        assert_eq!(bounds, Region {
+            min: Coord { x: 3, y: 0, z: 2, },
+            max: Coord { x: 19, y: 5, z: 8, },
+        });
+        for _ in 0 .. 1000 {
+            assert!(bounds.contains(&super::pick_random_coord(&bounds, &mut rng)));
+        }
+
+        let empty = Matrix::new(Resolution(20));
+        assert_eq!(super::default_work_bounds(&empty, &empty), Region {
+            min: Coord { x: 0, y: 0, z: 0, },
+            max: Coord { x: 19, y: 19, z: 19, },
+        });
     }
 
     #[test]
-    fn solve_move_and_halt() {
+    fn work_bounds_checked() {
         use rand::{SeedableRng, prng::XorShiftRng};
         let mut rng: XorShiftRng =
             SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-        let source_model = Matrix::from_iter(Resolution(3), vec![]);
-        let target_model = Matrix::from_iter(Resolution(3), vec![]);
-        let script = super::solve_rng(
-            source_model,
-            target_model,
-            super::Config {
-                init_bots: vec![(1, Bot { pos: Coord { x: 1, y: 0, z: 0, }, seeds: vec![], })],
-                rtt_limit: 64,
-                route_attempts_limit: 16,
-                global_ticks_limit: 100,
-                max_spawns: 1,
-            },
-            &mut rng,
-        ).unwrap();
+        let source_model = Matrix::from_iter(Resolution(10), vec![Coord { x: 2, y: 0, z: 2, }]);
+        let target_model = Matrix::from_iter(Resolution(10), vec![Coord { x: 5, y: 1, z: 3, }]);
+        let checked = |work_bounds| super::checked_work_bounds(
+            &source_model,
+            &target_model,
+            &super::Config { work_bounds, ..super::Config::default() },
+        );
+        assert_eq!(checked(None), Ok(super::default_work_bounds(&source_model, &target_model)));
+        // inverted corners are put in order, the part outside of the matrix is cut off
         assert_eq!(
-            script,
-            vec![
-                BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::X, value: -1 } },
-                BotCommand::Halt
-            ],
+            checked(Some(Region { min: Coord { x: 12, y: 4, z: 6, }, max: Coord { x: 1, y: -3, z: 0, }, })),
+            Ok(Region { min: Coord { x: 1, y: 0, z: 0, }, max: Coord { x: 9, y: 4, z: 6, }, }),
         );
+        // the target voxel at x = 5 is left out
+        let models = Region { min: Coord { x: 2, y: 0, z: 2, }, max: Coord { x: 5, y: 1, z: 3, }, };
+        let work_bounds = Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 4, y: 4, z: 4, }, };
+        assert_eq!(checked(Some(work_bounds)), Err(super::Error::WorkBoundsMissModels { work_bounds, models, }));
+
+        // and the solver refuses to start with them rather than crop the target voxel away
+        let result = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config { work_bounds: Some(work_bounds), crop_to_models: true, ..super::Config::default() },
+            &mut rng,
+        );
+        assert_eq!(result, Err((super::Error::WorkBoundsMissModels { work_bounds, models, }, vec![])));
     }
 
     #[test]
-    fn solve_fusion_2_and_halt() {
+    fn wander_backoff_spends_fewer_rtt_samples() {
+        use std::cell::Cell;
         use rand::{SeedableRng, prng::XorShiftRng};
-        let mut rng: XorShiftRng =
-            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-        let source_model = Matrix::from_iter(Resolution(3), vec![]);
-        let target_model = Matrix::from_iter(Resolution(3), vec![]);
-        let script = super::solve_rng(
-            source_model,
-            target_model,
-            super::Config {
+        // a bot jammed in a crowd: nothing longer than a voxel is passable, so every wander route fails
+        // and burns its samples, each of them checking at least one edge path
+        let checks_spent = |wander_backoff_after| {
+            let mut rng = XorShiftRng::seed_from_u64(17);
+            let model = Matrix::new(Resolution(16));
+            let env = super::Env::new(model.clone(), model.clone(), super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                rtt_wander_limit: 64,
+                wander_backoff_after,
+                ..super::Config::default()
+            });
+            let nanobot = super::Nanobot {
+                bid: 1,
+                bot: Bot { pos: Coord { x: 8, y: 8, z: 8, }, seeds: vec![], },
+                plan: super::Plan::HeadingFor { target: Coord { x: 15, y: 15, z: 15, }, attempts: 0, goal: super::Goal::Wander, },
+            };
+            let mut void_towers = super::TowerJobs::new(vec![], |tower| tower.max, |tower| -tower.min.y);
+            let mut fill_towers = super::FillJobs::new(&env, vec![]);
+            let checks = Cell::new(0);
+            let result = nanobot.implement_plan(
+                &env,
+                &model,
+                super::WorkState::InProgress,
+                None,
+                1,
+                |region: &Region| {
+                    checks.set(checks.get() + 1);
+                    region.min == region.max
+                },
+                &mut Vec::new(),
+                &mut void_towers,
+                &mut fill_towers,
+                &mut super::ReservationTable::new(),
+                0,
+                &mut super::SolveStats::default(),
+                &mut rng,
+            );
+            match result {
+                super::PlanResult::Error(super::Error::RouteAttempsLimitExceeded { .. }) => (),
+                _ => panic!("expected the route attempts limit exceeded"),
+            }
+            checks.get()
+        };
+        let full = checks_spent(0);
+        let backed_off = checks_spent(4);
+        assert!(backed_off * 3 < full * 2, "{} passable checks with backoff vs {} without", backed_off, full);
+    }
+
+    #[test]
+    fn wander_backoff_limits() {
+        let model = Matrix::new(Resolution(16));
+        let mut config = super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 100,
+            rtt_wander_limit: 128,
+            ..super::Config::default()
+        };
+        assert_eq!((0 .. 40).map(|failures| super::wander_rtt_limit(&config, failures)).max(), Some(128));
+        config.wander_backoff_after = 3;
+        let limits: Vec<_> = [0, 2, 3, 5, 6, 9, 12, 15, 40].iter()
+            .map(|&failures| super::wander_rtt_limit(&config, failures))
+            .collect();
+        assert_eq!(limits, vec![128, 128, 64, 64, 32, 16, 8, 8, 8]);
+
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng = XorShiftRng::seed_from_u64(5);
+        let env = super::Env::new(model.clone(), model, config);
+        let pos = Coord { x: 2, y: 3, z: 4, };
+        for _ in 0 .. 100 {
+            // work bounds span 15, so after 9 failures (3 halvings) targets are at most a voxel away per axis
+            let target = super::pick_wander_target(&env, &pos, 9, &mut rng);
+            assert!(target != pos);
+            assert!(target.diff(&pos).l_inf_norm() <= 1);
+        }
+    }
+
+    #[test]
+    fn tower_jobs_take_nearest() {
+        let column = |x, z, min_y, max_y| Region {
+            min: Coord { x, y: min_y, z, },
+            max: Coord { x, y: max_y, z, },
+        };
+        let mut jobs = TowerJobs::new(
+            vec![column(0, 0, 0, 2), column(5, 5, 0, 0), column(3, 3, 1, 1), column(5, 5, 2, 4), column(9, 9, 0, 1)],
+            |tower| Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, },
+            |tower| tower.min.y,
+        );
+        let anchor = Coord { x: 6, y: 0, z: 6, };
+        // a tower with the ungrounded voxel goes first, even from a higher level
+        assert_eq!(jobs.take_nearest(&anchor, Some(Coord { x: 5, y: 3, z: 5, })), Some(column(5, 5, 2, 4)));
+        assert_eq!(jobs.take_nearest(&anchor, Some(Coord { x: 5, y: 3, z: 5, })), Some(column(5, 5, 0, 0)));
+        assert_eq!(jobs.take_nearest(&anchor, None), Some(column(9, 9, 0, 1)));
+        assert_eq!(jobs.take_nearest(&anchor, None), Some(column(0, 0, 0, 2)));
+        assert_eq!(jobs.take_nearest(&anchor, None), Some(column(3, 3, 1, 1)));
+        assert_eq!(jobs.take_nearest(&anchor, None), None);
+    }
+
+    #[test]
+    fn tower_jobs_tie_break() {
+        let column = |x, z| Region {
+            min: Coord { x, y: 0, z, },
+            max: Coord { x, y: 0, z, },
+        };
+        // all four are at the same distance from the anchor, in any input order
+        let towers = vec![column(4, 2), column(2, 4), column(0, 2), column(2, 0)];
+        for shift in 0 .. towers.len() {
+            let mut rotated = towers.clone();
+            rotated.rotate_left(shift);
+            let mut jobs = TowerJobs::new(
+                rotated,
+                |tower| Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, },
+                |tower| tower.min.y,
+            );
+            let anchor = Coord { x: 2, y: 1, z: 2, };
+            let order: Vec<_> = (0 .. 4).map(|_| jobs.take_nearest(&anchor, None).unwrap()).collect();
+            assert_eq!(order, vec![column(0, 2), column(2, 0), column(2, 4), column(4, 2)]);
+        }
+    }
+
+    #[test]
+    fn solve_empty() {
+        let source_model = Matrix::from_iter(Resolution(3), vec![]);
+        let target_model = Matrix::from_iter(Resolution(3), vec![]);
+        let script = super::solve(source_model, target_model, super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 100,
+            rtt_wander_limit: 64,
+            ..super::Config::default()
+        }).unwrap();
+        assert_eq!(script, vec![BotCommand::Halt]);
+    }
+
+    #[test]
+    fn solve_move_and_halt() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(3), vec![]);
+        let target_model = Matrix::from_iter(Resolution(3), vec![]);
+        let script = super::solve_rng(
+            source_model,
+            target_model,
+            super::Config {
+                init_bots: vec![(1, Bot { pos: Coord { x: 1, y: 0, z: 0, }, seeds: vec![], })],
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(
+            script,
+            vec![
+                BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::X, value: -1 } },
+                BotCommand::Halt
+            ],
+        );
+    }
+
+    #[test]
+    fn solve_fusion_2_and_halt() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(3), vec![]);
+        let target_model = Matrix::from_iter(Resolution(3), vec![]);
+        let script = super::solve_rng(
+            source_model,
+            target_model,
+            super::Config {
                 init_bots: vec![
                     (1, Bot { pos: Coord { x: 0, y: 1, z: 1, }, seeds: vec![], }),
                     (2, Bot { pos: Coord { x: 2, y: 2, z: 2, }, seeds: vec![], }),
                     ],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
@@ -815,9 +2409,9 @@ mod test {
                     (3, Bot { pos: Coord { x: 0, y: 2, z: 0, }, seeds: vec![], }),
                     ],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
@@ -832,20 +2426,25 @@ mod test {
                     short1: LinearCoordDiff::Short { axis: Axis::Y, value: -2 },
                     short2: LinearCoordDiff::Short { axis: Axis::Z, value: -2 },
                 },
+                // bot 2 has reserved (1, 0, 0) as its landing spot, so bot 3 parks at (0, 0, 1) instead
                 BotCommand::LMove {
-                    short1: LinearCoordDiff::Short { axis: Axis::X, value: 1 },
-                    short2: LinearCoordDiff::Short { axis: Axis::Y, value: -2 },
+                    short1: LinearCoordDiff::Short { axis: Axis::Z, value: 2 },
+                    short2: LinearCoordDiff::Short { axis: Axis::Y, value: -1 },
                 },
 
-                BotCommand::FusionP { near: CoordDiff(Coord { x: 1, y: 0, z: 0 }) },
+                BotCommand::Wait,
+                BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::X, value: -1 } },
                 BotCommand::LMove {
-                    short1: LinearCoordDiff::Short { axis: Axis::Y, value: 1 },
-                    short2: LinearCoordDiff::Short { axis: Axis::X, value: -1 },
+                    short1: LinearCoordDiff::Short { axis: Axis::Z, value: -1 },
+                    short2: LinearCoordDiff::Short { axis: Axis::Y, value: -1 },
                 },
-                BotCommand::FusionS { near: CoordDiff(Coord { x: -1, y: 0, z: 0 }) },
 
-                BotCommand::FusionP { near: CoordDiff(Coord { x: 1, y: 1, z: 0 }) },
-                BotCommand::FusionS { near: CoordDiff(Coord { x: -1, y: -1, z: 0 }) },
+                BotCommand::FusionP { near: CoordDiff(Coord { x: 0, y: 0, z: 1 }) },
+                BotCommand::Wait,
+                BotCommand::FusionS { near: CoordDiff(Coord { x: 0, y: 0, z: -1 }) },
+
+                BotCommand::FusionP { near: CoordDiff(Coord { x: 1, y: 0, z: 0 }) },
+                BotCommand::FusionS { near: CoordDiff(Coord { x: -1, y: 0, z: 0 }) },
 
                 BotCommand::Halt
             ],
@@ -863,11 +2462,10 @@ mod test {
             source_model,
             target_model,
             super::Config {
-                init_bots: vec![],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
@@ -906,11 +2504,10 @@ mod test {
             source_model,
             target_model,
             super::Config {
-                init_bots: vec![],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
@@ -952,11 +2549,10 @@ mod test {
             source_model,
             target_model,
             super::Config {
-                init_bots: vec![],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
@@ -1005,19 +2601,1246 @@ mod test {
             source_model,
             target_model,
             super::Config {
-                init_bots: vec![],
                 rtt_limit: 64,
-                route_attempts_limit: 16,
                 global_ticks_limit: 100,
-                max_spawns: 1,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        // the common (1, 0, 1) and (1, 1, 1) are kept: only the top is voided and the arms are filled
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 4);
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Void { .. } = cmd { true } else { false }).count(), 1);
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+    }
+
+    #[test]
+    fn solve_swap_reassembly_order() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let source_model = Matrix::from_iter(Resolution(5), (0 .. 2).map(|y| Coord { x: 1, y, z: 1, }));
+        let target_model = Matrix::from_iter(Resolution(5), (0 .. 2).map(|y| Coord { x: 3, y, z: 3, }));
+        let solve = |reassembly_order| {
+            let mut rng: XorShiftRng =
+                SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            let script = super::solve_rng(
+                source_model.clone(),
+                target_model.clone(),
+                super::Config {
+                    rtt_limit: 64,
+                    global_ticks_limit: 200,
+                    rtt_wander_limit: 64,
+                    reassembly_order,
+                    ..super::Config::default()
+                },
+                &mut rng,
+            ).unwrap();
+            let report = simulate(source_model.clone(), target_model.clone(), &script);
+            assert_eq!(report.failure, None);
+            assert!(report.success);
+            let fills: Vec<_> = script.iter().enumerate()
+                .filter(|&(_, cmd)| if let BotCommand::Fill { .. } = cmd { true } else { false })
+                .map(|(index, _)| index)
+                .collect();
+            let voids: Vec<_> = script.iter().enumerate()
+                .filter(|&(_, cmd)| if let BotCommand::Void { .. } = cmd { true } else { false })
+                .map(|(index, _)| index)
+                .collect();
+            assert_eq!((fills.len(), voids.len()), (2, 2));
+            (fills, voids)
+        };
+
+        let (fills, voids) = solve(super::ReassemblyOrder::VoidFirst);
+        assert!(voids[voids.len() - 1] < fills[0]);
+        let (fills, voids) = solve(super::ReassemblyOrder::FillFirst);
+        assert!(fills[fills.len() - 1] < voids[0]);
+    }
+
+    #[test]
+    fn spawn_slots_along_x() {
+        let target_model = Matrix::from_iter(Resolution(12), (2 .. 10).map(|x| Coord { x, y: 0, z: 5, }));
+        assert_eq!(super::spawn_slots(&target_model, 3), vec![
+            Coord { x: 3, y: 1, z: 5, },
+            Coord { x: 6, y: 1, z: 5, },
+            Coord { x: 8, y: 1, z: 5, },
+        ]);
+        assert_eq!(super::spawn_slots(&Matrix::new(Resolution(12)), 3), vec![]);
+    }
+
+    #[test]
+    fn solve_spread_spawns_visit_slots() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::new(Resolution(12));
+        let target_model = Matrix::from_iter(Resolution(12), (2 .. 10).map(|x| Coord { x, y: 0, z: 5, }));
+        let script = super::solve_rng(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 300,
+                max_spawns: 3,
+                nearest_jobs: true,
+                rtt_wander_limit: 64,
+                spread_spawns: true,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+
+        let mut state = State::new(source_model, vec![]).with_event_log();
+        state.run_mut(script).unwrap();
+        assert_eq!(state.matrix, target_model);
+        let slots = super::spawn_slots(&target_model, 3);
+        for (index, slot) in slots.iter().enumerate() {
+            let bid = index + 1;
+            assert!(state.events().iter().flat_map(|tick| tick.bots.iter()).any(|event| event.bid == bid && event.pos == Some(*slot)));
+        }
+    }
+
+    #[test]
+    fn solve_within_auto_ticks_limit() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::new(Resolution(12));
+        let target_model = Matrix::from_iter(Resolution(12), (2 .. 10).map(|x| Coord { x, y: 0, z: 5, }));
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 1,
+                max_spawns: 3,
+                nearest_jobs: true,
+                rtt_wander_limit: 64,
+                auto_tick_limit: true,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        // 8 voxels for 3 bots in a 12 cube is 36 + 8 * 3 ticks, raised to the minimum
+        assert_eq!(super::auto_ticks_limit(&source_model, &target_model, 3), super::AUTO_TICKS_MIN);
+        assert!(stats.ticks <= super::AUTO_TICKS_MIN);
+        assert_eq!(stats.coverage_ratio, 1.0);
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+
+        let empty = Matrix::new(Resolution(30));
+        let cube = Matrix::from_iter(Resolution(30), (0 .. 1000).map(|i| Coord { x: i % 10, y: i / 100, z: (i / 10) % 10, }));
+        assert_eq!(super::auto_ticks_limit(&empty, &cube, 1), 90 + 8 * 1000);
+        assert_eq!(super::auto_ticks_limit(&cube, &empty, 40), 90 + 8 * 25);
+        assert_eq!(super::auto_ticks_limit(&empty, &cube, 100), 90 + 8 * 25);
+    }
+
+    #[test]
+    fn solve_shifted_wall_keeps_common_voxels() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let wall = |x_min| iproduct!(x_min .. x_min + 3, 0 .. 3).map(|(x, y)| Coord { x, y, z: 1, }).collect::<Vec<_>>();
+        let source_model = Matrix::from_iter(Resolution(6), wall(1));
+        let target_model = Matrix::from_iter(Resolution(6), wall(2));
+        let common: Vec<_> = wall(2).into_iter().filter(|voxel| voxel.x < 4).collect();
+        let script = super::solve_rng(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 200,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
             },
             &mut rng,
         ).unwrap();
-        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 6);
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 3);
         assert_eq!(script.iter().filter(|cmd| if let BotCommand::Void { .. } = cmd { true } else { false }).count(), 3);
+
+        // the common voxels stay filled all the way through
+        let mut state = State::new(source_model, vec![]);
+        let mut trace_it = script.into_iter();
+        while !state.is_halt() {
+            state.step_mut(&mut trace_it).unwrap();
+            assert!(common.iter().all(|voxel| state.matrix.is_filled(voxel)));
+        }
+        assert_eq!(state.matrix, target_model);
+    }
+
+    #[test]
+    fn solve_with_progress_reports() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(10), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(10),
+            (1 .. 9).flat_map(|x| (1 .. 9).map(move |z| Coord { x, y: 0, z, })),
+        );
+        // (tick, voxels remaining, energy estimate, to fill, to void)
+        let mut reports = Vec::new();
+        let script = super::solve_with_progress(
+            source_model,
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 1000,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+            Some(&mut |progress: super::Progress| {
+                assert!(progress.voxels_to_fill().all(|voxel| voxel.y == 0));
+                reports.push((
+                    progress.tick,
+                    progress.voxels_remaining,
+                    progress.energy_estimate,
+                    progress.voxels_to_fill().count(),
+                    progress.voxels_to_void().count(),
+                ));
+            }),
+        ).unwrap();
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+        assert!(!reports.is_empty());
+        assert_eq!(reports[0].0, super::PROGRESS_TICKS);
+        assert!(reports[0].1 < 64);
+        assert!(reports[0].2 > 0);
+        assert!(reports.windows(2).all(|w| w[0].1 >= w[1].1));
+        for &(_, voxels_remaining, _, to_fill, to_void) in reports.iter() {
+            assert_eq!(to_void, 0);
+            assert_eq!(to_fill, voxels_remaining);
+        }
+    }
+
+    #[test]
+    fn solve_time_limit_exceeded() {
+        use std::time::Duration;
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let result = super::solve_rng(
+            source_model,
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                time_limit: Some(Duration::from_millis(0)),
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        );
+        match result {
+            Err((super::Error::TimeLimitExceeded { ticks: 1, voxels_to_do: 3, }, script)) =>
+                assert_eq!(script, vec![BotCommand::Halt]),
+            other =>
+                panic!("unexpected solver result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn solve_seeded_is_reproducible() {
+        let make_models = || (
+            Matrix::from_iter(Resolution(4), vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 1, y: 2, z: 1, },
+            ]),
+            Matrix::from_iter(Resolution(4), vec![
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 1, y: 1, z: 1, },
+                Coord { x: 0, y: 1, z: 1, },
+                Coord { x: 2, y: 1, z: 1, },
+                Coord { x: 1, y: 1, z: 0, },
+                Coord { x: 1, y: 1, z: 2, },
+            ]),
+        );
+        let make_config = || super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 100,
+            max_spawns: 4,
+            seed: Some(17),
+            rtt_wander_limit: 64,
+            ..super::Config::default()
+        };
+        let (source_model, target_model) = make_models();
+        let script_a = super::solve(source_model, target_model, make_config())
+            .unwrap_or_else(|(_, script)| script);
+        let (source_model, target_model) = make_models();
+        let script_b = super::solve(source_model, target_model, make_config())
+            .unwrap_or_else(|(_, script)| script);
+        assert!(!script_a.is_empty());
+        assert_eq!(
+            super::super::super::cmd::into_bytes(&script_a).unwrap(),
+            super::super::super::cmd::into_bytes(&script_b).unwrap(),
+        );
+    }
+
+    #[test]
+    fn solve_seeded_overhang_is_reproducible() {
+        // a table: the top is ungrounded until it reaches one of the legs
+        let make_target = || Matrix::from_iter(
+            Resolution(7),
+            iproduct!(1 .. 6, 0 .. 4, 1 .. 6)
+                .filter(|&(x, y, z)| y == 3 || ((x == 1 || x == 5) && (z == 1 || z == 5)))
+                .map(|(x, y, z)| Coord { x, y, z, }));
+        let make_config = || super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 400,
+            max_spawns: 4,
+            seed: Some(5),
+            nearest_jobs: true,
+            rtt_wander_limit: 64,
+            ..super::Config::default()
+        };
+        let scripts: Vec<_> = (0 .. 3)
+            .map(|_| super::solve(Matrix::new(Resolution(7)), make_target(), make_config())
+                 .unwrap_or_else(|(_, script)| script))
+            .collect();
+        assert!(!scripts[0].is_empty());
+        assert_eq!(scripts[0], scripts[1]);
+        assert_eq!(scripts[1], scripts[2]);
+    }
+
+    #[test]
+    fn solve_detailed_fill_tower_stats() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let (script, stats) = super::solve_detailed(
+            source_model,
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(script.len(), 10);
+        assert_eq!(stats, super::SolveStats {
+            ticks: 11,
+            peak_bots: 1,
+            route_retries: 0,
+            voxels_filled: 3,
+            voxels_voided: 0,
+            coverage_ratio: 1.0,
+            recoveries: 0,
+            commands_limit_hit: false,
+            fill_deferrals: 0,
+        });
+    }
+
+    #[test]
+    fn solve_stall_recovery() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let solve = |max_spawns, stall_ticks_limit| {
+            let mut rng: XorShiftRng =
+                SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            super::solve_detailed(
+                source_model.clone(),
+                target_model.clone(),
+                super::Config {
+                    rtt_limit: 64,
+                    global_ticks_limit: 200,
+                    max_spawns,
+                    stall_ticks_limit,
+                    rtt_wander_limit: 64,
+                    ..super::Config::default()
+                },
+                &mut rng,
+            ).unwrap()
+        };
+
+        // the first tick is always a move, so the tightest limit forces a recovery right away;
+        // a single bot is never held, it only gets its routing attempts back
+        let (script_plain, stats_plain) = solve(1, 0);
+        assert_eq!(stats_plain.recoveries, 0);
+        let (script, stats) = solve(1, 1);
+        assert!(stats.recoveries > 0);
+        assert_eq!(script, script_plain);
+
+        // several bots: one of them waits after every idle tick, the model still gets done
+        let (script, stats) = solve(3, 1);
+        assert!(stats.recoveries > 0);
+        let report = simulate(source_model.clone(), target_model.clone(), &script);
+        assert_eq!(report.failure, None);
+        assert!(report.success);
+    }
+
+    #[test]
+    fn solve_fill_tower_astar_and_halt() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let script = super::solve_rng(
+            source_model,
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                router: super::RouterKind::Astar,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 3);
         assert_eq!(script.last(), Some(&BotCommand::Halt));
     }
 
+    #[test]
+    fn solve_cooperative_routing_and_halt() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::new(Resolution(8));
+        let target_model = Matrix::from_iter(Resolution(8), (1 .. 7).map(|x| Coord { x, y: 0, z: 3, }));
+        let script = super::solve_rng(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 300,
+                max_spawns: 4,
+                router: super::RouterKind::Astar,
+                nearest_jobs: true,
+                rtt_wander_limit: 64,
+                cooperative_window: Some(8),
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert!(script.iter().any(|cmd| if let BotCommand::Fission { .. } = cmd { true } else { false }));
+        let mut state = State::new(source_model, vec![]);
+        state.run_mut(script).unwrap();
+        assert_eq!(state.matrix, target_model);
+    }
+
+    #[test]
+    fn solve_cooperative_corridor_fewer_ticks() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        // a roofed corridor three cells wide and two high along x, open at x = 0; the row to fill runs
+        // down the middle of its floor, so the two bots share the lanes beside and above it
+        let walls: Vec<Coord> = (1 .. 8)
+            .flat_map(|x| (0 .. 2).flat_map(move |y| vec![Coord { x, y, z: 1, }, Coord { x, y, z: 5, }]))
+            .chain((1 .. 8).flat_map(|x| (1 .. 6).map(move |z| Coord { x, y: 2, z, })))
+            .collect();
+        let source_model = Matrix::from_iter(Resolution(8), walls.clone());
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            walls.into_iter().chain((1 .. 7).map(|x| Coord { x, y: 0, z: 3, })),
+        );
+        let solve = |cooperative_window| {
+            let mut rng: XorShiftRng =
+                SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            let (script, stats) = super::solve_detailed(
+                source_model.clone(),
+                target_model.clone(),
+                super::Config {
+                    rtt_limit: 64,
+                    global_ticks_limit: 1000,
+                    max_spawns: 2,
+                    router: super::RouterKind::Astar,
+                    nearest_jobs: true,
+                    rtt_wander_limit: 64,
+                    cooperative_window,
+                    ..super::Config::default()
+                },
+                &mut rng,
+            ).unwrap();
+            let mut state = State::new(source_model.clone(), vec![]);
+            state.run_mut(script).unwrap();
+            assert_eq!(state.matrix, target_model);
+            stats.ticks
+        };
+        assert!(solve(Some(8)) < solve(None));
+    }
+
+    #[test]
+    fn solve_cropped_tower_in_big_matrix() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(30), vec![]);
+        let target_model = Matrix::from_iter(Resolution(30), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let config = super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 100,
+            rtt_wander_limit: 64,
+            crop_to_models: true,
+            ..super::Config::default()
+        };
+        assert_eq!(
+            super::cropped_frame(&source_model, &target_model, &config),
+            Region { min: Coord { x: 0, y: 0, z: 0, }, max: Coord { x: 5, y: 5, z: 5, }, },
+        );
+        let script = super::solve_rng(source_model.clone(), target_model.clone(), config, &mut rng).unwrap();
+
+        // the trace runs in the original matrix and never leaves the cropped cube
+        let mut state = State::new(source_model, vec![]).with_event_log();
+        state.run_mut(script).unwrap();
+        assert_eq!(state.matrix, target_model);
+        assert!(state.events().iter()
+                .flat_map(|tick| tick.bots.iter())
+                .filter_map(|event| event.pos)
+                .all(|pos| pos.x < 6 && pos.y < 6 && pos.z < 6));
+    }
+
+    #[test]
+    fn solve_cropped_tower_far_from_origin() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(30), vec![]);
+        let target_model = Matrix::from_iter(Resolution(30), vec![
+            Coord { x: 20, y: 0, z: 20, },
+            Coord { x: 20, y: 1, z: 20, },
+            Coord { x: 20, y: 2, z: 20, },
+        ]);
+        let config = super::Config {
+            rtt_limit: 64,
+            global_ticks_limit: 200,
+            rtt_wander_limit: 64,
+            crop_to_models: true,
+            ..super::Config::default()
+        };
+        // a cube of 7 around the tower rather than one of 21 from the origin
+        let frame = super::cropped_frame(&source_model, &target_model, &config);
+        assert_eq!(frame, Region { min: Coord { x: 17, y: 0, z: 17, }, max: Coord { x: 23, y: 6, z: 23, }, });
+        let script = super::solve_rng(source_model.clone(), target_model.clone(), config, &mut rng).unwrap();
+
+        // the moves are relative, so the trace solved in the cube fills the tower in the original
+        // frame; out of the cube the bot only walks along the edges from the origin to its corner
+        let mut state = State::new(source_model, vec![]).with_event_log();
+        state.run_mut(script).unwrap();
+        assert_eq!(state.matrix, target_model);
+        assert!(state.events().iter()
+                .flat_map(|tick| tick.bots.iter())
+                .filter_map(|event| event.pos)
+                .all(|pos| frame.contains(&pos) || (pos.y == 0 && (pos.z == 0 || pos.x == frame.min.x))));
+    }
+
+    #[test]
+    fn solve_fusion_blocked_slot_and_halt() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // the default fusion slot (1, 0, 0) is filled and there is no bot with bid 1
+        let model = vec![Coord { x: 1, y: 0, z: 0, }];
+        let source_model = Matrix::from_iter(Resolution(3), model.clone());
+        let target_model = Matrix::from_iter(Resolution(3), model);
+        let script = super::solve_rng(
+            source_model,
+            target_model,
+            super::Config {
+                init_bots: vec![
+                    (2, Bot { pos: Coord { x: 0, y: 0, z: 2, }, seeds: vec![], }),
+                    (3, Bot { pos: Coord { x: 2, y: 0, z: 2, }, seeds: vec![], }),
+                ],
+                rtt_limit: 64,
+                global_ticks_limit: 100,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::FusionP { .. } = cmd { true } else { false }).count(), 1);
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::FusionS { .. } = cmd { true } else { false }).count(), 1);
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+    }
+
+    #[test]
+    fn solve_nearest_jobs_saves_move_energy() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let move_energy = |nearest_jobs| {
+            let mut rng: XorShiftRng =
+                SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            let source_model = Matrix::from_iter(Resolution(12), vec![]);
+            // two clusters with equal distances to the center, so the default order zigzags between them
+            let target_model = Matrix::from_iter(Resolution(12), vec![
+                Coord { x: 2, y: 0, z: 2, },
+                Coord { x: 1, y: 0, z: 2, },
+                Coord { x: 1, y: 0, z: 1, },
+                Coord { x: 10, y: 0, z: 10, },
+                Coord { x: 10, y: 0, z: 11, },
+                Coord { x: 11, y: 0, z: 11, },
+            ]);
+            let script = super::solve_rng(
+                source_model,
+                target_model,
+                super::Config {
+                    rtt_limit: 64,
+                    global_ticks_limit: 200,
+                    nearest_jobs,
+                    rtt_wander_limit: 64,
+                    ..super::Config::default()
+                },
+                &mut rng,
+            ).unwrap();
+            assert_eq!(script.last(), Some(&BotCommand::Halt));
+            script.iter()
+                .map(|cmd| match cmd {
+                    &BotCommand::SMove { ref long, } =>
+                        2 * long.to_coord_diff().l_1_norm(),
+                    &BotCommand::LMove { ref short1, ref short2, } =>
+                        2 * (short1.to_coord_diff().l_1_norm() + 2 + short2.to_coord_diff().l_1_norm()),
+                    _ =>
+                        0,
+                })
+                .sum::<usize>()
+        };
+        let energy_center = move_energy(false);
+        let energy_nearest = move_energy(true);
+        assert!(energy_nearest < energy_center, "nearest = {}, center = {}", energy_nearest, energy_center);
+    }
+
+    #[test]
+    fn solve_max_commands_halts_early() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 200,
+                rtt_wander_limit: 64,
+                max_commands: Some(3),
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert!(stats.commands_limit_hit);
+        assert!(stats.coverage_ratio < 1.0);
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+        let mut state = State::new(source_model, vec![]);
+        state.run_mut(script).unwrap();
+        assert_eq!(state.bots.len(), 0);
+    }
+
+    #[test]
+    fn solve_partial_halts() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(4), vec![]);
+        let target_model = Matrix::from_iter(Resolution(4), vec![
+            Coord { x: 1, y: 0, z: 1, },
+            Coord { x: 1, y: 1, z: 1, },
+            Coord { x: 1, y: 2, z: 1, },
+        ]);
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 5,
+                allow_partial: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+        assert!(stats.coverage_ratio > 0.0 && stats.coverage_ratio < 1.0);
+        let mut state = State::new(source_model, vec![]);
+        state.run_mut(script).unwrap();
+        assert_eq!(state.bots.len(), 0);
+    }
+
+    #[test]
+    fn solve_split_seeds_spawn_in_parallel() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(10), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(10),
+            iproduct!(1 .. 9, 1 .. 9).map(|(x, z)| Coord { x, y: 0, z, }));
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 1000,
+                max_spawns: 8,
+                nearest_jobs: true,
+                rtt_wander_limit: 64,
+                seed_distribution: super::SeedDistribution::Split,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        // all the spawns allowed happened, and not all of them by the first bot
+        assert_eq!(stats.peak_bots, 8);
+        let splits: Vec<_> = script.iter()
+            .filter_map(|cmd| if let &BotCommand::Fission { split_m, .. } = cmd { Some(split_m) } else { None })
+            .collect();
+        assert_eq!(splits.len(), 7);
+        // the first child takes half of the 6 spawns left after it
+        assert_eq!(splits[0], 3);
+
+        let report = simulate(source_model, target_model, &script);
+        assert_eq!(report.failure, None);
+        assert!(report.success);
+    }
+
+    #[test]
+    fn solve_floor_with_group_fill() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(8), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            iproduct!(1 .. 7, 1 .. 7).map(|(x, z)| Coord { x, y: 0, z, }));
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 300,
+                max_spawns: 2,
+                use_group_commands: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::GFill { .. } = cmd { true } else { false }).count(), 12);
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 0);
+        assert_eq!(stats.voxels_filled, 36);
+
+        let report = simulate(source_model, target_model, &script);
+        assert_eq!(report.failure, None);
+        assert!(report.success);
+        assert!(report.energy > 0);
+    }
+
+    #[test]
+    fn hybrid_router_first_moves() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let matrix = Matrix::from_iter(Resolution(16), vec![Coord { x: 4, y: 2, z: 2, }]);
+        let config = |router| super::Config {
+            global_ticks_limit: 100,
+            router,
+            ..super::Config::default()
+        };
+        let hybrid = config(super::RouterKind::Hybrid { astar_max_distance: 6, });
+        let is_passable = |region: &Region| !matrix.contains_filled(region);
+        let start = Coord { x: 2, y: 2, z: 2, };
+        let is_valid_move = |cmd: BotCommand| {
+            let end = match cmd {
+                BotCommand::SMove { long, } => start.add(long.to_coord_diff()),
+                BotCommand::LMove { short1, short2, } => start.add(short1.to_coord_diff()).add(short2.to_coord_diff()),
+                other => panic!("unexpected first move {:?}", other),
+            };
+            let (leg_a, leg_b) = cmd.affected_regions(start);
+            matrix.is_valid_coord(&end) && is_passable(&leg_a) && leg_b.map_or(true, |leg| is_passable(&leg))
+        };
+
+        // a short hop around the obstacle goes the astar way
+        let short_finish = Coord { x: 6, y: 2, z: 2, };
+        let mut commands_buf = Vec::new();
+        let hybrid_move = super::route_and_step(&start, &short_finish, &matrix, is_passable, &mut commands_buf, &hybrid, 256, &mut rng)
+            .unwrap().unwrap();
+        commands_buf.clear();
+        let astar_move = super::route_and_step(
+            &start, &short_finish, &matrix, is_passable, &mut commands_buf, &config(super::RouterKind::Astar), 256, &mut rng,
+        ).unwrap().unwrap();
+        assert_eq!(hybrid_move, astar_move);
+        assert!(is_valid_move(hybrid_move));
+
+        // a long one goes the rtt way
+        let long_finish = Coord { x: 14, y: 12, z: 13, };
+        commands_buf.clear();
+        let hybrid_move = super::route_and_step(&start, &long_finish, &matrix, is_passable, &mut commands_buf, &hybrid, 256, &mut rng)
+            .unwrap().unwrap();
+        assert!(is_valid_move(hybrid_move));
+    }
+
+    #[test]
+    fn solve_block_demolition_with_group_void() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(
+            Resolution(6),
+            iproduct!(1 .. 5, 0 .. 3, 1 .. 5).map(|(x, y, z)| Coord { x, y, z, }));
+        let target_model = Matrix::from_iter(Resolution(6), vec![]);
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 300,
+                max_spawns: 2,
+                use_group_commands: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        // 3 layers of 4 lines, every line voided by a pair of GVoid
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::GVoid { .. } = cmd { true } else { false }).count(), 24);
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Void { .. } = cmd { true } else { false }).count(), 0);
+        assert_eq!(stats.voxels_voided, 48);
+
+        let report = simulate(source_model, target_model, &script);
+        assert_eq!(report.failure, None);
+        assert!(report.success);
+    }
+
+    #[test]
+    fn solve_respects_max_active_bots() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(8), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            iproduct!(1 .. 7, 0 .. 3, 1 .. 7)
+                .filter(|&(x, _, z)| x % 2 == 1 && z % 2 == 1)
+                .map(|(x, y, z)| Coord { x, y, z, }));
+        let result = super::solve_rng(
+            source_model.clone(),
+            target_model,
+            super::Config {
+                rtt_limit: 64,
+                route_attempts_limit: 64,
+                global_ticks_limit: 400,
+                max_spawns: 39,
+                max_active_bots: 3,
+                allow_partial: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        );
+        // even a failed run has to keep the cap in the part it produced
+        let script = match result {
+            Ok(script) => script,
+            Err((_, script)) => script,
+        };
+        assert!(script.iter().any(|cmd| if let BotCommand::Fission { .. } = cmd { true } else { false }));
+
+        let mut state = State::new(source_model, vec![]);
+        let mut consumed = 0;
+        let mut peak_bots = 0;
+        while !state.is_halt() && consumed + state.bots.len() <= script.len() {
+            let tick_len = state.bots.len();
+            state.step_mut(&mut script[consumed .. consumed + tick_len].iter().cloned()).unwrap();
+            consumed += tick_len;
+            peak_bots = ::std::cmp::max(peak_bots, state.bots.len());
+        }
+        assert!(peak_bots <= 3, "peak bots = {}", peak_bots);
+        assert!(peak_bots > 1);
+    }
+
+    #[test]
+    fn resume_half_built_tower() {
+        use super::super::super::state::State;
+        let tower: Vec<_> = (0 .. 4).map(|y| Coord { x: 2, y, z: 2, }).collect();
+        let current_model = Matrix::from_iter(Resolution(5), tower[.. 2].to_vec());
+        let target_model = Matrix::from_iter(Resolution(5), tower.clone());
+        let script = super::resume(
+            current_model.clone(),
+            target_model.clone(),
+            vec![(1, Bot { pos: Coord { x: 0, y: 0, z: 0, }, seeds: vec![], })],
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 200,
+                seed: Some(7),
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+        ).unwrap();
+        // the existing half is kept: only the two missing voxels are filled
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::Fill { .. } = cmd { true } else { false }).count(), 2);
+        assert!(!script.iter().any(|cmd| if let BotCommand::Void { .. } = cmd { true } else { false }));
+
+        let mut state = State::new(current_model, vec![]);
+        state.run_mut(script).unwrap();
+        assert!(state.is_halt());
+        assert_eq!(state.matrix.count_filled(), 4);
+        assert!(tower.iter().all(|voxel| state.matrix.is_filled(voxel)));
+    }
+
+    #[test]
+    fn trim_satisfied_tower() {
+        let tower = Region {
+            min: Coord { x: 1, y: 0, z: 1, },
+            max: Coord { x: 1, y: 5, z: 1, },
+        };
+        assert_eq!(super::trim_satisfied(tower, |coord: &Coord| coord.y < 2 || coord.y == 5), Some(Region {
+            min: Coord { x: 1, y: 2, z: 1, },
+            max: Coord { x: 1, y: 4, z: 1, },
+        }));
+        assert_eq!(super::trim_satisfied(tower, |_: &Coord| false), Some(tower));
+        assert_eq!(super::trim_satisfied(tower, |_: &Coord| true), None);
+    }
+
+    #[test]
+    fn solve_half_prefilled_tower_skips_done_voxels() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let tower: Vec<_> = (0 .. 4).map(|y| Coord { x: 2, y, z: 2, }).collect();
+        let source_model = Matrix::from_iter(Resolution(5), tower[.. 2].to_vec());
+        let target_model = Matrix::from_iter(Resolution(5), tower.clone());
+        let script = super::solve_rng(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 200,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+
+        // replay the trace and collect every voxel a Fill is aimed at
+        let mut state = State::new(source_model, vec![]);
+        let mut filled_at = Vec::new();
+        let mut cmds = script.into_iter();
+        while !state.is_halt() {
+            let bots: Vec<_> = state.bots.iter().map(|(_, bot)| bot.pos).collect();
+            let tick: Vec<_> = cmds.by_ref().take(bots.len()).collect();
+            for (pos, cmd) in bots.iter().zip(tick.iter()) {
+                if let &BotCommand::Fill { near, } = cmd {
+                    filled_at.push(pos.add(near));
+                }
+            }
+            state.step_mut(&mut tick.into_iter()).unwrap();
+        }
+        assert_eq!(filled_at.len(), 2);
+        assert!(filled_at.iter().all(|voxel| !tower[.. 2].contains(voxel)));
+        assert!(tower.iter().all(|voxel| state.matrix.is_filled(voxel)));
+    }
+
+    #[test]
+    fn resume_scattered_bots_fuse_home() {
+        use super::super::super::state::State;
+        let model = Matrix::new(Resolution(6));
+        let bots = vec![
+            (1, Coord { x: 3, y: 0, z: 0, }),
+            (2, Coord { x: 0, y: 0, z: 3, }),
+            (3, Coord { x: 3, y: 3, z: 3, }),
+            (4, Coord { x: 2, y: 0, z: 2, }),
+            (5, Coord { x: 0, y: 2, z: 0, }),
+        ];
+        let script = super::resume(
+            model.clone(),
+            model.clone(),
+            bots.iter().map(|&(bid, pos)| (bid, Bot { pos, seeds: vec![], })).collect(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 200,
+                seed: Some(7),
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+        ).unwrap();
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::FusionS { .. } = cmd { true } else { false }).count(), 4);
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+
+        let mut state = State::new(model, vec![]);
+        state.bots = bots.into_iter().map(|(bid, pos)| (bid, Bot { pos, seeds: vec![], })).collect();
+        state.run_mut(script).unwrap();
+        assert!(state.is_halt());
+    }
+
+    #[test]
+    fn fill_jobs_defer_ungrounded_towers() {
+        use std::collections::HashMap;
+        // the bottom of the column under (3, 3, 3) is in place already, nothing holds (2, 1, 1) yet
+        let current_model = Matrix::from_iter(Resolution(5), (0 .. 3).map(|y| Coord { x: 3, y, z: 3, }));
+        let hanging = Region { min: Coord { x: 2, y: 1, z: 1, }, max: Coord { x: 2, y: 2, z: 1, }, };
+        let standing = Region { min: Coord { x: 3, y: 3, z: 3, }, max: Coord { x: 3, y: 4, z: 3, }, };
+        let mut fill_towers = super::FillJobs {
+            pools: vec![super::TowerJobs::new(
+                vec![hanging, standing],
+                |tower| Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, },
+                |tower| tower.min.y,
+            )],
+            assigned: HashMap::new(),
+        };
+        let anchor = Coord { x: 2, y: 2, z: 1, };
+        let take = |fill_towers: &mut super::FillJobs| fill_towers.take_groundable(
+            1,
+            &anchor,
+            None,
+            |_: &Coord| false,
+            |coord: &Coord| current_model.will_be_grounded(coord),
+        );
+        // the hanging tower is on the lower level, but it has to wait
+        assert_eq!(take(&mut fill_towers), (Some(standing), 1));
+        // with nothing else left it goes anyway
+        assert_eq!(take(&mut fill_towers), (Some(hanging), 0));
+        assert_eq!(take(&mut fill_towers), (None, 0));
+    }
+
+    #[test]
+    fn fill_jobs_defer_within_component() {
+        use std::collections::HashMap;
+        let current_model = Matrix::from_iter(Resolution(5), (0 .. 3).map(|y| Coord { x: 3, y, z: 3, }));
+        let hanging = Region { min: Coord { x: 2, y: 1, z: 1, }, max: Coord { x: 2, y: 2, z: 1, }, };
+        let standing = Region { min: Coord { x: 3, y: 3, z: 3, }, max: Coord { x: 3, y: 4, z: 3, }, };
+        let other = Region { min: Coord { x: 0, y: 0, z: 4, }, max: Coord { x: 0, y: 1, z: 4, }, };
+        let pool = |towers: Vec<Region>| super::TowerJobs::new(
+            towers,
+            |tower| Coord { x: tower.min.x, y: tower.min.y + 1, z: tower.min.z, },
+            |tower| tower.min.y,
+        );
+        let mut fill_towers = super::FillJobs {
+            pools: vec![pool(vec![hanging, standing]), pool(vec![other])],
+            assigned: vec![(1, 0)].into_iter().collect::<HashMap<_, _>>(),
+        };
+        let anchor = Coord { x: 2, y: 2, z: 1, };
+        let take = |fill_towers: &mut super::FillJobs| fill_towers.take_groundable(
+            1,
+            &anchor,
+            None,
+            |_: &Coord| false,
+            |coord: &Coord| current_model.will_be_grounded(coord),
+        );
+        // the put off tower goes back to its own component, which the bot does not leave
+        assert_eq!(take(&mut fill_towers), (Some(standing), 1));
+        assert!(!fill_towers.pools[0].is_empty());
+        assert_eq!(fill_towers.assigned[&1], 0);
+        assert_eq!(take(&mut fill_towers), (Some(hanging), 0));
+        assert_eq!(fill_towers.assigned[&1], 0);
+        // and only once it is really done the bot helps with the other one
+        assert_eq!(take(&mut fill_towers), (Some(other), 0));
+        assert_eq!(fill_towers.assigned[&1], 1);
+    }
+
+    #[test]
+    fn solve_tower_hanging_off_neighbour() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::State;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // the (2, _, 2) tower only touches the ground through the (1, _, 2) one
+        let source_model = Matrix::new(Resolution(6));
+        let target_model = Matrix::from_iter(Resolution(6), vec![
+            Coord { x: 1, y: 0, z: 2, },
+            Coord { x: 1, y: 1, z: 2, },
+            Coord { x: 1, y: 2, z: 2, },
+            Coord { x: 1, y: 3, z: 2, },
+            Coord { x: 2, y: 2, z: 2, },
+            Coord { x: 2, y: 3, z: 2, },
+            Coord { x: 4, y: 0, z: 4, },
+        ]);
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 300,
+                max_spawns: 2,
+                nearest_jobs: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        assert_eq!(stats.coverage_ratio, 1.0);
+        let mut state = State::new(source_model, vec![]);
+        state.run_mut(script).unwrap();
+        assert_eq!(state.matrix, target_model);
+    }
+
+    #[test]
+    fn solve_per_component_two_towers() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(8), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            (0 .. 4).map(|y| Coord { x: 1, y, z: 1, })
+                .chain((0 .. 4).map(|y| Coord { x: 6, y, z: 6, })));
+        let (script, stats) = super::solve_detailed(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 400,
+                per_component: true,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        ).unwrap();
+        // `max_spawns` is raised to the components count
+        assert_eq!(stats.peak_bots, 2);
+        assert_eq!(stats.voxels_filled, 8);
+
+        let report = simulate(source_model, target_model, &script);
+        assert_eq!(report.failure, None);
+        assert!(report.success);
+    }
+
+    #[test]
+    fn finalize_scattered_bots() {
+        use super::super::super::state::State;
+        use super::{Nanobot, Plan};
+        let mut state = State::new(Matrix::from_iter(Resolution(5), vec![Coord { x: 0, y: 0, z: 1, }]), vec![]);
+        let mut trace_it = vec![
+            BotCommand::Fission { near: CoordDiff(Coord { x: 1, y: 0, z: 0, }), split_m: 1, },
+            BotCommand::Wait,
+            BotCommand::Fission { near: CoordDiff(Coord { x: 0, y: 1, z: 0, }), split_m: 0, },
+            BotCommand::Wait,
+            BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Z, value: 3, }, },
+            BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::Y, value: 2, }, },
+        ].into_iter();
+        for _ in 0 .. 3 {
+            state.step_mut(&mut trace_it).unwrap();
+        }
+        assert_eq!(state.bots_snapshot(), vec![
+            (1, Coord { x: 0, y: 0, z: 0, }),
+            (2, Coord { x: 1, y: 0, z: 3, }),
+            (3, Coord { x: 1, y: 3, z: 0, }),
+        ]);
+
+        let mut nanobots: Vec<_> = state.bots.iter()
+            .map(|(&bid, bot)| Nanobot { bid, bot: bot.clone(), plan: Plan::Init, })
+            .collect();
+        let script = super::finalize(&mut nanobots, &state.matrix);
+        assert!(nanobots.is_empty());
+        assert_eq!(script.iter().filter(|cmd| if let BotCommand::FusionP { .. } = cmd { true } else { false }).count(), 2);
+        state.run_mut(script).unwrap();
+        assert!(state.is_halt());
+    }
+
+    #[test]
+    fn solve_ticks_limit_exceeded_halts() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(8), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            iproduct!(2 .. 6, 0 .. 4).map(|(x, y)| Coord { x, y, z: 4, }));
+        let result = super::solve_rng(
+            source_model.clone(),
+            target_model.clone(),
+            super::Config {
+                rtt_limit: 64,
+                global_ticks_limit: 8,
+                max_spawns: 3,
+                rtt_wander_limit: 64,
+                ..super::Config::default()
+            },
+            &mut rng,
+        );
+        let script = match result {
+            Err((super::Error::GlobalTicksLimitExceeded { ticks: 8, .. }, script)) =>
+                script,
+            other =>
+                panic!("unexpected solver result: {:?}", other),
+        };
+        // the wall is not done, but the bots are home and halted
+        assert_eq!(script.last(), Some(&BotCommand::Halt));
+        let report = simulate(source_model, target_model, &script);
+        assert_eq!(report.failure, None);
+        assert!(!report.success);
+    }
+
+    #[test]
+    fn solve_checkpoints_are_valid_traces() {
+        use rand::{SeedableRng, prng::XorShiftRng};
+        use super::super::super::state::simulate;
+        let mut rng: XorShiftRng =
+            SeedableRng::from_seed([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let source_model = Matrix::from_iter(Resolution(8), vec![]);
+        let target_model = Matrix::from_iter(
+            Resolution(8),
+            iproduct!(2 .. 6, 0 .. 4).map(|(x, y)| Coord { x, y, z: 4, }));
+        let mut dumps: Vec<Vec<BotCommand>> = Vec::new();
+        let result = {
+            let mut keep = |script: &[BotCommand]| dumps.push(script.to_vec());
+            super::solve_detailed_with_checkpoints(
+                source_model.clone(),
+                target_model.clone(),
+                super::Config {
+                    rtt_limit: 64,
+                    global_ticks_limit: 1000,
+                    max_spawns: 3,
+                    rtt_wander_limit: 64,
+                    checkpoint_every: Some(4),
+                    ..super::Config::default()
+                },
+                &mut rng,
+                None,
+                Some(&mut keep),
+            )
+        };
+        assert!(result.is_ok());
+        assert!(!dumps.is_empty());
+        for dump in dumps.iter() {
+            assert_eq!(dump.last(), Some(&BotCommand::Halt));
+            let report = simulate(source_model.clone(), target_model.clone(), dump);
+            assert_eq!(report.failure, None);
+        }
+    }
+
     // #[test]
     // fn solve_la008_tgt_mdl() {
     //     use rand::{SeedableRng, prng::XorShiftRng};
@@ -1035,6 +3858,9 @@ mod test {
     //             route_attempts_limit: 512,
     //             global_ticks_limit: 4096,
     //             max_spawns: 1,
+    //             time_limit: None,
+    //             seed: None,
+    //             router: super::RouterKind::Rtt,
     //         },
     //         &mut rng,
     //     ).unwrap();
@@ -0,0 +1,176 @@
+use std::cmp;
+
+use super::super::{
+    coord::{
+        Axis,
+        Coord,
+        CoordDiff,
+        LinearCoordDiff,
+        M,
+    },
+    cmd::BotCommand,
+};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    NotEnoughSeeds { bots: usize, seeds: usize, },
+    // the first position whose x is not greater than the one before
+    XNotIncreasing { pos: Coord, },
+    NegativePosition { pos: Coord, },
+}
+
+// Spawns one bot per target position starting from a single bot at the origin, and joins them back.
+//
+// Bots are fissioned one after another along the x axis (on y = 0, z = 0), every new bot taking all
+// the remaining seeds, so bids grow together with x. Then each bot climbs to its target in y and z.
+// The join sequence does the same backwards and leaves the only bot at the origin (without `Halt`).
+//
+// Target positions must have strictly increasing x, and there should be at most `seeds + 1` of them.
+pub fn spawn_tree(target_positions: &[Coord], seeds: usize) -> Result<(Vec<BotCommand>, Vec<BotCommand>), Error> {
+    if target_positions.len() > seeds + 1 {
+        return Err(Error::NotEnoughSeeds { bots: target_positions.len(), seeds, });
+    }
+    if let Some(index) = (1 .. target_positions.len()).find(|&i| target_positions[i - 1].x >= target_positions[i].x) {
+        return Err(Error::XNotIncreasing { pos: target_positions[index], });
+    }
+    if let Some(&pos) = target_positions.iter().find(|p| p.x < 0 || p.y < 0 || p.z < 0) {
+        return Err(Error::NegativePosition { pos, });
+    }
+
+    let mut spawn = Vec::new();
+    let mut join = Vec::new();
+    if target_positions.is_empty() {
+        return Ok((spawn, join));
+    }
+
+    /* spawn along x axis */
+    let mut bots = 1;
+    let mut pos_x = 0;
+    for (i, target) in target_positions.iter().enumerate() {
+        if i > 0 {
+            push_single(&mut spawn, bots, BotCommand::fission(CoordDiff::new(1, 0, 0), (seeds - i) as u8).unwrap());
+            bots += 1;
+            pos_x = target_positions[i - 1].x + 1;
+        }
+        for cmd in line_moves(Axis::X, target.x - pos_x) {
+            push_single(&mut spawn, bots, cmd);
+        }
+    }
+
+    /* climb to targets */
+    push_parallel(&mut spawn, target_positions.iter()
+        .map(|p| line_moves(Axis::Y, p.y).into_iter().chain(line_moves(Axis::Z, p.z)).collect())
+        .collect());
+
+    /* join: get back to the x axis */
+    push_parallel(&mut join, target_positions.iter()
+        .map(|p| line_moves(Axis::Z, -p.z).into_iter().chain(line_moves(Axis::Y, -p.y)).collect())
+        .collect());
+
+    /* fuse along x axis */
+    for i in (1 .. target_positions.len()).rev() {
+        for cmd in line_moves(Axis::X, target_positions[i - 1].x + 1 - target_positions[i].x) {
+            push_single(&mut join, bots, cmd);
+        }
+        for _ in 2 .. bots {
+            join.push(BotCommand::wait().unwrap());
+        }
+        join.push(BotCommand::pfusion(CoordDiff::new(1, 0, 0)).unwrap());
+        join.push(BotCommand::sfusion(CoordDiff::new(-1, 0, 0)).unwrap());
+        bots -= 1;
+    }
+    for cmd in line_moves(Axis::X, -target_positions[0].x) {
+        join.push(cmd);
+    }
+
+    Ok((spawn, join))
+}
+
+// one tick where only the last (highest bid) bot acts
+fn push_single(cmds: &mut Vec<BotCommand>, bots: usize, cmd: BotCommand) {
+    for _ in 1 .. bots {
+        cmds.push(BotCommand::wait().unwrap());
+    }
+    cmds.push(cmd);
+}
+
+fn push_parallel(cmds: &mut Vec<BotCommand>, per_bot: Vec<Vec<BotCommand>>) {
+    let ticks = per_bot.iter().map(|moves| moves.len()).max().unwrap_or(0);
+    for tick in 0 .. ticks {
+        for moves in per_bot.iter() {
+            cmds.push(moves.get(tick).cloned().unwrap_or(BotCommand::Wait));
+        }
+    }
+}
+
+// straight move by `delta` along `axis` split into SMoves of at most 15
+pub fn line_moves(axis: Axis, mut delta: M) -> Vec<BotCommand> {
+    let mut moves = Vec::new();
+    while delta != 0 {
+        let value = cmp::max(-15, cmp::min(15, delta));
+        moves.push(BotCommand::smove(LinearCoordDiff::Long { axis, value, }).unwrap());
+        delta -= value;
+    }
+    moves
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::{
+        coord::{
+            Coord,
+            Matrix,
+            Resolution,
+        },
+        cmd::BotCommand,
+        state::State,
+    };
+
+    #[test]
+    fn spawn_four_and_join() {
+        let targets = vec![
+            Coord { x: 1, y: 0, z: 2, },
+            Coord { x: 4, y: 3, z: 0, },
+            Coord { x: 5, y: 0, z: 0, },
+            Coord { x: 20, y: 1, z: 17, },
+        ];
+        let (spawn, join) = super::spawn_tree(&targets, 39).unwrap();
+
+        let mut state = State::new(Matrix::new(Resolution(25)), vec![]);
+        let mut spawn_it = spawn.into_iter().peekable();
+        while spawn_it.peek().is_some() {
+            state.step_mut(&mut spawn_it).unwrap();
+        }
+        assert_eq!(state.bots.values().map(|bot| bot.pos).collect::<Vec<_>>(), targets);
+
+        let mut join_it = join.into_iter().chain(Some(BotCommand::halt().unwrap())).peekable();
+        while join_it.peek().is_some() {
+            state.step_mut(&mut join_it).unwrap();
+        }
+        assert!(state.is_halt());
+    }
+
+    #[test]
+    fn spawn_single_at_origin() {
+        let (spawn, join) = super::spawn_tree(&[Coord { x: 0, y: 0, z: 0, }], 39).unwrap();
+        assert_eq!(spawn, vec![]);
+        assert_eq!(join, vec![]);
+    }
+
+    #[test]
+    fn spawn_invalid_targets() {
+        assert_eq!(super::spawn_tree(&[
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 1, y: 0, z: 0, },
+            Coord { x: 2, y: 0, z: 0, },
+        ], 1), Err(super::Error::NotEnoughSeeds { bots: 3, seeds: 1, }));
+        assert_eq!(super::spawn_tree(&[
+            Coord { x: 2, y: 0, z: 0, },
+            Coord { x: 2, y: 1, z: 0, },
+        ], 39), Err(super::Error::XNotIncreasing { pos: Coord { x: 2, y: 1, z: 0, }, }));
+        assert_eq!(super::spawn_tree(&[
+            Coord { x: 0, y: 0, z: 0, },
+            Coord { x: 1, y: -1, z: 0, },
+        ], 39), Err(super::Error::NegativePosition { pos: Coord { x: 1, y: -1, z: 0, }, }));
+    }
+}
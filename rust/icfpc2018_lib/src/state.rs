@@ -4,12 +4,14 @@ use std::mem;
 use super::{
     coord::{
         Axis,
-        LinearCoordDiff,
         Coord,
+        CoordDiff,
         Matrix,
         Region,
+        RegionDim,
     },
     cmd::{
+        self,
         BotCommand,
     },
 };
@@ -31,14 +33,99 @@ pub struct Bot {
 #[derive(Debug)]
 pub struct Command;
 
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct EnergyBreakdown {
+    pub harmonics: usize,
+    pub bots: usize,
+    pub moves: usize,
+    pub fill_void: isize,
+    pub fission_fusion: isize,
+}
+
+// What `step_mut` verifies before performing a tick. Turning the checks off only makes sense for
+// traces already known to be valid (e.g. batch scoring of traces that passed a full run): a bad
+// trace is then not reported but performed anyway, giving a meaningless energy and matrix, or
+// panicking on a bid or a seed that is not there.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScoringConfig {
+    // grounding (in low harmonics), bots in filled voxels and seeds disjointness before every tick
+    pub check_wellformed: bool,
+    // command preconditions, interference and group completeness of every tick
+    pub check_preconditions: bool,
+}
+
+impl ScoringConfig {
+    // no checks at all: just the energy and the final matrix of a known wellformed trace
+    pub fn trusted() -> ScoringConfig {
+        ScoringConfig { check_wellformed: false, check_preconditions: false, }
+    }
+}
+
+impl Default for ScoringConfig {
+    fn default() -> ScoringConfig {
+        ScoringConfig { check_wellformed: true, check_preconditions: true, }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ModelDiff {
+    pub extra: Vec<Coord>,
+    pub missing: Vec<Coord>,
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.extra.is_empty() && self.missing.is_empty()
+    }
+}
+
+// what a bot was told to do in a tick and where it ended up, `None` if it is gone (fused or halted)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BotEvent {
+    pub bid: Bid,
+    pub cmd: BotCommand,
+    pub pos: Option<Coord>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TickEvent {
+    pub bots: Vec<BotEvent>,
+}
+
 #[derive(Debug)]
 pub struct State {
     pub steps: usize,
     pub energy: usize,
+    pub peak_bots: usize,
+    pub commands_executed: usize,
+    breakdown: EnergyBreakdown,
     pub harmonics: Harmonics,
     pub matrix: Matrix,
     pub bots: BTreeMap<Bid, Bot>,
     pub trace: Vec<BotCommand>,
+    energy_by_bot: Option<BTreeMap<Bid, i64>>,
+    events: Option<Vec<TickEvent>>,
+    scoring_config: ScoringConfig,
+}
+
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    steps: usize,
+    energy: usize,
+    peak_bots: usize,
+    commands_executed: usize,
+    breakdown: EnergyBreakdown,
+    harmonics: Harmonics,
+    matrix: Matrix,
+    bots: BTreeMap<Bid, Bot>,
+    energy_by_bot: Option<BTreeMap<Bid, i64>>,
+    events: Option<Vec<TickEvent>>,
+}
+
+impl Checkpoint {
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -50,19 +137,52 @@ pub enum WellformedStatus {
     SeedIsTheSameAsActiveBot,
 }
 
+// `WellformedStatus` along with who or what is at fault
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum WellformedReport {
+    Wellformed,
+    // the first ungrounded voxel found
+    NotGroundedWhileLowHarmonics { voxel: Coord },
+    BotInFilledVoxel { bid: Bid, pos: Coord },
+    // the seed given to more than one bot
+    SeedsAreNotDisjoint { seed: Bid },
+    // the active bot that is also someone's seed
+    SeedIsTheSameAsActiveBot { bid: Bid },
+}
+
+impl WellformedReport {
+    pub fn status(&self) -> WellformedStatus {
+        match *self {
+            WellformedReport::Wellformed => WellformedStatus::Wellformed,
+            WellformedReport::NotGroundedWhileLowHarmonics { .. } => WellformedStatus::NotGroundedWhileLowHarmonics,
+            WellformedReport::BotInFilledVoxel { .. } => WellformedStatus::BotInFilledVoxel,
+            WellformedReport::SeedsAreNotDisjoint { .. } => WellformedStatus::SeedsAreNotDisjoint,
+            WellformedReport::SeedIsTheSameAsActiveBot { .. } => WellformedStatus::SeedIsTheSameAsActiveBot,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Error {
-    StateNotWellformed{status: WellformedStatus},
+    StateNotWellformed{report: WellformedReport},
     NotEnoughCommands,
-    CommandsInterfere,
+    CommandsInterfere {bid_a: Bid, bid_b: Bid, region: Region},
     InvalidBid{bid: Bid},
     HaltNotAtZeroCoord,
     HaltTooManyBots,
     HaltNotInLow,
     MoveOutOfBounds {c: Coord},
     MoveRegionIsNotVoid {r: Region},
+    NotNear {d: CoordDiff},
+    FillOnBot {bid: Bid, c: Coord},
     NoSeedsAvailable,
     TooBigSplitSeed,
+    GroupRegionInvalid,
+    GroupIncomplete {r: Region},
+    EnergyCapExceeded {at_step: usize},
+    StepLimitExceeded {steps: usize},
+    AllBotsFusedAway,
+    FusionUnmatched {bid: Bid},
 }
 
 
@@ -78,34 +198,63 @@ impl State {
         State {
             steps: 0,
             energy: 0,
+            peak_bots: 1,
+            commands_executed: 0,
+            breakdown: EnergyBreakdown::default(),
             harmonics: Harmonics::Low,
             matrix,
             bots,
             trace,
+            energy_by_bot: None,
+            events: None,
+            scoring_config: ScoringConfig::default(),
+        }
+    }
+
+    // record every tick performed from now on, see `events`
+    pub fn with_event_log(mut self) -> State {
+        if self.events.is_none() {
+            self.events = Some(Vec::new());
         }
+        self
+    }
+
+    // see `ScoringConfig` for what is given up with the checks turned off
+    pub fn with_scoring_config(mut self, scoring_config: ScoringConfig) -> State {
+        self.scoring_config = scoring_config;
+        self
+    }
+
+    pub fn scoring_config(&self) -> ScoringConfig {
+        self.scoring_config
     }
 
-    pub fn wellformed(&self) -> WellformedStatus {
+    pub fn wellformed(&self) -> WellformedReport {
         if let Harmonics::Low = self.harmonics {
-            if !self.matrix.all_voxels_are_grounded() {
-                return WellformedStatus::NotGroundedWhileLowHarmonics;
+            if !self.is_currently_grounded() {
+                /* the cache only tells whether there is one, look it up the slow way */
+                let ungrounded_voxel = self.matrix.first_ungrounded_voxel();
+                debug_assert!(ungrounded_voxel.is_some(), "grounded cache is out of sync with the matrix");
+                if let Some(voxel) = ungrounded_voxel {
+                    return WellformedReport::NotGroundedWhileLowHarmonics { voxel, };
+                }
             }
         }
-        if !self.bots.values().map(|bot| &bot.pos).all(|pos| !self.matrix.is_filled(pos)) {
-            return WellformedStatus::BotInFilledVoxel;
+        if let Some((&bid, bot)) = self.bots.iter().find(|&(_, bot)| self.matrix.is_filled(&bot.pos)) {
+            return WellformedReport::BotInFilledVoxel { bid, pos: bot.pos, };
         }
 
         let mut bids_seen = HashSet::new();
         for &bid in self.bots.values().flat_map(|bot| bot.seeds.iter()) {
             if !bids_seen.insert(bid) {
-                return WellformedStatus::SeedsAreNotDisjoint;
+                return WellformedReport::SeedsAreNotDisjoint { seed: bid, };
             }
             if self.bots.contains_key(&bid) {
-                return WellformedStatus::SeedIsTheSameAsActiveBot;
+                return WellformedReport::SeedIsTheSameAsActiveBot { bid, };
             }
         }
 
-        WellformedStatus::Wellformed
+        WellformedReport::Wellformed
     }
 
     pub fn bot_pos(&self, bid: &Bid) -> Option<Coord> {
@@ -116,13 +265,32 @@ impl State {
         self.bots.is_empty()
     }
 
+    // whether every filled voxel of the matrix, as left by the last performed tick, is grounded;
+    // cheap to poll every tick once `matrix.enable_grounding_cache()` is on
+    pub fn is_currently_grounded(&self) -> bool {
+        self.matrix.all_voxels_are_grounded_cached()
+    }
+
+    // sorted by bid, for renderers and monitors that should not hold a borrow of `bots`
+    pub fn bots_snapshot(&self) -> Vec<(Bid, Coord)> {
+        self.bots.iter().map(|(&bid, bot)| (bid, bot.pos)).collect()
+    }
+
+    pub fn active_bot_count(&self) -> usize {
+        self.bots.len()
+    }
+
+    // a tick in which every bot just waits changes nothing but the energy, so trace tools may compact it
+    pub fn is_waiting_tick(cmds: &[BotCommand]) -> bool {
+        !cmds.is_empty() && cmds.iter().all(|cmd| *cmd == BotCommand::Wait)
+    }
+
     pub fn check_precondition(&self, bid: &Bid, cmd: &BotCommand) -> Result<(Region, Option<Region>), Error> {
         if let None = self.bots.get(bid) {
             return Err(Error::InvalidBid{bid:*bid})
         }
 
         let c = self.bot_pos(&bid).unwrap();
-        let bot_reg = Region::from_corners(&c,&c);
 
         match cmd {
             BotCommand::Halt => {
@@ -132,14 +300,14 @@ impl State {
                 let check_low = self.harmonics == Harmonics::Low;
 
                 match (check_coord, check_the_only_bot, check_low) {
-                    (true, true, true) => Ok((bot_reg, None)),
+                    (true, true, true) => Ok(cmd.affected_regions(c)),
                     (false, _, _) => return Err(Error::HaltNotAtZeroCoord),
                     (_, false, _) => return Err(Error::HaltTooManyBots),
                     (_, _, false) => return Err(Error::HaltNotInLow),
                 }
             },
-            BotCommand::Wait => Ok((bot_reg, None)),
-            BotCommand::Flip => Ok((bot_reg, None)),
+            BotCommand::Wait => Ok(cmd.affected_regions(c)),
+            BotCommand::Flip => Ok(cmd.affected_regions(c)),
             BotCommand::SMove{ long } => {
                 let d = long.to_coord_diff();
                 let cf = c.add(d);
@@ -153,7 +321,7 @@ impl State {
                     return Err(Error::MoveRegionIsNotVoid{r: volatile_reg})
                 }
 
-                Ok((volatile_reg, None))
+                Ok(cmd.affected_regions(c))
             },
             BotCommand::LMove{ short1, short2 } => {
                 let d1 = short1.to_coord_diff();
@@ -173,42 +341,41 @@ impl State {
                     return Err(Error::MoveOutOfBounds{c: cff})
                 }
 
-                let volatile_reg2 = Region::from_corners(&c, &cf);
+                let volatile_reg2 = Region::from_corners(&cf, &cff);
                 if self.matrix.contains_filled(&volatile_reg2) {
                     return Err(Error::MoveRegionIsNotVoid{r: volatile_reg2})
                 }
 
-                match (short1, short2) {
-                    (LinearCoordDiff::Short{axis:axis1, value:value1}, LinearCoordDiff::Short{axis:axis2, value:value2}) =>
-                        if axis1 == axis2 {
-                            let d = LinearCoordDiff::Short { axis: *axis1, value: value1 + value2 }.to_coord_diff();
-                            let cf = c.add(d);
-
-                            Ok((Region::from_corners(&c, &cf), None))
-                        } else {
-                            Ok((volatile_reg, Some(volatile_reg2)))
-                        },
-                    (_,_) => panic!("Unexpected diff!"),
-                }
+                Ok(cmd.affected_regions(c))
 
             },
             BotCommand::Fill{ near } => {
                 let n = *near;
+                if !n.is_near() {
+                    return Err(Error::NotNear{d: n})
+                }
                 let cf = c.add(n);
                 if !self.matrix.is_valid_coord(&cf) {
                     return Err(Error::MoveOutOfBounds{c: cf})
                 }
+                /* another bot stands there: it would end up inside a full voxel */
+                if let Some((&other, _)) = self.bots.iter().find(|&(_, bot)| bot.pos == cf) {
+                    return Err(Error::FillOnBot{bid: other, c: cf})
+                }
 
-                Ok((Region::from_corners(&c, &cf), None))
+                Ok(cmd.affected_regions(c))
             },
             BotCommand::Void{ near } => {
                 let n = *near;
+                if !n.is_near() {
+                    return Err(Error::NotNear{d: n})
+                }
                 let cf = c.add(n);
                 if !self.matrix.is_valid_coord(&cf) {
                     return Err(Error::MoveOutOfBounds{c: cf})
                 }
 
-                Ok((Region::from_corners(&c, &cf), None))
+                Ok(cmd.affected_regions(c))
             },
             BotCommand::Fission{ near, split_m } => {
                 let n = *near;
@@ -227,7 +394,7 @@ impl State {
                     return Err(Error::MoveRegionIsNotVoid{r: Region::from_corners(&cf, &cf)})
                 }
 
-                Ok((Region::from_corners(&c, &c), Some(Region::from_corners(&cf, &cf))))
+                Ok(cmd.affected_regions(c))
             }
             BotCommand::FusionP{ near } => {
                 let n = *near;
@@ -236,7 +403,7 @@ impl State {
                     return Err(Error::MoveOutOfBounds{c: cf})
                 }
 
-                Ok((bot_reg, None))
+                Ok(cmd.affected_regions(c))
             },
             BotCommand::FusionS{ near } => {
                 let n = *near;
@@ -245,10 +412,129 @@ impl State {
                     return Err(Error::MoveOutOfBounds{c: cf})
                 }
 
-                Ok((bot_reg, None))
+                Ok(cmd.affected_regions(c))
+            },
+            BotCommand::GFill{ near, far } | BotCommand::GVoid{ near, far } => {
+                if !near.is_near() || !far.is_far() {
+                    return Err(Error::GroupRegionInvalid)
+                }
+                let cn = c.add(*near);
+                let cf = cn.add(*far);
+                if !self.matrix.is_valid_coord(&cn) {
+                    return Err(Error::MoveOutOfBounds{c: cn})
+                }
+                if !self.matrix.is_valid_coord(&cf) {
+                    return Err(Error::MoveOutOfBounds{c: cf})
+                }
+                let group_reg = Region::from_corners(&cn, &cf);
+                if group_reg.contains(&c) {
+                    return Err(Error::GroupRegionInvalid)
+                }
+
+                Ok(cmd.affected_regions(c))
+            },
+        }
+    }
+
+    // Precondition and interference check of one tick without performing it, the same as `step_mut` does.
+    // Returns the volatile regions of the tick (a group region is counted once for all its members).
+    pub fn check_tick(&self, cmds: &[(Bid, BotCommand)]) -> Result<Vec<Region>, Error> {
+        let volatile = self.tick_volatile(cmds)?;
+        for (index, &(bid, ref reg)) in volatile.iter().enumerate() {
+            let clash = volatile[.. index].iter()
+                .filter(|&&(owner, _)| owner != bid)
+                .filter_map(|&(owner, ref vol_reg)| vol_reg.intersection(reg).map(|region| Error::CommandsInterfere {
+                    bid_a: owner,
+                    bid_b: bid,
+                    region,
+                }))
+                .next();
+            if let Some(error) = clash {
+                return Err(error)
+            }
+        }
+
+        /* every FusionP needs exactly one FusionS pointing back at it, and the other way round */
+        for &(bid, ref cmd) in cmds {
+            let (near, wants_secondary) = match *cmd {
+                BotCommand::FusionP{ near } => (near, true),
+                BotCommand::FusionS{ near } => (near, false),
+                _ => continue,
+            };
+            let pos = self.bot_pos(&bid).unwrap();
+            let partners = cmds.iter()
+                .filter(|&&(other_bid, other_cmd)| {
+                    let back = match (other_cmd, wants_secondary) {
+                        (BotCommand::FusionS{ near: back }, true) | (BotCommand::FusionP{ near: back }, false) => back,
+                        _ => return false,
+                    };
+                    let other_pos = self.bot_pos(&other_bid).unwrap();
+                    other_pos == pos.add(near) && other_pos.add(back) == pos
+                })
+                .count();
+            if partners != 1 {
+                return Err(Error::FusionUnmatched{bid})
+            }
+        }
+
+        let mut groups: Vec<(Region, usize)> = Vec::new();
+        for &(bid, ref cmd) in cmds {
+            if let Some(group_reg) = self.group_region(&bid, cmd) {
+                match groups.iter().position(|g| g.0 == group_reg) {
+                    Some(index) => groups[index].1 += 1,
+                    None => groups.push((group_reg, 1)),
+                }
+            }
+        }
+        for &(group_reg, members) in &groups {
+            let corners = match group_reg.dimension() {
+                RegionDim::Point => 1,
+                RegionDim::Line => 2,
+                RegionDim::Plane => 4,
+                RegionDim::Box => 8,
+            };
+            if members != corners {
+                return Err(Error::GroupIncomplete{r: group_reg})
+            }
+        }
+        Ok(volatile.into_iter().map(|(_, region)| region).collect())
+    }
+
+    // The volatile regions of a tick, only the preconditions checked, see `tick_volatile`.
+    pub fn volatile_regions(&self, commands: &[(Bid, BotCommand)]) -> Result<Vec<Region>, Error> {
+        self.tick_volatile(commands)
+            .map(|volatile| volatile.into_iter().map(|(_, region)| region).collect())
+    }
+
+    // Every command's volatile regions with the bid they belong to, in the commands order. The group
+    // region is volatile once for all its members, so it is listed for the first of them only.
+    fn tick_volatile(&self, cmds: &[(Bid, BotCommand)]) -> Result<Vec<(Bid, Region)>, Error> {
+        let mut volatile = Vec::with_capacity(cmds.len() * 2);
+        let mut groups_seen: Vec<Region> = Vec::new();
+        for &(bid, ref cmd) in cmds {
+            let (vol1, maybe_vol2) = self.check_precondition(&bid, cmd)?;
+            volatile.push((bid, vol1));
+            if let Some(vol2) = maybe_vol2 {
+                if let Some(group_reg) = self.group_region(&bid, cmd) {
+                    if groups_seen.contains(&group_reg) {
+                        continue;
+                    }
+                    groups_seen.push(group_reg);
+                }
+                volatile.push((bid, vol2));
+            }
+        }
+        Ok(volatile)
+    }
+
+    /// Region shared by all the bots taking part in the same `GFill` or `GVoid`.
+    pub fn group_region(&self, bid: &Bid, cmd: &BotCommand) -> Option<Region> {
+        match cmd {
+            BotCommand::GFill{ near, far } | BotCommand::GVoid{ near, far } => {
+                let cn = self.bot_pos(bid)?.add(*near);
+                Some(Region::from_corners(&cn, &cn.add(*far)))
             },
-            BotCommand::GFill{ near: _, far: _ } => unimplemented!(),
-            BotCommand::GVoid{ near: _, far: _ } => unimplemented!(),
+            _ => None,
         }
     }
 
@@ -276,6 +562,7 @@ impl State {
 
                 self.bots.get_mut(&bid).unwrap().pos = cf;
                 self.energy += 2 * d.l_1_norm();
+                self.breakdown.moves += 2 * d.l_1_norm();
             },
             BotCommand::LMove{ short1, short2 } => {
                 let c = self.bots.get(&bid).unwrap().pos;
@@ -288,6 +575,7 @@ impl State {
 
                 self.bots.get_mut(&bid).unwrap().pos = cff;
                 self.energy += 2 * (d1.l_1_norm() + 2 + d2.l_1_norm());
+                self.breakdown.moves += 2 * (d1.l_1_norm() + 2 + d2.l_1_norm());
             },
             BotCommand::Fill{ near } => {
                 let c = self.bots.get(&bid).unwrap().pos;
@@ -295,12 +583,13 @@ impl State {
                 let n = *near;
                 let cf = c.add(n);
 
-                if !self.matrix.is_filled(&cf) {
-                    self.matrix.set_filled(&cf);
+                if self.matrix.apply_fill(&cf) {
                     self.energy += 12;
+                    self.breakdown.fill_void += 12;
                 }
                 else {
                     self.energy += 6;
+                    self.breakdown.fill_void += 6;
                 }
             },
             BotCommand::Void{ near } => {
@@ -309,12 +598,13 @@ impl State {
                 let n = *near;
                 let cf = c.add(n);
 
-                if self.matrix.is_filled(&cf) {
-                    self.matrix.set_void(&cf);
+                if self.matrix.apply_void(&cf) {
                     self.energy -= 12;
+                    self.breakdown.fill_void -= 12;
                 }
                 else {
                     self.energy += 3;
+                    self.breakdown.fill_void += 3;
                 }
             },
             BotCommand::Fission{ near, split_m } => {
@@ -336,8 +626,10 @@ impl State {
                 };
                 self.bots.insert(new_bid, new_bot);
                 self.energy += 24;
+                self.breakdown.fission_fusion += 24;
             },
             BotCommand::FusionP{ near } => {
+                /* `check_tick` made sure the secondary is there and fuses with this primary only */
                 let c = self.bots.get(&bid).unwrap().pos;
 
                 let n = *near;
@@ -358,11 +650,56 @@ impl State {
                     self.bots.get_mut(&bid).unwrap().seeds.sort();
                     self.bots.remove(&other_bid);
                     self.energy -= 24;
+                    self.breakdown.fission_fusion -= 24;
                 }
             },
             BotCommand::FusionS{ near: _ } => {}, /* Everything is done by FusionP cmd */
-            BotCommand::GFill{ near: _, far: _ } => unimplemented!(),
-            BotCommand::GVoid{ near: _, far: _ } => unimplemented!(),
+            BotCommand::GFill{ .. } => {
+                /* the whole region is filled by the first bot of the group, see `group_region` */
+                let region = self.group_region(bid, cmd).unwrap();
+                for cf in region.coord_set() {
+                    if !self.matrix.is_filled(&cf) {
+                        self.matrix.set_filled(&cf);
+                        self.energy += 12;
+                        self.breakdown.fill_void += 12;
+                    }
+                    else {
+                        self.energy += 6;
+                        self.breakdown.fill_void += 6;
+                    }
+                }
+            },
+            BotCommand::GVoid{ .. } => {
+                let region = self.group_region(bid, cmd).unwrap();
+                for cf in region.coord_set() {
+                    if self.matrix.is_filled(&cf) {
+                        self.matrix.set_void(&cf);
+                        self.energy -= 12;
+                        self.breakdown.fill_void -= 12;
+                    }
+                    else {
+                        self.energy += 3;
+                        self.breakdown.fill_void += 3;
+                    }
+                }
+            },
+        }
+    }
+
+    fn perform_tick_mut(&mut self, bids: &[Bid], cmds: &[BotCommand]) {
+        let mut groups_done: Vec<Region> = Vec::new();
+        for (bid, cmd) in bids.iter().zip(cmds.iter()) {
+            if let Some(region) = self.group_region(bid, cmd) {
+                if groups_done.contains(&region) {
+                    continue;
+                }
+                groups_done.push(region);
+            }
+            let energy_before = self.energy as i64;
+            self.perform_mut(bid, cmd);
+            if let Some(ref mut energy_by_bot) = self.energy_by_bot {
+                *energy_by_bot.entry(*bid).or_insert(0) += self.energy as i64 - energy_before;
+            }
         }
     }
 
@@ -397,93 +734,178 @@ impl State {
         where T : Iterator<Item = BotCommand> {
 
         /* check the state is well-formed */
-        let wf = self.wellformed();
-        if WellformedStatus::Wellformed != wf {
-            return Err(Error::StateNotWellformed{status: wf})
+        if self.scoring_config.check_wellformed {
+            let report = self.wellformed();
+            if WellformedReport::Wellformed != report {
+                return Err(Error::StateNotWellformed{report})
+            }
         }
 
         /* check there are enough commands */
         let bids: Vec<Bid> = self.bots.keys().cloned().collect();
         let cmds: Vec<BotCommand> = cmd_iter.take(bids.len()).collect();
+        if cmds.len() < bids.len() {
+            return Err(Error::NotEnoughCommands);
+        }
 
         /* check command preconditions & end commands interference */
-        let mut volatile: Vec<Region> = Vec::with_capacity(bids.len() * 2);
-        let mut bid_iter = bids.iter();
-        let mut cmd_iter = cmds.iter();
-        loop {
-            match (bid_iter.next(), cmd_iter.next()) {
-                (Some(bid), Some(cmd)) => {
-                    let res = self.check_precondition(bid, &cmd);
-                    match res {
-                        Ok((vol1, maybe_vol2)) => {
-                            for vol_reg in &volatile {
-                                if vol_reg.intersects(&vol1) {
-                                    println!("cmd: {:?}", cmd);
-                                    return Err(Error::CommandsInterfere)
-                                }
-                            }
-
-                            if let Some(vol2) = maybe_vol2 {
-                                for vol_reg in &volatile {
-                                    if vol_reg.intersects(&vol2) {
-                                        println!("cmd: {:?}", cmd);
-                                        return Err(Error::CommandsInterfere)
-                                    }
-                                }
-
-                                volatile.push(vol2);
-                            }
-                            volatile.push(vol1);
-                        },
-                        Err(e) => return Err(e),
-                    }
-                },
-                (Some(_), None) => { return Err(Error::NotEnoughCommands); }
-                (None, _) => { break; }
-            }
+        let tick: Vec<(Bid, BotCommand)> = bids.iter().cloned().zip(cmds.iter().cloned()).collect();
+        if self.scoring_config.check_preconditions {
+            self.check_tick(&tick)?;
+        }
+
+        /* only a Halt may leave no bots: primaries fusing each other away may not */
+        let fused_away: HashSet<Coord> = tick.iter()
+            .filter_map(|&(bid, ref cmd)| match *cmd {
+                BotCommand::FusionP{ near } => self.bot_pos(&bid).map(|c| c.add(near)),
+                _ => None,
+            })
+            .collect();
+        if !fused_away.is_empty() && self.bots.values().all(|bot| fused_away.contains(&bot.pos)) {
+            return Err(Error::AllBotsFusedAway);
         }
 
         // energy step for the step itself
-        match self.harmonics {
+        let harmonics_energy = match self.harmonics {
             Harmonics::Low =>
-                self.energy += 3 * self.matrix.dim() * self.matrix.dim() * self.matrix.dim(),
+                3 * self.matrix.dim() * self.matrix.dim() * self.matrix.dim(),
             Harmonics::High =>
-                self.energy += 30 * self.matrix.dim() * self.matrix.dim() * self.matrix.dim(),
-        }
+                30 * self.matrix.dim() * self.matrix.dim() * self.matrix.dim(),
+        };
+        self.energy += harmonics_energy;
+        self.breakdown.harmonics += harmonics_energy;
 
         // energy for each nanobot
         self.energy += 20 * self.bots.len();
+        self.breakdown.bots += 20 * self.bots.len();
+        self.commands_executed += cmds.len();
+
+        /* the ambient energy split evenly over the bots, the remainder to the lowest bids so it all adds up */
+        if let Some(ref mut energy_by_bot) = self.energy_by_bot {
+            if !bids.is_empty() {
+                let share = harmonics_energy / bids.len();
+                let remainder = harmonics_energy % bids.len();
+                for (index, bid) in bids.iter().enumerate() {
+                    let extra = if index < remainder { 1 } else { 0 };
+                    *energy_by_bot.entry(*bid).or_insert(0) += (share + extra + 20) as i64;
+                }
+            }
+        }
 
-        let mut bid_iter = bids.iter();
-        let mut cmd_iter = cmds.iter();
-        loop {
-            match (bid_iter.next(), cmd_iter.next()) {
-                (Some(bid), Some(cmd)) => {
-                    self.perform_mut(bid, &cmd);
-                },
-                (Some(_), None) => { return Err(Error::NotEnoughCommands); }
-                (None, _) => { break; }
+        self.perform_tick_mut(&bids, &cmds);
+        if self.bots.len() > self.peak_bots {
+            self.peak_bots = self.bots.len();
+        }
+        if self.events.is_some() {
+            let bots = tick.iter()
+                .map(|&(bid, cmd)| BotEvent { bid, cmd, pos: self.bot_pos(&bid), })
+                .collect();
+            if let Some(ref mut events) = self.events {
+                events.push(TickEvent { bots, });
             }
         }
         Ok(())
     }
 
+    pub fn model_diff(&self, target: &Matrix) -> ModelDiff {
+        let (extra, missing) = self.matrix.diff(target);
+        ModelDiff { extra, missing, }
+    }
+
+    // commands per tick over the `steps` counted so far (as `run_mut` counts them), 0 before any
+    pub fn avg_bots_per_tick(&self) -> f64 {
+        if self.steps == 0 {
+            0.0
+        } else {
+            self.commands_executed as f64 / self.steps as f64
+        }
+    }
+
+    pub fn energy_breakdown(&self) -> EnergyBreakdown {
+        self.breakdown
+    }
+
+    // from now on attribute the energy to the bots: the commands energy (moves, fills and voids,
+    // fissions and fusions) to the bots issuing them, and every tick's harmonics energy split evenly
+    // over the active bots along with their own per bot overhead, so the totals add up to `energy`
+    pub fn track_energy_by_bot(&mut self) {
+        if self.energy_by_bot.is_none() {
+            self.energy_by_bot = Some(BTreeMap::new());
+        }
+    }
+
+    // every bot that issued a command while tracking, idle ones with zero; empty when not tracking
+    pub fn energy_by_bot(&self) -> BTreeMap<Bid, i64> {
+        self.energy_by_bot.clone().unwrap_or_default()
+    }
+
+    // one entry per tick performed by `step_mut` with the log on, empty when it is off
+    pub fn events(&self) -> &[TickEvent] {
+        self.events.as_ref().map(|events| &events[..]).unwrap_or(&[])
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            steps: self.steps,
+            energy: self.energy,
+            peak_bots: self.peak_bots,
+            commands_executed: self.commands_executed,
+            breakdown: self.breakdown,
+            harmonics: self.harmonics,
+            matrix: self.matrix.clone(),
+            bots: self.bots.clone(),
+            energy_by_bot: self.energy_by_bot.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.steps = checkpoint.steps;
+        self.energy = checkpoint.energy;
+        self.peak_bots = checkpoint.peak_bots;
+        self.commands_executed = checkpoint.commands_executed;
+        self.breakdown = checkpoint.breakdown;
+        self.harmonics = checkpoint.harmonics;
+        self.matrix = checkpoint.matrix.clone();
+        self.bots = checkpoint.bots.clone();
+        self.energy_by_bot = checkpoint.energy_by_bot.clone();
+        self.events = checkpoint.events.clone();
+    }
+
     pub fn run_mut(&mut self, commands: Vec<BotCommand>) -> Result<(), Error> {
+        self.run_mut_with(commands, usize::max_value(), |_| Ok(()))
+    }
+
+    // The same as `run_mut`, but gives up if the trace has not halted after `max_steps` steps.
+    pub fn run_mut_bounded(&mut self, commands: Vec<BotCommand>, max_steps: usize) -> Result<(), Error> {
+        self.run_mut_with(commands, max_steps, |_| Ok(()))
+    }
+
+    // The same as `run_mut`, but gives up as soon as the energy spent goes over `energy_cap`.
+    pub fn run_mut_capped(&mut self, commands: Vec<BotCommand>, energy_cap: i64) -> Result<(), Error> {
+        self.run_mut_with(commands, usize::max_value(), |state| if state.energy as i64 > energy_cap {
+            Err(Error::EnergyCapExceeded{at_step: state.steps})
+        } else {
+            Ok(())
+        })
+    }
+
+    // The loop behind the `run_mut` family: runs at most `max_steps` steps and hands the state to
+    // `after_step` right after each of them (the halting one included), stopping on the first error
+    // it returns.
+    pub fn run_mut_with<F>(&mut self, commands: Vec<BotCommand>, max_steps: usize, mut after_step: F) -> Result<(), Error>
+        where F: FnMut(&State) -> Result<(), Error> {
         let mut cmd_iter = commands.into_iter();
-        loop {
+        for _ in 0 .. max_steps {
             self.steps += 1;
-            let res = self.step_mut(&mut cmd_iter);
-            match res {
-                Err(e) => {
-                    return Err(e);
-                },
-                Ok(_) => ()
-            }
+            self.step_mut(&mut cmd_iter)?;
+            after_step(self)?;
 
             if self.is_halt() {
                 return Ok(())
             }
         }
+        Err(Error::StepLimitExceeded{steps: max_steps})
     }
 
 
@@ -493,9 +915,7 @@ impl State {
         let bids: Vec<Bid> = self.bots.keys().cloned().collect();
 
         let len = self.bots.len();
-        for (bid, cmd) in bids.iter().zip(commands[*next_command .. (*next_command + len)].iter()) {
-            self.perform_mut(bid, &cmd);
-        }
+        self.perform_tick_mut(&bids, &commands[*next_command .. (*next_command + len)]);
         *next_command += len;
         
         Ok(())
@@ -542,6 +962,130 @@ impl State {
     
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SimulationReport {
+    pub energy: usize,
+    pub steps: usize,
+    pub commands_executed: usize,
+    pub success: bool,
+    pub failure: Option<SimulationFailure>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SimulationFailure {
+    pub step: usize,
+    pub error: Error,
+}
+
+pub fn simulate(source: Matrix, target: Matrix, cmds: &[BotCommand]) -> SimulationReport {
+    let mut state = State::new(source, vec![]);
+    let failure = state.run_mut(cmds.to_vec())
+        .err()
+        .map(|error| SimulationFailure { step: state.steps, error, });
+    SimulationReport {
+        energy: state.energy,
+        steps: state.steps,
+        commands_executed: state.commands_executed,
+        success: failure.is_none() && state.model_diff(&target).is_empty(),
+        failure,
+    }
+}
+
+// Energy of a trace known to be valid, replaying only what energy depends on: bots count,
+// harmonics and per-command costs. No positions or matrix are tracked, so every Fill (GFill)
+// is assumed to hit a void voxel and every Void (GVoid) a full one, which holds for traces
+// without redundant fills.
+pub fn estimate_energy(matrix_dim: usize, cmds: &[BotCommand]) -> i64 {
+    let volume = (matrix_dim * matrix_dim * matrix_dim) as i64;
+    let mut energy: i64 = 0;
+    let mut harmonics = Harmonics::Low;
+    // each group member accounts for `8 / corners` eighths of the group region
+    let mut group_eighths: i64 = 0;
+    let mut bots: i64 = 1;
+    for tick in cmd::group_by_ticks(cmds) {
+        energy += match harmonics {
+            Harmonics::Low => 3 * volume,
+            Harmonics::High => 30 * volume,
+        };
+        energy += 20 * bots;
+        for command in tick {
+            match command {
+                BotCommand::Halt =>
+                    bots -= 1,
+                BotCommand::Wait =>
+                    (),
+                BotCommand::Flip =>
+                    harmonics = match harmonics {
+                        Harmonics::Low => Harmonics::High,
+                        Harmonics::High => Harmonics::Low,
+                    },
+                BotCommand::SMove{ long } =>
+                    energy += 2 * long.to_coord_diff().l_1_norm() as i64,
+                BotCommand::LMove{ short1, short2 } =>
+                    energy += 2 * (short1.to_coord_diff().l_1_norm() + 2 + short2.to_coord_diff().l_1_norm()) as i64,
+                BotCommand::Fill{ .. } =>
+                    energy += 12,
+                BotCommand::Void{ .. } =>
+                    energy -= 12,
+                BotCommand::Fission{ .. } => {
+                    bots += 1;
+                    energy += 24;
+                },
+                BotCommand::FusionP{ .. } =>
+                    energy -= 24,
+                BotCommand::FusionS{ .. } =>
+                    bots -= 1,
+                BotCommand::GFill{ far, .. } =>
+                    group_eighths += 12 * group_member_eighths(&far.0),
+                BotCommand::GVoid{ far, .. } =>
+                    group_eighths -= 12 * group_member_eighths(&far.0),
+            }
+        }
+    }
+    energy + group_eighths / 8
+}
+
+fn group_member_eighths(far: &Coord) -> i64 {
+    let sides = [far.x, far.y, far.z];
+    let region_volume: i64 = sides.iter().map(|side| side.abs() as i64 + 1).product();
+    let corners: i64 = sides.iter().map(|&side| if side == 0 { 1 } else { 2 }).product();
+    region_volume * (8 / corners)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceComparison {
+    pub a: SimulationReport,
+    pub b: SimulationReport,
+    pub energy_diff_percent: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompareError {
+    TraceA(SimulationFailure),
+    TraceB(SimulationFailure),
+}
+
+pub fn compare_traces(source: &Matrix, target: &Matrix, a: &[BotCommand], b: &[BotCommand]) -> Result<TraceComparison, CompareError> {
+    let report_a = simulate(source.clone(), target.clone(), a);
+    if let Some(failure) = report_a.failure {
+        return Err(CompareError::TraceA(failure));
+    }
+    let report_b = simulate(source.clone(), target.clone(), b);
+    if let Some(failure) = report_b.failure {
+        return Err(CompareError::TraceB(failure));
+    }
+    let energy_diff_percent = if report_a.energy == 0 {
+        0.0
+    } else {
+        (report_b.energy as f64 - report_a.energy as f64) * 100.0 / report_a.energy as f64
+    };
+    Ok(TraceComparison {
+        a: report_a,
+        b: report_b,
+        energy_diff_percent,
+    })
+}
+
 #[derive(Debug,Copy,Clone)]
 pub struct Wellness {
     pub ok: bool,
@@ -560,6 +1104,7 @@ mod test {
             Resolution,
             LinearCoordDiff,
             Axis,
+            M,
         }
     };
 
@@ -701,6 +1246,26 @@ mod test {
         assert_eq!(res, Err(Error::MoveOutOfBounds{c: Coord {x:1, y:0, z:0}}));
     }
 
+    #[test]
+    fn check_precondition_far_fill() {
+        let matrix = Matrix::new(Resolution(4));
+        let state = State::new(matrix, vec![]);
+
+        /* within bounds but not adjacent, only a hand-built command can have it */
+        let df = CoordDiff{0: Coord { x:2, y:0, z:0 }};
+        let res = state.check_precondition(&1, &BotCommand::Fill{ near: df });
+        assert_eq!(res, Err(Error::NotNear{d: df}));
+        let res = state.check_precondition(&1, &BotCommand::Void{ near: df });
+        assert_eq!(res, Err(Error::NotNear{d: df}));
+
+        let df = CoordDiff{0: Coord { x:1, y:1, z:1 }};
+        let res = state.check_precondition(&1, &BotCommand::Fill{ near: df });
+        assert_eq!(res, Err(Error::NotNear{d: df}));
+
+        let df = CoordDiff{0: Coord { x:1, y:1, z:0 }};
+        assert!(state.check_precondition(&1, &BotCommand::Fill{ near: df }).is_ok());
+    }
+
     #[test]
     fn do_cmd_void() {
         let matrix = Matrix::new(Resolution(4));
@@ -871,5 +1436,609 @@ mod test {
         assert_eq!(res, Ok(()));
         assert_eq!(state.steps, 212);
         assert_eq!(state.energy, 45727148);
+        assert_eq!(state.commands_executed, 1212);
+        assert_eq!(state.avg_bots_per_tick(), 1212.0 / 212.0);
+        assert!(State::new(Matrix::new(Resolution(3)), vec![]).avg_bots_per_tick() == 0.0);
+
+        let breakdown = state.energy_breakdown();
+        assert_eq!(
+            (breakdown.harmonics + breakdown.bots + breakdown.moves) as isize
+                + breakdown.fill_void + breakdown.fission_fusion,
+            state.energy as isize,
+        );
+        assert!(state.peak_bots > 1);
+    }
+
+    #[test]
+    fn trusted_scoring_fa001() {
+        let matrix = super::super::model::read_model(FA001_TGT_MDL).unwrap().new_empty_of_same_size();
+        let cmds = super::super::cmd::from_bytes(FA001_MULTIBOT_NBT).unwrap();
+
+        let mut full = State::new(matrix.clone(), vec![]);
+        assert_eq!(full.scoring_config(), ScoringConfig::default());
+        full.run_mut(cmds.clone()).unwrap();
+
+        let mut fast = State::new(matrix, vec![]).with_scoring_config(ScoringConfig::trusted());
+        assert_eq!(fast.run_mut(cmds), Ok(()));
+        assert_eq!(fast.steps, full.steps);
+        assert_eq!(fast.energy, full.energy);
+        assert_eq!(fast.energy_breakdown(), full.energy_breakdown());
+        assert_eq!(fast.matrix, full.matrix);
+    }
+
+    #[test]
+    fn run_capped_fa001() {
+        let matrix = super::super::model::read_model(FA001_TGT_MDL).unwrap().new_empty_of_same_size();
+        let cmds = super::super::cmd::from_bytes(FA001_MULTIBOT_NBT).unwrap();
+
+        let mut state = State::new(matrix.clone(), vec![]);
+        match state.run_mut_capped(cmds.clone(), 1_000_000) {
+            Err(Error::EnergyCapExceeded{at_step}) => {
+                assert_eq!(at_step, state.steps);
+                assert!(at_step < 212);
+                assert!(state.energy > 1_000_000);
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // the final energy itself is still within the cap
+        let mut state = State::new(matrix, vec![]);
+        assert_eq!(state.run_mut_capped(cmds, 45727148), Ok(()));
+        assert_eq!(state.steps, 212);
+        assert_eq!(state.energy, 45727148);
+    }
+
+    #[test]
+    fn run_bounded_never_halting() {
+        let matrix = Matrix::new(Resolution(3));
+        let waits = vec![BotCommand::Wait; 100];
+
+        let mut state = State::new(matrix.clone(), vec![]);
+        assert_eq!(state.run_mut_bounded(waits.clone(), 10), Err(Error::StepLimitExceeded{steps: 10}));
+        assert_eq!(state.steps, 10);
+
+        let mut state = State::new(matrix, vec![]);
+        let mut cmds = waits;
+        cmds.truncate(5);
+        cmds.push(BotCommand::Halt);
+        assert_eq!(state.run_mut_bounded(cmds, 10), Ok(()));
+        assert_eq!(state.steps, 6);
+    }
+
+    #[test]
+    fn run_with_hook_after_every_step() {
+        let matrix = Matrix::new(Resolution(3));
+        let cmds = vec![
+            BotCommand::Fill { near: CoordDiff(Coord { x: 1, y: 0, z: 0, }), },
+            BotCommand::Wait,
+            BotCommand::Halt,
+        ];
+
+        let mut steps_seen = Vec::new();
+        let mut state = State::new(matrix.clone(), vec![]);
+        assert_eq!(state.run_mut_with(cmds.clone(), 10, |state| { steps_seen.push((state.steps, state.is_halt())); Ok(()) }), Ok(()));
+        assert_eq!(steps_seen, vec![(1, false), (2, false), (3, true)]);
+
+        // the first error from the hook stops the run right after that step
+        let mut state = State::new(matrix, vec![]);
+        let res = state.run_mut_with(cmds, 10, |state| if state.steps == 2 {
+            Err(Error::StepLimitExceeded{steps: state.steps})
+        } else {
+            Ok(())
+        });
+        assert_eq!(res, Err(Error::StepLimitExceeded{steps: 2}));
+        assert_eq!(state.steps, 2);
+        assert!(state.matrix.is_filled(&Coord { x: 1, y: 0, z: 0, }));
+    }
+
+    #[test]
+    fn multibot_fa001_model_diff() {
+        let target = super::super::model::read_model(FA001_TGT_MDL).unwrap();
+        let cmds = super::super::cmd::from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let mut state = State::new(target.new_empty_of_same_size(), vec![]);
+        state.run_mut(cmds).unwrap();
+        assert!(state.model_diff(&target).is_empty());
+
+        let mut wrong_target = target.clone();
+        let removed = *target.filled_voxels().min().unwrap();
+        wrong_target.set_void(&removed);
+        let added = Coord { x: 0, y: 0, z: 0, };
+        wrong_target.set_filled(&added);
+        let diff = state.model_diff(&wrong_target);
+        assert_eq!(diff.extra, vec![removed]);
+        assert_eq!(diff.missing, vec![added]);
+    }
+
+    #[test]
+    fn simulate_fa001() {
+        let target = super::super::model::read_model(FA001_TGT_MDL).unwrap();
+        let cmds = super::super::cmd::from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let report = simulate(target.new_empty_of_same_size(), target.clone(), &cmds);
+        assert_eq!(report, SimulationReport {
+            energy: 45727148,
+            steps: 212,
+            commands_executed: 1212,
+            success: true,
+            failure: None,
+        });
+
+        let report = simulate(target.new_empty_of_same_size(), target.new_empty_of_same_size(), &cmds);
+        assert!(!report.success);
+        assert_eq!(report.failure, None);
+
+        let report = simulate(target.new_empty_of_same_size(), target.clone(), &cmds[.. 100]);
+        assert!(!report.success);
+        assert_eq!(report.failure.map(|f| f.error), Some(Error::NotEnoughCommands));
+        assert_eq!(report.failure.map(|f| f.step), Some(report.steps));
+    }
+
+    #[test]
+    fn estimate_energy_fa001() {
+        let target = super::super::model::read_model(FA001_TGT_MDL).unwrap();
+        let cmds = super::super::cmd::from_bytes(FA001_MULTIBOT_NBT).unwrap();
+        let mut state = State::new(target.new_empty_of_same_size(), vec![]);
+        state.run_mut(cmds.clone()).unwrap();
+        assert_eq!(super::estimate_energy(target.dim(), &cmds), state.energy as i64);
+    }
+
+    #[test]
+    fn estimate_energy_gfill() {
+        let cmds = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 0).unwrap(),
+            BotCommand::gfill(CoordDiff(Coord { x: 0, y: 0, z: 1, }), CoordDiff(Coord { x: 2, y: 0, z: 0, })).unwrap(),
+            BotCommand::gfill(CoordDiff(Coord { x: 1, y: 0, z: 1, }), CoordDiff(Coord { x: -2, y: 0, z: 0, })).unwrap(),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0, })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ];
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        state.run_mut(cmds.clone()).unwrap();
+        assert_eq!(state.matrix.count_filled(), 3);
+        assert_eq!(super::estimate_energy(4, &cmds), state.energy as i64);
+    }
+
+    #[test]
+    fn volatile_regions_fission_fill() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 1).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.bots.len(), 2);
+
+        let point = |x, y, z| Region::from_corners(&Coord { x, y, z, }, &Coord { x, y, z, });
+        let regions = state.volatile_regions(&[
+            (1, BotCommand::fission(CoordDiff(Coord { x: 0, y: 1, z: 0, }), 0).unwrap()),
+            (2, BotCommand::fill(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap()),
+        ]).unwrap();
+        assert_eq!(regions, vec![
+            point(0, 0, 0),
+            point(0, 1, 0),
+            point(1, 0, 0),
+            point(2, 0, 0),
+        ]);
+
+        assert_eq!(
+            state.volatile_regions(&[(3, BotCommand::wait().unwrap())]),
+            Err(Error::InvalidBid { bid: 3, }),
+        );
+    }
+
+    #[test]
+    fn bots_snapshot_after_fission() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        assert_eq!(state.bots_snapshot(), vec![(1, Coord { x: 0, y: 0, z: 0, })]);
+        assert_eq!(state.active_bot_count(), 1);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 0, y: 1, z: 1, }), 3).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.active_bot_count(), 2);
+        assert_eq!(state.bots_snapshot(), vec![
+            (1, Coord { x: 0, y: 0, z: 0, }),
+            (2, Coord { x: 0, y: 1, z: 1, }),
+        ]);
+    }
+
+    #[test]
+    fn energy_by_bot_two_bots() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        assert!(state.energy_by_bot().is_empty());
+        state.track_energy_by_bot();
+        let smove_z = |value| BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Z, value, }).unwrap();
+        state.run_mut(vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 0).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: 0, z: 1, })).unwrap(), smove_z(2),
+            BotCommand::wait().unwrap(), smove_z(-2),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0, })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ]).unwrap();
+
+        let energy_by_bot = state.energy_by_bot();
+        // fission + fill - fusion for the first, two moves for the second; the first one is alone in the
+        // first and the last tick and takes all the 3 * 4^3 harmonics then, they share it in the other three
+        assert_eq!(energy_by_bot.into_iter().collect::<Vec<_>>(), vec![
+            (1, 24 + 12 - 24 + 2 * (192 + 20) + 3 * (96 + 20)),
+            (2, 4 + 4 + 3 * (96 + 20)),
+        ]);
+        assert_eq!(state.energy_by_bot().values().sum::<i64>(), state.energy as i64);
+
+        // 3 * 3^3 does not split in two: the lower bid takes the odd one
+        let mut state = State::new(Matrix::new(Resolution(3)), vec![]);
+        state.track_energy_by_bot();
+        state.run_mut(vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 0).unwrap(),
+            BotCommand::pfusion(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: -1, y: 0, z: 0, })).unwrap(),
+            BotCommand::halt().unwrap(),
+        ]).unwrap();
+        assert_eq!(state.energy_by_bot().into_iter().collect::<Vec<_>>(), vec![
+            (1, 24 - 24 + 2 * (81 + 20) + 41 + 20),
+            (2, 40 + 20),
+        ]);
+        assert_eq!(state.energy_by_bot().values().sum::<i64>(), state.energy as i64);
+    }
+
+    #[test]
+    fn check_tick_regions() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        let energy = state.energy;
+        let region = |min: (M, M, M), max: (M, M, M)| Region {
+            min: Coord { x: min.0, y: min.1, z: min.2, },
+            max: Coord { x: max.0, y: max.1, z: max.2, },
+        };
+        let smove_z = BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Z, value: 2, }).unwrap();
+
+        // both bots go along z side by side
+        assert_eq!(state.check_tick(&[(1, smove_z), (2, smove_z)]), Ok(vec![
+            region((0, 0, 0), (0, 0, 2)),
+            region((1, 0, 0), (1, 0, 2)),
+        ]));
+
+        // the second bot steps into the path of the first one
+        let lmove = BotCommand::lmove(
+            LinearCoordDiff::Short { axis: Axis::X, value: -1, },
+            LinearCoordDiff::Short { axis: Axis::Z, value: 1, },
+        ).unwrap();
+        assert_eq!(state.check_tick(&[(1, smove_z), (2, lmove)]), Err(Error::CommandsInterfere {
+            bid_a: 1,
+            bid_b: 2,
+            region: region((0, 0, 0), (0, 0, 0)),
+        }));
+        assert_eq!(state.check_tick(&[(1, smove_z), (3, smove_z)]), Err(Error::InvalidBid { bid: 3 }));
+
+        // nothing is performed
+        assert_eq!(state.bot_pos(&1), Some(Coord { x: 0, y: 0, z: 0, }));
+        assert_eq!(state.bot_pos(&2), Some(Coord { x: 1, y: 0, z: 0, }));
+        assert_eq!(state.energy, energy);
+    }
+
+    #[test]
+    fn step_mut_interference_location() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+
+        // both bots fill (0, 0, 1): the reported region is just that voxel
+        let mut trace_it = vec![
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: 0, z: 1, })).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: -1, y: 0, z: 1, })).unwrap(),
+        ].into_iter();
+        assert_eq!(state.step_mut(&mut trace_it), Err(Error::CommandsInterfere {
+            bid_a: 1,
+            bid_b: 2,
+            region: Region::from_corners(&Coord { x: 0, y: 0, z: 1, }, &Coord { x: 0, y: 0, z: 1, }),
+        }));
+        assert!(!state.matrix.is_filled(&Coord { x: 0, y: 0, z: 1, }));
+    }
+
+    #[test]
+    fn step_mut_short_tick() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        let energy = state.energy;
+
+        // the only command left would fill where the second bot stands, but the tick is short anyway
+        let mut trace_it = vec![BotCommand::fill(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap()].into_iter();
+        assert_eq!(state.step_mut(&mut trace_it), Err(Error::NotEnoughCommands));
+        assert_eq!(state.energy, energy);
+    }
+
+    #[test]
+    fn fill_on_other_bot() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+
+        // the first bot fills where the second one stands, whatever the second one does
+        let fill = BotCommand::fill(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap();
+        let error = Error::FillOnBot { bid: 2, c: Coord { x: 1, y: 0, z: 0, }, };
+        assert_eq!(state.check_tick(&[(1, fill), (2, BotCommand::Wait)]), Err(error));
+        let mut trace_it = vec![fill, BotCommand::Wait].into_iter();
+        assert_eq!(state.step_mut(&mut trace_it), Err(error));
+        let mut trace_it = vec![fill, BotCommand::fill(CoordDiff(Coord { x: 0, y: 1, z: 0, })).unwrap()].into_iter();
+        assert_eq!(state.step_mut(&mut trace_it), Err(error));
+        assert!(!state.matrix.is_filled(&Coord { x: 1, y: 0, z: 0, }));
+
+        // and the other way round
+        let fill_back = BotCommand::fill(CoordDiff(Coord { x: -1, y: 0, z: 0, })).unwrap();
+        let mut trace_it = vec![BotCommand::Wait, fill_back].into_iter();
+        assert_eq!(state.step_mut(&mut trace_it), Err(Error::FillOnBot { bid: 1, c: Coord { x: 0, y: 0, z: 0, }, }));
+        assert!(!state.matrix.is_filled(&Coord { x: 0, y: 0, z: 0, }));
+    }
+
+    #[test]
+    fn mutual_fusion_leaves_no_bots() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap(),
+            BotCommand::FusionP { near: CoordDiff(Coord { x: 1, y: 0, z: 0, }), },
+            BotCommand::FusionP { near: CoordDiff(Coord { x: -1, y: 0, z: 0, }), },
+        ].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        let tick: Vec<BotCommand> = trace_it.collect();
+        assert_eq!(state.step_mut(&mut tick.clone().into_iter()), Err(Error::FusionUnmatched { bid: 1, }));
+        // even with the commands checks off
+        state = state.with_scoring_config(ScoringConfig::trusted());
+        assert_eq!(state.step_mut(&mut tick.into_iter()), Err(Error::AllBotsFusedAway));
+        assert_eq!(state.bots.len(), 2);
+    }
+
+    #[test]
+    fn fusion_pairing() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let near = |x, y, z| CoordDiff(Coord { x, y, z, });
+        let mut trace_it = vec![
+            BotCommand::fission(near(1, 0, 0), 5).unwrap(),
+            BotCommand::fission(near(0, 0, 1), 0).unwrap(), BotCommand::fission(near(1, 0, 0), 0).unwrap(),
+        ].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.bots_snapshot(), vec![
+            (1, Coord { x: 0, y: 0, z: 0, }),
+            (2, Coord { x: 1, y: 0, z: 0, }),
+            (3, Coord { x: 2, y: 0, z: 0, }),
+            (8, Coord { x: 0, y: 0, z: 1, }),
+        ]);
+        let (p, s) = (BotCommand::FusionP { near: near(1, 0, 0), }, BotCommand::FusionS { near: near(-1, 0, 0), });
+
+        // a chain: 1 fuses 2, which fuses 3
+        assert_eq!(state.check_tick(&[(1, p), (2, p), (3, s), (8, BotCommand::Wait)]), Err(Error::FusionUnmatched { bid: 1, }));
+        // a secondary nobody fuses
+        assert_eq!(state.check_tick(&[(1, BotCommand::Wait), (2, BotCommand::Wait), (3, s), (8, BotCommand::Wait)]),
+                   Err(Error::FusionUnmatched { bid: 3, }));
+        // a secondary pointing somewhere else
+        let s_up = BotCommand::FusionS { near: near(0, 0, 1), };
+        assert_eq!(state.check_tick(&[(1, p), (2, s_up), (3, BotCommand::Wait), (8, BotCommand::Wait)]),
+                   Err(Error::FusionUnmatched { bid: 1, }));
+        assert!(state.check_tick(&[(1, BotCommand::Wait), (2, p), (3, s), (8, BotCommand::Wait)]).is_ok());
+    }
+
+    #[test]
+    fn fission_placement_interference() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0, }), 5).unwrap()].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.bot_pos(&2), Some(Coord { x: 1, y: 0, z: 0, }));
+
+        // both bots spawn a new bot at (1, 0, 1)
+        let mut trace_it = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 1, }), 0).unwrap(),
+            BotCommand::fission(CoordDiff(Coord { x: 0, y: 0, z: 1, }), 0).unwrap(),
+        ].into_iter();
+        assert!(match state.step_mut(&mut trace_it) {
+            Err(Error::CommandsInterfere{ .. }) => true,
+            _ => false,
+        });
+
+        // fission to (0, 0, 1) while the other bot moves through it
+        let lmove = BotCommand::lmove(
+            LinearCoordDiff::Short { axis: Axis::Z, value: 1, },
+            LinearCoordDiff::Short { axis: Axis::X, value: -1, },
+        ).unwrap();
+        let mut trace_it = vec![BotCommand::fission(CoordDiff(Coord { x: 0, y: 0, z: 1, }), 0).unwrap(), lmove].into_iter();
+        assert!(match state.step_mut(&mut trace_it) {
+            Err(Error::CommandsInterfere{ .. }) => true,
+            _ => false,
+        });
+
+        // the bot with the lower bid moves into the coordinate the other one spawns at
+        let mut trace_it = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Z, value: 1, }).unwrap(),
+            BotCommand::fission(CoordDiff(Coord { x: -1, y: 0, z: 1, }), 0).unwrap(),
+        ].into_iter();
+        assert!(match state.step_mut(&mut trace_it) {
+            Err(Error::CommandsInterfere{ .. }) => true,
+            _ => false,
+        });
+        assert_eq!(state.bots.len(), 2);
+
+        // disjoint placements are fine
+        let mut trace_it = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 0, y: 0, z: 1, }), 0).unwrap(),
+            BotCommand::fission(CoordDiff(Coord { x: 0, y: 0, z: 1, }), 0).unwrap(),
+        ].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.bots.len(), 4);
+    }
+
+    #[test]
+    fn compare_merged_moves() {
+        let source = Matrix::new(Resolution(8));
+        let target = Matrix::from_iter(Resolution(8), vec![Coord { x: 5, y: 0, z: 0, }]);
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value, }).unwrap();
+        let fill = BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 0, })).unwrap();
+        let a = vec![
+            smove(Axis::Y, 1), smove(Axis::X, 2), smove(Axis::X, 3), fill,
+            smove(Axis::X, -2), smove(Axis::X, -3), smove(Axis::Y, -1), BotCommand::halt().unwrap(),
+        ];
+        let b = vec![
+            smove(Axis::Y, 1), smove(Axis::X, 5), fill,
+            smove(Axis::X, -5), smove(Axis::Y, -1), BotCommand::halt().unwrap(),
+        ];
+
+        let cmp = compare_traces(&source, &target, &a, &b).unwrap();
+        assert!(cmp.a.success && cmp.b.success);
+        assert_eq!(cmp.a.commands_executed, 8);
+        assert_eq!(cmp.b.commands_executed, 6);
+        assert_eq!(cmp.a.steps - cmp.b.steps, 2);
+        assert!(cmp.b.energy < cmp.a.energy);
+        assert!(cmp.energy_diff_percent < 0.0);
+
+        let cmp = compare_traces(&source, &target, &b, &a).unwrap();
+        assert!(cmp.energy_diff_percent > 0.0);
+
+        match compare_traces(&source, &target, &a, &b[.. 3]) {
+            Err(CompareError::TraceB(SimulationFailure { error: Error::NotEnoughCommands, .. })) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn gfill_gvoid_line_two_bots() {
+        let mut state = State::new(Matrix::new(Resolution(5)), vec![]);
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value, }).unwrap();
+        let line = |dz| (CoordDiff(Coord { x: 0, y: -1, z: dz, }), CoordDiff(Coord { x: 0, y: 0, z: 2 * dz, }));
+        let (near1, far1) = line(1);
+        let (near2, far2) = line(-1);
+        let trace = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 0, y: 1, z: 0, }), 0).unwrap(),
+            smove(Axis::X, 2), smove(Axis::Z, 4),
+            smove(Axis::Y, 1), smove(Axis::X, 2),
+            BotCommand::gfill(near1, far1).unwrap(), BotCommand::gfill(near2, far2).unwrap(),
+        ];
+        let mut trace_it = trace.into_iter();
+        for _ in 0 .. 4 {
+            state.step_mut(&mut trace_it).unwrap();
+        }
+        for z in 1 .. 4 {
+            assert!(state.matrix.is_filled(&Coord { x: 2, y: 0, z, }));
+        }
+        assert_eq!(state.energy_breakdown().fill_void, 36);
+
+        let mut trace_it = vec![
+            BotCommand::gvoid(near1, far1).unwrap(), BotCommand::gvoid(near2, far2).unwrap(),
+        ].into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert_eq!(state.matrix.filled_voxels().count(), 0);
+        assert_eq!(state.energy_breakdown().fill_void, 0);
+
+        let mut trace_it = vec![
+            BotCommand::gfill(near1, far1).unwrap(), BotCommand::wait().unwrap(),
+        ].into_iter();
+        assert_eq!(
+            state.step_mut(&mut trace_it),
+            Err(Error::GroupIncomplete { r: Region::from_corners(&Coord { x: 2, y: 0, z: 1, }, &Coord { x: 2, y: 0, z: 3, }) }),
+        );
+    }
+
+    #[test]
+    fn checkpoint_restore() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        let trace = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Y, value: 1, }).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 0, })).unwrap(),
+            BotCommand::flip().unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::X, value: 2, }).unwrap(),
+        ];
+        let mut trace_it = trace.into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        let checkpoint = state.checkpoint();
+        assert_eq!(checkpoint.steps(), state.steps);
+        let energy = state.energy;
+
+        for _ in 0 .. 3 {
+            state.step_mut(&mut trace_it).unwrap();
+        }
+        assert!(state.matrix.is_filled(&Coord { x: 0, y: 0, z: 0, }));
+        assert_eq!(state.harmonics, Harmonics::High);
+
+        state.restore(&checkpoint);
+        assert_eq!(state.energy, energy);
+        assert_eq!(state.commands_executed, 1);
+        assert_eq!(state.harmonics, Harmonics::Low);
+        assert_eq!(state.matrix.filled_voxels().count(), 0);
+        assert_eq!(state.bots.get(&1).unwrap().pos, Coord { x: 0, y: 1, z: 0, });
+    }
+
+    #[test]
+    fn waiting_tick() {
+        assert!(State::is_waiting_tick(&[BotCommand::Wait, BotCommand::Wait, BotCommand::Wait]));
+        assert!(!State::is_waiting_tick(&[BotCommand::Wait, BotCommand::Flip, BotCommand::Wait]));
+        assert!(!State::is_waiting_tick(&[]));
+    }
+
+    #[test]
+    fn event_log_per_tick() {
+        let trace = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Y, value: 1, }).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 0, })).unwrap(),
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::X, value: 2, }).unwrap(),
+            BotCommand::Wait,
+        ];
+        let mut quiet = State::new(Matrix::new(Resolution(4)), vec![]);
+        quiet.step_mut(&mut trace.clone().into_iter()).unwrap();
+        assert!(quiet.events().is_empty());
+
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]).with_event_log();
+        let mut trace_it = trace.into_iter();
+        for _ in 0 .. 4 {
+            state.step_mut(&mut trace_it).unwrap();
+        }
+        assert_eq!(state.events().len(), 4);
+        assert_eq!(state.events()[2], TickEvent {
+            bots: vec![BotEvent {
+                bid: 1,
+                cmd: BotCommand::SMove { long: LinearCoordDiff::Long { axis: Axis::X, value: 2, }, },
+                pos: Some(Coord { x: 2, y: 1, z: 0, }),
+            }],
+        });
+        assert_eq!(state.events()[3].bots[0].pos, Some(Coord { x: 2, y: 1, z: 0, }));
+    }
+
+    #[test]
+    fn wellformed_reports_culprit() {
+        let floating = Coord { x: 2, y: 2, z: 1, };
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        assert_eq!(state.wellformed(), WellformedReport::Wellformed);
+        state.matrix.set_filled(&floating);
+        assert_eq!(state.wellformed(), WellformedReport::NotGroundedWhileLowHarmonics { voxel: floating, });
+        match state.step_mut(&mut vec![BotCommand::Wait].into_iter()) {
+            Err(Error::StateNotWellformed { report, }) =>
+                assert_eq!(report.status(), WellformedStatus::NotGroundedWhileLowHarmonics),
+            other =>
+                panic!("expected not wellformed, got {:?}", other),
+        }
+
+        state.harmonics = Harmonics::High;
+        assert_eq!(state.wellformed(), WellformedReport::Wellformed);
+        state.bots.insert(3, Bot { pos: floating, seeds: vec![], });
+        assert_eq!(state.wellformed(), WellformedReport::BotInFilledVoxel { bid: 3, pos: floating, });
+
+        state.bots.get_mut(&1).unwrap().seeds = vec![7, 8];
+        state.bots.get_mut(&3).unwrap().pos = Coord { x: 3, y: 3, z: 3, };
+        state.bots.get_mut(&3).unwrap().seeds = vec![7];
+        assert_eq!(state.wellformed(), WellformedReport::SeedsAreNotDisjoint { seed: 7, });
+
+        state.bots.get_mut(&3).unwrap().seeds = vec![5];
+        state.bots.insert(5, Bot { pos: Coord { x: 0, y: 3, z: 3, }, seeds: vec![], });
+        assert_eq!(state.wellformed(), WellformedReport::SeedIsTheSameAsActiveBot { bid: 5, });
+    }
+
+    #[test]
+    fn currently_grounded_floating_fill() {
+        let mut state = State::new(Matrix::new(Resolution(4)), vec![]);
+        state.matrix.enable_grounding_cache();
+        let trace = vec![
+            BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Y, value: 2, }).unwrap(),
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 0, })).unwrap(),
+        ];
+        let mut trace_it = trace.into_iter();
+        state.step_mut(&mut trace_it).unwrap();
+        assert!(state.is_currently_grounded());
+        state.step_mut(&mut trace_it).unwrap();
+        assert!(state.matrix.is_filled(&Coord { x: 0, y: 1, z: 0, }));
+        assert!(!state.is_currently_grounded());
+        assert!(!state.matrix.all_voxels_are_grounded());
     }
 }
@@ -1,4 +1,7 @@
 extern crate gfx_core;
+extern crate gfx_device_gl;
+extern crate gfx_gl;
+extern crate image;
 extern crate env_logger;
 extern crate piston_window;
 extern crate gfx_debug_draw;
@@ -12,8 +15,11 @@ extern crate icfpc2018_lib;
 
 use std::{
     fs,
-    io::{self, Write},
+    collections::HashSet,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
     process,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use clap::Arg;
@@ -23,6 +29,7 @@ use piston_window::{
     WindowSettings,
     RenderEvent,
     ResizeEvent,
+    UpdateEvent,
     Event,
     Input,
     Button,
@@ -49,6 +56,7 @@ use icfpc2018_lib::{
         Resolution,
     },
     cmd::{self, BotCommand},
+    state::{self, State, Checkpoint, Harmonics},
     router,
 };
 
@@ -69,7 +77,11 @@ fn main() {
 #[derive(Debug)]
 enum Error {
     MissingParameter(&'static str),
+    Args(clap::Error),
     Model(model::Error),
+    SourceTargetDimMismatch { source: usize, target: usize, },
+    TraceIo(io::Error),
+    TraceCmd(cmd::Error),
     Piston(PistonError),
 }
 
@@ -79,10 +91,193 @@ enum PistonError {
     DebugRendererInit(gfx_debug_draw::DebugRendererError),
     DebugRendererRender(gfx_debug_draw::DebugRendererError),
     VoxelRenderer(voxel::Error),
+    ScreenshotBuffer { width: u32, height: u32, },
+    ScreenshotSave(io::Error),
 }
 
 const SCREEN_WIDTH: u32 = 640;
 const SCREEN_HEIGHT: u32 = 480;
+const CHECKPOINT_INTERVAL: usize = 256;
+
+struct Playback {
+    state: State,
+    commands: Vec<BotCommand>,
+    checkpoints: Vec<Checkpoint>,
+    error: Option<state::Error>,
+    playing: bool,
+    tick_interval: f64,
+    elapsed: f64,
+    clock: f64,
+    ungrounded: HashSet<Coord>,
+    ungrounded_tick: Option<usize>,
+}
+
+impl Playback {
+    fn is_finished(&self) -> bool {
+        self.error.is_some() || self.state.is_halt() || self.state.commands_executed >= self.commands.len()
+    }
+
+    fn step(&mut self) {
+        if self.is_finished() {
+            return;
+        }
+        if let Err(e) = step_script(&mut self.state, &self.commands) {
+            self.error = Some(e);
+        } else if self.state.steps % CHECKPOINT_INTERVAL == 0 &&
+            self.checkpoints.last().map(|c| c.steps() < self.state.steps).unwrap_or(true)
+        {
+            self.checkpoints.push(self.state.checkpoint());
+        }
+    }
+
+    fn seek(&mut self, tick: usize) {
+        {
+            let checkpoint = self.checkpoints.iter()
+                .rev()
+                .find(|c| c.steps() <= tick)
+                .expect("initial checkpoint is always present");
+            self.state.restore(checkpoint);
+        }
+        self.error = None;
+        while self.state.steps < tick && !self.is_finished() {
+            self.step();
+        }
+    }
+
+    fn step_back(&mut self) {
+        if self.state.steps > 0 {
+            let tick = self.state.steps - 1;
+            self.seek(tick);
+        }
+    }
+
+    fn rewind(&mut self) {
+        self.seek(0);
+    }
+
+    fn fast_forward(&mut self) {
+        while !self.is_finished() {
+            self.step();
+        }
+    }
+
+    fn refresh_grounding(&mut self) {
+        if self.ungrounded_tick != Some(self.state.steps) {
+            self.ungrounded = self.state.matrix.ungrounded_voxels();
+            self.ungrounded_tick = Some(self.state.steps);
+        }
+    }
+
+    fn grounding_violated(&self) -> bool {
+        self.state.harmonics == Harmonics::Low && !self.ungrounded.is_empty()
+    }
+
+    fn update(&mut self, dt: f64) {
+        self.clock += dt;
+        if !self.playing {
+            return;
+        }
+        self.elapsed += dt;
+        while self.elapsed >= self.tick_interval && !self.is_finished() {
+            self.elapsed -= self.tick_interval;
+            self.step();
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DiffLayers {
+    All,
+    Keep,
+    Void,
+    Fill,
+}
+
+impl DiffLayers {
+    fn next(self) -> DiffLayers {
+        match self {
+            DiffLayers::All => DiffLayers::Keep,
+            DiffLayers::Keep => DiffLayers::Void,
+            DiffLayers::Void => DiffLayers::Fill,
+            DiffLayers::Fill => DiffLayers::All,
+        }
+    }
+
+    fn shows(self, layer: DiffLayers) -> bool {
+        self == DiffLayers::All || self == layer
+    }
+}
+
+struct Reassembly {
+    source: Matrix,
+    keep: HashSet<Coord>,
+    void: HashSet<Coord>,
+    fill: HashSet<Coord>,
+    layers: DiffLayers,
+}
+
+impl Reassembly {
+    fn new(source: Matrix, target: &Matrix) -> Reassembly {
+        let (void, fill) = source.diff(target);
+        let keep = source.filled_voxels()
+            .filter(|voxel| target.is_filled(voxel))
+            .cloned()
+            .collect();
+        Reassembly {
+            source,
+            keep,
+            void: void.into_iter().collect(),
+            fill: fill.into_iter().collect(),
+            layers: DiffLayers::All,
+        }
+    }
+
+    fn layer_of(&self, voxel: &Coord) -> Option<DiffLayers> {
+        let layer = if self.keep.contains(voxel) {
+            DiffLayers::Keep
+        } else if self.void.contains(voxel) {
+            DiffLayers::Void
+        } else if self.fill.contains(voxel) {
+            DiffLayers::Fill
+        } else {
+            return None;
+        };
+        if self.layers.shows(layer) {
+            Some(layer)
+        } else {
+            None
+        }
+    }
+}
+
+fn step_script(state: &mut State, script: &[BotCommand]) -> Result<(), state::Error> {
+    state.steps += 1;
+    let mut cmd_iter = script[state.commands_executed ..].iter().cloned();
+    state.step_mut(&mut cmd_iter)
+}
+
+// Reads the frame just drawn back from the (not yet swapped) back buffer and saves it as a timestamped png.
+fn save_screenshot(window: &mut PistonWindow, width: u32, height: u32, dir: &Path) -> Result<PathBuf, PistonError> {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    unsafe {
+        window.device.with_gl(|gl| {
+            gl.PixelStorei(gfx_gl::PACK_ALIGNMENT, 1);
+            gl.ReadBuffer(gfx_gl::BACK);
+            gl.ReadPixels(0, 0, width as i32, height as i32, gfx_gl::RGBA, gfx_gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+        });
+    }
+    let frame = image::RgbaImage::from_raw(width, height, pixels)
+        .ok_or(PistonError::ScreenshotBuffer { width, height, })?;
+    // gl rows go bottom up
+    let frame = image::imageops::flip_vertical(&frame);
+
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() * 1000 + (since.subsec_nanos() / 1_000_000) as u64)
+        .unwrap_or(0);
+    let path = dir.join(format!("screenshot-{}.png", stamp));
+    frame.save(&path).map_err(PistonError::ScreenshotSave)?;
+    Ok(path)
+}
 
 fn run() -> Result<(), Error> {
     let matches = app_from_crate!()
@@ -90,7 +285,7 @@ fn run() -> Result<(), Error> {
              .short("a")
              .long("assets-dir")
              .value_name("DIR")
-             .help("Graphics resources directory")
+             .help("Graphics resources directory, F12 screenshots are saved there")
              .default_value("./assets")
              .takes_value(true))
         .arg(Arg::with_name("model")
@@ -100,15 +295,85 @@ fn run() -> Result<(), Error> {
              .help("Model file to visualize")
              .default_value("../../problems/FA001_tgt.mdl")
              .takes_value(true))
+        .arg(Arg::with_name("source")
+             .long("source")
+             .value_name("FILE")
+             .help("Source model of a reassembly problem (shown as a diff against --target)")
+             .requires("target")
+             .takes_value(true))
+        .arg(Arg::with_name("target")
+             .long("target")
+             .value_name("FILE")
+             .help("Target model of a reassembly problem (L: cycle diff layers)")
+             .requires("source")
+             .takes_value(true))
+        .arg(Arg::with_name("trace")
+             .short("t")
+             .long("trace")
+             .value_name("FILE")
+             .help("Trace file to play back (N/Right: next tick, Left: previous tick, Home/End: jump to start/end, G: toggle autoplay)")
+             .takes_value(true))
+        .arg(Arg::with_name("autoplay")
+             .long("autoplay")
+             .value_name("TICKS_PER_SEC")
+             .help("Start trace playback automatically at given rate")
+             .takes_value(true))
         .get_matches();
 
-    let _assets_dir = matches.value_of("assets-dir")
+    let assets_dir = matches.value_of("assets-dir")
         .ok_or(Error::MissingParameter("assets-dir"))?;
     let model_file = matches.value_of("model")
         .ok_or(Error::MissingParameter("model"))?;
 
-    let matrix = model::read_model_file(model_file)
-        .map_err(Error::Model)?;
+    let (matrix, mut reassembly) = match (matches.value_of("source"), matches.value_of("target")) {
+        (Some(source_file), Some(target_file)) => {
+            let source = model::read_model_file(source_file)
+                .map_err(Error::Model)?;
+            let target = model::read_model_file(target_file)
+                .map_err(Error::Model)?;
+            if source.dim() != target.dim() {
+                return Err(Error::SourceTargetDimMismatch { source: source.dim(), target: target.dim(), });
+            }
+            let reassembly = Reassembly::new(source, &target);
+            (target, Some(reassembly))
+        },
+        _ => {
+            let matrix = model::read_model_file(model_file)
+                .map_err(Error::Model)?;
+            (matrix, None)
+        },
+    };
+    let initial_matrix = match reassembly {
+        Some(ref r) => r.source.clone(),
+        None => Matrix::new(Resolution(matrix.dim() as isize)),
+    };
+
+    let mut playback = if let Some(trace_file) = matches.value_of("trace") {
+        let mut f = fs::File::open(trace_file).map_err(Error::TraceIo)?;
+        let mut buffer = Vec::new();
+        f.read_to_end(&mut buffer).map_err(Error::TraceIo)?;
+        let commands = cmd::from_bytes(&buffer).map_err(Error::TraceCmd)?;
+        let (playing, ticks_per_sec) = if matches.is_present("autoplay") {
+            (true, value_t!(matches, "autoplay", f64).map_err(Error::Args)?)
+        } else {
+            (false, 10.0)
+        };
+        let state = State::new(initial_matrix.clone(), vec![]);
+        Some(Playback {
+            checkpoints: vec![state.checkpoint()],
+            state,
+            commands,
+            error: None,
+            playing,
+            tick_interval: 1.0 / ticks_per_sec,
+            elapsed: 0.0,
+            clock: 0.0,
+            ungrounded: HashSet::new(),
+            ungrounded_tick: None,
+        })
+    } else {
+        None
+    };
 
     let opengl = OpenGL::V4_1;
     let mut window: PistonWindow = WindowSettings::new("icfpc2018 visualizer", [SCREEN_WIDTH, SCREEN_HEIGHT])
@@ -149,12 +414,17 @@ fn run() -> Result<(), Error> {
     }
 
     let mut script = Vec::new();
-    let mut filled_matrix = Matrix::new(Resolution(matrix.dim() as isize));
+    let mut script_state = State::new(initial_matrix.clone(), vec![]);
+    let mut script_error = None;
+    let mut filled_matrix = initial_matrix;
+    // voxels of `filled_matrix` that would need High harmonics, recomputed on every fill only
+    let mut filled_ungrounded = filled_matrix.ungrounded_voxels();
     let mut nanobot = Coord { x: 0, y: 0, z: 0, };
     let mut cursor = Coord { x: 1, y: 0, z: 1, };
     let mut cursor_state = CursorState::Moving;
     let mut last_route: Option<Vec<Coord>> = None;
     let mut show_model = true;
+    let mut screenshot_requested = false;
 
     loop {
         let event = if let Some(ev) = window.next() {
@@ -174,6 +444,11 @@ fn run() -> Result<(), Error> {
         });
         orbit_zoom_camera.event(&event);
 
+        if let Some(pb) = playback.as_mut() {
+            event.update(|args| pb.update(args.dt));
+            pb.refresh_grounding();
+        }
+
         let maybe_result = window.draw_3d(&event, |win| {
             if let Some(args) = event.render_args() {
                 win.encoder.clear(&win.output_color, [1.0, 1.0, 1.0, 1.0]);
@@ -198,6 +473,10 @@ fn run() -> Result<(), Error> {
 
                 {
                     let dim = matrix.dim() as f32;
+                    let shown_matrix = match playback {
+                        Some(ref pb) => &pb.state.matrix,
+                        None => &filled_matrix,
+                    };
 
                     // Draw floor
                     voxel_renderer.draw_voxel([0.0, 0.0, 0.0], [dim, -1.0, dim], [0.33, 0.33, 0.33, 1.0]);
@@ -241,13 +520,23 @@ fn run() -> Result<(), Error> {
                     // Draw model matrix
                     if show_model {
                         for voxel in matrix.filled_voxels() {
-                            if filled_matrix.is_filled(&voxel) {
+                            if shown_matrix.is_filled(&voxel) {
                                 continue;
                             }
+                            let ghost_color = match reassembly.as_ref().map(|r| r.layer_of(voxel)) {
+                                None =>
+                                    [0.0, 0.0, 0.0, 0.15],
+                                Some(Some(DiffLayers::Fill)) =>
+                                    [0.0, 0.8, 0.0, 0.3],
+                                Some(Some(_)) =>
+                                    [0.5, 0.5, 0.5, 0.3],
+                                Some(None) =>
+                                    continue,
+                            };
                             // draw voxel
                             let min_point = [voxel.x as f32, voxel.y as f32, voxel.z as f32];
                             let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
-                            voxel_renderer.draw_voxel(min_point, max_point, [0.0, 0.0, 0.0, 0.15]);
+                            voxel_renderer.draw_voxel(min_point, max_point, ghost_color);
                             // draw mesh
                             let position =
                                 [voxel.x as f32, voxel.y as f32, voxel.z as f32];
@@ -256,55 +545,137 @@ fn run() -> Result<(), Error> {
                     }
 
                     // Draw filled matrix
-                    for voxel in filled_matrix.filled_voxels() {
+                    for voxel in shown_matrix.filled_voxels() {
                         // draw voxel
                         let min_point = [voxel.x as f32, voxel.y as f32, voxel.z as f32];
                         let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
-                        voxel_renderer.draw_voxel(min_point, max_point, [0.54, 0.27, 0.07, 1.0]);
+                        let voxel_color = match playback {
+                            Some(ref pb) if pb.ungrounded.contains(voxel) =>
+                                [1.0, 0.0, 0.0, 1.0],
+                            None if filled_ungrounded.contains(voxel) =>
+                                [1.0, 0.0, 0.0, 1.0],
+                            _ => match reassembly.as_ref().and_then(|r| r.layer_of(voxel)) {
+                                Some(DiffLayers::Keep) =>
+                                    [0.5, 0.5, 0.5, 1.0],
+                                Some(DiffLayers::Void) =>
+                                    [0.0, 0.0, 1.0, 1.0],
+                                Some(DiffLayers::Fill) =>
+                                    [0.0, 0.8, 0.0, 1.0],
+                                _ =>
+                                    [0.54, 0.27, 0.07, 1.0],
+                            },
+                        };
+                        voxel_renderer.draw_voxel(min_point, max_point, voxel_color);
                         // draw mesh
                         let position =
                             [voxel.x as f32, voxel.y as f32, voxel.z as f32];
                         draw_cube_mesh(position, vec3_add(position, [1.0, 1.0, 1.0]), [0.0, 0.0, 0.0, 1.0]);
                     }
 
-                    // Draw cursor
-                    let cursor_color = match cursor_state {
-                        CursorState::Moving =>
-                            if filled_matrix.is_filled(&cursor) {
-                                [1.0, 0.0, 0.0, 1.0]
-                            } else {
-                                [0.0, 0.0, 1.0, 1.0]
-                            },
-                        CursorState::Filling =>
-                            if cursor.diff(&nanobot).is_near() && !filled_matrix.is_filled(&cursor) {
-                                [0.0, 1.0, 0.0, 1.0]
-                            } else {
-                                [1.0, 0.0, 0.0, 1.0]
-                            },
-                    };
-                    let min_point = [cursor.x as f32, cursor.y as f32, cursor.z as f32];
-                    let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
-                    voxel_renderer.draw_voxel(min_point, max_point, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
-                    draw_cube_mesh(min_point, vec3_add(min_point, [1.0, 1.0, 1.0]), cursor_color);
-                    let x_proj_min = [0.0, min_point[1], min_point[2]];
-                    let x_proj_max = vec3_add(x_proj_min, [0.0, 1.0, 1.0]);
-                    voxel_renderer.draw_voxel(x_proj_min, x_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
-                    let y_proj_min = [min_point[0], 0.0, min_point[2]];
-                    let y_proj_max = vec3_add(y_proj_min, [1.0, 0.0, 1.0]);
-                    voxel_renderer.draw_voxel(y_proj_min, y_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
-                    let z_proj_min = [min_point[0], min_point[1], 0.0];
-                    let z_proj_max = vec3_add(z_proj_min, [1.0, 1.0, 0.0]);
-                    voxel_renderer.draw_voxel(z_proj_min, z_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
-
-                    // Draw nanobot
-                    let min_point = [nanobot.x as f32, nanobot.y as f32, nanobot.z as f32];
-                    let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
-                    voxel_renderer.draw_voxel(min_point, max_point, [1.0, 1.0, 0.0, 1.0]);
-                    draw_cube_mesh(min_point, vec3_add(min_point, [1.0, 1.0, 1.0]), [0.0, 0.0, 0.0, 1.0]);
+                    // Draw trace nanobots
+                    if let Some(ref pb) = playback {
+                        for bot in pb.state.bots.values() {
+                            let min_point = [bot.pos.x as f32, bot.pos.y as f32, bot.pos.z as f32];
+                            let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
+                            voxel_renderer.draw_voxel(min_point, max_point, [1.0, 1.0, 0.0, 1.0]);
+                            draw_cube_mesh(min_point, max_point, [0.0, 0.0, 0.0, 1.0]);
+                        }
+                    } else {
+                        // Draw cursor
+                        let cursor_color = match cursor_state {
+                            CursorState::Moving =>
+                                if filled_matrix.is_filled(&cursor) {
+                                    [1.0, 0.0, 0.0, 1.0]
+                                } else {
+                                    [0.0, 0.0, 1.0, 1.0]
+                                },
+                            CursorState::Filling =>
+                                if cursor.diff(&nanobot).is_near() && !filled_matrix.is_filled(&cursor) {
+                                    [0.0, 1.0, 0.0, 1.0]
+                                } else {
+                                    [1.0, 0.0, 0.0, 1.0]
+                                },
+                        };
+                        let min_point = [cursor.x as f32, cursor.y as f32, cursor.z as f32];
+                        let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
+                        voxel_renderer.draw_voxel(min_point, max_point, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
+                        draw_cube_mesh(min_point, vec3_add(min_point, [1.0, 1.0, 1.0]), cursor_color);
+                        let x_proj_min = [0.0, min_point[1], min_point[2]];
+                        let x_proj_max = vec3_add(x_proj_min, [0.0, 1.0, 1.0]);
+                        voxel_renderer.draw_voxel(x_proj_min, x_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
+                        let y_proj_min = [min_point[0], 0.0, min_point[2]];
+                        let y_proj_max = vec3_add(y_proj_min, [1.0, 0.0, 1.0]);
+                        voxel_renderer.draw_voxel(y_proj_min, y_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
+                        let z_proj_min = [min_point[0], min_point[1], 0.0];
+                        let z_proj_max = vec3_add(z_proj_min, [1.0, 1.0, 0.0]);
+                        voxel_renderer.draw_voxel(z_proj_min, z_proj_max, [cursor_color[0], cursor_color[1], cursor_color[2], 0.5]);
+
+                        // Draw nanobot
+                        let min_point = [nanobot.x as f32, nanobot.y as f32, nanobot.z as f32];
+                        let max_point = vec3_add(min_point, [1.0, 1.0, 1.0]);
+                        voxel_renderer.draw_voxel(min_point, max_point, [1.0, 1.0, 0.0, 1.0]);
+                        draw_cube_mesh(min_point, vec3_add(min_point, [1.0, 1.0, 1.0]), [0.0, 0.0, 0.0, 1.0]);
+                    }
                 }
 
+                let (hud_state, status, script) = match playback {
+                    Some(ref pb) => {
+                        let status = if let Some(ref e) = pb.error {
+                            format!("error: {:?}", e)
+                        } else if pb.state.is_halt() {
+                            "halted".to_string()
+                        } else if pb.playing {
+                            "playing".to_string()
+                        } else {
+                            "paused".to_string()
+                        };
+                        (&pb.state, format!("command: {}/{}, {}", pb.state.commands_executed, pb.commands.len(), status),
+                         &pb.commands[.. pb.state.commands_executed])
+                    },
+                    None => {
+                        let status = if let Some(ref e) = script_error {
+                            format!("error: {:?}", e)
+                        } else if script_state.is_halt() {
+                            "halted".to_string()
+                        } else {
+                            "editing".to_string()
+                        };
+                        (&script_state, format!("commands: {}, {}", script.len(), status), &script[..])
+                    },
+                };
+                debug_renderer.draw_text_on_screen(
+                    &match reassembly {
+                        Some(ref r) => format!("tick: {}, {}, diff layers: {:?}", hud_state.steps, status, r.layers),
+                        None => format!("tick: {}, {}", hud_state.steps, status),
+                    },
+                    [10, 10],
+                    [0.0, 0.0, 0.0, 1.0],
+                );
+                debug_renderer.draw_text_on_screen(
+                    &format!("energy: {}, harmonics: {:?}, bots: {}", hud_state.energy, hud_state.harmonics, hud_state.bots.len()),
+                    [10, 30],
+                    [0.0, 0.0, 0.0, 1.0],
+                );
+
+                let mut oi = 2;
+                if let Some(ref pb) = playback {
+                    if pb.grounding_violated() && (pb.clock * 4.0) as usize % 2 == 0 {
+                        debug_renderer.draw_text_on_screen(
+                            &format!("WARNING: {} ungrounded voxels while harmonics is Low", pb.ungrounded.len()),
+                            [10, 50],
+                            [1.0, 0.0, 0.0, 1.0],
+                        );
+                    }
+                    oi += 1;
+                } else if !filled_ungrounded.is_empty() {
+                    debug_renderer.draw_text_on_screen(
+                        &format!("{} ungrounded voxels: High harmonics required", filled_ungrounded.len()),
+                        [10, 50],
+                        [1.0, 0.0, 0.0, 1.0],
+                    );
+                    oi += 1;
+                }
                 let total = script.len();
-                let mut oi = 0;
                 for (i, cmd) in script.iter().enumerate() {
                     if (i as isize) < (total as isize) - 10 {
                         continue;
@@ -328,12 +699,52 @@ fn run() -> Result<(), Error> {
         if let Some(result) = maybe_result {
             let () = result.map_err(Error::Piston)?;
         }
+        if screenshot_requested {
+            if let Some(args) = event.render_args() {
+                screenshot_requested = false;
+                match save_screenshot(&mut window, args.draw_width, args.draw_height, Path::new(assets_dir)) {
+                    Ok(path) =>
+                        info!("screenshot saved to {:?}", path),
+                    Err(e) =>
+                        error!("screenshot failed: {:?}", e),
+                }
+            }
+        }
 
         match event {
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::Q), state: ButtonState::Release, .. })) =>
                 return Ok(()),
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::I), state: ButtonState::Release, .. })) =>
                 show_model = !show_model,
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::F12), state: ButtonState::Release, .. })) =>
+                // taken right after the next frame is drawn
+                screenshot_requested = true,
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::L), state: ButtonState::Release, .. })) =>
+                if let Some(r) = reassembly.as_mut() {
+                    r.layers = r.layers.next();
+                },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::N), state: ButtonState::Release, .. })) |
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::Right), state: ButtonState::Release, .. })) =>
+                if let Some(pb) = playback.as_mut() {
+                    pb.step();
+                },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::Left), state: ButtonState::Release, .. })) =>
+                if let Some(pb) = playback.as_mut() {
+                    pb.step_back();
+                },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::Home), state: ButtonState::Release, .. })) =>
+                if let Some(pb) = playback.as_mut() {
+                    pb.rewind();
+                },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::End), state: ButtonState::Release, .. })) =>
+                if let Some(pb) = playback.as_mut() {
+                    pb.fast_forward();
+                },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::G), state: ButtonState::Release, .. })) =>
+                if let Some(pb) = playback.as_mut() {
+                    pb.playing = !pb.playing;
+                    pb.elapsed = 0.0;
+                },
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::A), state: ButtonState::Release, .. })) =>
                 if cursor.x > 0 { cursor.x -= 1; },
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::S), state: ButtonState::Release, .. })) =>
@@ -377,6 +788,7 @@ fn run() -> Result<(), Error> {
                     CursorState::Filling =>
                         if nanobot.diff(&cursor).is_near() && !filled_matrix.is_filled(&cursor) {
                             filled_matrix.set_filled(&cursor);
+                            filled_ungrounded = filled_matrix.ungrounded_voxels();
                             script.push(BotCommand::fill(nanobot.diff(&cursor)).unwrap());
                         },
                 },
@@ -395,6 +807,7 @@ fn run() -> Result<(), Error> {
                         filled_matrix.set_filled(&coord);
                         script.push(BotCommand::fill(nanobot.diff(&coord)).unwrap());
                     }
+                    filled_ungrounded = filled_matrix.ungrounded_voxels();
                 },
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::H), state: ButtonState::Release, .. })) =>
                 if nanobot.x == 0 && nanobot.y == 0 && nanobot.z == 0 {
@@ -407,5 +820,12 @@ fn run() -> Result<(), Error> {
             _ =>
                 (),
         }
+
+        // Keep the energy of the script-so-far up to date
+        while script_error.is_none() && !script_state.is_halt() && script_state.commands_executed < script.len() {
+            if let Err(e) = step_script(&mut script_state, &script) {
+                script_error = Some(e);
+            }
+        }
     }
 }
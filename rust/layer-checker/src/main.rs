@@ -1,18 +1,16 @@
 extern crate icfpc2018_lib;
 #[macro_use] extern crate clap;
 
-mod optimizer;
-use optimizer::Optimizer;
-
 use clap::Arg;
 use std::fs::File;
 use std::io::Write;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::iter::DoubleEndedIterator;
 
 use icfpc2018_lib as kernel;
 use kernel::cmd::BotCommand;
-use kernel::coord::{LinearCoordDiff,Axis,M,Coord,CoordDiff};
+use kernel::cmd::optimize::Optimizer;
+use kernel::coord::{LinearCoordDiff,Axis,M,Coord,CoordDiff,Matrix,Region};
 
 #[derive(Debug)]
 enum Error {
@@ -59,6 +57,14 @@ impl Delta {
             Delta::One => (0 .. 1).into_iter(),
         }
     }
+    // the first and the last offset of `get_iter`
+    fn span(&self) -> (M, M) {
+        match &self {
+            Delta::Full => (-1, 1),
+            Delta::Pair => (0, 1),
+            Delta::One => (0, 0),
+        }
+    }
 }
 
 #[derive(Debug,Clone,Copy)]
@@ -67,6 +73,7 @@ enum Cmd {
     YMove(M),
     ZMove(M),
     Fill(Coord),
+    Void(Coord),
 }
 
 
@@ -112,36 +119,28 @@ impl<I> Iterator for Translator<I>
                     let v = x - self.bot_state.x;
                     self.bot_state.x = x;
                     if v==0 { continue; }
-                    let d = if v<0 { -1 } else { 1 };
-                    let mut m = v.abs();
-                    while m>0 {
-                        self.buffer.push_back(BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::X, value: if m > 15 { 15*d } else { m*d }}).unwrap());
-                        m -= 15;
+                    for lld in (LinearCoordDiff::Long{ axis: Axis::X, value: v }).split_into_smoves() {
+                        self.buffer.push_back(BotCommand::smove(lld).unwrap());
                     }
                 },
                 Some(Cmd::YMove(y)) => {
                     let v = y - self.bot_state.y;
                     self.bot_state.y = y;
                     if v==0 { continue; }
-                    let d = if v<0 { -1 } else { 1 };
-                    let mut m = v.abs();
-                    while m>0 {
-                        self.buffer.push_back(BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Y, value: if m > 15 { 15*d } else { m*d }}).unwrap());
-                        m -= 15;
+                    for lld in (LinearCoordDiff::Long{ axis: Axis::Y, value: v }).split_into_smoves() {
+                        self.buffer.push_back(BotCommand::smove(lld).unwrap());
                     }
                 },
                 Some(Cmd::ZMove(z)) => {
                     let v = z - self.bot_state.z;
                     self.bot_state.z = z;
                     if v==0 { continue; }
-                    let d = if v<0 { -1 } else { 1 };
-                    let mut m = v.abs();
-                    while m>0 {
-                        self.buffer.push_back(BotCommand::smove(LinearCoordDiff::Long{ axis: Axis::Z, value: if m > 15 { 15*d } else { m*d }}).unwrap());
-                        m -= 15;
+                    for lld in (LinearCoordDiff::Long{ axis: Axis::Z, value: v }).split_into_smoves() {
+                        self.buffer.push_back(BotCommand::smove(lld).unwrap());
                     }
                 },
                 Some(Cmd::Fill(c)) => return Some(BotCommand::Fill{ near: CoordDiff(c) }),
+                Some(Cmd::Void(c)) => return Some(BotCommand::Void{ near: CoordDiff(c) }),
             }
         }
     }
@@ -161,24 +160,8 @@ impl Iterator for Reverser {
     type Item = BotCommand;
     
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next_back() {
-            None => None,
-            Some(BotCommand::Wait) |
-            Some(BotCommand::Halt) |
-            Some(BotCommand::Flip) |
-            Some(BotCommand::GFill{ .. }) |
-            Some(BotCommand::GVoid{ .. }) |
-            Some(BotCommand::FusionP{ .. }) |
-            Some(BotCommand::FusionS{ .. }) |
-            Some(BotCommand::Fission{ .. }) => unimplemented!(),
-            Some(BotCommand::Fill{ near }) => Some(BotCommand::Void{ near }),
-            Some(BotCommand::Void{ near }) => Some(BotCommand::Fill{ near }),
-            Some(BotCommand::SMove{ long: LinearCoordDiff::Long{ axis, value} }) => Some(BotCommand::SMove{ long: LinearCoordDiff::Long{ axis: axis, value: -value } }),
-            Some(BotCommand::LMove{ short1: LinearCoordDiff::Short { axis: a1, value: v1}, short2: LinearCoordDiff::Short { axis: a2, value: v2} }) => {
-                Some(BotCommand::LMove{ short1: LinearCoordDiff::Short { axis: a2, value: -v2}, short2: LinearCoordDiff::Short { axis: a1, value: -v1} })
-            },
-            _ => unreachable!(),
-        }
+        self.iter.next_back()
+            .map(|c| kernel::cmd::invert(&c).unwrap())
     }
 }
 
@@ -209,45 +192,27 @@ fn balance_counts(mut vec: Vec<usize>, n: usize) -> Vec<usize> {
 }
 
 
-fn main() -> Result<(),Error> {
-    let app = app_from_crate!()
-        .arg(Arg::with_name("original")
-             .display_order(1)
-             .short("i")
-             .long("in")
-             .help("Original model (In)",)
-             .takes_value(true))
-        .arg(Arg::with_name("optimized")
-             .display_order(2)
-             .short("o")
-             .long("out")
-             .help("Optimized trace (Out)")
-             .takes_value(true))
-        .arg(Arg::with_name("n")
-             .display_order(3)
-             .short("n")
-             .long("num-bots")
-             .help("bot number")
-             .takes_value(true))
-        .arg(Arg::with_name("reverse")
-             .display_order(4)
-             .short("r")
-             .long("reverse")
-             .help("Reverse"))
-        .arg(Arg::with_name("flip")
-             .display_order(5)
-             .long("no-flip")
-             .help("No flip"));
+struct Options {
+    bots_count: usize,
+    reverse: bool,
+    flip: bool,
+    demolish: bool,
+    stripe_width: usize,
+    grounded_order: bool,
+}
 
-    let matches = app.get_matches();
-    let original = value_t!(matches, "original", String).map_err(Error::Args)?;
-    let optimized = value_t!(matches, "optimized", String).map_err(Error::Args)?;
-    let mut bots_count = value_t!(matches, "n", usize).map_err(Error::Args)?;
-    let reverse = matches.is_present("reverse");
+fn is_supported(voxel: &Coord, placed: &HashSet<Coord>) -> bool {
+    voxel.y == 0 || voxel.near_neighbours().any(|c| placed.contains(&c))
+}
 
-    let flip = !matches.is_present("flip");
-    
-    let matrix = kernel::model::read_model_file(&original).map_err(Error::ModelReadError)?;
+fn ungrounded_ticks(matrix: &Matrix, trace: &Vec<BotCommand>) -> usize {
+    let mut state = kernel::state::State::new(matrix.clone(), vec![]);
+    state.stateless_run(trace)
+        .map(|wellness| wellness.iter().filter(|w| !w.ok).count())
+        .unwrap_or(0)
+}
+
+fn build_trace(matrix: &Matrix, options: &Options) -> Vec<BotCommand> {
     let mut iter = matrix.filled_voxels();
     let (mut min, mut max) = match iter.next() {
         Some(c) => (c.clone(),c.clone()),
@@ -269,23 +234,19 @@ fn main() -> Result<(),Error> {
     let mut stripes = Vec::new();
     let mut x = min.x;
     while width > 0 {
-        if width >= 3 {
-            stripes.push(Stripe {
-                x: x + 1,
-                min_z: min.z,
-                max_z: max.z,
-                dx: Delta::Full,
-            });
-            x += 3;
-        } else {
-            stripes.push(Stripe {
-                x: x,
-                min_z: min.z,
-                max_z: max.z,
-                dx: if width==2 { Delta::Pair } else { Delta::One },
-            });
-        }
-        width -= 3;
+        let w = std::cmp::min(width, options.stripe_width as M);
+        stripes.push(Stripe {
+            x: if w == 3 { x + 1 } else { x },
+            min_z: min.z,
+            max_z: max.z,
+            dx: match w {
+                3 => Delta::Full,
+                2 => Delta::Pair,
+                _ => Delta::One,
+            },
+        });
+        x += w;
+        width -= w;
     }
 
     let mut ocount = Vec::new();
@@ -296,10 +257,11 @@ fn main() -> Result<(),Error> {
         for (si,s) in stripes.iter().enumerate() {
             let mut oc = 0;
             for z in s.min_z .. s.max_z + 1 {
-                let mut k = 0;
-                for dx in s.dx.get_iter() {
-                    if matrix.is_filled(&Coord{ x: s.x + dx, y: y, z: z }) { k += 1; }
-                }
+                let (dx_min, dx_max) = s.dx.span();
+                let mut k = matrix.count_filled_in(&Region {
+                    min: Coord { x: s.x + dx_min, y, z },
+                    max: Coord { x: s.x + dx_max, y, z },
+                });
                 if k>0 { k += 1; }
                 oc += k;
             }
@@ -310,6 +272,7 @@ fn main() -> Result<(),Error> {
         println!("{:?} {}",s,ocount[si]);
     }
 
+    let mut bots_count = options.bots_count;
     if bots_count>stripes.len() {
         bots_count = stripes.len();
     }
@@ -338,7 +301,17 @@ fn main() -> Result<(),Error> {
         states.push(Coord{ x: stripes[bot.first].x, y: 0, z: 0});
         let mut layer_direction = Direction::Forward;
         let mut stripe_direction = Direction::Forward;
-        for y in min.y .. max.y + 1 {
+        let mut placed = HashSet::new();
+        let mut deferred = Vec::new();
+        let layers: Vec<M> = if options.demolish {
+            (min.y .. max.y + 1).rev().collect()
+        } else {
+            (min.y .. max.y + 1).collect()
+        };
+        if options.demolish {
+            cmds.push(Cmd::YMove(max.y+1));
+        }
+        for y in layers {
             cmds.push(Cmd::YMove(y+1));
             let mut iter = (bot.first .. bot.first + bot.count).into_iter();
             loop {
@@ -363,13 +336,43 @@ fn main() -> Result<(),Error> {
                     cmds.push(Cmd::ZMove(z));
 
                     for dx in s.dx.get_iter() {
-                        if matrix.is_filled(&Coord{ x: s.x + dx, y: y, z: z }) {
-                            cmds.push(Cmd::Fill(Coord{ x: dx, y: -1, z: 0}));
+                        let voxel = Coord{ x: s.x + dx, y: y, z: z };
+                        if matrix.is_filled(&voxel) {
+                            if options.demolish {
+                                cmds.push(Cmd::Void(Coord{ x: dx, y: -1, z: 0}));
+                            } else if options.grounded_order && !is_supported(&voxel, &placed) {
+                                deferred.push(voxel);
+                            } else {
+                                cmds.push(Cmd::Fill(Coord{ x: dx, y: -1, z: 0}));
+                                placed.insert(voxel);
+                            }
                         }
                     }
                     
                 }
                 stripe_direction.switch();
+
+                /* sweep back over the stripe for voxels which had no support on the way */
+                while !deferred.is_empty() {
+                    deferred.sort_by_key(|c| (c.z, c.x));
+                    if stripe_direction == Direction::Backward {
+                        deferred.reverse();
+                    }
+                    let mut progress = deferred.iter().any(|c| is_supported(c, &placed));
+                    let mut left = Vec::new();
+                    for voxel in deferred.drain(0..) {
+                        if !progress || is_supported(&voxel, &placed) {
+                            cmds.push(Cmd::ZMove(voxel.z));
+                            cmds.push(Cmd::Fill(Coord{ x: voxel.x - s.x, y: -1, z: 0}));
+                            placed.insert(voxel);
+                            progress = true;
+                        } else {
+                            left.push(voxel);
+                        }
+                    }
+                    deferred = left;
+                    stripe_direction.switch();
+                }
             }
 
             layer_direction.switch();
@@ -399,13 +402,13 @@ fn main() -> Result<(),Error> {
         }
     }
 
-    if flip {
+    if options.flip {
         for _ in 1 .. n_bots { asc.push(BotCommand::wait().unwrap()); }
         asc.push(BotCommand::flip().unwrap());
     }
     
     /* proc */
-    if reverse {
+    if options.reverse {
         let mut v = VecDeque::new();
         for _ in bot_config.iter().enumerate() {
             v.push_front(Reverser::new(Optimizer::new(Translator::new(states.pop().unwrap(),commands.pop().unwrap().into_iter()))));
@@ -448,7 +451,7 @@ fn main() -> Result<(),Error> {
     }
 
     /* join */
-    if flip {
+    if options.flip {
         asc.push(BotCommand::flip().unwrap());
         for _ in 1 .. n_bots { asc.push(BotCommand::wait().unwrap()); }
     }
@@ -470,6 +473,77 @@ fn main() -> Result<(),Error> {
     }
     asc.push(BotCommand::halt().unwrap()); 
 
+    asc
+}
+
+fn main() -> Result<(),Error> {
+    let app = app_from_crate!()
+        .arg(Arg::with_name("original")
+             .display_order(1)
+             .short("i")
+             .long("in")
+             .help("Original model (In)",)
+             .takes_value(true))
+        .arg(Arg::with_name("optimized")
+             .display_order(2)
+             .short("o")
+             .long("out")
+             .help("Optimized trace (Out)")
+             .takes_value(true))
+        .arg(Arg::with_name("n")
+             .display_order(3)
+             .short("n")
+             .long("num-bots")
+             .help("bot number")
+             .takes_value(true))
+        .arg(Arg::with_name("reverse")
+             .display_order(4)
+             .short("r")
+             .long("reverse")
+             .help("Reverse"))
+        .arg(Arg::with_name("flip")
+             .display_order(5)
+             .long("no-flip")
+             .help("No flip"))
+        .arg(Arg::with_name("demolish")
+             .display_order(6)
+             .short("d")
+             .long("demolish")
+             .help("Demolish the model (In) layer by layer from the top"))
+        .arg(Arg::with_name("stripe-width")
+             .display_order(7)
+             .long("stripe-width")
+             .help("Stripe width")
+             .possible_values(&["1", "2", "3"])
+             .default_value("3")
+             .takes_value(true))
+        .arg(Arg::with_name("grounded-order")
+             .display_order(8)
+             .long("grounded-order")
+             .help("Fill voxels of a layer only next to already placed ones where possible"));
+
+    let matches = app.get_matches();
+    let original = value_t!(matches, "original", String).map_err(Error::Args)?;
+    let optimized = value_t!(matches, "optimized", String).map_err(Error::Args)?;
+    let options = Options {
+        bots_count: value_t!(matches, "n", usize).map_err(Error::Args)?,
+        reverse: matches.is_present("reverse"),
+        flip: !matches.is_present("flip"),
+        demolish: matches.is_present("demolish"),
+        stripe_width: value_t!(matches, "stripe-width", usize).map_err(Error::Args)?,
+        grounded_order: matches.is_present("grounded-order"),
+    };
+
+    let matrix = kernel::model::read_model_file(&original).map_err(Error::ModelReadError)?;
+    let asc = build_trace(&matrix, &options);
+    if options.grounded_order && !options.demolish {
+        let source = matrix.new_empty_of_same_size();
+        let plain = build_trace(&matrix, &Options { grounded_order: false, ..options });
+        println!("Ungrounded ticks: {} before, {} after",
+                 ungrounded_ticks(&source, &plain),
+                 ungrounded_ticks(&source, &asc));
+    }
+
     let mut cnt = 0;
     for _c in &asc {
         //println!("{:?}",_c);
@@ -486,3 +560,86 @@ fn main() -> Result<(),Error> {
     Ok(())
 }
 
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kernel::coord::Resolution;
+    use kernel::state::State;
+
+    fn tower() -> Matrix {
+        Matrix::from_iter(
+            Resolution(5),
+            vec![
+                Coord { x: 2, y: 0, z: 2 },
+                Coord { x: 2, y: 1, z: 2 },
+                Coord { x: 2, y: 2, z: 2 },
+                Coord { x: 3, y: 0, z: 2 },
+            ])
+    }
+
+    #[test]
+    fn test_demolish_tower() {
+        let matrix = tower();
+        let trace = build_trace(&matrix, &Options {
+            bots_count: 1,
+            reverse: false,
+            flip: true,
+            demolish: true,
+            stripe_width: 3,
+            grounded_order: false,
+        });
+        let mut state = State::new(matrix.clone(), vec![]);
+        assert_eq!(state.run_mut(trace), Ok(()));
+        assert!(state.model_diff(&matrix.new_empty_of_same_size()).is_empty());
+    }
+
+    #[test]
+    fn test_width_1_stripes_tower() {
+        let matrix = tower();
+        for &bots_count in &[1, 2] {
+            let trace = build_trace(&matrix, &Options {
+                bots_count,
+                reverse: false,
+                flip: true,
+                demolish: false,
+                stripe_width: 1,
+                grounded_order: false,
+            });
+            let mut state = State::new(matrix.new_empty_of_same_size(), vec![]);
+            assert_eq!(state.run_mut(trace), Ok(()));
+            assert!(state.model_diff(&matrix).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_grounded_order_stays_low() {
+        let matrix = Matrix::from_iter(
+            Resolution(5),
+            vec![
+                Coord { x: 1, y: 0, z: 1 },
+                Coord { x: 1, y: 1, z: 1 },
+                Coord { x: 1, y: 1, z: 2 },
+                Coord { x: 1, y: 1, z: 3 },
+            ]);
+        let options = Options {
+            bots_count: 1,
+            reverse: false,
+            flip: false,
+            demolish: false,
+            stripe_width: 1,
+            grounded_order: false,
+        };
+        let source = matrix.new_empty_of_same_size();
+
+        let plain = build_trace(&matrix, &options);
+        assert!(ungrounded_ticks(&source, &plain) > 0);
+
+        let grounded = build_trace(&matrix, &Options { grounded_order: true, ..options });
+        assert_eq!(ungrounded_ticks(&source, &grounded), 0);
+        let mut state = State::new(source, vec![]);
+        assert_eq!(state.run_mut(grounded), Ok(()));
+        assert!(state.model_diff(&matrix).is_empty());
+    }
+}
@@ -4,15 +4,11 @@ extern crate icfpc2018_lib;
 #[macro_use] extern crate log;
 #[macro_use] extern crate clap;
 
-use std::{io::{self, Write}, fs, process};
+use std::{io::{self, Write}, fs, path::Path, process, time::Duration};
 use clap::Arg;
+use rand::{RngCore, SeedableRng, prng::XorShiftRng};
 
 use icfpc2018_lib::{
-    coord::{
-        M,
-        Matrix,
-        Resolution,
-    },
     cmd,
     model,
     solver::random_swarm,
@@ -33,8 +29,7 @@ fn main() {
 #[derive(Debug)]
 enum Error {
     InvalidIntegerValue(clap::Error),
-    NoSourceOrTargetModelProvided,
-    Model(model::Error),
+    Model(model::PairError),
     Solver(random_swarm::Error),
     OutScriptFileCompile(cmd::Error),
     OutScriptFileOpen(io::Error),
@@ -62,6 +57,9 @@ fn run() -> Result<(), Error> {
              .help("Solver global ticks limit parameter")
              .default_value("1024")
              .takes_value(true))
+        .arg(Arg::with_name("auto-ticks-limit")
+             .long("auto-ticks-limit")
+             .help("Derive the global ticks limit from the voxels to change per bot instead of taking --global-ticks-limit"))
         .arg(Arg::with_name("rtt-limit")
              .short("r")
              .long("rtt-limit")
@@ -69,6 +67,18 @@ fn run() -> Result<(), Error> {
              .help("Solver RTT router samples limit")
              .default_value("256")
              .takes_value(true))
+        .arg(Arg::with_name("rtt-wander-limit")
+             .long("rtt-wander-limit")
+             .value_name("LIMIT")
+             .help("Solver RTT router samples limit for wandering routes")
+             .default_value("256")
+             .takes_value(true))
+        .arg(Arg::with_name("wander-backoff-after")
+             .long("wander-backoff-after")
+             .value_name("FAILURES")
+             .help("Halve the wandering RTT samples and distance after every this many failed wander routes in a row (0 to disable)")
+             .default_value("0")
+             .takes_value(true))
         .arg(Arg::with_name("route-attempts-limit")
              .long("route-attempts-limit")
              .value_name("LIMIT")
@@ -82,6 +92,88 @@ fn run() -> Result<(), Error> {
              .help("Solver maximum child spawns limit")
              .default_value("1")
              .takes_value(true))
+        .arg(Arg::with_name("max-active-bots")
+             .long("max-active-bots")
+             .value_name("COUNT")
+             .help("Solver maximum simultaneously active bots (never more than 40)")
+             .default_value("40")
+             .takes_value(true))
+        .arg(Arg::with_name("time-limit")
+             .long("time-limit")
+             .value_name("SECONDS")
+             .help("Solver wall-clock time limit")
+             .takes_value(true))
+        .arg(Arg::with_name("seed")
+             .long("seed")
+             .value_name("SEED")
+             .help("Solver random seed for reproducible runs")
+             .takes_value(true))
+        .arg(Arg::with_name("router")
+             .long("router")
+             .value_name("KIND")
+             .help("Solver route planning backend")
+             .possible_values(&["rtt", "astar", "hybrid"])
+             .default_value("rtt")
+             .takes_value(true))
+        .arg(Arg::with_name("astar-max-distance")
+             .long("astar-max-distance")
+             .value_name("DISTANCE")
+             .help("Hybrid router uses astar for routes shorter than this (L1), rtt for the rest")
+             .default_value("8")
+             .takes_value(true))
+        .arg(Arg::with_name("nearest-jobs")
+             .long("nearest-jobs")
+             .help("Pick the job closest to the idle bot instead of closest to the model center"))
+        .arg(Arg::with_name("allow-partial")
+             .long("allow-partial")
+             .help("On global ticks limit halt with a partially built model instead of failing"))
+        .arg(Arg::with_name("group-commands")
+             .long("group-commands")
+             .help("Fill (empty source) or void (empty target) straight lines with GFill/GVoid by two bots before starting the swarm"))
+        .arg(Arg::with_name("stall-ticks-limit")
+             .long("stall-ticks-limit")
+             .value_name("TICKS")
+             .help("Force a jam recovery after this many ticks without a voxel filled or voided (0 to disable)")
+             .default_value("0")
+             .takes_value(true))
+        .arg(Arg::with_name("per-component")
+             .long("per-component")
+             .help("Give every target connected component its own bot building only that component"))
+        .arg(Arg::with_name("reassembly-order")
+             .long("reassembly-order")
+             .value_name("ORDER")
+             .help("Which jobs an idle bot prefers while both voids and fills are left")
+             .possible_values(&["void-first", "fill-first", "interleaved"])
+             .default_value("void-first")
+             .takes_value(true))
+        .arg(Arg::with_name("spread-spawns")
+             .long("spread-spawns")
+             .help("Send the bots first to spots spread evenly along the target x axis instead of random ones"))
+        .arg(Arg::with_name("max-commands")
+             .long("max-commands")
+             .value_name("COUNT")
+             .help("Take the bots home and halt with a partial model once the script gets this long")
+             .takes_value(true))
+        .arg(Arg::with_name("seed-distribution")
+             .long("seed-distribution")
+             .value_name("MODE")
+             .help("Spawn all bots from the first one (root) or hand every child half of the spawns left (split)")
+             .possible_values(&["root", "split"])
+             .default_value("root")
+             .takes_value(true))
+        .arg(Arg::with_name("cooperative-window")
+             .long("cooperative-window")
+             .value_name("TICKS")
+             .help("Route the bots around where the others are going to be for this many ticks ahead instead of only where they are now")
+             .takes_value(true))
+        .arg(Arg::with_name("crop")
+             .long("crop")
+             .help("Solve in a cube around the models instead of the whole matrix (faster for small models)"))
+        .arg(Arg::with_name("checkpoint-every")
+             .long("checkpoint-every")
+             .value_name("TICKS")
+             .help("Every this many ticks save the halted script so far next to the output as <output>.partial.nbt")
+             .takes_value(true))
         .arg(Arg::with_name("output")
              .short("o")
              .long("output")
@@ -90,28 +182,18 @@ fn run() -> Result<(), Error> {
              .default_value("a.nbt")
              .takes_value(true))
         .get_matches();
-    let (source_model, target_model) =
-        if let Some(source_model_file) = matches.value_of("source-model") {
-            if let Some(target_model_file) = matches.value_of("target-model") {
-                (model::read_model_file(source_model_file).map_err(Error::Model)?,
-                 model::read_model_file(target_model_file).map_err(Error::Model)?)
-            } else {
-                let source_model = model::read_model_file(source_model_file).map_err(Error::Model)?;
-                let target_model = Matrix::new(Resolution(source_model.dim() as M));
-                (source_model, target_model)
-            }
-        } else {
-            if let Some(target_model_file) = matches.value_of("target-model") {
-                let target_model = model::read_model_file(target_model_file).map_err(Error::Model)?;
-                let source_model = Matrix::new(Resolution(target_model.dim() as M));
-                (source_model, target_model)
-            } else {
-                return Err(Error::NoSourceOrTargetModelProvided);
-            }
-        };
+    let (source_model, target_model) = model::load_pair(
+        matches.value_of("source-model").map(Path::new),
+        matches.value_of("target-model").map(Path::new),
+    ).map_err(Error::Model)?;
 
     info!("source model with {} voxels", source_model.filled_voxels().count());
     info!("target model with {} voxels", target_model.filled_voxels().count());
+    info!("target model stats: {:?}", model::stats(&target_model));
+    if let Err(validation) = model::validate(&target_model) {
+        warn!("target model is not grounded: {} ungrounded voxels, first at {:?}",
+              validation.ungrounded_count, validation.first_ungrounded);
+    }
 
     let config = random_swarm::Config {
         init_bots: vec![],
@@ -123,20 +205,109 @@ fn run() -> Result<(), Error> {
             .map_err(Error::InvalidIntegerValue)?,
         max_spawns: value_t!(matches, "max-spawns", usize)
             .map_err(Error::InvalidIntegerValue)?,
+        max_active_bots: value_t!(matches, "max-active-bots", usize)
+            .map_err(Error::InvalidIntegerValue)?,
+        time_limit: if matches.is_present("time-limit") {
+            Some(Duration::from_secs(value_t!(matches, "time-limit", u64).map_err(Error::InvalidIntegerValue)?))
+        } else {
+            None
+        },
+        seed: if matches.is_present("seed") {
+            Some(value_t!(matches, "seed", u64).map_err(Error::InvalidIntegerValue)?)
+        } else {
+            None
+        },
+        router: match matches.value_of("router") {
+            Some("astar") => random_swarm::RouterKind::Astar,
+            Some("hybrid") => random_swarm::RouterKind::Hybrid {
+                astar_max_distance: value_t!(matches, "astar-max-distance", usize)
+                    .map_err(Error::InvalidIntegerValue)?,
+            },
+            _ => random_swarm::RouterKind::Rtt,
+        },
+        nearest_jobs: matches.is_present("nearest-jobs"),
+        allow_partial: matches.is_present("allow-partial"),
+        use_group_commands: matches.is_present("group-commands"),
+        stall_ticks_limit: value_t!(matches, "stall-ticks-limit", usize)
+            .map_err(Error::InvalidIntegerValue)?,
+        work_bounds: None,
+        per_component: matches.is_present("per-component"),
+        rtt_wander_limit: value_t!(matches, "rtt-wander-limit", usize)
+            .map_err(Error::InvalidIntegerValue)?,
+        wander_backoff_after: value_t!(matches, "wander-backoff-after", usize)
+            .map_err(Error::InvalidIntegerValue)?,
+        checkpoint_every: if matches.is_present("checkpoint-every") {
+            Some(value_t!(matches, "checkpoint-every", usize).map_err(Error::InvalidIntegerValue)?)
+        } else {
+            None
+        },
+        reassembly_order: match matches.value_of("reassembly-order") {
+            Some("fill-first") => random_swarm::ReassemblyOrder::FillFirst,
+            Some("interleaved") => random_swarm::ReassemblyOrder::Interleaved,
+            _ => random_swarm::ReassemblyOrder::VoidFirst,
+        },
+        spread_spawns: matches.is_present("spread-spawns"),
+        max_commands: if matches.is_present("max-commands") {
+            Some(value_t!(matches, "max-commands", usize).map_err(Error::InvalidIntegerValue)?)
+        } else {
+            None
+        },
+        seed_distribution: match matches.value_of("seed-distribution") {
+            Some("split") => random_swarm::SeedDistribution::Split,
+            _ => random_swarm::SeedDistribution::Root,
+        },
+        cooperative_window: if matches.is_present("cooperative-window") {
+            Some(value_t!(matches, "cooperative-window", usize).map_err(Error::InvalidIntegerValue)?)
+        } else {
+            None
+        },
+        crop_to_models: matches.is_present("crop"),
+        auto_tick_limit: matches.is_present("auto-ticks-limit"),
+    };
+    let mut rng: Box<dyn RngCore> = if let Some(seed) = config.seed {
+        Box::new(XorShiftRng::seed_from_u64(seed))
+    } else {
+        Box::new(rand::thread_rng())
     };
 
+    let output_filename = value_t!(matches, "output", String).unwrap();
+    let partial_filename = format!("{}.partial.nbt", output_filename.trim_right_matches(".nbt"));
+
     info!("Everything is ready, start solving");
 
-    let solve_result = random_swarm::solve_rng(
+    let solve_result = random_swarm::solve_detailed_with_checkpoints(
         source_model,
         target_model,
         config,
-        &mut rand::thread_rng(),
+        &mut rng,
+        Some(&mut |progress: random_swarm::Progress| info!(
+            "tick {}: {} voxels remaining, {} bots active, energy ~{}",
+            progress.tick,
+            progress.voxels_remaining,
+            progress.active_bots,
+            progress.energy_estimate,
+        )),
+        Some(&mut |script: &[cmd::BotCommand]| {
+            let written = cmd::into_bytes(&script.to_vec())
+                .map_err(Error::OutScriptFileCompile)
+                .and_then(|trace| fs::write(&partial_filename, &trace).map_err(Error::OutScriptFileWrite));
+            match written {
+                Ok(()) =>
+                    info!("checkpoint of {} commands saved to {}", script.len(), partial_filename),
+                Err(e) =>
+                    warn!("failed to save checkpoint to {}: {:?}", partial_filename, e),
+            }
+        }),
     );
 
     let (script, status) = match solve_result {
-        Ok(script) =>
-            (script, Ok(())),
+        Ok((script, stats)) => {
+            info!("solved in {} ticks with coverage ratio {:.3} and {} jam recoveries", stats.ticks, stats.coverage_ratio, stats.recoveries);
+            if stats.commands_limit_hit {
+                warn!("stopped early on the commands limit with a partially built model");
+            }
+            (script, Ok(()))
+        },
         Err((error, script)) =>
             (script, Err(Error::Solver(error))),
     };
@@ -144,7 +315,6 @@ fn run() -> Result<(), Error> {
 
     let trace = cmd::into_bytes(&script)
         .map_err(Error::OutScriptFileCompile)?;
-    let output_filename = value_t!(matches, "output", String).unwrap();
     let file = fs::File::create(output_filename)
         .map_err(Error::OutScriptFileOpen)?;
     let mut writer = io::BufWriter::new(file);
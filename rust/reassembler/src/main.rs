@@ -12,7 +12,8 @@ use std::iter::DoubleEndedIterator;
 
 use icfpc2018_lib as kernel;
 use kernel::cmd::BotCommand;
-use kernel::coord::{LinearCoordDiff,Axis,M,Coord,CoordDiff};
+use kernel::coord::{LinearCoordDiff,Axis,M,Coord,CoordDiff,Matrix,Region};
+use kernel::router::astar;
 
 #[derive(Debug)]
 enum Error {
@@ -20,6 +21,7 @@ enum Error {
     Io(std::io::Error),
     Cmd(kernel::cmd::Error),
     ModelReadError(kernel::model::Error),
+    NoRoute(Coord),
 }
 
 
@@ -162,25 +164,73 @@ impl Iterator for Reverser {
     type Item = BotCommand;
     
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next_back() {
-            None => None,
-            Some(BotCommand::Wait) |
-            Some(BotCommand::Halt) |
-            Some(BotCommand::Flip) |
-            Some(BotCommand::GFill{ .. }) |
-            Some(BotCommand::GVoid{ .. }) |
-            Some(BotCommand::FusionP{ .. }) |
-            Some(BotCommand::FusionS{ .. }) |
-            Some(BotCommand::Fission{ .. }) => unimplemented!(),
-            Some(BotCommand::Fill{ near }) => Some(BotCommand::Void{ near }),
-            Some(BotCommand::Void{ near }) => Some(BotCommand::Fill{ near }),
-            Some(BotCommand::SMove{ long: LinearCoordDiff::Long{ axis, value} }) => Some(BotCommand::SMove{ long: LinearCoordDiff::Long{ axis: axis, value: -value } }),
-            Some(BotCommand::LMove{ short1: LinearCoordDiff::Short { axis: a1, value: v1}, short2: LinearCoordDiff::Short { axis: a2, value: v2} }) => {
-                Some(BotCommand::LMove{ short1: LinearCoordDiff::Short { axis: a2, value: -v2}, short2: LinearCoordDiff::Short { axis: a1, value: -v1} })
-            },
-            _ => unreachable!(),
+        self.iter.next_back()
+            .map(|c| kernel::cmd::invert(&c).unwrap())
+    }
+}
+
+fn near_diffs() -> Vec<CoordDiff> {
+    let mut diffs = vec![CoordDiff(Coord{ x: 0, y: -1, z: 0 })];
+    for dx in -1 .. 2 {
+        for dy in -1 .. 2 {
+            for dz in -1 .. 2 {
+                let d = CoordDiff(Coord{ x: dx, y: dy, z: dz });
+                if d.is_near() && !diffs.contains(&d) {
+                    diffs.push(d);
+                }
+            }
         }
     }
+    diffs
+}
+
+// Moves the bot next to `voxel` and performs `make_cmd` on it. The first candidate
+// position is right above the voxel, the rest are tried in turn if it can't be reached.
+fn visit_voxel<F>(matrix: &Matrix, bot: &mut Coord, voxel: &Coord, asc: &mut Vec<BotCommand>, make_cmd: F) -> Result<(),Error>
+    where F: Fn(CoordDiff) -> BotCommand
+{
+    let dim = matrix.dim() as M;
+    let is_passable = |region: &Region| !matrix.contains_filled(region);
+    for near in near_diffs() {
+        let pos = Coord{ x: voxel.x - near.0.x, y: voxel.y - near.0.y, z: voxel.z - near.0.z };
+        if pos.x < 0 || pos.y < 0 || pos.z < 0 || pos.x >= dim || pos.y >= dim || pos.z >= dim {
+            continue;
+        }
+        if let Some((moves, _)) = astar::plan_route_passable(bot, &pos, matrix.dim(), &is_passable) {
+            asc.extend(moves.into_iter().filter_map(|mv| mv.cmd_performed));
+            asc.push(make_cmd(near));
+            *bot = pos;
+            return Ok(());
+        }
+    }
+    Err(Error::NoRoute(*voxel))
+}
+
+// Single bot reassembly: voids the voxels missing from the target top-down, then fills
+// the new ones bottom-up, all in High harmonics, keeping the voxels both models share.
+fn reassemble(source: &Matrix, target: &Matrix) -> Result<Vec<BotCommand>,Error> {
+    let (mut removed, mut added) = source.diff(target);
+    removed.sort_by_key(|c| (-c.y, c.x, c.z));
+    added.sort_by_key(|c| (c.y, c.x, c.z));
+
+    let mut matrix = source.clone();
+    let mut bot = Coord{ x: 0, y: 0, z: 0 };
+    let mut asc = vec![BotCommand::flip().unwrap()];
+    for voxel in removed {
+        visit_voxel(&matrix, &mut bot, &voxel, &mut asc, |near| BotCommand::void(near).unwrap())?;
+        matrix.set_void(&voxel);
+    }
+    for voxel in added {
+        visit_voxel(&matrix, &mut bot, &voxel, &mut asc, |near| BotCommand::fill(near).unwrap())?;
+        matrix.set_filled(&voxel);
+    }
+    let origin = Coord{ x: 0, y: 0, z: 0 };
+    let (moves, _) = astar::plan_route_passable(&bot, &origin, matrix.dim(), |region: &Region| !matrix.contains_filled(region))
+        .ok_or(Error::NoRoute(origin))?;
+    asc.extend(moves.into_iter().filter_map(|mv| mv.cmd_performed));
+    asc.push(BotCommand::flip().unwrap());
+    asc.push(BotCommand::halt().unwrap());
+    Ok(asc)
 }
 
 fn main() -> Result<(),Error> {
@@ -197,6 +247,19 @@ fn main() -> Result<(),Error> {
              .long("target")
              .help("Target trace (In)")
              .takes_value(true))
+        .arg(Arg::with_name("source-model")
+             .display_order(4)
+             .long("source-model")
+             .help("Source model: reassemble directly instead of chaining traces")
+             .requires("target-model")
+             .conflicts_with_all(&["source", "target"])
+             .takes_value(true))
+        .arg(Arg::with_name("target-model")
+             .display_order(5)
+             .long("target-model")
+             .help("Target model")
+             .requires("source-model")
+             .takes_value(true))
         .arg(Arg::with_name("trace")
              .display_order(3)
              .short("o")
@@ -205,25 +268,35 @@ fn main() -> Result<(),Error> {
              .takes_value(true));
 
     let matches = app.get_matches();
+    let trace = value_t!(matches, "trace", String).map_err(Error::Args)?;
+
+    if let Some(source_model) = matches.value_of("source-model") {
+        let target_model = value_t!(matches, "target-model", String).map_err(Error::Args)?;
+        let source_model = kernel::model::read_model_file(source_model).map_err(Error::ModelReadError)?;
+        let target_model = kernel::model::read_model_file(target_model).map_err(Error::ModelReadError)?;
+        let asc = reassemble(&source_model, &target_model)?;
+        let buffer = kernel::cmd::into_bytes(&asc).unwrap();
+        let mut f = File::create(&trace).map_err(Error::Io)?;
+        f.write_all(&buffer).map_err(Error::Io)?;
+        return Ok(());
+    }
+
     let source = value_t!(matches, "source", String).map_err(Error::Args)?;
     let target = value_t!(matches, "target", String).map_err(Error::Args)?;
-    let trace = value_t!(matches, "trace", String).map_err(Error::Args)?;
 
 
 
     let mut f = File::open(&source).map_err(Error::Io)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer).map_err(Error::Io)?;
-    let mut destroy_cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
+    let destroy_cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
 
     let mut f = File::open(&target).map_err(Error::Io)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer).map_err(Error::Io)?;
     let create_cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
 
-    destroy_cmds.pop();
-
-    let asc = destroy_cmds.into_iter().chain(create_cmds.into_iter()).collect();
+    let asc = kernel::cmd::concat_aligned(&[destroy_cmds, create_cmds]).map_err(Error::Cmd)?;
   
     {
         let buffer = kernel::cmd::into_bytes(&asc).unwrap();
@@ -234,3 +307,37 @@ fn main() -> Result<(),Error> {
     Ok(())
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kernel::coord::Resolution;
+    use kernel::state::State;
+
+    #[test]
+    fn test_reassemble_tower_into_cross() {
+        let tower = Matrix::from_iter(
+            Resolution(5),
+            vec![
+                Coord{ x: 2, y: 0, z: 2 },
+                Coord{ x: 2, y: 1, z: 2 },
+                Coord{ x: 2, y: 2, z: 2 },
+                Coord{ x: 2, y: 3, z: 2 },
+            ]);
+        let cross = Matrix::from_iter(
+            Resolution(5),
+            vec![
+                Coord{ x: 2, y: 0, z: 2 },
+                Coord{ x: 1, y: 1, z: 2 },
+                Coord{ x: 2, y: 1, z: 1 },
+                Coord{ x: 2, y: 1, z: 3 },
+                Coord{ x: 2, y: 1, z: 2 },
+                Coord{ x: 3, y: 1, z: 2 },
+                Coord{ x: 2, y: 2, z: 2 },
+            ]);
+        let trace = reassemble(&tower, &cross).unwrap();
+        let mut state = State::new(tower.clone(), vec![]);
+        assert_eq!(state.run_mut(trace), Ok(()));
+        assert!(state.model_diff(&cross).is_empty());
+    }
+}
@@ -2,8 +2,8 @@ extern crate icfpc2018_lib;
 #[macro_use] extern crate clap;
 
 use clap::Arg;
-use std::fs::File;
-use std::io::Read;
+use std::path::Path;
+use std::process;
 
 use icfpc2018_lib as kernel;
 
@@ -16,18 +16,84 @@ use kernel::{
 #[derive(Debug)]
 enum Error {
     Args(clap::Error),
-    NoSourceOrTargetModelProvided,
     Io(std::io::Error),
-    Model(model::Error),
+    Model(model::PairError),
     Cmd(cmd::Error),
     State(state::Error),
+    NotWellformed { step: usize, report: state::WellformedReport, },
     ModelNotMatch,
 }
 
 
 
-fn main() -> Result<(),Error> {
+const DIFF_VOXELS_SHOWN: usize = 20;
+// way more than any sane solution takes, just so a trace that never halts cannot hang the scorer
+const MAX_STEPS_DEFAULT: &str = "10000000";
+
+fn print_model_diff(diff: &state::ModelDiff) {
+    println!("Filled but should be void: {} voxels", diff.extra.len());
+    for voxel in diff.extra.iter().take(DIFF_VOXELS_SHOWN) {
+        println!("  {:?}", voxel);
+    }
+    println!("Missing: {} voxels", diff.missing.len());
+    for voxel in diff.missing.iter().take(DIFF_VOXELS_SHOWN) {
+        println!("  {:?}", voxel);
+    }
+}
+
+fn print_wellformed_report(report: &state::WellformedReport) {
+    match *report {
+        state::WellformedReport::Wellformed =>
+            (),
+        state::WellformedReport::NotGroundedWhileLowHarmonics { voxel, } =>
+            println!("Voxel at {:?} is not grounded while in low harmonics", voxel),
+        state::WellformedReport::BotInFilledVoxel { bid, pos, } =>
+            println!("Bot {} at {:?} is inside a filled voxel", bid, pos),
+        state::WellformedReport::SeedsAreNotDisjoint { seed, } =>
+            println!("Seed {} is given to more than one bot", seed),
+        state::WellformedReport::SeedIsTheSameAsActiveBot { bid, } =>
+            println!("Bot {} is active and a seed at the same time", bid),
+    }
+}
+
+impl Error {
+    fn exit_code(&self) -> i32 {
+        match self {
+            &Error::ModelNotMatch => 2,
+            &Error::Cmd(_) | &Error::State(_) | &Error::NotWellformed { .. } => 3,
+            &Error::Model(model::PairError::NoSourceOrTarget) | &Error::Args(_) => 5,
+            &Error::Io(_) | &Error::Model(_) => 4,
+        }
+    }
+}
+
+// Like `State::run_mut_bounded`, but checks the state is wellformed right after every step (the
+// last one included) instead of before the next one, failing with the step that broke it.
+fn run_strict(state: &mut state::State, cmds: Vec<cmd::BotCommand>, max_steps: usize) -> Result<(), Error> {
+    let res = state.run_mut_with(cmds, max_steps, |state| match state.wellformed() {
+        state::WellformedReport::Wellformed =>
+            Ok(()),
+        report =>
+            Err(state::Error::StateNotWellformed { report, }),
+    });
+    match res {
+        Err(state::Error::StateNotWellformed { report, }) =>
+            Err(Error::NotWellformed { step: state.steps, report, }),
+        res =>
+            res.map_err(Error::State),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<(),Error> {
     let app = app_from_crate!()
+        .after_help("EXIT CODES:\n    0    success\n    2    resulting model does not match the destination model\n    3    simulation error (bad trace)\n    4    I/O error\n    5    bad arguments")
         .arg(Arg::with_name("source-model")
              .short("s")
              .long("source-model")
@@ -45,63 +111,120 @@ fn main() -> Result<(),Error> {
              .short("t")
              .long("trace")
              .help("Trace",)
-             .takes_value(true));
+             .takes_value(true))
+        .arg(Arg::with_name("trusted")
+             .long("trusted")
+             .help("Skip the wellformedness and command checks of a trace known to be valid, only computing its energy and model (a bad trace gives garbage or crashes)"))
+        .arg(Arg::with_name("strict")
+             .long("strict")
+             .help("Check the state is wellformed after every step and report the first step that breaks it"))
+        .arg(Arg::with_name("max-steps")
+             .long("max-steps")
+             .value_name("STEPS")
+             .help("Give up on a trace that has not halted after this many steps")
+             .default_value(MAX_STEPS_DEFAULT)
+             .takes_value(true))
+        .arg(Arg::with_name("json")
+             .long("json")
+             .help("Print a single JSON object with the result instead of human readable lines"));
 
     let matches = app.get_matches();
-    let (source_model, dst_model) =
-        if let Some(source_model_file) = matches.value_of("source-model") {
-            if let Some(dst_model_file) = matches.value_of("dst-model") {
-                (model::read_model_file(source_model_file).map_err(Error::Model)?,
-                 model::read_model_file(dst_model_file).map_err(Error::Model)?)
-            } else {
-                let source_model = model::read_model_file(source_model_file).map_err(Error::Model)?;
-                let dst_model = source_model.new_empty_of_same_size();
-                (source_model, dst_model)
-            }
-        } else {
-            if let Some(dst_model_file) = matches.value_of("dst-model") {
-                let dst_model = model::read_model_file(dst_model_file).map_err(Error::Model)?;
-                let source_model = dst_model.new_empty_of_same_size();
-                (source_model, dst_model)
-            } else {
-                return Err(Error::NoSourceOrTargetModelProvided);
-            }
-        };
+    let (source_model, dst_model) = model::load_pair(
+        matches.value_of("source-model").map(Path::new),
+        matches.value_of("dst-model").map(Path::new),
+    ).map_err(Error::Model)?;
 
     let trace_filename = value_t!(matches, "trace", String).map_err(Error::Args)?;
-
-    let mut f = File::open(&trace_filename).map_err(Error::Io)?;
-    let mut buffer = Vec::new();
-    f.read_to_end(&mut buffer).map_err(Error::Io)?;
+    let max_steps = value_t!(matches, "max-steps", usize).map_err(Error::Args)?;
 
     let mut state = state::State::new(source_model, vec![]);
+    if matches.is_present("trusted") {
+        state = state.with_scoring_config(state::ScoringConfig::trusted());
+    }
+
+    let cmds = cmd::read_trace_file(&trace_filename)
+        .map_err(|error| match error {
+            cmd::Error::TraceOpen(error) | cmd::Error::TraceRead(error) =>
+                Error::Io(error),
+            error =>
+                Error::Cmd(error),
+        })?;
+    let commands_total = cmds.len();
+    let json = matches.is_present("json");
+    if !json {
+        println!("Commands: {}", commands_total);
+    }
+
+    let res = if matches.is_present("strict") {
+        run_strict(&mut state, cmds, max_steps)
+    } else {
+        state.run_mut_bounded(cmds, max_steps).map_err(Error::State)
+    };
+    let outcome = match res {
+        Err(e) =>
+            Err(e),
+        Ok(()) => {
+            if state.model_diff(&dst_model).is_empty() {
+                Ok(())
+            } else {
+                Err(Error::ModelNotMatch)
+            }
+        },
+    };
 
-    let cmds = cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
-    println!("Commands: {}", cmds.len());
+    if json {
+        let error = match outcome {
+            Ok(()) =>
+                "null".to_string(),
+            Err(ref e) =>
+                format!("\"{}\"", format!("{:?}", e).replace('\\', "\\\\").replace('"', "\\\"")),
+        };
+        println!(
+            "{{ \"success\": {}, \"energy\": {}, \"steps\": {}, \"error\": {}, \"commands\": {}, \"avg_bots\": {:.3} }}",
+            outcome.is_ok(),
+            state.energy as i64,
+            state.steps,
+            error,
+            commands_total,
+            state.avg_bots_per_tick(),
+        );
+        return outcome;
+    }
 
-    let res = state.run_mut(cmds);
     println!("Steps: {} ", state.steps);
     println!("ENERGY: {}", state.energy);
-    match res {
-        Err(e) => {
+    let breakdown = state.energy_breakdown();
+    println!("  harmonics: {}", breakdown.harmonics);
+    println!("  bots: {}", breakdown.bots);
+    println!("  moves: {}", breakdown.moves);
+    println!("  fill/void: {}", breakdown.fill_void);
+    println!("  fission/fusion: {}", breakdown.fission_fusion);
+    println!("Peak bots: {}", state.peak_bots);
+    println!("Commands executed: {}", state.commands_executed);
+    println!("Average bots per tick: {:.2}", state.avg_bots_per_tick());
+    match outcome {
+        Err(Error::State(e)) => {
             println!("ERROR: {:?}", e);
+            if let state::Error::StateNotWellformed { report, } = e {
+                print_wellformed_report(&report);
+            }
             Err(Error::State(e))
         },
-        Ok(_) => {
-            for voxel in state.matrix.filled_voxels() {
-                if state.matrix.is_filled(voxel) && !dst_model.is_filled(voxel) {
-                    return Err(Error::ModelNotMatch);
-                }
-            }
-            for voxel in dst_model.filled_voxels() {
-                if !state.matrix.is_filled(voxel) {
-                    return Err(Error::ModelNotMatch);
-                }
-            }
-
+        Err(Error::NotWellformed { step, report, }) => {
+            println!("ERROR: not wellformed after step {}: {:?}", step, report);
+            print_wellformed_report(&report);
+            Err(Error::NotWellformed { step, report, })
+        },
+        Err(Error::ModelNotMatch) => {
+            print_model_diff(&state.model_diff(&dst_model));
+            Err(Error::ModelNotMatch)
+        },
+        Err(e) =>
+            Err(e),
+        Ok(()) => {
             println!("SUCCESS. FINAL ENERGY {}", state.energy);
             Ok(())
-        }
+        },
     }
 }
 
@@ -109,4 +232,49 @@ fn main() -> Result<(),Error> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use kernel::coord::{Axis, Coord, CoordDiff, LinearCoordDiff, Matrix, Resolution};
+
+    // a voxel filled in the air in low harmonics, then the bot waits and comes back
+    fn floating_fill() -> Vec<cmd::BotCommand> {
+        vec![
+            cmd::BotCommand::smove(LinearCoordDiff::Long { axis: Axis::Y, value: 2, }).unwrap(),
+            cmd::BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 0, })).unwrap(),
+            cmd::BotCommand::wait().unwrap(),
+            cmd::BotCommand::smove(LinearCoordDiff::Long { axis: Axis::X, value: 1, }).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn strict_reports_first_ungrounded_step() {
+        let mut state = state::State::new(Matrix::new(Resolution(4)), vec![]);
+        match run_strict(&mut state, floating_fill(), 100) {
+            Err(Error::NotWellformed { step, report, }) => {
+                assert_eq!(step, 2);
+                assert_eq!(report, state::WellformedReport::NotGroundedWhileLowHarmonics { voxel: Coord { x: 0, y: 1, z: 0, }, });
+            },
+            other =>
+                panic!("expected not wellformed, got {:?}", other),
+        }
+
+        // without it the violation only shows up before the next step, as a plain simulation error
+        let mut state = state::State::new(Matrix::new(Resolution(4)), vec![]);
+        match state.run_mut(floating_fill()) {
+            Err(state::Error::StateNotWellformed { .. }) =>
+                assert_eq!(state.steps, 3),
+            other =>
+                panic!("expected not wellformed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn strict_passes_valid_trace() {
+        let cmds = vec![
+            cmd::BotCommand::fill(CoordDiff(Coord { x: 1, y: 0, z: 0, })).unwrap(),
+            cmd::BotCommand::halt().unwrap(),
+        ];
+        let mut state = state::State::new(Matrix::new(Resolution(3)), vec![]);
+        assert!(run_strict(&mut state, cmds, 100).is_ok());
+        assert_eq!(state.steps, 2);
+        assert!(state.matrix.is_filled(&Coord { x: 1, y: 0, z: 0, }));
+    }
 }
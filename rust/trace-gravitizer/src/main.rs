@@ -18,59 +18,14 @@ enum Error {
     ModelReadError(kernel::model::Error),
     Cmd(kernel::cmd::Error),
     State(kernel::state::Error),
+    OddFlipsCount(usize),
 }
 
 
 
-fn main() -> Result<(),Error> {
-    let app = app_from_crate!()
-        .arg(Arg::with_name("model")
-             .display_order(1)
-             .short("m")
-             .long("model")
-             .help("Model file (In)",)
-             .default_value("")
-             .takes_value(true))
-        .arg(Arg::with_name("trace")
-             .display_order(1)
-             .short("t")
-             .long("trace")
-             .help("Original trace (In)",)
-             .default_value("")
-             .takes_value(true))
-        .arg(Arg::with_name("fliptrace")
-             .display_order(2)
-             .short("f")
-             .long("flip-trace")
-             .help("Flipped trace (Out)",)
-             .default_value("tmp.nbt")
-             .takes_value(true));
-
-    let matches = app.get_matches();
-    let model_filename = value_t!(matches, "model", String).map_err(Error::Args)?;
-    let trace_filename = value_t!(matches, "trace", String).map_err(Error::Args)?;
-    let out_trace_filename = value_t!(matches, "fliptrace", String).map_err(Error::Args)?;
-
-    let matrix = if model_filename != "" {
-        match kernel::model::read_model_file(model_filename) {
-            Err(e) => return Err(Error::ModelReadError(e)),
-            Ok(m) => m,
-        }
-    } else {
-        Matrix::new(Resolution(250))
-    };
-
-    let mut f = File::open(&trace_filename).map_err(Error::Io)?;
-    let mut buffer = Vec::new();
-    f.read_to_end(&mut buffer).map_err(Error::Io)?;
-
-    //let matrix = ref_model.new_empty_of_same_size();
-    //let matrix = Matrix::new(Resolution(250));
+fn gravitize(matrix: Matrix, cmds: Vec<BotCommand>, true_threshold: usize) -> Result<Vec<BotCommand>,Error> {
     let mut state = kernel::state::State::new(matrix, vec![]);
 
-    let cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
-    println!("Commands: {}", cmds.len());
-
     let res = state.stateless_run(&cmds);
     match res {
         Err(e) => {
@@ -79,7 +34,6 @@ fn main() -> Result<(),Error> {
         },
         Ok(script) => {
             println!("SUCCESS");
-            let true_threshold = 2;
             let flipper = {
                 let mut cur = true;
                 let mut flip = Vec::new();
@@ -138,16 +92,118 @@ fn main() -> Result<(),Error> {
             }
             new_cmd.extend(cmd_iter);
             if sfl % 2 !=0 {
-                panic!("FLIPS: {}",sfl);
-            }
-            let buffer = kernel::cmd::into_bytes(&new_cmd).unwrap();
-            {
-                let mut f = File::create(&out_trace_filename).map_err(Error::Io)?;
-                f.write_all(&buffer).map_err(Error::Io)?;
+                return Err(Error::OddFlipsCount(sfl));
             }
-            Ok(())
+            Ok(new_cmd)
         }
     }
 }
 
+fn main() -> Result<(),Error> {
+    let app = app_from_crate!()
+        .arg(Arg::with_name("model")
+             .display_order(1)
+             .short("m")
+             .long("model")
+             .help("Model file (In)",)
+             .default_value("")
+             .takes_value(true))
+        .arg(Arg::with_name("trace")
+             .display_order(1)
+             .short("t")
+             .long("trace")
+             .help("Original trace (In)",)
+             .default_value("")
+             .takes_value(true))
+        .arg(Arg::with_name("fliptrace")
+             .display_order(2)
+             .short("f")
+             .long("flip-trace")
+             .help("Flipped trace (Out)",)
+             .default_value("tmp.nbt")
+             .takes_value(true))
+        .arg(Arg::with_name("threshold")
+             .display_order(3)
+             .long("threshold")
+             .help("Minimum length in ticks of a grounded interval worth flipping back to Low for")
+             .default_value("2")
+             .takes_value(true));
 
+    let matches = app.get_matches();
+    let model_filename = value_t!(matches, "model", String).map_err(Error::Args)?;
+    let trace_filename = value_t!(matches, "trace", String).map_err(Error::Args)?;
+    let out_trace_filename = value_t!(matches, "fliptrace", String).map_err(Error::Args)?;
+    let true_threshold = value_t!(matches, "threshold", usize).map_err(Error::Args)?;
+
+    let matrix = if model_filename != "" {
+        match kernel::model::read_model_file(model_filename) {
+            Err(e) => return Err(Error::ModelReadError(e)),
+            Ok(m) => m,
+        }
+    } else {
+        Matrix::new(Resolution(250))
+    };
+
+    let mut f = File::open(&trace_filename).map_err(Error::Io)?;
+    let mut buffer = Vec::new();
+    f.read_to_end(&mut buffer).map_err(Error::Io)?;
+
+    //let matrix = ref_model.new_empty_of_same_size();
+    //let matrix = Matrix::new(Resolution(250));
+    let cmds = kernel::cmd::from_bytes(&buffer).map_err(Error::Cmd)?;
+    println!("Commands: {}", cmds.len());
+
+    let new_cmd = gravitize(matrix, cmds, true_threshold)?;
+    let buffer = kernel::cmd::into_bytes(&new_cmd).unwrap();
+    {
+        let mut f = File::create(&out_trace_filename).map_err(Error::Io)?;
+        f.write_all(&buffer).map_err(Error::Io)?;
+    }
+    Ok(())
+}
+
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kernel::coord::{Axis,Coord,CoordDiff,LinearCoordDiff};
+    use kernel::state::{State,WellformedStatus};
+
+    #[test]
+    fn test_gfill_floating_line() {
+        let smove = |axis, value| BotCommand::smove(LinearCoordDiff::Long { axis, value }).unwrap();
+        let cmds = vec![
+            BotCommand::fission(CoordDiff(Coord { x: 1, y: 0, z: 0 }), 0).unwrap(),
+            smove(Axis::Y, 2), smove(Axis::Y, 2),
+            BotCommand::wait().unwrap(), smove(Axis::Z, 4),
+            smove(Axis::X, 2), smove(Axis::X, 1),
+            /* floating line (2, 1, 1) - (2, 1, 3) */
+            BotCommand::gfill(CoordDiff(Coord { x: 0, y: -1, z: 1 }), CoordDiff(Coord { x: 0, y: 0, z: 2 })).unwrap(),
+            BotCommand::gfill(CoordDiff(Coord { x: 0, y: -1, z: -1 }), CoordDiff(Coord { x: 0, y: 0, z: -2 })).unwrap(),
+            smove(Axis::Y, -1), BotCommand::wait().unwrap(),
+            /* support (2, 0, 1) grounds the line */
+            BotCommand::fill(CoordDiff(Coord { x: 0, y: -1, z: 1 })).unwrap(), BotCommand::wait().unwrap(),
+            BotCommand::wait().unwrap(), smove(Axis::Z, -4),
+            BotCommand::pfusion(CoordDiff(Coord { x: 0, y: 1, z: 0 })).unwrap(),
+            BotCommand::sfusion(CoordDiff(Coord { x: 0, y: -1, z: 0 })).unwrap(),
+            smove(Axis::X, -2),
+            smove(Axis::Y, -1),
+            BotCommand::halt().unwrap(),
+        ];
+
+        let mut original = State::new(Matrix::new(Resolution(5)), vec![]);
+        match original.run_mut(cmds.clone()) {
+            Err(kernel::state::Error::StateNotWellformed { report }) =>
+                assert_eq!(report.status(), WellformedStatus::NotGroundedWhileLowHarmonics),
+            other =>
+                panic!("expected not grounded, got {:?}", other),
+        }
+
+        let gravitized = gravitize(Matrix::new(Resolution(5)), cmds, 2).unwrap();
+        let mut state = State::new(Matrix::new(Resolution(5)), vec![]);
+        assert_eq!(state.run_mut(gravitized), Ok(()));
+        assert_eq!(state.matrix.filled_voxels().count(), 4);
+    }
+}
@@ -0,0 +1,68 @@
+extern crate icfpc2018_lib;
+#[macro_use] extern crate clap;
+
+use std::process;
+
+use clap::Arg;
+
+use icfpc2018_lib::cmd;
+
+#[derive(Debug)]
+enum Error {
+    Args(clap::Error),
+    TraceRead { file: String, error: cmd::Error, },
+    Seam { file: String, error: cmd::Error, },
+    TraceWrite(cmd::Error),
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let matches = app_from_crate!()
+        .arg(Arg::with_name("in")
+             .value_name("FILE")
+             .help("Input traces in the order they run, each one halting with a single bot")
+             .required(true)
+             .multiple(true))
+        .arg(Arg::with_name("out")
+             .short("o")
+             .long("out")
+             .value_name("FILE")
+             .help("Output trace file (gzipped when it ends with .gz)")
+             .default_value("a.nbt")
+             .takes_value(true))
+        .get_matches();
+
+    let in_filenames = values_t!(matches, "in", String).map_err(Error::Args)?;
+    let out_filename = value_t!(matches, "out", String).map_err(Error::Args)?;
+
+    let mut traces = Vec::with_capacity(in_filenames.len());
+    for file in in_filenames.iter() {
+        let trace = cmd::read_trace_file(file)
+            .map_err(|error| Error::TraceRead { file: file.clone(), error, })?;
+        traces.push(trace);
+    }
+
+    let merged = cmd::concat_aligned(&traces)
+        .map_err(|error| {
+            let trace = match error {
+                cmd::Error::ConcatNoHalt { trace, } |
+                cmd::Error::ConcatBotsAtSeam { trace, .. } |
+                cmd::Error::ConcatTrailingCommands { trace, .. } =>
+                    trace,
+                _ =>
+                    0,
+            };
+            Error::Seam { file: in_filenames[trace].clone(), error, }
+        })?;
+
+    cmd::write_trace_file(&out_filename, &merged).map_err(Error::TraceWrite)?;
+
+    println!("merged {} traces into {} commands", traces.len(), merged.len());
+    Ok(())
+}
@@ -1,98 +1,85 @@
 extern crate icfpc2018_lib;
+#[macro_use] extern crate clap;
 
-use std::{fs, io::{self, Write}};
+use std::{fs, io::{self, Read, Write}, process};
 
-use icfpc2018_lib::{
-    cmd::{
-        self,
-        BotCommand,
-    },
-    coord::{
-        Axis,
-        Coord,
-        CoordDiff,
-        LinearCoordDiff,
-    },
-};
+use clap::Arg;
+
+use icfpc2018_lib::cmd::{self, text};
+
+#[derive(Debug)]
+enum Error {
+    Args(clap::Error),
+    ScriptOpen(io::Error),
+    ScriptRead(io::Error),
+    CorruptedCommand { index: usize, error: text::Error, },
+    InvalidCommand { index: usize, error: cmd::Error, },
+    Encode(cmd::Error),
+    TraceCreate(io::Error),
+    TraceWrite(io::Error),
+}
 
 fn main() {
-    let mut script: Vec<Result<BotCommand, _>> = Vec::new();
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let matches = app_from_crate!()
+        .arg(Arg::with_name("in")
+             .short("i")
+             .long("in")
+             .value_name("FILE")
+             .help("Text script, one command per line (e.g. `SMove <0,0,5>`)")
+             .required(true)
+             .takes_value(true))
+        .arg(Arg::with_name("out")
+             .short("o")
+             .long("out")
+             .value_name("FILE")
+             .help("Output trace file")
+             .default_value("a.nbt")
+             .takes_value(true))
+        .get_matches();
+
+    let in_filename = value_t!(matches, "in", String).map_err(Error::Args)?;
+    let out_filename = value_t!(matches, "out", String).map_err(Error::Args)?;
 
-    script.extend(
-        vec![
-            BotCommand::halt(),
-            BotCommand::wait(),
-            BotCommand::flip(),
-        ]
-    );
-    script.extend(
-        (0 .. 10)
-            .map(|i| {
-                BotCommand::smove(
-                    LinearCoordDiff::Long {
-                        axis: Axis::X,
-                        value: i,
-                    },
-                )
-            })
-    );
-    script.extend(
-        vec![
-            BotCommand::lmove(
-                LinearCoordDiff::Short {
-                    axis: Axis::Y,
-                    value: 4,
-                },
-                LinearCoordDiff::Short {
-                    axis: Axis::Z,
-                    value: -4,
-                },
-            ),
-            BotCommand::fission(
-                CoordDiff(Coord {
-                    x: 1,
-                    y: 0,
-                    z: 0,
-                }),
-                1,
-            ),
-            BotCommand::fill(
-                CoordDiff(Coord {
-                    x: -1,
-                    y: 0,
-                    z: 0,
-                }),
-            ),
-            BotCommand::pfusion(
-                CoordDiff(Coord {
-                    x: 0,
-                    y: 1,
-                    z: 0,
-                }),
-            ),
-            BotCommand::sfusion(
-                CoordDiff(Coord {
-                    x: 0,
-                    y: -1,
-                    z: 0,
-                }),
-            ),
-        ]
-    );
+    let mut script_text = String::new();
+    fs::File::open(&in_filename)
+        .map_err(Error::ScriptOpen)?
+        .read_to_string(&mut script_text)
+        .map_err(Error::ScriptRead)?;
+
+    let script = text::parse_text(&script_text);
+
+    let mut parsed_script = Vec::with_capacity(script.len());
+    for (index, maybe_command) in script.into_iter().enumerate() {
+        match maybe_command {
+            Ok(cmd) =>
+                parsed_script.push(cmd),
+            Err(error) =>
+                return Err(Error::CorruptedCommand { index, error, }),
+        }
+    }
 
-    let mut validated_script = Vec::with_capacity(script.len());
-    for (i, maybe_command) in script.into_iter().enumerate() {
+    // a parsed command may still carry a diff out of range, point at it instead of failing in `into_bytes`
+    let mut validated_script = Vec::with_capacity(parsed_script.len());
+    for (index, maybe_command) in cmd::validate_iter(parsed_script.into_iter()).enumerate() {
         match maybe_command {
             Ok(cmd) =>
                 validated_script.push(cmd),
-            Err(err) =>
-                panic!("command {} is corrupted: {:?}", i, err),
+            Err(error) =>
+                return Err(Error::InvalidCommand { index, error, }),
         }
     }
 
-    let trace = cmd::into_bytes(&validated_script).unwrap();
+    let trace = cmd::into_bytes(&validated_script).map_err(Error::Encode)?;
 
-    let file = fs::File::create("a.nbt").unwrap();
+    let file = fs::File::create(&out_filename).map_err(Error::TraceCreate)?;
     let mut writer = io::BufWriter::new(file);
-    writer.write_all(&trace).unwrap();
+    writer.write_all(&trace).map_err(Error::TraceWrite)?;
+    Ok(())
 }